@@ -1,6 +1,6 @@
 use ggcat_api::{
     ColoredQueryOutputFormat, ExtraElaboration, GGCATConfig, GGCATInstance,
-    GeneralSequenceBlockData,
+    GeneralSequenceBlockData, MinimizerScheme,
 };
 use itertools::Itertools;
 use std::{path::PathBuf, sync::Mutex};
@@ -10,10 +10,14 @@ fn main() {
         temp_dir: Some(PathBuf::from("/tmp")),
         memory: 2.0,
         prefer_memory: true,
+        hard_memory_limit: None,
         total_threads_count: 16,
         intermediate_compression_level: None,
         stats_file: None,
-    });
+        minimizer_scheme: MinimizerScheme::Random,
+        progress_callback: None,
+    })
+    .unwrap();
 
     let graph_file = PathBuf::from("/tmp/sal-dbg.fa");
     let k = 31;
@@ -0,0 +1,89 @@
+use colors::storage::format::ColormapError;
+use config::cancellation::Cancelled;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// Errors returned by the GGCAT operations
+#[derive(Debug)]
+pub enum GGCATError {
+    /// The given options are not valid, with the reason
+    InvalidOptions(String),
+    /// An input file does not exist or cannot be read
+    InputFileNotFound(PathBuf),
+    /// The colormap file is missing or it is not a valid GGCAT colormap
+    InvalidColormap { path: PathBuf, reason: String },
+    /// An input/output error happened while running the operation
+    Io(std::io::Error),
+    /// The operation failed with an unexpected internal error
+    Internal(String),
+    /// The operation was stopped by its cancellation token
+    Cancelled,
+    /// Different kmers share the same hash and no wider hash is available for the kmer length
+    HashCollisions { collisions_count: u64 },
+    /// The temporary files do not fit in the given memory in memory-only mode
+    OutOfMemory(String),
+}
+
+impl Display for GGCATError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GGCATError::InvalidOptions(reason) => write!(f, "Invalid options: {}", reason),
+            GGCATError::InputFileNotFound(path) => {
+                write!(f, "Input file not found: {}", path.display())
+            }
+            GGCATError::InvalidColormap { path, reason } => {
+                write!(f, "Invalid colormap {}: {}", path.display(), reason)
+            }
+            GGCATError::Io(error) => write!(f, "I/O error: {}", error),
+            GGCATError::Internal(message) => write!(f, "Internal error: {}", message),
+            GGCATError::Cancelled => write!(f, "Operation cancelled"),
+            GGCATError::HashCollisions { collisions_count } => write!(
+                f,
+                "Found {} hash collisions and no wider hash is available for this kmer length",
+                collisions_count
+            ),
+            GGCATError::OutOfMemory(message) => write!(f, "Out of memory: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GGCATError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GGCATError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GGCATError {
+    fn from(error: std::io::Error) -> Self {
+        GGCATError::Io(error)
+    }
+}
+
+/// Describes why the given colormap cannot be opened
+pub(crate) fn invalid_colormap(path: &Path, error: ColormapError) -> GGCATError {
+    GGCATError::InvalidColormap {
+        path: path.to_path_buf(),
+        reason: error.to_string(),
+    }
+}
+
+/// Runs the operation converting any panic into an internal error, so that the caller can recover from it
+pub(crate) fn catch_internal_error<T>(
+    function: impl FnOnce() -> T + std::panic::UnwindSafe,
+) -> Result<T, GGCATError> {
+    std::panic::catch_unwind(function).map_err(|payload| {
+        if payload.is::<Cancelled>() {
+            return GGCATError::Cancelled;
+        }
+        GGCATError::Internal(
+            payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown error".to_string()),
+        )
+    })
+}
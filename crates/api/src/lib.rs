@@ -1,28 +1,51 @@
+mod error;
+mod options;
 mod utils;
 
+use crate::debug::DebugOptions;
 use colors::bundles::graph_querying::ColorBundleGraphQuerying;
 use colors::colors_manager::ColorsManager;
 use colors::{
     bundles::multifile_building::ColorBundleMultifileBuilding, non_colored::NonColoredManager,
 };
+use config::instance::{InstanceContext, InstanceSettings, RunningOperation};
+use config::memory_budget::MemoryBudget;
 use hashes::MinimizerHashFunctionFactory;
 use hashes::{cn_nthash::CanonicalNtHashIteratorFactory, fw_nthash::ForwardNtHashIteratorFactory};
 use io::sequences_stream::fasta::FastaFileSequencesStream;
+use io::sequences_stream::general::GeneralSequencesStream;
 use io::sequences_stream::GenericSequencesStream;
 use parallel_processor::enable_counters_logging;
 use parallel_processor::memory_data_size::MemoryDataSize;
+use parallel_processor::memory_fs::allocator::CHUNKS_ALLOCATOR;
 use parallel_processor::memory_fs::MemoryFs;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parking_lot::Mutex;
+use querier::QueryResultsVisitor;
 use std::cmp::max;
-use std::fs::create_dir_all;
-use std::ops::Deref;
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
+pub use crate::error::GGCATError;
+pub use crate::options::{
+    BuildOptions, BuildOptionsBuilder, QueryOptions, QueryOptionsBuilder, QueryResultsCallback,
+};
 pub use crate::utils::HashType;
+pub use assembler::KmersSpectrumEstimate;
+pub use colors::colors_manager::ColorMapReader;
+pub use colors::storage::format::ColormapError;
+pub use colors::storage::mmap_reader::ColorsMmapReader;
+pub use colors::storage::reordering::ColormapReorderingStats;
+pub use colors::storage::upgrade::ColormapUpgrade;
+pub use colors::storage::ColormapEncoding;
+pub use config::cancellation::CancellationToken;
+pub use config::progress::{ProgressCallback, ProgressEvent};
 pub use config::ColorIndexType;
+pub use hashes::minimizer_scheme::MinimizerScheme;
 pub use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 pub use io::sequences_stream::{
     general::{DynamicSequencesStream, GeneralSequenceBlockData},
@@ -33,41 +56,62 @@ pub use querier::ColoredQueryOutputFormat;
 pub mod debug {
     use crate::utils::HashType;
     use assembler::AssemblerStartingStep;
-    pub use config::KEEP_FILES as DEBUG_KEEP_FILES;
-    use parking_lot::Mutex;
     use querier::QuerierStartingStep;
-    use std::sync::atomic::{AtomicBool, AtomicUsize};
-
-    pub static DEBUG_ASSEMBLER_FIRST_STEP: Mutex<AssemblerStartingStep> =
-        Mutex::new(AssemblerStartingStep::MinimizerBucketing);
-    pub static DEBUG_ASSEMBLER_LAST_STEP: Mutex<AssemblerStartingStep> =
-        Mutex::new(AssemblerStartingStep::MaximalUnitigsLinks);
-
-    pub static DEBUG_QUERIER_FIRST_STEP: Mutex<QuerierStartingStep> =
-        Mutex::new(QuerierStartingStep::MinimizerBucketing);
 
-    pub static DEBUG_HASH_TYPE: Mutex<HashType> = Mutex::new(HashType::Auto);
-
-    pub static DEBUG_LINK_PHASE_ITERATION_START_STEP: AtomicUsize = AtomicUsize::new(0);
-    pub static DEBUG_ONLY_BSTATS: AtomicBool = AtomicBool::new(false);
+    /// Options used to run only some steps of the pipelines and to debug them, owned by each instance
+    #[derive(Clone, Debug)]
+    pub struct DebugOptions {
+        pub assembler_first_step: AssemblerStartingStep,
+        pub assembler_last_step: AssemblerStartingStep,
+        pub querier_first_step: QuerierStartingStep,
+        pub hash_type: HashType,
+        /// Check that no different kmers share the same Rabin-Karp hash, switching to a wider hash if needed
+        pub verify_hash_collisions: bool,
+        pub link_phase_iteration_start_step: usize,
+        pub only_bstats: bool,
+        pub buckets_count_log_force: Option<usize>,
+        /// Do not remove the temporary files after they are used
+        pub keep_files: bool,
+    }
 
-    pub static BUCKETS_COUNT_LOG_FORCE: Mutex<Option<usize>> = Mutex::new(None);
+    impl Default for DebugOptions {
+        fn default() -> Self {
+            Self {
+                assembler_first_step: AssemblerStartingStep::MinimizerBucketing,
+                assembler_last_step: AssemblerStartingStep::MaximalUnitigsLinks,
+                querier_first_step: QuerierStartingStep::MinimizerBucketing,
+                hash_type: HashType::Auto,
+                verify_hash_collisions: false,
+                link_phase_iteration_start_step: 0,
+                only_bstats: false,
+                buckets_count_log_force: None,
+                keep_files: false,
+            }
+        }
+    }
 }
 
-/// Main config of GGCAT. This config is global and should be passed to GGCATInstance::create
+/// Main config of GGCAT, owned by the instance created with GGCATInstance::create
 pub struct GGCATConfig {
-    /// Directory for temporary files
+    /// Directory for temporary files, if None all the temporary files are kept in memory
     pub temp_dir: Option<PathBuf>,
 
     /// Maximum suggested memory usage (GB)
     /// The tool will try use only up to this GB of memory to store temporary files
     /// without writing to disk. This usage does not include the needed memory for the processing steps.
-    /// GGCAT can allocate extra memory for files if the current memory is not enough to complete the current operation
+    /// GGCAT can allocate extra memory for files if the current memory is not enough to complete the current operation,
+    /// except without a temporary directory, where the operation fails with an out of memory error
     pub memory: f64,
 
     /// Use all the given memory before writing to disk
     pub prefer_memory: bool,
 
+    /// Optional hard memory limit for the whole process (GB), including the processing steps.
+    /// When set the temporary files in memory are capped to a share of it, and the processing steps use
+    /// smaller packets, resplit more buckets and flush their buffers earlier to stay under the limit.
+    /// The limit is shared by all the instances alive at the same time, that must use the same one
+    pub hard_memory_limit: Option<f64>,
+
     /// The total threads to be used
     pub total_threads_count: usize,
 
@@ -76,6 +120,12 @@ pub struct GGCATConfig {
 
     /// The path to an optional json-formatted real time stats file
     pub stats_file: Option<PathBuf>,
+
+    /// The scheme used to choose the minimizers for the buckets, shared by all the bucketing steps
+    pub minimizer_scheme: MinimizerScheme,
+
+    /// Optional callback receiving the progress events (phases, processed buckets, links compaction and input reading)
+    pub progress_callback: Option<ProgressCallback>,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -89,70 +139,284 @@ pub enum ExtraElaboration {
     Eulertigs,
     /// Generate pathtigs
     Pathtigs,
+    /// Split the maximal unitigs at each change of colors subset, so that every sequence has a single subset,
+    /// with links between the pieces in the same format of UnitigLinks (requires colors)
+    ColorCompactedUnitigs,
 }
 
-static INSTANCE: Mutex<Option<&'static GGCATInstance>> = Mutex::new(None);
+/// Main GGCAT struct, created by passing a GGCATConfig.
+/// Each instance owns its memory budget and settings, so that different instances can run their operations
+/// side by side. Every operation runs in its own threads pool, with its own cancellation token.
+pub struct GGCATInstance {
+    config: GGCATConfig,
+    context: Arc<InstanceContext>,
+    debug_options: Mutex<DebugOptions>,
+}
 
-pub struct GGCATInstance(GGCATConfig);
+/// The in-memory temporary files storage and the hard memory budget are shared by the whole process.
+/// They are initialized with the memory settings of the first instance and released when the last instance is dropped.
+/// The instances created while they are running must use the same memory for the temporary files and the same
+/// hard limit, while the following ones can use their own memory settings
+struct MemoryFsState {
+    instances_count: usize,
+    memory: usize,
+    /// Memory allocated by the in-memory file system, the memory-only files cannot use more than it
+    files_memory: usize,
+    memory_budget: Option<Arc<MemoryBudget>>,
+}
+
+static MEMORY_FS_STATE: Mutex<MemoryFsState> = parking_lot::const_mutex(MemoryFsState {
+    instances_count: 0,
+    memory: 0,
+    files_memory: 0,
+    memory_budget: None,
+});
+
+/// Base path of the files of the memory-only operations, it is never created on disk
+const MEMORY_ONLY_FILES_PATH: &str = "ggcat-memory-only";
 
-fn create_tempdir(base_path: Option<PathBuf>) -> Option<PathBuf> {
-    base_path.map(|t| {
-        let temp_dir = t.join(&format!("build_graph_{}", uuid::Uuid::new_v4()));
-        let _ = create_dir_all(&temp_dir);
-        temp_dir
-    })
+/// Interval between two checks of the memory used by the memory-only files
+const MEMORY_ONLY_WATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Temporary directory of a single operation, removed together with the end of the current phase when dropped,
+/// so that it is cleaned up also if the operation is cancelled or fails.
+/// Without a base path the operation is memory-only, and the path only names its in-memory files
+struct OperationTempDir {
+    path: PathBuf,
+    on_disk: bool,
 }
 
-fn remove_tempdir(temp_dir: Option<PathBuf>) {
-    if let Some(temp_dir) = temp_dir {
-        let _ = std::fs::remove_dir_all(temp_dir);
+impl OperationTempDir {
+    fn create(base_path: Option<&Path>) -> Self {
+        let name = format!("build_graph_{}", uuid::Uuid::new_v4());
+        match base_path {
+            Some(base_path) => {
+                let path = base_path.join(name);
+                let _ = create_dir_all(&path);
+                Self {
+                    path,
+                    on_disk: true,
+                }
+            }
+            None => Self {
+                path: Path::new(MEMORY_ONLY_FILES_PATH).join(name),
+                on_disk: false,
+            },
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
     }
 }
 
-/// Main GGCAT struct. It's a singleton and can be create by passing a GGCATConfig.
-/// Successive calls to create will return the same instance, ignoring the new configuration.
-impl GGCATInstance {
-    /// Creates a new GGCATInstance. If an instance already exists, it will be returned, ignoring the new config.
-    pub fn create(config: GGCATConfig) -> &'static Self {
-        let mut instance = INSTANCE.lock();
+impl Drop for OperationTempDir {
+    fn drop(&mut self) {
+        if self.on_disk {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+        config::progress::end_phase();
+    }
+}
+
+/// Checks that all the input files exist
+fn check_input_streams(input_streams: &[GeneralSequenceBlockData]) -> Result<(), GGCATError> {
+    for stream in input_streams {
+        if let GeneralSequenceBlockData::FASTA(file) = stream {
+            if !file.is_file() {
+                return Err(GGCATError::InputFileNotFound(file.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the sequences of the given streams to a fasta file, named by their index
+fn write_sequences_to_fasta(
+    input_streams: &[GeneralSequenceBlockData],
+    fasta_file: &Path,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(fasta_file)?);
+    let mut stream = GeneralSequencesStream::new();
+    let mut index = 0;
+    let mut result = Ok(());
+
+    for block in input_streams {
+        stream.read_block(block, false, None, |sequence, _info| {
+            if result.is_ok() {
+                result = writeln!(writer, ">{}", index)
+                    .and_then(|_| writer.write_all(sequence.seq))
+                    .and_then(|_| writer.write_all(b"\n"));
+            }
+            index += 1;
+        });
+    }
+
+    result.and_then(|_| writer.flush())
+}
+
+/// Checks that the directory of the output file exists
+fn check_output_file(output_file: &Path) -> Result<(), GGCATError> {
+    match output_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => {
+            Err(GGCATError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("output directory {} does not exist", dir.display()),
+            )))
+        }
+        _ => Ok(()),
+    }
+}
 
-        if let Some(instance) = instance.deref() {
-            return instance;
+impl Drop for GGCATInstance {
+    fn drop(&mut self) {
+        let mut memory_fs_state = MEMORY_FS_STATE.lock();
+        self.context
+            .memory_budget()
+            .remove_threads(self.config.total_threads_count);
+        memory_fs_state.instances_count -= 1;
+        if memory_fs_state.instances_count == 0 {
+            MemoryFs::terminate();
+            memory_fs_state.memory_budget = None;
         }
+    }
+}
 
+impl GGCATInstance {
+    /// Creates a new independent GGCATInstance.
+    /// Fails if the temporary directory cannot be created, or if the memory for the temporary files or the hard limit
+    /// are different from the ones of the other instances alive, as they are shared by the whole process
+    pub fn create(config: GGCATConfig) -> Result<Arc<Self>, GGCATError> {
         // Increase the maximum allowed number of open files
         fdlimit::raise_fd_limit();
 
-        config::PREFER_MEMORY.store(config.prefer_memory, Ordering::Relaxed);
-
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(config.total_threads_count)
-            .thread_name(|i| format!("rayon-thread-{}", i))
-            .build_global()
-            .unwrap();
+        let hard_memory_limit = config.hard_memory_limit.map(|limit| {
+            MemoryDataSize::from_bytes(
+                (limit * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64)) as usize,
+            )
+        });
 
+        // Without a temporary directory all the intermediate files are kept in memory
         if let Some(temp_dir) = &config.temp_dir {
-            create_dir_all(temp_dir).unwrap();
-        } else {
-            todo!("Force memory-only usage")
+            create_dir_all(temp_dir)?;
+        }
+
+        let mut memory_fs_state = MEMORY_FS_STATE.lock();
+
+        let memory_budget = match &memory_fs_state.memory_budget {
+            Some(memory_budget) => {
+                let limit = memory_budget.get_hard_memory_limit();
+                if limit.map(|l| l.as_bytes()) != hard_memory_limit.map(|l| l.as_bytes()) {
+                    return Err(GGCATError::InvalidOptions(format!(
+                        "the hard memory limit is shared with the other instances, {} cannot be used while it is {}",
+                        hard_memory_limit.map_or("no limit".to_string(), |l| format!("{:.2}", l)),
+                        limit.map_or("no limit".to_string(), |l| format!("{:.2}", l))
+                    )));
+                }
+                memory_budget.clone()
+            }
+            None => Arc::new(MemoryBudget::new(hard_memory_limit, 0)),
+        };
+
+        let temp_files_memory = memory_budget.get_temp_files_memory(MemoryDataSize::from_bytes(
+            (config.memory * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64)) as usize,
+        ));
+        if memory_fs_state.instances_count == 0 {
+            MemoryFs::init(
+                temp_files_memory,
+                config::FLUSH_QUEUE_FACTOR * config.total_threads_count,
+                max(1, config.total_threads_count / 4),
+                8192,
+            );
+            memory_fs_state.memory = temp_files_memory.as_bytes();
+            memory_fs_state.files_memory = CHUNKS_ALLOCATOR.get_total_memory().as_bytes();
+        } else if memory_fs_state.memory != temp_files_memory.as_bytes() {
+            return Err(GGCATError::InvalidOptions(format!(
+                "the memory for the temporary files is shared with the other instances, {:.2} cannot be used while it is {:.2}",
+                temp_files_memory,
+                MemoryDataSize::from_bytes(memory_fs_state.memory)
+            )));
         }
+        memory_fs_state.instances_count += 1;
+        memory_budget.add_threads(config.total_threads_count);
+        memory_fs_state.memory_budget = Some(memory_budget.clone());
+        drop(memory_fs_state);
+
+        let context = InstanceContext::new(InstanceSettings {
+            prefer_memory: config.prefer_memory,
+            memory_only: config.temp_dir.is_none(),
+            keep_files: false,
+            intermediate_compression_level: config.intermediate_compression_level,
+            minimizer_scheme: config.minimizer_scheme,
+            hard_memory_limit,
+            threads_count: config.total_threads_count,
+            memory_budget: Some(memory_budget),
+            progress_callback: config.progress_callback.clone(),
+        });
 
         if let Some(stats_file) = &config.stats_file {
-            enable_counters_logging(stats_file, Duration::from_millis(1000), |val| {
-                val["phase"] = PHASES_TIMES_MONITOR.read().get_phase_desc().into();
+            let stats_context = context.clone();
+            enable_counters_logging(stats_file, Duration::from_millis(1000), move |val| {
+                val["phase"] = config::progress::get_last_started_phase(&stats_context).into();
             });
         }
 
-        MemoryFs::init(
-            MemoryDataSize::from_bytes(
-                (config.memory * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64)) as usize,
-            ),
-            config::FLUSH_QUEUE_FACTOR * config.total_threads_count,
-            max(1, config.total_threads_count / 4),
-            8192,
-        );
-        *instance = Some(Box::leak(Box::new(GGCATInstance(config))));
-        return instance.unwrap();
+        Ok(Arc::new(GGCATInstance {
+            config,
+            context,
+            debug_options: Mutex::new(DebugOptions::default()),
+        }))
+    }
+
+    pub fn get_debug_options(&self) -> DebugOptions {
+        self.debug_options.lock().clone()
+    }
+
+    /// Sets the debug options used by the next operations of this instance
+    pub fn set_debug_options(&self, debug_options: DebugOptions) {
+        self.context.set_keep_files(debug_options.keep_files);
+        *self.debug_options.lock() = debug_options;
+    }
+
+    /// Estimates from a sample of the input kmers the number of distinct and solid kmers for each candidate k,
+    /// returning the k with the most solid kmers (the best one to build the graph) together with the whole curve
+    pub fn suggest_k(
+        &self,
+        // The input streams
+        input_streams: &[GeneralSequenceBlockData],
+        // The k values to be evaluated
+        k_candidates: &[usize],
+        // Treats reverse complementary kmers as different
+        forward_only: bool,
+        // Minimum multiplicity required to consider a kmer solid
+        min_multiplicity: usize,
+        // Only one out of 2^sampling_log kmers is counted, defaults to assembler::DEFAULT_KMERS_SAMPLING_LOG
+        sampling_log: Option<usize>,
+    ) -> (Option<usize>, Vec<KmersSpectrumEstimate>) {
+        let sampling_log = sampling_log.unwrap_or(assembler::DEFAULT_KMERS_SAMPLING_LOG);
+        let threads_count = self.config.total_threads_count;
+
+        let spectra = self.run_operation(|| {
+            if forward_only {
+                assembler::estimate_kmers_spectra::<ForwardNtHashIteratorFactory>(
+                    input_streams,
+                    k_candidates,
+                    min_multiplicity,
+                    sampling_log,
+                    threads_count,
+                )
+            } else {
+                assembler::estimate_kmers_spectra::<CanonicalNtHashIteratorFactory>(
+                    input_streams,
+                    k_candidates,
+                    min_multiplicity,
+                    sampling_log,
+                    threads_count,
+                )
+            }
+        });
+
+        (assembler::best_k_from_spectra(&spectra), spectra)
     }
 
     /// Builds a new graph from the given input streams, with the specified parameters
@@ -184,17 +448,293 @@ impl GGCATInstance {
 
         extra_elab: ExtraElaboration,
     ) -> PathBuf {
+        let debug_options = self.get_debug_options();
+        self.run_operation(|| {
+            self.run_build_graph(
+                input_streams,
+                output_file,
+                color_names,
+                kmer_length,
+                threads_count,
+                forward_only,
+                minimizer_length,
+                colors,
+                min_multiplicity,
+                extra_elab,
+                debug_options.hash_type,
+                debug_options.verify_hash_collisions,
+                ColormapEncoding::default(),
+            )
+        })
+        .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Builds a new graph from the given input streams with validated options,
+    /// returning an error instead of panicking if the inputs are not valid or the build fails
+    pub fn build(
+        &self,
+        // The input streams
+        input_streams: Vec<GeneralSequenceBlockData>,
+        // The output file
+        output_file: PathBuf,
+        options: &BuildOptions,
+    ) -> Result<PathBuf, GGCATError> {
+        check_input_streams(&input_streams)?;
+        check_output_file(&output_file)?;
+
+        let output_file = self.run_cancellable(&options.kmers.cancellation_token, || {
+            self.run_build_graph(
+                input_streams,
+                output_file,
+                Some(options.color_names.as_slice()),
+                options.kmers.kmer_length,
+                options.kmers.threads_count,
+                options.kmers.forward_only,
+                options.kmers.minimizer_length,
+                options.kmers.colors,
+                options.min_multiplicity,
+                options.extra_elab,
+                options.kmers.hash_type,
+                options.verify_hash_collisions,
+                options.colormap_encoding,
+            )
+        })??;
+
+        if options.optimize_colormap && options.kmers.colors {
+            let stats = Self::optimize_colormap(Self::get_colormap_file(&output_file), None)?;
+            log::info!(
+                "Colormap reordered: {} => {} bytes ({:.2}% reduction)",
+                stats.original_size,
+                stats.reordered_size,
+                stats.size_reduction() * 100.0
+            );
+        }
+
+        if options.graph_checksum {
+            let checksum = io::checksum::write_file_checksum(&output_file)?;
+            log::info!(
+                "Graph checksum {:08x} written to {}",
+                checksum,
+                io::checksum::checksum_file_path(&output_file).display()
+            );
+        }
+
+        Ok(output_file)
+    }
+
+    /// Creates the threads pool of a new operation, whose threads enter a context created for the operation only.
+    /// In this way the operations running at the same time on this instance have independent cancellation tokens
+    fn create_operation_thread_pool(
+        &self,
+        operation: &Arc<InstanceContext>,
+    ) -> Result<rayon::ThreadPool, GGCATError> {
+        let operation = operation.clone();
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.config.total_threads_count)
+            .thread_name(|i| format!("rayon-thread-{}", i))
+            .start_handler(move |_| config::instance::enter(&operation))
+            .build()
+            .map_err(|error| {
+                GGCATError::Internal(format!("cannot create the threads pool: {}", error))
+            })
+    }
+
+    /// Runs the function of an operation, failing the operation if its temporary files do not fit in memory
+    /// in memory-only mode. Returns also if the memory has been exhausted
+    fn watch_memory_only_files<T: Send>(
+        &self,
+        operation: &InstanceContext,
+        function: impl FnOnce() -> T + Send,
+    ) -> (T, bool) {
+        if self.config.temp_dir.is_some() {
+            return (function(), false);
+        }
+
+        let files_memory = MEMORY_FS_STATE.lock().files_memory;
+        let finished = AtomicBool::new(false);
+        let out_of_memory = AtomicBool::new(false);
+
+        let result = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !finished.load(Ordering::Relaxed) {
+                    // The in-memory file system allocates more memory instead of swapping the memory-only files
+                    let used_memory = CHUNKS_ALLOCATOR
+                        .get_total_memory()
+                        .as_bytes()
+                        .saturating_sub(CHUNKS_ALLOCATOR.get_free_memory().as_bytes());
+                    if used_memory > files_memory {
+                        out_of_memory.store(true, Ordering::Relaxed);
+                        operation.fail(format!(
+                            "the temporary files do not fit in the {:.2} of memory",
+                            MemoryDataSize::from_bytes(files_memory)
+                        ));
+                        break;
+                    }
+                    std::thread::sleep(MEMORY_ONLY_WATCH_INTERVAL);
+                }
+            });
+
+            let result = std::panic::catch_unwind(AssertUnwindSafe(function));
+            finished.store(true, Ordering::Relaxed);
+            result
+        });
+
+        match result {
+            Ok(result) => (result, out_of_memory.load(Ordering::Relaxed)),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Runs an operation that cannot be cancelled, panicking if it fails
+    fn run_operation<T: Send>(&self, function: impl FnOnce() -> T + Send) -> T {
+        let operation = self.context.new_operation(None);
+        let _running_operation = RunningOperation::start();
+        let thread_pool = self
+            .create_operation_thread_pool(&operation)
+            .unwrap_or_else(|error| panic!("{}", error));
+        let (result, _) =
+            self.watch_memory_only_files(&operation, || thread_pool.install(function));
+        if let Some(failure) = operation.get_failure() {
+            panic!("{}", failure);
+        }
+        result
+    }
+
+    /// Runs an operation that can be stopped by the given token, converting its failures to errors
+    fn run_cancellable<T: Send>(
+        &self,
+        cancellation_token: &Option<CancellationToken>,
+        function: impl FnOnce() -> T + Send,
+    ) -> Result<T, GGCATError> {
+        let operation = self.context.new_operation(cancellation_token.clone());
+        let _running_operation = RunningOperation::start();
+        let thread_pool = self.create_operation_thread_pool(&operation)?;
+        let (result, out_of_memory) = self.watch_memory_only_files(&operation, || {
+            error::catch_internal_error(AssertUnwindSafe(|| thread_pool.install(function)))
+        });
+        // A failure stops the operation as a cancellation, so it is reported instead of the cancellation error
+        match operation.get_failure() {
+            Some(failure) if out_of_memory => Err(GGCATError::OutOfMemory(failure)),
+            Some(failure) => Err(GGCATError::Internal(failure)),
+            None => result,
+        }
+    }
+
+    fn run_build_graph(
+        &self,
+        input_streams: Vec<GeneralSequenceBlockData>,
+        output_file: PathBuf,
+        color_names: Option<&[String]>,
+        kmer_length: usize,
+        threads_count: usize,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        min_multiplicity: usize,
+        extra_elab: ExtraElaboration,
+        hash_type: HashType,
+        verify_hash_collisions: bool,
+        colormap_encoding: ColormapEncoding,
+    ) -> Result<PathBuf, GGCATError> {
         let bucketing_hash_dispatch = if forward_only {
             <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::DYNAMIC_DISPATCH_ID
         } else {
             <CanonicalNtHashIteratorFactory as MinimizerHashFunctionFactory>::DYNAMIC_DISPATCH_ID
         };
 
-        let merging_hash_dispatch = utils::get_hash_static_id(
-            debug::DEBUG_HASH_TYPE.lock().clone(),
+        let colors_hash = if colors {
+            ColorBundleMultifileBuilding::DYNAMIC_DISPATCH_ID
+        } else {
+            NonColoredManager::DYNAMIC_DISPATCH_ID
+        };
+
+        let debug_options = self.get_debug_options();
+
+        utils::run_with_hash_collisions_check(
+            hash_type,
             kmer_length,
             forward_only,
-        );
+            verify_hash_collisions,
+            |merging_hash_dispatch, hash_collisions_check| {
+                let temp_dir = OperationTempDir::create(self.config.temp_dir.as_deref());
+
+                let result = assembler::dynamic_dispatch::run_assembler(
+                    (bucketing_hash_dispatch, merging_hash_dispatch, colors_hash),
+                    kmer_length,
+                    minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
+                    debug_options.assembler_first_step,
+                    debug_options.assembler_last_step,
+                    input_streams.clone(),
+                    color_names.unwrap_or(&[]),
+                    colormap_encoding,
+                    output_file.clone(),
+                    Some(temp_dir.path()),
+                    threads_count,
+                    min_multiplicity,
+                    debug_options.buckets_count_log_force,
+                    Some(debug_options.link_phase_iteration_start_step),
+                    self.config.intermediate_compression_level,
+                    matches!(
+                        extra_elab,
+                        ExtraElaboration::UnitigLinks | ExtraElaboration::ColorCompactedUnitigs
+                    ),
+                    match extra_elab {
+                        ExtraElaboration::GreedyMatchtigs => {
+                            Some(assembler::MatchtigMode::GreedyTigs)
+                        }
+                        ExtraElaboration::Eulertigs => Some(assembler::MatchtigMode::EulerTigs),
+                        ExtraElaboration::Pathtigs => Some(assembler::MatchtigMode::PathTigs),
+                        _ => None,
+                    },
+                    extra_elab == ExtraElaboration::ColorCompactedUnitigs,
+                    debug_options.only_bstats,
+                    hash_collisions_check,
+                    None,
+                );
+
+                drop(temp_dir);
+
+                result
+            },
+        )
+    }
+
+    /// Counts the kmers of the given input streams, calling the output function on each solid kmer
+    /// (with multiplicity at least min_multiplicity) with its multiplicity and, if colors are enabled, its colors.
+    /// If forward_only is false the kmers are passed in their canonical (lexicographically smaller) form.
+    pub fn visit_kmers(
+        &self,
+        // The input streams
+        input_streams: Vec<GeneralSequenceBlockData>,
+
+        // The names of the colors, ordered by color index
+        color_names: Option<&[String]>,
+
+        // Specifies the k-mers length
+        kmer_length: usize,
+        // The threads to be used
+        threads_count: usize,
+        // Treats reverse complementary kmers as different
+        forward_only: bool,
+        // Overrides the default m-mers (minimizers) length
+        minimizer_length: Option<usize>,
+
+        // Enable colors
+        colors: bool,
+
+        // Minimum multiplicity required to keep a kmer
+        min_multiplicity: usize,
+
+        // Call the output function from a single thread at a time,
+        // avoiding the need for synchronization in the user code
+        single_thread_output_function: bool,
+        output_function: impl Fn(&[u8], u64, &[ColorIndexType]) + Send + Sync,
+    ) {
+        let bucketing_hash_dispatch = if forward_only {
+            <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::DYNAMIC_DISPATCH_ID
+        } else {
+            <CanonicalNtHashIteratorFactory as MinimizerHashFunctionFactory>::DYNAMIC_DISPATCH_ID
+        };
 
         let colors_hash = if colors {
             ColorBundleMultifileBuilding::DYNAMIC_DISPATCH_ID
@@ -202,36 +742,78 @@ impl GGCATInstance {
             NonColoredManager::DYNAMIC_DISPATCH_ID
         };
 
-        let temp_dir = create_tempdir(self.0.temp_dir.clone());
+        let single_thread_lock = Mutex::new(());
 
-        let output_file = assembler::dynamic_dispatch::run_assembler(
-            (bucketing_hash_dispatch, merging_hash_dispatch, colors_hash),
-            kmer_length,
-            minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
-            debug::DEBUG_ASSEMBLER_FIRST_STEP.lock().clone(),
-            debug::DEBUG_ASSEMBLER_LAST_STEP.lock().clone(),
-            input_streams,
-            color_names.unwrap_or(&[]),
-            output_file,
-            temp_dir.clone(),
-            threads_count,
-            min_multiplicity,
-            *debug::BUCKETS_COUNT_LOG_FORCE.lock(),
-            Some(debug::DEBUG_LINK_PHASE_ITERATION_START_STEP.load(Ordering::Relaxed)),
-            self.0.intermediate_compression_level,
-            extra_elab == ExtraElaboration::UnitigLinks,
-            match extra_elab {
-                ExtraElaboration::GreedyMatchtigs => Some(assembler::MatchtigMode::GreedyTigs),
-                ExtraElaboration::Eulertigs => Some(assembler::MatchtigMode::EulerTigs),
-                ExtraElaboration::Pathtigs => Some(assembler::MatchtigMode::PathTigs),
-                _ => None,
-            },
-            debug::DEBUG_ONLY_BSTATS.load(Ordering::Relaxed),
-        );
+        let kmers_visitor = |kmer: &[u8], multiplicity: u64, colors: &[ColorIndexType]| {
+            let _lock = if single_thread_output_function {
+                Some(single_thread_lock.lock())
+            } else {
+                None
+            };
 
-        remove_tempdir(temp_dir);
+            if forward_only {
+                output_function(kmer, multiplicity, colors);
+            } else {
+                utils::with_canonical_kmer(kmer, |kmer| {
+                    output_function(kmer, multiplicity, colors)
+                });
+            }
+        };
 
-        output_file
+        let debug_options = self.get_debug_options();
+
+        self.run_operation(|| {
+            utils::run_with_hash_collisions_check(
+                debug_options.hash_type,
+                kmer_length,
+                forward_only,
+                debug_options.verify_hash_collisions,
+                |merging_hash_dispatch, hash_collisions_check| {
+                    let temp_dir = OperationTempDir::create(self.config.temp_dir.as_deref());
+                    // The colormap and the abundance histogram are always written to disk, as the colormap is read
+                    // back to decode the colors. In memory-only mode they go to the system temporary directory
+                    let files_dir = OperationTempDir::create(Some(
+                        &self
+                            .config
+                            .temp_dir
+                            .clone()
+                            .unwrap_or_else(std::env::temp_dir),
+                    ));
+
+                    // The collisions are checked before visiting the kmers, so a failed run never calls the visitor
+                    let result = assembler::dynamic_dispatch::run_assembler(
+                        (bucketing_hash_dispatch, merging_hash_dispatch, colors_hash),
+                        kmer_length,
+                        minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
+                        debug_options.assembler_first_step,
+                        assembler::AssemblerStartingStep::KmersMerge,
+                        input_streams.clone(),
+                        color_names.unwrap_or(&[]),
+                        ColormapEncoding::default(),
+                        // Only used to store the colormap and the abundance histogram
+                        files_dir.path().join("counted_kmers"),
+                        Some(temp_dir.path()),
+                        threads_count,
+                        min_multiplicity,
+                        debug_options.buckets_count_log_force,
+                        None,
+                        self.config.intermediate_compression_level,
+                        false,
+                        None,
+                        false,
+                        false,
+                        hash_collisions_check,
+                        Some(&kmers_visitor),
+                    );
+
+                    drop(temp_dir);
+                    drop(files_dir);
+
+                    result.map(|_| ())
+                },
+            )
+        })
+        .unwrap_or_else(|error| panic!("{}", error))
     }
 
     /// Queries a (optionally) colored graph with a specific set of sequences as queries
@@ -259,6 +841,143 @@ impl GGCATInstance {
 
         // Query output format
         color_output_format: ColoredQueryOutputFormat,
+    ) -> PathBuf {
+        self.run_operation(|| {
+            self.run_query_graph(
+                input_graph,
+                input_query,
+                output_file_prefix,
+                kmer_length,
+                threads_count,
+                forward_only,
+                minimizer_length,
+                colors,
+                color_output_format,
+                self.get_debug_options().hash_type,
+                None,
+            )
+        })
+    }
+
+    /// Queries a (optionally) colored graph with validated options, returning an error
+    /// instead of panicking if the inputs or the colormap are not valid or the query fails
+    pub fn query(
+        &self,
+        // The input graph
+        input_graph: PathBuf,
+        // The input query as a .fasta file
+        input_query: PathBuf,
+        // The output file
+        output_file_prefix: PathBuf,
+        options: &QueryOptions,
+    ) -> Result<PathBuf, GGCATError> {
+        if !input_query.is_file() {
+            return Err(GGCATError::InputFileNotFound(input_query));
+        }
+        self.check_query_inputs(&input_graph, &output_file_prefix, options)?;
+
+        self.run_cancellable(&options.kmers.cancellation_token, || {
+            self.run_query_graph(
+                input_graph,
+                input_query,
+                output_file_prefix,
+                options.kmers.kmer_length,
+                options.kmers.threads_count,
+                options.kmers.forward_only,
+                options.kmers.minimizer_length,
+                options.kmers.colors,
+                options.color_output_format,
+                options.kmers.hash_type,
+                options.results_callback.as_deref(),
+            )
+        })
+    }
+
+    /// Queries a (optionally) colored graph with the sequences of the given streams, indexed in order,
+    /// that are copied to a temporary fasta file before starting the query
+    pub fn query_from_streams(
+        &self,
+        // The input graph
+        input_graph: PathBuf,
+        // The input query streams
+        input_query: Vec<GeneralSequenceBlockData>,
+        // The output file
+        output_file_prefix: PathBuf,
+        options: &QueryOptions,
+    ) -> Result<PathBuf, GGCATError> {
+        check_input_streams(&input_query)?;
+        self.check_query_inputs(&input_graph, &output_file_prefix, options)?;
+
+        // The querier reads the queries from a file, in memory-only mode it is in the system temporary directory
+        let query_file = self
+            .config
+            .temp_dir
+            .clone()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(format!("query_{}.fa", uuid::Uuid::new_v4()));
+
+        let result = self
+            .run_cancellable(&options.kmers.cancellation_token, || {
+                write_sequences_to_fasta(&input_query, &query_file)
+            })
+            .and_then(|result| result.map_err(GGCATError::Io))
+            .and_then(|_| {
+                self.run_cancellable(&options.kmers.cancellation_token, || {
+                    self.run_query_graph(
+                        input_graph,
+                        query_file.clone(),
+                        output_file_prefix,
+                        options.kmers.kmer_length,
+                        options.kmers.threads_count,
+                        options.kmers.forward_only,
+                        options.kmers.minimizer_length,
+                        options.kmers.colors,
+                        options.color_output_format,
+                        options.kmers.hash_type,
+                        options.results_callback.as_deref(),
+                    )
+                })
+            });
+
+        let _ = std::fs::remove_file(&query_file);
+        result
+    }
+
+    /// Checks the graph, its colormap and the output directory of a query
+    fn check_query_inputs(
+        &self,
+        input_graph: &Path,
+        output_file_prefix: &Path,
+        options: &QueryOptions,
+    ) -> Result<(), GGCATError> {
+        if !input_graph.is_file() {
+            return Err(GGCATError::InputFileNotFound(input_graph.to_path_buf()));
+        }
+        check_output_file(output_file_prefix)?;
+
+        if options.kmers.colors {
+            use colors::storage::deserializer::ColorsDeserializer;
+
+            let colormap_file = Self::get_colormap_file(input_graph);
+            ColorsDeserializer::check_colormap_file(&colormap_file)
+                .map_err(|error| error::invalid_colormap(&colormap_file, error))?;
+        }
+        Ok(())
+    }
+
+    fn run_query_graph(
+        &self,
+        input_graph: PathBuf,
+        input_query: PathBuf,
+        output_file_prefix: PathBuf,
+        kmer_length: usize,
+        threads_count: usize,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        color_output_format: ColoredQueryOutputFormat,
+        hash_type: HashType,
+        results_visitor: Option<QueryResultsVisitor>,
     ) -> PathBuf {
         let bucketing_hash_dispatch = if forward_only {
             <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::DYNAMIC_DISPATCH_ID
@@ -266,11 +985,7 @@ impl GGCATInstance {
             <CanonicalNtHashIteratorFactory as MinimizerHashFunctionFactory>::DYNAMIC_DISPATCH_ID
         };
 
-        let merging_hash_dispatch = utils::get_hash_static_id(
-            debug::DEBUG_HASH_TYPE.lock().clone(),
-            kmer_length,
-            forward_only,
-        );
+        let merging_hash_dispatch = utils::get_hash_static_id(hash_type, kmer_length, forward_only);
 
         let colors_hash = if colors {
             ColorBundleGraphQuerying::DYNAMIC_DISPATCH_ID
@@ -278,24 +993,27 @@ impl GGCATInstance {
             NonColoredManager::DYNAMIC_DISPATCH_ID
         };
 
-        let temp_dir = create_tempdir(self.0.temp_dir.clone());
+        let debug_options = self.get_debug_options();
+
+        let temp_dir = OperationTempDir::create(self.config.temp_dir.as_deref());
 
         let output_file = querier::dynamic_dispatch::run_query(
             (bucketing_hash_dispatch, merging_hash_dispatch, colors_hash),
             kmer_length,
             minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
-            debug::DEBUG_QUERIER_FIRST_STEP.lock().clone(),
+            debug_options.querier_first_step,
             input_graph,
             input_query,
             output_file_prefix,
-            temp_dir.clone(),
-            *debug::BUCKETS_COUNT_LOG_FORCE.lock(),
+            Some(temp_dir.path()),
+            debug_options.buckets_count_log_force,
             threads_count,
-            self.0.intermediate_compression_level,
+            self.config.intermediate_compression_level,
             color_output_format,
+            results_visitor,
         );
 
-        remove_tempdir(temp_dir);
+        drop(temp_dir);
 
         output_file
     }
@@ -305,6 +1023,166 @@ impl GGCATInstance {
         graph_file.as_ref().with_extension("colors.dat")
     }
 
+    /// Reorders the colors of a colormap placing the ones that appear in similar subsets next to each other,
+    /// to improve its compression. The color names are reordered accordingly, while the subsets indexes
+    /// referenced by the graph are unchanged. If no output is given the colormap is replaced in place,
+    /// but only if the reordered one is smaller
+    pub fn optimize_colormap(
+        // The input colormap
+        input_colormap: impl AsRef<Path>,
+        // The optional output colormap
+        output_colormap: Option<PathBuf>,
+    ) -> Result<ColormapReorderingStats, GGCATError> {
+        use colors::storage::deserializer::ColorsDeserializer;
+        use colors::storage::reordering::reorder_colormap;
+
+        let input_colormap = input_colormap.as_ref();
+        ColorsDeserializer::check_colormap_file(input_colormap)
+            .map_err(|error| error::invalid_colormap(input_colormap, error))?;
+
+        let output = match &output_colormap {
+            Some(output_colormap) => {
+                check_output_file(output_colormap)?;
+                output_colormap.clone()
+            }
+            None => input_colormap.with_extension("reordered.tmp"),
+        };
+
+        let stats = error::catch_internal_error(AssertUnwindSafe(|| {
+            reorder_colormap(input_colormap, &output)
+        }));
+
+        if output_colormap.is_none() {
+            match &stats {
+                Ok(stats) if stats.reordered_size < stats.original_size => {
+                    std::fs::rename(&output, input_colormap)?;
+                }
+                _ => {
+                    let _ = std::fs::remove_file(&output);
+                }
+            }
+        }
+
+        stats
+    }
+
+    /// Converts a colormap written with an older format version to the current one.
+    /// If no output is given the colormap is replaced in place. Returns the outcome of the upgrade,
+    /// nothing is written if the colormap already uses the current version
+    pub fn upgrade_colormap(
+        // The input colormap
+        input_colormap: impl AsRef<Path>,
+        // The optional output colormap
+        output_colormap: Option<PathBuf>,
+    ) -> Result<ColormapUpgrade, GGCATError> {
+        use colors::storage::upgrade::upgrade_colormap;
+
+        let input_colormap = input_colormap.as_ref();
+        if !input_colormap.is_file() {
+            return Err(GGCATError::InputFileNotFound(input_colormap.to_path_buf()));
+        }
+
+        let output = match &output_colormap {
+            Some(output_colormap) => {
+                check_output_file(output_colormap)?;
+                output_colormap.clone()
+            }
+            None => input_colormap.with_extension("upgraded.tmp"),
+        };
+
+        let outcome = error::catch_internal_error(AssertUnwindSafe(|| {
+            upgrade_colormap(input_colormap, &output)
+        }))?
+        .map_err(|error| error::invalid_colormap(input_colormap, error));
+
+        if output_colormap.is_none() {
+            match &outcome {
+                Ok(ColormapUpgrade::Upgraded { .. }) => {
+                    std::fs::rename(&output, input_colormap)?;
+                }
+                _ => {
+                    let _ = std::fs::remove_file(&output);
+                }
+            }
+        }
+
+        outcome
+    }
+
+    fn edit_colormap(
+        input_colormap: &Path,
+        output_colormap: Option<PathBuf>,
+        edit: impl FnOnce(&Path, &Path) -> Result<usize, ColormapError>,
+    ) -> Result<usize, GGCATError> {
+        if !input_colormap.is_file() {
+            return Err(GGCATError::InputFileNotFound(input_colormap.to_path_buf()));
+        }
+
+        let output = match &output_colormap {
+            Some(output_colormap) => {
+                check_output_file(output_colormap)?;
+                output_colormap.clone()
+            }
+            None => input_colormap.with_extension("edited.tmp"),
+        };
+
+        let outcome =
+            error::catch_internal_error(AssertUnwindSafe(|| edit(input_colormap, &output)))?
+                .map_err(|error| match error {
+                    // Errors in the requested edits, the colormap itself is valid
+                    ColormapError::UnknownColor(_) | ColormapError::InvalidMetadata { .. } => {
+                        GGCATError::InvalidOptions(error.to_string())
+                    }
+                    error => error::invalid_colormap(input_colormap, error),
+                });
+
+        if output_colormap.is_none() {
+            match &outcome {
+                Ok(_) => {
+                    std::fs::rename(&output, input_colormap)?;
+                }
+                Err(_) => {
+                    let _ = std::fs::remove_file(&output);
+                }
+            }
+        }
+
+        outcome
+    }
+
+    /// Renames the colors of a colormap, given pairs of old and new names.
+    /// If no output is given the colormap is replaced in place. The subsets are not decoded,
+    /// so the graph referencing them stays valid. Returns the number of renamed colors
+    pub fn rename_colors(
+        // The input colormap
+        input_colormap: impl AsRef<Path>,
+        // The optional output colormap
+        output_colormap: Option<PathBuf>,
+        // The pairs of old and new color names
+        renames: &[(String, String)],
+    ) -> Result<usize, GGCATError> {
+        Self::edit_colormap(input_colormap.as_ref(), output_colormap, |input, output| {
+            colors::storage::editing::rename_colors(input, output, renames)
+        })
+    }
+
+    /// Sets the json metadata of the colors of a colormap, given pairs of color names and metadata
+    /// (None removes the metadata of the color). If no output is given the colormap is replaced in place.
+    /// Each metadata must be valid json, otherwise an InvalidOptions error is returned and the colormap is
+    /// not changed. Returns the number of updated colors
+    pub fn set_colors_metadata(
+        // The input colormap
+        input_colormap: impl AsRef<Path>,
+        // The optional output colormap
+        output_colormap: Option<PathBuf>,
+        // The pairs of color names and json metadata
+        metadata: &[(String, Option<String>)],
+    ) -> Result<usize, GGCATError> {
+        Self::edit_colormap(input_colormap.as_ref(), output_colormap, |input, output| {
+            colors::storage::editing::set_colors_metadata(input, output, metadata)
+        })
+    }
+
     /// Returns an iterator over the color names in the given graph.
     /// The color indexes returned from the dump_unitigs function
     /// can be used to index this (collected) iterator.
@@ -314,10 +1192,8 @@ impl GGCATInstance {
     ) -> impl Iterator<Item = String> {
         use colors::colors_manager::ColorMapReader;
         use colors::storage::deserializer::ColorsDeserializer;
-        use colors::DefaultColorsSerializer;
 
-        let colors_deserializer =
-            ColorsDeserializer::<DefaultColorsSerializer>::new(input_colormap, true);
+        let colors_deserializer = ColorsDeserializer::new(input_colormap, true);
 
         (0..colors_deserializer.colors_count()).map(move |i| {
             colors_deserializer
@@ -328,7 +1204,8 @@ impl GGCATInstance {
 
     /// Dumps the unitigs of the given graph, optionally with colors
     /// It's not guaranteed that maximal unitigs are returned, as only kmers with the same colors subset
-    /// are returned as whole unitigs to speedup colormap reading times
+    /// are returned as whole unitigs to speedup colormap reading times. The graphs built with
+    /// ExtraElaboration::ColorCompactedUnitigs already have a single colors subset for each sequence
     pub fn dump_unitigs(
         &self,
         graph_input: PathBuf,
@@ -345,31 +1222,269 @@ impl GGCATInstance {
         single_thread_output_function: bool,
         output_function: impl Fn(&[u8], &[ColorIndexType], bool) + Send + Sync,
     ) {
-        let temp_dir = create_tempdir(self.0.temp_dir.clone());
+        let debug_options = self.get_debug_options();
 
-        if colors {
-            dumper::dump_unitigs(
-                kmer_length,
-                minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
-                graph_input,
-                temp_dir.clone(),
-                *debug::BUCKETS_COUNT_LOG_FORCE.lock(),
-                threads_count,
-                single_thread_output_function,
-                self.0.intermediate_compression_level,
-                output_function,
-            );
-        } else {
-            FastaFileSequencesStream::new().read_block(
-                &graph_input,
-                false,
-                Some(kmer_length - 1),
-                |seq, _info| {
-                    output_function(seq.ident_data, &[], false);
-                },
-            );
+        self.run_operation(|| {
+            let temp_dir = OperationTempDir::create(self.config.temp_dir.as_deref());
+
+            if colors {
+                dumper::dump_unitigs(
+                    kmer_length,
+                    minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
+                    graph_input,
+                    Some(temp_dir.path()),
+                    debug_options.buckets_count_log_force,
+                    threads_count,
+                    single_thread_output_function,
+                    self.config.intermediate_compression_level,
+                    output_function,
+                );
+            } else {
+                FastaFileSequencesStream::new().read_block(
+                    &graph_input,
+                    false,
+                    Some(kmer_length - 1),
+                    |seq, _info| {
+                        output_function(seq.ident_data, &[], false);
+                    },
+                );
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::memory_budget::MemoryReservation;
+    use std::sync::Barrier;
+
+    fn test_config(temp_dir: PathBuf) -> GGCATConfig {
+        GGCATConfig {
+            temp_dir: Some(temp_dir),
+            memory: 0.5,
+            prefer_memory: false,
+            hard_memory_limit: None,
+            total_threads_count: 2,
+            intermediate_compression_level: None,
+            stats_file: None,
+            minimizer_scheme: MinimizerScheme::Random,
+            progress_callback: None,
         }
+    }
+
+    /// Writes some pseudo random sequences, each one repeated twice to have solid kmers
+    fn write_test_sequences(path: &Path) {
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let mut contents = String::new();
+        for index in 0..50 {
+            let mut sequence = String::new();
+            for _ in 0..500 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                sequence.push(b"ACGT"[(state & 3) as usize] as char);
+            }
+            for copy in 0..2 {
+                contents.push_str(&format!(">{}_{}\n{}\n", index, copy, sequence));
+            }
+        }
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn cancel_one_of_concurrent_operations() {
+        let test_dir =
+            std::env::temp_dir().join(format!("ggcat-test-{}-operations", std::process::id()));
+        create_dir_all(&test_dir).unwrap();
+        let input = test_dir.join("input.fa");
+        write_test_sequences(&input);
+
+        let instance = GGCATInstance::create(test_config(test_dir.join("temp"))).unwrap();
+
+        let cancellation_token = CancellationToken::new();
+        let options = |token: &CancellationToken| {
+            BuildOptions::builder(21)
+                .threads_count(2)
+                .cancellation_token(token.clone())
+                .build()
+                .unwrap()
+        };
+        let cancelled_options = options(&cancellation_token);
+        let running_options = options(&CancellationToken::new());
+
+        let started = Barrier::new(2);
+        let (cancelled_result, running_result) = std::thread::scope(|scope| {
+            let cancelled = scope.spawn(|| {
+                started.wait();
+                cancellation_token.cancel();
+                instance.build(
+                    vec![GeneralSequenceBlockData::FASTA(input.clone())],
+                    test_dir.join("cancelled.fa"),
+                    &cancelled_options,
+                )
+            });
+            let running = scope.spawn(|| {
+                started.wait();
+                instance.build(
+                    vec![GeneralSequenceBlockData::FASTA(input.clone())],
+                    test_dir.join("running.fa"),
+                    &running_options,
+                )
+            });
+            (cancelled.join().unwrap(), running.join().unwrap())
+        });
+
+        let running_graph = running_result.map(|graph| std::fs::read_to_string(graph).unwrap());
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert!(matches!(cancelled_result, Err(GGCATError::Cancelled)));
+        assert!(!running_graph.unwrap().is_empty());
+    }
+
+    /// Total number of kmers in the unitigs of a graph
+    fn count_graph_kmers(graph: &Path, k: usize) -> usize {
+        std::fs::read_to_string(graph)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('>'))
+            .map(|unitig| unitig.len() - k + 1)
+            .sum()
+    }
+
+    #[test]
+    fn concurrent_instances_with_different_settings() {
+        let test_dir =
+            std::env::temp_dir().join(format!("ggcat-test-{}-instances", std::process::id()));
+        create_dir_all(&test_dir).unwrap();
+        let input = test_dir.join("input.fa");
+        write_test_sequences(&input);
+
+        let small_config = || test_config(test_dir.join("temp-small"));
+        // The memory for the temporary files and the hard limit are shared by the instances alive at the same time
+        let large_config = || GGCATConfig {
+            prefer_memory: true,
+            ..test_config(test_dir.join("temp-large"))
+        };
+
+        let build = |config: GGCATConfig, k: usize, name: &str| {
+            let instance = GGCATInstance::create(config).unwrap();
+            let options = BuildOptions::builder(k).threads_count(2).build().unwrap();
+            let graph = instance
+                .build(
+                    vec![GeneralSequenceBlockData::FASTA(input.clone())],
+                    test_dir.join(name),
+                    &options,
+                )
+                .unwrap();
+            count_graph_kmers(&graph, k)
+        };
+
+        let (concurrent_small, concurrent_large) = std::thread::scope(|scope| {
+            let small = scope.spawn(|| build(small_config(), 15, "concurrent-15.fa"));
+            let large = scope.spawn(|| build(large_config(), 31, "concurrent-31.fa"));
+            (small.join().unwrap(), large.join().unwrap())
+        });
+
+        let sequential_small = build(small_config(), 15, "sequential-15.fa");
+        let sequential_large = build(large_config(), 31, "sequential-31.fa");
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert_eq!(concurrent_small, sequential_small);
+        assert_eq!(concurrent_large, sequential_large);
+        assert!(concurrent_small > concurrent_large);
+    }
+
+    #[test]
+    fn instances_share_the_memory_budget() {
+        let test_dir =
+            std::env::temp_dir().join(format!("ggcat-test-{}-budget", std::process::id()));
+
+        let first = GGCATInstance::create(test_config(test_dir.join("first"))).unwrap();
+        let second = GGCATInstance::create(test_config(test_dir.join("second"))).unwrap();
+        let budget = second.context.memory_budget();
+        assert!(std::ptr::eq(first.context.memory_budget(), budget));
+
+        // The reservations of an operation of the first instance count also for the second one
+        config::instance::enter(&first.context.new_operation(None));
+        let reservation = MemoryReservation::with_size(1 << 30);
+        let tracked_reserved = budget.get_tracked_memory().as_bytes();
+        drop(reservation);
+
+        drop(first);
+        drop(second);
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert!(tracked_reserved >= 1 << 30);
+    }
+
+    #[test]
+    fn memory_only_instance() {
+        let test_dir =
+            std::env::temp_dir().join(format!("ggcat-test-{}-memory-only", std::process::id()));
+        create_dir_all(&test_dir).unwrap();
+        let input = test_dir.join("input.fa");
+        write_test_sequences(&input);
+
+        let build = |instance: &GGCATInstance, name: &str| {
+            let options = BuildOptions::builder(21).threads_count(2).build().unwrap();
+            let graph = instance
+                .build(
+                    vec![GeneralSequenceBlockData::FASTA(input.clone())],
+                    test_dir.join(name),
+                    &options,
+                )
+                .unwrap();
+            count_graph_kmers(&graph, 21)
+        };
+
+        // Checked at every progress event, the temporary files must never reach the disk
+        let disk_used = Arc::new(AtomicBool::new(false));
+        let check_disk = {
+            let disk_used = disk_used.clone();
+            let test_dir = test_dir.clone();
+            move || {
+                let system_temp_dirs = std::fs::read_dir(std::env::temp_dir())
+                    .unwrap()
+                    .filter_map(|entry| entry.ok())
+                    .any(|entry| {
+                        entry
+                            .file_name()
+                            .to_string_lossy()
+                            .starts_with("build_graph_")
+                    });
+                let test_dir_files = std::fs::read_dir(&test_dir)
+                    .unwrap()
+                    .filter_map(|entry| entry.ok())
+                    .any(|entry| {
+                        let name = entry.file_name().to_string_lossy().to_string();
+                        !name.starts_with("input") && !name.starts_with("memory")
+                    });
+                if Path::new(MEMORY_ONLY_FILES_PATH).exists() || system_temp_dirs || test_dir_files
+                {
+                    disk_used.store(true, Ordering::Relaxed);
+                }
+            }
+        };
+        let progress_check_disk = check_disk.clone();
+
+        let memory_instance = GGCATInstance::create(GGCATConfig {
+            temp_dir: None,
+            progress_callback: Some(Arc::new(move |_: &ProgressEvent| progress_check_disk())),
+            ..test_config(PathBuf::new())
+        })
+        .unwrap();
+
+        let memory_kmers = build(&memory_instance, "memory.fa");
+        drop(memory_instance);
+        check_disk();
+
+        let disk_instance = GGCATInstance::create(test_config(test_dir.join("temp"))).unwrap();
+        let disk_kmers = build(&disk_instance, "disk.fa");
+        drop(disk_instance);
+        let _ = std::fs::remove_dir_all(&test_dir);
 
-        remove_tempdir(temp_dir);
+        assert!(!disk_used.load(Ordering::Relaxed));
+        assert_eq!(memory_kmers, disk_kmers);
     }
 }
@@ -0,0 +1,432 @@
+use crate::{
+    CancellationToken, ColorIndexType, ColoredQueryOutputFormat, ColormapEncoding,
+    ExtraElaboration, GGCATError, HashType,
+};
+use ::utils::MAX_MINIMIZER_LENGTH;
+
+use std::cmp::min;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// Callback receiving each (query index, color, fraction of the query kmers found in the color) result,
+/// the color is always 0 for non colored graphs. It can be called concurrently from multiple threads
+pub type QueryResultsCallback = Arc<dyn Fn(usize, ColorIndexType, f64) + Send + Sync>;
+
+/// Maximum k supported by the sequence hash, bigger k values need a Rabin-Karp hash
+const MAX_SEQHASH_K: usize = 255;
+
+/// Options shared by the build and query operations
+#[derive(Clone, Debug)]
+pub(crate) struct KmersOptions {
+    pub(crate) kmer_length: usize,
+    pub(crate) minimizer_length: Option<usize>,
+    pub(crate) threads_count: usize,
+    pub(crate) forward_only: bool,
+    pub(crate) colors: bool,
+    pub(crate) hash_type: HashType,
+    pub(crate) cancellation_token: Option<CancellationToken>,
+}
+
+impl KmersOptions {
+    fn new(kmer_length: usize) -> Self {
+        Self {
+            kmer_length,
+            minimizer_length: None,
+            threads_count: std::thread::available_parallelism()
+                .map(|t| t.get())
+                .unwrap_or(1),
+            forward_only: false,
+            colors: false,
+            hash_type: HashType::Auto,
+            cancellation_token: None,
+        }
+    }
+
+    fn validate(&self) -> Result<(), GGCATError> {
+        let k = self.kmer_length;
+        if k < 2 {
+            return Err(GGCATError::InvalidOptions(format!(
+                "k must be at least 2, found {}",
+                k
+            )));
+        }
+
+        if let Some(m) = self.minimizer_length {
+            let max_m = min(k - 1, MAX_MINIMIZER_LENGTH);
+            if m == 0 || m > max_m {
+                return Err(GGCATError::InvalidOptions(format!(
+                    "the minimizer length must be between 1 and {} (the minimum of k - 1 and {}), found {}",
+                    max_m, MAX_MINIMIZER_LENGTH, m
+                )));
+            }
+        }
+
+        if self.hash_type == HashType::SeqHash && k > MAX_SEQHASH_K {
+            return Err(GGCATError::InvalidOptions(format!(
+                "the sequence hash supports k up to {}, found {}, use a Rabin-Karp hash",
+                MAX_SEQHASH_K, k
+            )));
+        }
+
+        if self.threads_count == 0 {
+            return Err(GGCATError::InvalidOptions(
+                "at least one thread is needed".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+macro_rules! kmers_options_setters {
+    ($builder:ident) => {
+        impl $builder {
+            /// Overrides the default m-mers (minimizers) length
+            pub fn minimizer_length(mut self, minimizer_length: usize) -> Self {
+                self.0.kmers.minimizer_length = Some(minimizer_length);
+                self
+            }
+
+            /// The threads to be used, all the available ones by default
+            pub fn threads_count(mut self, threads_count: usize) -> Self {
+                self.0.kmers.threads_count = threads_count;
+                self
+            }
+
+            /// Treats reverse complementary kmers as different
+            pub fn forward_only(mut self, forward_only: bool) -> Self {
+                self.0.kmers.forward_only = forward_only;
+                self
+            }
+
+            /// Enable colors
+            pub fn colors(mut self, colors: bool) -> Self {
+                self.0.kmers.colors = colors;
+                self
+            }
+
+            /// Hash type used to identify kmers
+            pub fn hash_type(mut self, hash_type: HashType) -> Self {
+                self.0.kmers.hash_type = hash_type;
+                self
+            }
+
+            /// Token that can be used to stop the operation from another thread,
+            /// making it return GGCATError::Cancelled
+            pub fn cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+                self.0.kmers.cancellation_token = Some(cancellation_token);
+                self
+            }
+        }
+    };
+}
+
+/// Validated options for GGCATInstance::build, created with BuildOptions::builder
+#[derive(Clone, Debug)]
+pub struct BuildOptions {
+    pub(crate) kmers: KmersOptions,
+    pub(crate) color_names: Vec<String>,
+    pub(crate) min_multiplicity: usize,
+    pub(crate) extra_elab: ExtraElaboration,
+    pub(crate) verify_hash_collisions: bool,
+    pub(crate) colormap_encoding: ColormapEncoding,
+    pub(crate) optimize_colormap: bool,
+    pub(crate) graph_checksum: bool,
+}
+
+pub struct BuildOptionsBuilder(BuildOptions);
+
+impl BuildOptions {
+    pub fn builder(kmer_length: usize) -> BuildOptionsBuilder {
+        BuildOptionsBuilder(BuildOptions {
+            kmers: KmersOptions::new(kmer_length),
+            color_names: vec![],
+            min_multiplicity: 2,
+            extra_elab: ExtraElaboration::None,
+            verify_hash_collisions: false,
+            colormap_encoding: ColormapEncoding::RunLength,
+            optimize_colormap: false,
+            graph_checksum: false,
+        })
+    }
+
+    pub fn kmer_length(&self) -> usize {
+        self.kmers.kmer_length
+    }
+}
+
+kmers_options_setters!(BuildOptionsBuilder);
+
+impl BuildOptionsBuilder {
+    /// The names of the colors, ordered by color index
+    pub fn color_names(mut self, color_names: Vec<String>) -> Self {
+        self.0.color_names = color_names;
+        self
+    }
+
+    /// Minimum multiplicity required to keep a kmer, 2 by default
+    pub fn min_multiplicity(mut self, min_multiplicity: usize) -> Self {
+        self.0.min_multiplicity = min_multiplicity;
+        self
+    }
+
+    /// Extra elaboration step done on the unitigs
+    pub fn extra_elaboration(mut self, extra_elab: ExtraElaboration) -> Self {
+        self.0.extra_elab = extra_elab;
+        self
+    }
+
+    /// Check that different kmers never share the same Rabin-Karp hash, repeating the build with a wider hash
+    /// if any collision is found, or failing with GGCATError::HashCollisions if no wider hash is available
+    pub fn verify_hash_collisions(mut self, verify_hash_collisions: bool) -> Self {
+        self.0.verify_hash_collisions = verify_hash_collisions;
+        self
+    }
+
+    /// Encoding of the colors subsets in the colormap, run-length by default.
+    /// Roaring bitmaps are smaller when the graph has many colors shared by most kmers
+    pub fn colormap_encoding(mut self, colormap_encoding: ColormapEncoding) -> Self {
+        self.0.colormap_encoding = colormap_encoding;
+        self
+    }
+
+    /// Reorder the colors by similarity after the build, to reduce the colormap size.
+    /// The color indexes in the colormap then follow the new order instead of the input order
+    pub fn optimize_colormap(mut self, optimize_colormap: bool) -> Self {
+        self.0.optimize_colormap = optimize_colormap;
+        self
+    }
+
+    /// Write the CRC32 of the output graph to a file with the .crc32 extension appended,
+    /// that can be checked later to detect a corrupted graph
+    pub fn graph_checksum(mut self, graph_checksum: bool) -> Self {
+        self.0.graph_checksum = graph_checksum;
+        self
+    }
+
+    pub fn build(self) -> Result<BuildOptions, GGCATError> {
+        let options = self.0;
+        options.kmers.validate()?;
+
+        if options.min_multiplicity == 0 {
+            return Err(GGCATError::InvalidOptions(
+                "the minimum multiplicity must be at least 1".to_string(),
+            ));
+        }
+
+        // Matchtigs, eulertigs and pathtigs are computed on the bidirected graph
+        if options.kmers.forward_only
+            && matches!(
+                options.extra_elab,
+                ExtraElaboration::GreedyMatchtigs
+                    | ExtraElaboration::Eulertigs
+                    | ExtraElaboration::Pathtigs
+            )
+        {
+            return Err(GGCATError::InvalidOptions(format!(
+                "{:?} need reverse complementary kmers, they cannot be built in forward only mode",
+                options.extra_elab
+            )));
+        }
+
+        if !options.kmers.colors && options.extra_elab == ExtraElaboration::ColorCompactedUnitigs {
+            return Err(GGCATError::InvalidOptions(
+                "color compacted unitigs need colors to be enabled".to_string(),
+            ));
+        }
+
+        if !options.kmers.colors && !options.color_names.is_empty() {
+            return Err(GGCATError::InvalidOptions(
+                "color names are given but colors are not enabled".to_string(),
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+/// Validated options for GGCATInstance::query, created with QueryOptions::builder
+#[derive(Clone)]
+pub struct QueryOptions {
+    pub(crate) kmers: KmersOptions,
+    pub(crate) color_output_format: ColoredQueryOutputFormat,
+    pub(crate) results_callback: Option<QueryResultsCallback>,
+}
+
+impl Debug for QueryOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryOptions")
+            .field("kmers", &self.kmers)
+            .field("color_output_format", &self.color_output_format)
+            .field("results_callback", &self.results_callback.is_some())
+            .finish()
+    }
+}
+
+pub struct QueryOptionsBuilder(QueryOptions);
+
+impl QueryOptions {
+    /// The k must be the same used to build the graph
+    pub fn builder(kmer_length: usize) -> QueryOptionsBuilder {
+        QueryOptionsBuilder(QueryOptions {
+            kmers: KmersOptions::new(kmer_length),
+            color_output_format: ColoredQueryOutputFormat::JsonLinesWithNumbers,
+            results_callback: None,
+        })
+    }
+
+    pub fn kmer_length(&self) -> usize {
+        self.kmers.kmer_length
+    }
+}
+
+kmers_options_setters!(QueryOptionsBuilder);
+
+impl QueryOptionsBuilder {
+    /// Output format of the colored query results
+    pub fn color_output_format(mut self, color_output_format: ColoredQueryOutputFormat) -> Self {
+        self.0.color_output_format = color_output_format;
+        self
+    }
+
+    /// Callback receiving the query results, that are also written to the output file
+    pub fn results_callback(mut self, results_callback: QueryResultsCallback) -> Self {
+        self.0.results_callback = Some(results_callback);
+        self
+    }
+
+    pub fn build(self) -> Result<QueryOptions, GGCATError> {
+        let options = self.0;
+        options.kmers.validate()?;
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_reason<T: Debug>(result: Result<T, GGCATError>) -> String {
+        match result {
+            Err(GGCATError::InvalidOptions(reason)) => reason,
+            other => panic!("expected invalid options, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn build_options_builder() {
+        let token = CancellationToken::new();
+        let options = BuildOptions::builder(31)
+            .minimizer_length(12)
+            .threads_count(3)
+            .colors(true)
+            .color_names(vec!["a".to_string(), "b".to_string()])
+            .min_multiplicity(1)
+            .extra_elaboration(ExtraElaboration::ColorCompactedUnitigs)
+            .hash_type(HashType::RabinKarp64)
+            .verify_hash_collisions(true)
+            .cancellation_token(token.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(options.kmer_length(), 31);
+        assert_eq!(options.kmers.minimizer_length, Some(12));
+        assert_eq!(options.kmers.threads_count, 3);
+        assert_eq!(options.color_names.len(), 2);
+        assert_eq!(options.min_multiplicity, 1);
+        assert_eq!(options.kmers.hash_type, HashType::RabinKarp64);
+        assert!(options.verify_hash_collisions);
+
+        token.cancel();
+        assert!(options.kmers.cancellation_token.unwrap().is_cancelled());
+
+        let defaults = BuildOptions::builder(31).build().unwrap();
+        assert_eq!(defaults.kmers.minimizer_length, None);
+        assert_eq!(defaults.min_multiplicity, 2);
+        assert_eq!(defaults.extra_elab, ExtraElaboration::None);
+        assert!(!defaults.kmers.colors && !defaults.kmers.forward_only);
+    }
+
+    #[test]
+    fn query_options_builder() {
+        let options = QueryOptions::builder(27)
+            .colors(true)
+            .color_output_format(ColoredQueryOutputFormat::JsonLinesWithNames)
+            .results_callback(Arc::new(|_, _, _| {}))
+            .build()
+            .unwrap();
+
+        assert_eq!(options.kmer_length(), 27);
+        assert!(options.kmers.colors);
+        assert!(options.results_callback.is_some());
+
+        assert!(QueryOptions::builder(1).build().is_err());
+    }
+
+    #[test]
+    fn kmers_options_validation() {
+        assert!(invalid_reason(BuildOptions::builder(1).build()).contains("k must be at least 2"));
+        assert!(BuildOptions::builder(2).build().is_ok());
+
+        for k in 2..4 {
+            assert!(::utils::compute_best_m(k) < k);
+        }
+
+        assert!(BuildOptions::builder(31)
+            .minimizer_length(0)
+            .build()
+            .is_err());
+        assert!(BuildOptions::builder(31)
+            .minimizer_length(31)
+            .build()
+            .is_err());
+        assert!(BuildOptions::builder(31)
+            .minimizer_length(30)
+            .build()
+            .is_ok());
+
+        assert!(BuildOptions::builder(301)
+            .minimizer_length(MAX_MINIMIZER_LENGTH)
+            .hash_type(HashType::RabinKarp128)
+            .build()
+            .is_ok());
+        assert!(BuildOptions::builder(301)
+            .minimizer_length(MAX_MINIMIZER_LENGTH + 1)
+            .hash_type(HashType::RabinKarp128)
+            .build()
+            .is_err());
+        assert!(::utils::compute_best_m(1001) <= MAX_MINIMIZER_LENGTH);
+
+        assert!(BuildOptions::builder(301)
+            .hash_type(HashType::SeqHash)
+            .build()
+            .is_err());
+        assert!(BuildOptions::builder(31).threads_count(0).build().is_err());
+    }
+
+    #[test]
+    fn build_options_validation() {
+        assert!(BuildOptions::builder(31)
+            .min_multiplicity(0)
+            .build()
+            .is_err());
+        assert!(BuildOptions::builder(31)
+            .forward_only(true)
+            .extra_elaboration(ExtraElaboration::Eulertigs)
+            .build()
+            .is_err());
+        assert!(BuildOptions::builder(31)
+            .forward_only(true)
+            .extra_elaboration(ExtraElaboration::UnitigLinks)
+            .build()
+            .is_ok());
+        assert!(BuildOptions::builder(31)
+            .extra_elaboration(ExtraElaboration::ColorCompactedUnitigs)
+            .build()
+            .is_err());
+        assert!(BuildOptions::builder(31)
+            .color_names(vec!["a".to_string()])
+            .build()
+            .is_err());
+    }
+}
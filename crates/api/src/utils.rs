@@ -1,6 +1,11 @@
+use crate::GGCATError;
+use ::utils::Utils;
+use assembler::{AssemblerError, HashCollisionsCheck, HashCollisionsDetected};
 use dynamic_dispatch::DynamicDispatch;
+use log::info;
+use std::cell::RefCell;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum HashType {
     Auto = 0,
     SeqHash = 1,
@@ -9,25 +14,39 @@ pub enum HashType {
     RabinKarp128 = 4,
 }
 
-pub(crate) fn get_hash_static_id(
-    hash_type: HashType,
-    k: usize,
-    forward_only: bool,
-) -> DynamicDispatch<()> {
-    use hashes::*;
-
-    let hash_type = match hash_type {
+fn resolve_hash_type(hash_type: HashType, k: usize) -> HashType {
+    match hash_type {
         HashType::Auto => {
-            if k <= 64 {
+            if k <= 255 {
                 HashType::SeqHash
             } else {
                 HashType::RabinKarp128
             }
         }
         x => x,
-    };
+    }
+}
 
+/// Returns the hash type to be used in place of one that had collisions, preferring
+/// the exact sequence hash if it supports the k value, or None if no better hash exists
+fn get_collision_free_hash_type(hash_type: HashType, k: usize) -> Option<HashType> {
     match hash_type {
+        HashType::Auto | HashType::SeqHash => None,
+        _ if k <= 255 => Some(HashType::SeqHash),
+        HashType::RabinKarp32 => Some(HashType::RabinKarp64),
+        HashType::RabinKarp64 => Some(HashType::RabinKarp128),
+        HashType::RabinKarp128 => None,
+    }
+}
+
+pub(crate) fn get_hash_static_id(
+    hash_type: HashType,
+    k: usize,
+    forward_only: bool,
+) -> DynamicDispatch<()> {
+    use hashes::*;
+
+    match resolve_hash_type(hash_type, k) {
         HashType::SeqHash => {
             if k <= 8 {
                 if forward_only {
@@ -53,8 +72,20 @@ pub(crate) fn get_hash_static_id(
                 } else {
                     cn_seqhash::u128::CanonicalSeqHashFactory::DYNAMIC_DISPATCH_ID
                 }
+            } else if k <= 128 {
+                if forward_only {
+                    fw_seqhash::u256::ForwardSeqHashFactory::DYNAMIC_DISPATCH_ID
+                } else {
+                    cn_seqhash::u256::CanonicalSeqHashFactory::DYNAMIC_DISPATCH_ID
+                }
+            } else if k <= 255 {
+                if forward_only {
+                    fw_seqhash::u512::ForwardSeqHashFactory::DYNAMIC_DISPATCH_ID
+                } else {
+                    cn_seqhash::u512::CanonicalSeqHashFactory::DYNAMIC_DISPATCH_ID
+                }
             } else {
-                panic!("Cannot use sequence hash for k > 64!");
+                panic!("Cannot use sequence hash for k > 255!");
             }
         }
         HashType::RabinKarp32 => {
@@ -83,3 +114,185 @@ pub(crate) fn get_hash_static_id(
         }
     }
 }
+
+/// Runs the function with the hash selected by hash_type. If collisions verification is enabled and the run
+/// finds kmers sharing the same hash, it is repeated with a wider hash, or with the exact sequence hash if possible.
+/// When no better hash exists the collisions are returned as an error, as the graph would contain wrong links.
+/// The other assembler errors are returned to the caller
+pub(crate) fn run_with_hash_collisions_check<T>(
+    hash_type: HashType,
+    k: usize,
+    forward_only: bool,
+    verify_hash_collisions: bool,
+    mut function: impl FnMut(DynamicDispatch<()>, HashCollisionsCheck) -> Result<T, AssemblerError>,
+) -> Result<T, GGCATError> {
+    let mut hash_type = resolve_hash_type(hash_type, k);
+    let mut resolved_collisions = 0;
+
+    let hash_collisions_check = if verify_hash_collisions {
+        HashCollisionsCheck::Abort
+    } else {
+        HashCollisionsCheck::Disabled
+    };
+
+    loop {
+        match function(
+            get_hash_static_id(hash_type, k, forward_only),
+            hash_collisions_check,
+        ) {
+            Ok(result) => {
+                if resolved_collisions > 0 {
+                    info!(
+                        "Resolved {} hash collisions using the {:?} hash",
+                        resolved_collisions, hash_type
+                    );
+                }
+                return Ok(result);
+            }
+            Err(AssemblerError::Io(error)) => return Err(GGCATError::Io(error)),
+            Err(AssemblerError::HashCollisions(HashCollisionsDetected { collisions_count })) => {
+                let Some(new_hash_type) = get_collision_free_hash_type(hash_type, k) else {
+                    return Err(GGCATError::HashCollisions { collisions_count });
+                };
+                info!(
+                    "Found {} hash collisions with the {:?} hash, retrying with the {:?} hash",
+                    collisions_count, hash_type, new_hash_type
+                );
+                resolved_collisions += collisions_count;
+                hash_type = new_hash_type;
+            }
+        }
+    }
+}
+
+/// Calls the function with the canonical form of the kmer, the lexicographically smaller
+/// between the kmer and its reverse complement
+pub(crate) fn with_canonical_kmer<T>(kmer: &[u8], function: impl FnOnce(&[u8]) -> T) -> T {
+    thread_local! {
+        static REVERSE_COMPLEMENT_BUFFER: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+    }
+
+    let rc_base = |index: usize| {
+        Utils::decompress_base(Utils::compress_base(kmer[kmer.len() - 1 - index]) ^ 2)
+    };
+
+    let use_reverse_complement = (0..kmer.len())
+        .map(|index| kmer[index].cmp(&rc_base(index)))
+        .find(|ordering| ordering.is_ne())
+        .map(|ordering| ordering.is_gt())
+        .unwrap_or(false);
+
+    if use_reverse_complement {
+        REVERSE_COMPLEMENT_BUFFER.with(|buffer| {
+            let mut buffer = buffer.borrow_mut();
+            buffer.clear();
+            buffer.extend((0..kmer.len()).map(rc_base));
+            function(&buffer)
+        })
+    } else {
+        function(kmer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the hash collisions check on a function that finds collisions in every run that
+    /// verifies them, returning the checks requested by each run and the final result
+    fn run_always_colliding(
+        hash_type: HashType,
+        k: usize,
+        verify: bool,
+    ) -> (Vec<HashCollisionsCheck>, Result<(), GGCATError>) {
+        let mut checks = vec![];
+        let result = run_with_hash_collisions_check(hash_type, k, false, verify, |_, check| {
+            checks.push(check);
+            match check {
+                HashCollisionsCheck::Abort => Err(HashCollisionsDetected {
+                    collisions_count: 1,
+                }
+                .into()),
+                HashCollisionsCheck::Disabled => Ok(()),
+            }
+        });
+        (checks, result)
+    }
+
+    #[test]
+    fn collision_free_hash_type() {
+        assert_eq!(get_collision_free_hash_type(HashType::SeqHash, 31), None);
+        assert_eq!(
+            get_collision_free_hash_type(HashType::RabinKarp32, 31),
+            Some(HashType::SeqHash)
+        );
+        assert_eq!(
+            get_collision_free_hash_type(HashType::RabinKarp32, 301),
+            Some(HashType::RabinKarp64)
+        );
+        assert_eq!(
+            get_collision_free_hash_type(HashType::RabinKarp64, 301),
+            Some(HashType::RabinKarp128)
+        );
+        assert_eq!(
+            get_collision_free_hash_type(HashType::RabinKarp128, 301),
+            None
+        );
+    }
+
+    #[test]
+    fn hash_collisions_retries() {
+        use HashCollisionsCheck::*;
+
+        let (checks, result) = run_always_colliding(HashType::RabinKarp32, 31, false);
+        assert_eq!(checks, vec![Disabled]);
+        assert!(result.is_ok());
+
+        let (checks, result) = run_always_colliding(HashType::RabinKarp64, 31, true);
+        assert_eq!(checks, vec![Abort, Abort]);
+        assert!(matches!(
+            result,
+            Err(GGCATError::HashCollisions {
+                collisions_count: 1
+            })
+        ));
+
+        let (checks, result) = run_always_colliding(HashType::RabinKarp32, 301, true);
+        assert_eq!(checks, vec![Abort, Abort, Abort]);
+        assert!(matches!(result, Err(GGCATError::HashCollisions { .. })));
+    }
+
+    #[test]
+    fn collisions_without_better_hash_are_errors() {
+        let mut runs = 0;
+        let result: Result<(), _> =
+            run_with_hash_collisions_check(HashType::Auto, 301, false, true, |_, _| {
+                runs += 1;
+                Err(HashCollisionsDetected {
+                    collisions_count: 3,
+                }
+                .into())
+            });
+
+        assert_eq!(runs, 1);
+        assert!(matches!(
+            result,
+            Err(GGCATError::HashCollisions {
+                collisions_count: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn assembler_errors_are_returned() {
+        let mut runs = 0;
+        let result: Result<(), _> =
+            run_with_hash_collisions_check(HashType::RabinKarp64, 31, false, true, |_, _| {
+                runs += 1;
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "write failed").into())
+            });
+
+        assert_eq!(runs, 1);
+        assert!(matches!(result, Err(GGCATError::Io(_))));
+    }
+}
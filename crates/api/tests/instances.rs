@@ -0,0 +1,48 @@
+//! The in-memory temporary files storage and the hard memory budget are shared by the whole process,
+//! so the instances alive at the same time with different memory settings are tested in their own process
+
+use ggcat_api::{GGCATConfig, GGCATError, GGCATInstance, MinimizerScheme};
+
+fn config(memory: f64) -> GGCATConfig {
+    GGCATConfig {
+        temp_dir: Some(std::env::temp_dir().join(format!("ggcat-test-{}", std::process::id()))),
+        memory,
+        prefer_memory: false,
+        hard_memory_limit: None,
+        total_threads_count: 2,
+        intermediate_compression_level: None,
+        stats_file: None,
+        minimizer_scheme: MinimizerScheme::Random,
+        progress_callback: None,
+    }
+}
+
+#[test]
+fn side_by_side_instances_with_different_memory_settings() {
+    let first = GGCATInstance::create(config(0.5)).unwrap();
+    let same_memory = GGCATInstance::create(config(0.5)).unwrap();
+    let conflicting = GGCATInstance::create(config(1.0));
+    let conflicting_limit = GGCATInstance::create(GGCATConfig {
+        hard_memory_limit: Some(4.0),
+        ..config(0.5)
+    });
+
+    // The memory is released only when all the instances using it are dropped
+    drop(first);
+    let conflicting_while_alive = GGCATInstance::create(config(1.0));
+    drop(same_memory);
+    let after_drop = GGCATInstance::create(config(1.0));
+
+    let _ = std::fs::remove_dir_all(config(0.5).temp_dir.unwrap());
+
+    assert!(matches!(conflicting, Err(GGCATError::InvalidOptions(_))));
+    assert!(matches!(
+        conflicting_limit,
+        Err(GGCATError::InvalidOptions(_))
+    ));
+    assert!(matches!(
+        conflicting_while_alive,
+        Err(GGCATError::InvalidOptions(_))
+    ));
+    assert!(after_drop.is_ok());
+}
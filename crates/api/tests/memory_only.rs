@@ -0,0 +1,55 @@
+//! The in-memory temporary files storage is shared by the whole process and keeps the memory of its first instance,
+//! so the memory-only instances with a small memory are tested in their own process
+
+use ggcat_api::{
+    BuildOptions, GGCATConfig, GGCATError, GGCATInstance, GeneralSequenceBlockData, MinimizerScheme,
+};
+
+/// Writes many pseudo random sequences, whose temporary files are much larger than a few megabytes
+fn write_large_sequences(path: &std::path::Path) {
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let mut contents = String::new();
+    for index in 0..100000 {
+        contents.push_str(&format!(">{}\n", index));
+        for _ in 0..500 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            contents.push(b"ACGT"[(state & 3) as usize] as char);
+        }
+        contents.push('\n');
+    }
+    std::fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn memory_only_build_out_of_memory() {
+    let test_dir =
+        std::env::temp_dir().join(format!("ggcat-test-{}-out-of-memory", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let input = test_dir.join("input.fa");
+    write_large_sequences(&input);
+
+    let instance = GGCATInstance::create(GGCATConfig {
+        temp_dir: None,
+        memory: 0.004,
+        prefer_memory: false,
+        hard_memory_limit: None,
+        total_threads_count: 2,
+        intermediate_compression_level: None,
+        stats_file: None,
+        minimizer_scheme: MinimizerScheme::Random,
+        progress_callback: None,
+    })
+    .unwrap();
+
+    let result = instance.build(
+        vec![GeneralSequenceBlockData::FASTA(input)],
+        test_dir.join("output.fa"),
+        &BuildOptions::builder(21).threads_count(2).build().unwrap(),
+    );
+    drop(instance);
+    let _ = std::fs::remove_dir_all(&test_dir);
+
+    assert!(matches!(result, Err(GGCATError::OutOfMemory(_))));
+}
@@ -2,7 +2,9 @@
 #![feature(impl_trait_in_assoc_type)]
 
 use crate::pipeline::build_unitigs::build_unitigs;
+use crate::pipeline::color_compacted_unitigs::split_color_runs;
 use crate::pipeline::compute_matchtigs::{compute_matchtigs_thread, MatchtigsStorageBackend};
+use crate::pipeline::counted_kmers_visiting::counted_kmers_visiting;
 use crate::pipeline::hashes_sorting::hashes_sorting;
 use crate::pipeline::links_compaction::links_compaction;
 use crate::pipeline::maximal_unitig_links::build_maximal_unitigs_links;
@@ -11,10 +13,14 @@ use ::dynamic_dispatch::dynamic_dispatch;
 use assembler_kmers_merge::structs::RetType;
 use colors::colors_manager::ColorsManager;
 use colors::colors_manager::ColorsMergeManager;
+use colors::storage::ColormapEncoding;
+use config::cancellation;
+use config::instance;
+use config::progress::{self, ProgressEvent};
 use config::{
-    get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
-    INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW, KEEP_FILES,
-    MAXIMUM_SECOND_BUCKETS_LOG, MINIMUM_LOG_DELTA_TIME,
+    flush_temp_files, get_compression_level_info, get_memory_mode, remove_temp_files,
+    ColorIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE, MAXIMUM_SECOND_BUCKETS_LOG,
+    MINIMUM_LOG_DELTA_TIME, OUTPUT_COMPRESSION_LEVEL,
 };
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::concurrent::structured_sequences::binary::StructSeqBinaryWriter;
@@ -22,17 +28,17 @@ use io::concurrent::structured_sequences::fasta::FastaWriter;
 use io::concurrent::structured_sequences::StructuredSequenceWriter;
 use io::sequences_stream::general::GeneralSequenceBlockData;
 use io::{compute_stats_from_input_blocks, generate_bucket_names};
+use log::info;
 use parallel_processor::buckets::concurrent::BucketsThreadBuffer;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedCheckpointSize;
 use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
 use parallel_processor::buckets::MultiThreadBuckets;
 use parallel_processor::memory_data_size::MemoryDataSize;
 use parallel_processor::memory_fs::{MemoryFs, RemoveFileMode};
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
+use std::fmt::{Display, Formatter};
 use std::fs::remove_file;
 use std::path::PathBuf;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -40,8 +46,76 @@ mod pipeline;
 mod structs;
 
 pub use pipeline::compute_matchtigs::MatchtigMode;
+pub use pipeline::kmers_spectra_estimation::{
+    best_k_from_spectra, estimate_kmers_spectra, KmersSpectrumEstimate, DEFAULT_KMERS_SAMPLING_LOG,
+};
+
+/// Returned when the hash collisions verification finds different kmers sharing the same hash.
+/// In this case the graph is not built, and the assembly should be repeated with a wider or an invertible hash
+#[derive(Copy, Clone, Debug)]
+pub struct HashCollisionsDetected {
+    pub collisions_count: u64,
+}
+
+/// Errors that stop the assembly
+#[derive(Debug)]
+pub enum AssemblerError {
+    /// Different kmers share the same hash, the assembly should be repeated with a better hash
+    HashCollisions(HashCollisionsDetected),
+    /// The output graph cannot be written
+    Io(std::io::Error),
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblerError::HashCollisions(HashCollisionsDetected { collisions_count }) => {
+                write!(f, "found {} hash collisions", collisions_count)
+            }
+            AssemblerError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
 
-#[derive(Clone, PartialEq, PartialOrd)]
+impl From<HashCollisionsDetected> for AssemblerError {
+    fn from(error: HashCollisionsDetected) -> Self {
+        AssemblerError::HashCollisions(error)
+    }
+}
+
+impl From<std::io::Error> for AssemblerError {
+    fn from(error: std::io::Error) -> Self {
+        AssemblerError::Io(error)
+    }
+}
+
+/// How the assembler handles kmers sharing the same hash
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HashCollisionsCheck {
+    /// The collisions are not counted
+    Disabled,
+    /// The assembly stops returning HashCollisionsDetected, to be repeated with a better hash
+    Abort,
+}
+
+impl HashCollisionsCheck {
+    pub fn is_enabled(&self) -> bool {
+        *self != HashCollisionsCheck::Disabled
+    }
+
+    fn handle_collisions(&self, collisions_count: u64) -> Result<(), HashCollisionsDetected> {
+        if collisions_count == 0 {
+            return Ok(());
+        }
+
+        match self {
+            HashCollisionsCheck::Disabled => Ok(()),
+            HashCollisionsCheck::Abort => Err(HashCollisionsDetected { collisions_count }),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
 pub enum AssemblerStartingStep {
     MinimizerBucketing = 0,
     KmersMerge = 1,
@@ -60,6 +134,8 @@ pub enum AssemblerStartingStep {
     #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u32::ForwardSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u64::ForwardSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u128::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u256::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u512::ForwardSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u32::ForwardRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u64::ForwardRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u128::ForwardRabinKarpHashFactory,
@@ -67,6 +143,8 @@ pub enum AssemblerStartingStep {
     #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u32::CanonicalSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u64::CanonicalSeqHashFactory,
     hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u256::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u512::CanonicalSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u32::CanonicalRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u64::CanonicalRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u128::CanonicalRabinKarpHashFactory,
@@ -85,6 +163,7 @@ pub fn run_assembler<
     last_step: AssemblerStartingStep,
     input_blocks: Vec<GeneralSequenceBlockData>,
     color_names: &[String],
+    colormap_encoding: ColormapEncoding,
     output_file: PathBuf,
     temp_dir: Option<PathBuf>,
     threads_count: usize,
@@ -94,19 +173,21 @@ pub fn run_assembler<
     default_compression_level: Option<u32>,
     generate_maximal_unitigs_links: bool,
     compute_tigs_mode: Option<MatchtigMode>,
+    color_compacted_unitigs: bool,
     only_bstats: bool,
-) -> PathBuf {
+    hash_collisions_check: HashCollisionsCheck,
+    kmers_visitor: Option<&(dyn Fn(&[u8], u64, &[ColorIndexType]) + Send + Sync)>,
+) -> Result<PathBuf, AssemblerError> {
     let temp_dir = temp_dir.unwrap_or(PathBuf::new());
 
-    PHASES_TIMES_MONITOR.write().init();
+    progress::start_operation();
 
     let file_stats = compute_stats_from_input_blocks(&input_blocks);
 
     let buckets_count_log = buckets_count_log.unwrap_or_else(|| file_stats.best_buckets_count_log);
 
     if let Some(default_compression_level) = default_compression_level {
-        INTERMEDIATE_COMPRESSION_LEVEL_SLOW.store(default_compression_level, Ordering::Relaxed);
-        INTERMEDIATE_COMPRESSION_LEVEL_FAST.store(default_compression_level, Ordering::Relaxed);
+        instance::with_current(|context| context.set_compression_level(default_compression_level));
     }
 
     let buckets_count = 1 << buckets_count_log;
@@ -115,6 +196,7 @@ pub fn run_assembler<
         AssemblerColorsManager::ColorsMergeManagerType::create_colors_table(
             output_file.with_extension("colors.dat"),
             color_names,
+            colormap_encoding,
         ),
     );
 
@@ -137,19 +219,18 @@ pub fn run_assembler<
         )
     };
 
-    println!(
+    info!(
         "Temp buckets files size: {:.2}",
         MemoryDataSize::from_bytes(fs_extra::dir::get_size(&temp_dir).unwrap_or(0) as usize)
     );
 
+    cancellation::check_cancelled();
+
     if last_step <= AssemblerStartingStep::MinimizerBucketing {
-        PHASES_TIMES_MONITOR
-            .write()
-            .print_stats("Completed minimizer bucketing.".to_string());
-        return PathBuf::new();
+        progress::print_phases_stats("Completed minimizer bucketing.".to_string());
+        return Ok(PathBuf::new());
     } else {
-        MemoryFs::flush_all_to_disk();
-        MemoryFs::free_memory();
+        flush_temp_files();
     }
 
     if only_bstats {
@@ -167,10 +248,16 @@ pub fn run_assembler<
                 m,
             );
         });
-        return PathBuf::new();
+        return Ok(PathBuf::new());
     }
 
-    let RetType { sequences, hashes } = if step <= AssemblerStartingStep::KmersMerge {
+    let RetType {
+        sequences,
+        hashes,
+        abundance_histogram,
+        counted_kmers,
+        hash_collisions,
+    } = if step <= AssemblerStartingStep::KmersMerge {
         assembler_kmers_merge::kmers_merge::<BucketingHash, MergingHash, AssemblerColorsManager, _>(
             buckets,
             counters,
@@ -181,21 +268,59 @@ pub fn run_assembler<
             k,
             m,
             threads_count,
+            kmers_visitor.is_some(),
+            hash_collisions_check.is_enabled(),
         )
     } else {
         RetType {
             sequences: generate_bucket_names(temp_dir.join("result"), buckets_count, None),
             hashes: generate_bucket_names(temp_dir.join("hashes"), buckets_count, None),
+            abundance_histogram: None,
+            counted_kmers: vec![],
+            hash_collisions: 0,
         }
     };
+
+    cancellation::check_cancelled();
+
+    hash_collisions_check.handle_collisions(hash_collisions)?;
+
+    if let Some(abundance_histogram) = abundance_histogram {
+        let histogram_file = output_file.with_extension("histo.tsv");
+        abundance_histogram
+            .write_tsv(&histogram_file)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "Cannot write kmers abundance histogram to {}: {}",
+                    histogram_file.display(),
+                    err
+                )
+            });
+    }
+
+    if let Some(kmers_visitor) = kmers_visitor {
+        // Flush the colormap to disk, so that the kmers color subsets can be decoded
+        drop(global_colors_table);
+
+        counted_kmers_visiting(
+            counted_kmers,
+            if AssemblerColorsManager::COLORS_ENABLED {
+                Some(output_file.with_extension("colors.dat"))
+            } else {
+                None
+            },
+            kmers_visitor,
+        );
+
+        progress::print_phases_stats("Kmers counting completed.".to_string());
+        return Ok(PathBuf::new());
+    }
+
     if last_step <= AssemblerStartingStep::KmersMerge {
-        PHASES_TIMES_MONITOR
-            .write()
-            .print_stats("Completed kmers merge.".to_string());
-        return PathBuf::new();
+        progress::print_phases_stats("Completed kmers merge.".to_string());
+        return Ok(PathBuf::new());
     } else {
-        MemoryFs::flush_all_to_disk();
-        MemoryFs::free_memory();
+        flush_temp_files();
     }
 
     AssemblerColorsManager::ColorsMergeManagerType::print_color_stats(&global_colors_table);
@@ -203,18 +328,23 @@ pub fn run_assembler<
     drop(global_colors_table);
 
     let mut links = if step <= AssemblerStartingStep::HashesSorting {
-        hashes_sorting::<MergingHash, _>(hashes, temp_dir.as_path(), buckets_count)
+        let (links, hash_collisions) =
+            hashes_sorting::<MergingHash, _>(hashes, temp_dir.as_path(), buckets_count);
+        if !MergingHash::INVERTIBLE {
+            hash_collisions_check.handle_collisions(hash_collisions)?;
+        }
+        links
     } else {
         generate_bucket_names(temp_dir.join("links"), buckets_count, None)
     };
+
+    cancellation::check_cancelled();
+
     if last_step <= AssemblerStartingStep::HashesSorting {
-        PHASES_TIMES_MONITOR
-            .write()
-            .print_stats("Hashes sorting.".to_string());
-        return PathBuf::new();
+        progress::print_phases_stats("Hashes sorting.".to_string());
+        return Ok(PathBuf::new());
     } else {
-        MemoryFs::flush_all_to_disk();
-        MemoryFs::free_memory();
+        flush_temp_files();
     }
 
     let mut loop_iteration = loopit_number.unwrap_or(0);
@@ -259,9 +389,7 @@ pub fn run_assembler<
             );
         }
 
-        PHASES_TIMES_MONITOR
-            .write()
-            .start_phase("phase: links compaction".to_string());
+        progress::start_phase("phase: links compaction".to_string());
 
         let mut log_timer = Instant::now();
 
@@ -281,7 +409,7 @@ pub fn run_assembler<
             };
 
             if do_logging {
-                println!("Iteration: {}", loop_iteration);
+                info!("Iteration: {}", loop_iteration);
             }
 
             let (new_links, remaining) = links_compaction(
@@ -296,19 +424,20 @@ pub fn run_assembler<
                 &results_map_scoped_buffer,
             );
 
+            cancellation::check_cancelled();
+
             if do_logging {
-                println!(
-                    "Remaining: {} {}",
-                    remaining,
-                    PHASES_TIMES_MONITOR
-                        .read()
-                        .get_formatted_counter_without_memory()
-                );
+                info!("Remaining: {} {}", remaining, progress::get_times_desc());
             }
 
+            progress::report_progress(|| ProgressEvent::LinksCompaction {
+                iteration: loop_iteration,
+                remaining_links: remaining,
+            });
+
             links = new_links;
             if remaining == 0 {
-                println!("Completed compaction with {} iters", loop_iteration);
+                info!("Completed compaction with {} iters", loop_iteration);
                 break (final_buckets.finalize(), result_map_buckets.finalize());
             }
             loop_iteration += 1;
@@ -318,7 +447,7 @@ pub fn run_assembler<
             MemoryFs::remove_file(
                 &link_file,
                 RemoveFileMode::Remove {
-                    remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                    remove_fs: remove_temp_files(),
                 },
             )
             .unwrap();
@@ -329,23 +458,16 @@ pub fn run_assembler<
     };
 
     if last_step <= AssemblerStartingStep::LinksCompaction {
-        PHASES_TIMES_MONITOR
-            .write()
-            .print_stats("Links Compaction.".to_string());
-        return PathBuf::new();
+        progress::print_phases_stats("Links Compaction.".to_string());
+        return Ok(PathBuf::new());
     } else {
-        MemoryFs::flush_all_to_disk();
-        MemoryFs::free_memory();
+        flush_temp_files();
     }
 
-    let final_unitigs_file = StructuredSequenceWriter::new(match output_file.extension() {
-        Some(ext) => match ext.to_string_lossy().to_string().as_str() {
-            "lz4" => FastaWriter::new_compressed_lz4(&output_file, 2),
-            "gz" => FastaWriter::new_compressed_gzip(&output_file, 2),
-            _ => FastaWriter::new_plain(&output_file),
-        },
-        None => FastaWriter::new_plain(&output_file),
-    });
+    let final_unitigs_file = StructuredSequenceWriter::new(FastaWriter::new_from_extension(
+        &output_file,
+        OUTPUT_COMPRESSION_LEVEL,
+    ));
 
     // Temporary file to store maximal unitigs data without links info, if further processing is requested
     let compressed_temp_unitigs_file =
@@ -397,14 +519,13 @@ pub fn run_assembler<
         )
     };
 
+    cancellation::check_cancelled();
+
     if last_step <= AssemblerStartingStep::ReorganizeReads {
-        PHASES_TIMES_MONITOR
-            .write()
-            .print_stats("Reorganize reads.".to_string());
-        return PathBuf::new();
+        progress::print_phases_stats("Reorganize reads.".to_string());
+        return Ok(PathBuf::new());
     } else {
-        MemoryFs::flush_all_to_disk();
-        MemoryFs::free_memory();
+        flush_temp_files();
     }
 
     // links_manager.compute_id_offsets();
@@ -434,6 +555,8 @@ pub fn run_assembler<
         }
     }
 
+    cancellation::check_cancelled();
+
     if step <= AssemblerStartingStep::MaximalUnitigsLinks {
         if generate_maximal_unitigs_links || compute_tigs_mode.is_some() {
             let compressed_temp_unitigs_file = compressed_temp_unitigs_file.unwrap();
@@ -445,9 +568,11 @@ pub fn run_assembler<
 
                 let matchtigs_receiver = matchtigs_backend.get_receiver();
 
+                let instance_context = instance::current();
                 let handle = std::thread::Builder::new()
                     .name("greedy_matchtigs".to_string())
                     .spawn(move || {
+                        instance::enter(&instance_context);
                         compute_matchtigs_thread::<
                             BucketingHash,
                             MergingHash,
@@ -479,15 +604,9 @@ pub fn run_assembler<
             } else if generate_maximal_unitigs_links {
                 final_unitigs_file.finalize();
 
-                let final_unitigs_file =
-                    StructuredSequenceWriter::new(match output_file.extension() {
-                        Some(ext) => match ext.to_string_lossy().to_string().as_str() {
-                            "lz4" => FastaWriter::new_compressed_lz4(&output_file, 2),
-                            "gz" => FastaWriter::new_compressed_gzip(&output_file, 2),
-                            _ => FastaWriter::new_plain(&output_file),
-                        },
-                        None => FastaWriter::new_plain(&output_file),
-                    });
+                let final_unitigs_file = StructuredSequenceWriter::new(
+                    FastaWriter::new_from_extension(&output_file, OUTPUT_COMPRESSION_LEVEL),
+                );
 
                 build_maximal_unitigs_links::<
                     BucketingHash,
@@ -496,6 +615,19 @@ pub fn run_assembler<
                     FastaWriter<_, _>,
                 >(temp_path, temp_dir.as_path(), &final_unitigs_file, k);
                 final_unitigs_file.finalize();
+
+                if color_compacted_unitigs {
+                    split_color_runs(&output_file, k, OUTPUT_COMPRESSION_LEVEL).map_err(|err| {
+                        std::io::Error::new(
+                            err.kind(),
+                            format!(
+                                "cannot split the color runs of {}: {}",
+                                output_file.display(),
+                                err
+                            ),
+                        )
+                    })?;
+                }
             }
         } else {
             final_unitigs_file.finalize();
@@ -506,9 +638,7 @@ pub fn run_assembler<
 
     let _ = std::fs::remove_dir(temp_dir.as_path());
 
-    PHASES_TIMES_MONITOR
-        .write()
-        .print_stats("Compacted De Bruijn graph construction completed.".to_string());
+    progress::print_phases_stats("Compacted De Bruijn graph construction completed.".to_string());
 
-    output_file
+    Ok(output_file)
 }
@@ -1,6 +1,9 @@
 pub mod build_unitigs;
+pub mod color_compacted_unitigs;
 pub mod compute_matchtigs;
+pub mod counted_kmers_visiting;
 pub mod hashes_sorting;
+pub mod kmers_spectra_estimation;
 pub mod links_compaction;
 pub mod maximal_unitig_links;
 pub mod reorganize_reads;
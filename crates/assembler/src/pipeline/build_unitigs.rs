@@ -2,7 +2,8 @@ use crate::pipeline::reorganize_reads::ReorganizedReadsExtraData;
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::ColorsMergeManager;
 use colors::colors_manager::{color_types, ColorsManager};
-use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use config::progress;
+use config::{remove_temp_files, DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT};
 use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedReadIndipendent;
@@ -17,10 +18,8 @@ use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBi
 use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
 use parallel_processor::buckets::readers::BucketReader;
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use rayon::prelude::*;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::Ordering;
 
 #[derive(Copy, Clone, Debug)]
 struct FinalUnitigInfo {
@@ -74,9 +73,7 @@ pub fn build_unitigs<
     out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, (), BK>,
     k: usize,
 ) {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: unitigs building".to_string());
+    progress::start_phase("phase: unitigs building".to_string());
 
     read_buckets_files.sort();
     unitig_map_files.sort();
@@ -104,7 +101,7 @@ pub fn build_unitigs<
                 let mut unitigs_map_reader = LockFreeBinaryReader::new(
                     &unitigs_map_file,
                     RemoveFileMode::Remove {
-                        remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                        remove_fs: remove_temp_files(),
                     },
                     DEFAULT_PREFETCH_AMOUNT,
                 );
@@ -188,7 +185,7 @@ pub fn build_unitigs<
                 CompressedBinaryReader::new(
                     read_file,
                     RemoveFileMode::Remove {
-                        remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                        remove_fs: remove_temp_files(),
                     },
                     DEFAULT_PREFETCH_AMOUNT,
                 )
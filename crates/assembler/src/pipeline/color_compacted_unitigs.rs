@@ -0,0 +1,368 @@
+use config::{progress, ColorIndexType, DEFAULT_OUTPUT_BUFFER_SIZE};
+use io::concurrent::structured_sequences::fasta::FastaWriter;
+use io::concurrent::structured_sequences::StructuredSequenceBackend;
+use io::sequences_reader::SequencesReader;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Link of a unitig in the BCALM2 format L:<+/->:<other id>:<+/->
+struct UnitigLink {
+    flip_current: bool,
+    other: u64,
+    flip_other: bool,
+}
+
+fn invalid_header(ident: &str, reason: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("invalid unitig header '{}': {}", ident, reason),
+    )
+}
+
+/// Parses the id, the color runs and the links of a unitig header written by FastaWriter.
+/// Consecutive runs with the same colors subset are merged
+fn parse_unitig_header(
+    ident: &[u8],
+    color_runs: &mut Vec<(ColorIndexType, u64)>,
+    links: &mut Vec<UnitigLink>,
+) -> std::io::Result<u64> {
+    color_runs.clear();
+    links.clear();
+
+    let ident = String::from_utf8_lossy(ident);
+    let mut fields = ident.trim_start_matches('>').split_whitespace();
+    let id = fields
+        .next()
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| invalid_header(&ident, "missing unitig id"))?;
+
+    for field in fields {
+        if let Some(color) = field.strip_prefix("C:") {
+            let (subset, count) = color
+                .split_once(':')
+                .and_then(|(subset, count)| {
+                    Some((
+                        ColorIndexType::from_str_radix(subset, 16).ok()?,
+                        count.parse::<u64>().ok()?,
+                    ))
+                })
+                .ok_or_else(|| invalid_header(&ident, "malformed color run"))?;
+            match color_runs.last_mut() {
+                Some(last) if last.0 == subset => last.1 += count,
+                _ => color_runs.push((subset, count)),
+            }
+        } else if let Some(link) = field.strip_prefix("L:") {
+            let mut parts = link.split(':');
+            let flip_current = parts.next() == Some("-");
+            let other = parts
+                .next()
+                .and_then(|other| other.parse().ok())
+                .ok_or_else(|| invalid_header(&ident, "malformed link"))?;
+            links.push(UnitigLink {
+                flip_current,
+                other,
+                flip_other: parts.next() == Some("-"),
+            });
+        }
+    }
+
+    Ok(id)
+}
+
+fn link_sign(flip: bool) -> &'static str {
+    if flip {
+        "-"
+    } else {
+        "+"
+    }
+}
+
+/// Original graph moved aside while the split one is written, restored if the split does not complete
+struct UnsplitGraph<'a> {
+    path: PathBuf,
+    graph: &'a Path,
+    completed: bool,
+}
+
+impl Drop for UnsplitGraph<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            let _ = std::fs::remove_file(self.graph);
+            let _ = std::fs::rename(&self.path, self.graph);
+        } else {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Splits the maximal unitigs of a colored graph with links at each change of colors subset, so that every
+/// output sequence is a maximal path with a uniform colors subset. Consecutive pieces of the same unitig are
+/// linked together, while the links of each unitig are moved to its first (start side) or last (end side) piece.
+/// The graph is rewritten in place with the given compression level, and left unchanged if an error occurs
+pub fn split_color_runs(graph: &Path, k: usize, compression_level: u32) -> std::io::Result<()> {
+    progress::start_phase("phase: color compacted unitigs".to_string());
+
+    let file_name = graph.file_name().unwrap_or_default().to_string_lossy();
+    let mut unsplit_graph = UnsplitGraph {
+        path: graph.with_file_name(format!("unsplit.{}", file_name)),
+        graph,
+        completed: false,
+    };
+    std::fs::rename(graph, &unsplit_graph.path)?;
+
+    let mut color_runs = Vec::new();
+    let mut links = Vec::new();
+    let mut error = None;
+
+    // First piece and pieces count of each unitig, the pieces are numbered in the order of the unitigs in the file
+    let mut unitigs_pieces: Vec<(u64, u64)> = Vec::new();
+    let mut pieces_count = 0;
+
+    SequencesReader::new().process_file_extended(
+        &unsplit_graph.path,
+        |seq| {
+            if error.is_some() {
+                return;
+            }
+            let id = match parse_unitig_header(seq.ident_data, &mut color_runs, &mut links) {
+                Ok(id) => id as usize,
+                Err(err) => {
+                    error = Some(err);
+                    return;
+                }
+            };
+            if unitigs_pieces.len() <= id {
+                unitigs_pieces.resize(id + 1, (0, 0));
+            }
+            let count = color_runs.len().max(1) as u64;
+            unitigs_pieces[id] = (pieces_count, count);
+            pieces_count += count;
+        },
+        None,
+        true,
+        false,
+    );
+
+    if let Some(error) = error.take() {
+        return Err(error);
+    }
+
+    let mut writer = FastaWriter::<(), ()>::new_from_extension(graph, compression_level);
+
+    let mut buffer = Vec::new();
+    SequencesReader::new().process_file_extended(
+        &unsplit_graph.path,
+        |seq| {
+            if error.is_some() {
+                return;
+            }
+            if let Err(err) = write_unitig_pieces(
+                seq.ident_data,
+                seq.seq,
+                k,
+                &unitigs_pieces,
+                &mut color_runs,
+                &mut links,
+                &mut buffer,
+            ) {
+                error = Some(err);
+                return;
+            }
+
+            if buffer.len() >= DEFAULT_OUTPUT_BUFFER_SIZE {
+                writer.flush_temp_buffer(&mut buffer);
+            }
+        },
+        None,
+        true,
+        false,
+    );
+
+    if let Some(error) = error.take() {
+        return Err(error);
+    }
+
+    writer.flush_temp_buffer(&mut buffer);
+    drop(writer);
+
+    unsplit_graph.completed = true;
+    Ok(())
+}
+
+/// Returns the first or the last piece of a unitig, None if the unitig is missing from the graph
+fn piece_at_side(unitigs_pieces: &[(u64, u64)], unitig: u64, end_side: bool) -> Option<u64> {
+    match unitigs_pieces.get(unitig as usize) {
+        Some(&(first, count)) if count > 0 => {
+            Some(if end_side { first + count - 1 } else { first })
+        }
+        _ => None,
+    }
+}
+
+/// Writes to the buffer the pieces of a single unitig, one for each of its color runs
+fn write_unitig_pieces(
+    ident: &[u8],
+    sequence: &[u8],
+    k: usize,
+    unitigs_pieces: &[(u64, u64)],
+    color_runs: &mut Vec<(ColorIndexType, u64)>,
+    links: &mut Vec<UnitigLink>,
+    buffer: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    let id = parse_unitig_header(ident, color_runs, links)?;
+    let (first_piece, count) = unitigs_pieces[id as usize];
+
+    let invalid_unitig = |reason: &str| invalid_header(&String::from_utf8_lossy(ident), reason);
+
+    let kmers_count = (sequence.len() + 1)
+        .checked_sub(k)
+        .ok_or_else(|| invalid_unitig("sequence shorter than k"))? as u64;
+
+    // A unitig without colors is written as a single piece
+    let has_colors = !color_runs.is_empty();
+    if !has_colors {
+        color_runs.push((0, kmers_count));
+    }
+    if color_runs.iter().map(|r| r.1).sum::<u64>() != kmers_count {
+        return Err(invalid_unitig(
+            "the color runs do not cover the unitig kmers",
+        ));
+    }
+
+    let links_pieces = links
+        .iter()
+        .map(|link| {
+            piece_at_side(unitigs_pieces, link.other, link.flip_other)
+                .ok_or_else(|| invalid_unitig("link to a missing unitig"))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let mut kmers_offset = 0;
+    for (index, (subset, kmers)) in color_runs.iter().enumerate() {
+        let piece = first_piece + index as u64;
+        let piece_sequence = &sequence[kmers_offset..kmers_offset + *kmers as usize + k - 1];
+        kmers_offset += *kmers as usize;
+
+        write!(buffer, ">{} LN:i:{}", piece, piece_sequence.len())?;
+        if has_colors {
+            write!(buffer, " C:{:x}:{}", subset, kmers)?;
+        }
+
+        // Links of the start side, the previous piece or the links of the original unitig start
+        if index > 0 {
+            write!(buffer, " L:-:{}:-", piece - 1)?;
+        } else {
+            for (link, other_piece) in links.iter().zip(links_pieces.iter()) {
+                if link.flip_current {
+                    write!(
+                        buffer,
+                        " L:-:{}:{}",
+                        other_piece,
+                        link_sign(link.flip_other)
+                    )?;
+                }
+            }
+        }
+
+        // Links of the end side, the next piece or the links of the original unitig end
+        if (index as u64) + 1 < count {
+            write!(buffer, " L:+:{}:+", piece + 1)?;
+        } else {
+            for (link, other_piece) in links.iter().zip(links_pieces.iter()) {
+                if !link.flip_current {
+                    write!(
+                        buffer,
+                        " L:+:{}:{}",
+                        other_piece,
+                        link_sign(link.flip_other)
+                    )?;
+                }
+            }
+        }
+
+        buffer.push(b'\n');
+        buffer.extend_from_slice(piece_sequence);
+        buffer.push(b'\n');
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_graph_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ggcat-test-{}-{}.fa", std::process::id(), name))
+    }
+
+    fn unsplit_graph_exists(graph: &Path) -> bool {
+        graph
+            .with_file_name(format!(
+                "unsplit.{}",
+                graph.file_name().unwrap().to_string_lossy()
+            ))
+            .exists()
+    }
+
+    #[test]
+    fn split_linked_graph() {
+        let graph = temp_graph_path("split-linked-graph");
+        std::fs::write(
+            &graph,
+            concat!(
+                ">0 LN:i:5 C:1:2 C:2:1 L:+:1:+ L:+:0:- L:-:2:+ L:-:2:-\n",
+                "AAAAC\n",
+                ">1 LN:i:4 C:3:1 C:3:1 L:-:0:- L:-:1:+\n",
+                "CCCG\n",
+                ">2 LN:i:5 C:1:1 C:4:1 C:5:1 L:+:2:+ L:+:0:-\n",
+                "GGGTT\n",
+                ">3 LN:i:4\n",
+                "TTTT\n",
+            ),
+        )
+        .unwrap();
+
+        let result = split_color_runs(&graph, 3, 0);
+        let split_graph = std::fs::read_to_string(&graph).unwrap();
+        std::fs::remove_file(&graph).unwrap();
+        result.unwrap();
+
+        assert_eq!(
+            split_graph,
+            concat!(
+                ">0 LN:i:4 C:1:2 L:-:3:+ L:-:5:- L:+:1:+\n",
+                "AAAA\n",
+                ">1 LN:i:3 C:2:1 L:-:0:- L:+:2:+ L:+:1:-\n",
+                "AAC\n",
+                ">2 LN:i:4 C:3:2 L:-:1:- L:-:2:+\n",
+                "CCCG\n",
+                ">3 LN:i:3 C:1:1 L:+:4:+\n",
+                "GGG\n",
+                ">4 LN:i:3 C:4:1 L:-:3:- L:+:5:+\n",
+                "GGT\n",
+                ">5 LN:i:3 C:5:1 L:-:4:- L:+:3:+ L:+:1:-\n",
+                "GTT\n",
+                ">6 LN:i:4\n",
+                "TTTT\n",
+            )
+        );
+        assert!(!unsplit_graph_exists(&graph));
+    }
+
+    #[test]
+    fn split_invalid_graph() {
+        let graph = temp_graph_path("split-invalid-graph");
+        let contents = concat!(">0 LN:i:5 C:1:2 L:+:1:+\n", "AAAAC\n");
+        std::fs::write(&graph, contents).unwrap();
+
+        let result = split_color_runs(&graph, 3, 0);
+        let restored_graph = std::fs::read_to_string(&graph).unwrap();
+        std::fs::remove_file(&graph).unwrap();
+
+        // The color runs do not cover the kmers and the link points to a missing unitig
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(restored_graph, contents);
+        assert!(!unsplit_graph_exists(&graph));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::pipeline::maximal_unitig_links::maximal_unitig_index::DoubleMaximalUnitigLinks;
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::{color_types, ColorsManager, ColorsMergeManager};
+use config::progress;
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
 use crossbeam::channel::{Receiver, Sender};
 use genome_graph::bigraph::implementation::node_bigraph_wrapper::NodeBigraphWrapper;
@@ -19,7 +20,6 @@ use libmatchtigs::{
     EulertigAlgorithm, EulertigAlgorithmConfiguration, MatchtigEdgeData, PathtigAlgorithm,
 };
 use libmatchtigs::{GreedytigAlgorithm, GreedytigAlgorithmConfiguration, TigAlgorithm};
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::convert::identity;
 use std::ops::Deref;
 use std::path::PathBuf;
@@ -335,9 +335,7 @@ pub fn compute_matchtigs_thread<
         MatchtigMode::PathTigs => "pathtigs",
     };
 
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase(format!("phase: {} building [step1]", phase_name));
+    progress::start_phase(format!("phase: {} building [step1]", phase_name));
 
     /* assign weight to each edge */
     for edge_index in graph.edge_indices() {
@@ -376,9 +374,7 @@ pub fn compute_matchtigs_thread<
         }
     };
 
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase(format!("phase: {} building [step2]", phase_name));
+    progress::start_phase(format!("phase: {} building [step2]", phase_name));
 
     let mut output_buffer =
         FastaWriterConcurrentBuffer::new(&out_file, DEFAULT_OUTPUT_BUFFER_SIZE, true);
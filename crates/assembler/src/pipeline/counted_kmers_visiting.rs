@@ -0,0 +1,58 @@
+use assembler_kmers_merge::structs::CountedKmerSerializer;
+use colors::storage::deserializer::ColorsDeserializer;
+use config::progress;
+use config::{remove_temp_files, ColorIndexType, DEFAULT_PREFETCH_AMOUNT};
+use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::memory_fs::RemoveFileMode;
+use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
+use rayon::prelude::*;
+use std::path::PathBuf;
+
+/// Calls the visitor on each solid kmer dumped by the kmers merge step, with its multiplicity and
+/// its colors (empty if colors are disabled). The kmers are passed as plain bases, in the same
+/// orientation they had in the hashmap, so they are not guaranteed to be canonical.
+pub fn counted_kmers_visiting(
+    counted_kmers: Vec<PathBuf>,
+    colormap_file: Option<PathBuf>,
+    visitor: &(dyn Fn(&[u8], u64, &[ColorIndexType]) + Send + Sync),
+) {
+    progress::start_phase("phase: counted kmers visiting".to_string());
+
+    let tlocal_colormap_decoder = colormap_file.map(|colormap_file| {
+        ScopedThreadLocal::new(move || ColorsDeserializer::new(&colormap_file, false))
+    });
+
+    counted_kmers.par_iter().for_each(|input| {
+        let mut colormap_decoder = tlocal_colormap_decoder.as_ref().map(|d| d.get());
+        let mut colors_buffer = Vec::new();
+        let mut last_color = None;
+        let mut kmer_bases = Vec::new();
+
+        CompressedBinaryReader::new(
+            input,
+            RemoveFileMode::Remove {
+                remove_fs: remove_temp_files(),
+            },
+            DEFAULT_PREFETCH_AMOUNT,
+        )
+        .decode_all_bucket_items::<CountedKmerSerializer, _>(
+            vec![],
+            &mut (),
+            |counted_kmer, _| {
+                if let Some(colormap_decoder) = &mut colormap_decoder {
+                    if last_color != Some(counted_kmer.color) {
+                        colors_buffer.clear();
+                        colormap_decoder.get_color_mappings(counted_kmer.color, &mut colors_buffer);
+                        last_color = Some(counted_kmer.color);
+                    }
+                }
+
+                kmer_bases.clear();
+                kmer_bases.extend(counted_kmer.kmer.as_bases_iter());
+
+                visitor(&kmer_bases, counted_kmer.multiplicity, &colors_buffer);
+            },
+        );
+    });
+}
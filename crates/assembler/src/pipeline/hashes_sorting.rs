@@ -1,12 +1,16 @@
+use config::memory_budget::MemoryReservation;
+use config::progress;
+use log::warn;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use config::{
-    get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+    get_memory_mode, remove_temp_files, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
+    DEFAULT_PREFETCH_AMOUNT,
 };
 use hashes::HashFunctionFactory;
-use io::structs::hash_entry::{Direction, HashCompare, HashEntrySerializer};
+use io::structs::hash_entry::{Direction, HashCompare, HashEntry, HashEntrySerializer};
 use io::structs::unitig_link::{UnitigFlags, UnitigIndex, UnitigLink, UnitigLinkSerializer};
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
@@ -15,21 +19,22 @@ use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinar
 use parallel_processor::buckets::MultiThreadBuckets;
 use parallel_processor::fast_smart_bucket_sort::fast_smart_radix_sort;
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
 use utils::fast_rand_bool::FastRandBool;
 use utils::vec_slice::VecSlice;
 
+/// Links the unitigs ending with the same kmer, returning the links buckets and the number of hash collisions found.
+/// Only the kmers hashes are stored, so a collision is detected only if it results in more than two equal hashes,
+/// that are left unlinked. Two unitig ends with different kmers sharing the same hash look exactly like the two ends
+/// of a real link, so such a collision is not detected and the two unitigs are wrongly joined
 pub fn hashes_sorting<H: HashFunctionFactory, P: AsRef<Path>>(
     file_hashes_inputs: Vec<PathBuf>,
     output_dir: P,
     buckets_count: usize,
-) -> Vec<PathBuf> {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: hashes sorting".to_string());
+) -> (Vec<PathBuf>, u64) {
+    progress::start_phase("phase: hashes sorting".to_string());
 
     let links_buckets = Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
         buckets_count,
@@ -40,6 +45,8 @@ pub fn hashes_sorting<H: HashFunctionFactory, P: AsRef<Path>>(
         ),
     ));
 
+    let hash_collisions_count = AtomicU64::new(0);
+
     let buckets_thread_buffers = ScopedThreadLocal::new(move || {
         BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets_count)
     });
@@ -59,11 +66,15 @@ pub fn hashes_sorting<H: HashFunctionFactory, P: AsRef<Path>>(
             let mut hashes_vec = Vec::new();
 
             LockFreeBinaryReader::new(input, RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed)
+                remove_fs: remove_temp_files()
             }, DEFAULT_PREFETCH_AMOUNT).decode_all_bucket_items::<HashEntrySerializer<H::HashTypeUnextendable>, _>((), &mut (), |h, _| {
                 hashes_vec.push(h);
             });
 
+            let _hashes_reservation = MemoryReservation::with_size(
+                hashes_vec.capacity() * std::mem::size_of::<HashEntry<H::HashTypeUnextendable>>()
+            );
+
             fast_smart_radix_sort::<_, HashCompare<H>, false>(&mut hashes_vec[..]);
 
             let mut unitigs_vec = Vec::new();
@@ -113,14 +124,15 @@ pub fn hashes_sorting<H: HashFunctionFactory, P: AsRef<Path>>(
                         );
                     },
                     1 => {
-                        println!("Warning spurious hash detected ({:?}) with index {}, this is a bug or a collision in the KmersMerge phase!", x[0].hash, x[0].entry());
+                        warn!("Spurious hash detected ({:?}) with index {}, this is a bug or a collision in the KmersMerge phase!", x[0].hash, x[0].entry());
                     }
                     _ => {
-                        println!("More than 2 equal hashes found in hashes sorting phase, this indicates an hash ({}) collision!",  x[0].hash);
+                        hash_collisions_count.fetch_add(1, Ordering::Relaxed);
+                        warn!("More than 2 equal hashes found in hashes sorting phase, this indicates an hash ({}) collision!",  x[0].hash);
                     }
                 }
             }
             buffers.put_back(links_tmp.finalize().0);
         });
-    links_buckets.finalize()
+    (links_buckets.finalize(), hash_collisions_count.into_inner())
 }
@@ -0,0 +1,231 @@
+use config::{instance, progress};
+use crossbeam::channel::TrySendError;
+use hashbrown::HashMap;
+use hashes::{ExtendableHashTraitType, HashFunction, HashFunctionFactory};
+use io::sequences_stream::general::{GeneralSequenceBlockData, GeneralSequencesStream};
+use io::sequences_stream::GenericSequencesStream;
+use parking_lot::Mutex;
+
+/// Counting one kmer out of 64 still samples millions of kmers from a typical sequencing dataset,
+/// enough to rank the candidate k values, while using 1/64 of the memory and time of an exact count
+pub const DEFAULT_KMERS_SAMPLING_LOG: usize = 6;
+
+/// The sampled kmers of each k are split by hash in 256 shards, so that
+/// the threads rarely wait on each other when merging their counts
+const SHARDS_COUNT_LOG: usize = 8;
+
+/// Bases read from the input before handing them to a counting thread, large enough to make
+/// the channel overhead negligible and small enough to split a single input file between all threads
+const SEQUENCES_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of kmers counted by a thread before merging them into the shared shards,
+/// bounding the memory used by the thread local counts
+const MAX_LOCAL_SAMPLED_KMERS: usize = 1024 * 1024 * 4;
+
+/// Estimated kmers spectrum for a single k value
+#[derive(Copy, Clone, Debug)]
+pub struct KmersSpectrumEstimate {
+    pub k: usize,
+    /// Estimated number of distinct kmers
+    pub distinct_kmers: u64,
+    /// Estimated number of distinct kmers with multiplicity at least min_multiplicity
+    pub solid_kmers: u64,
+}
+
+/// Sequences read from the input, already split on the non ACGT bases and converted to uppercase
+#[derive(Default)]
+struct SequencesChunk {
+    bases: Vec<u8>,
+    ends: Vec<usize>,
+}
+
+impl SequencesChunk {
+    fn iter_sequences(&self) -> impl Iterator<Item = &[u8]> {
+        let starts = std::iter::once(0).chain(self.ends.iter().copied());
+        starts
+            .zip(self.ends.iter().copied())
+            .map(|(start, end)| &self.bases[start..end])
+    }
+}
+
+/// Estimates the number of distinct and solid kmers of the input for each of the candidate k values.
+/// Like in minimizer bucketing, the kmers are split by their hash and only those in the first
+/// of the 2^sampling_log buckets are counted exactly, so the counts are scaled by the same factor.
+/// The input files are read in chunks of sequences, so that also a single file is counted by all the threads
+pub fn estimate_kmers_spectra<H: HashFunctionFactory<HashTypeUnextendable = u64>>(
+    input_blocks: &[GeneralSequenceBlockData],
+    k_candidates: &[usize],
+    min_multiplicity: usize,
+    sampling_log: usize,
+    threads_count: usize,
+) -> Vec<KmersSpectrumEstimate> {
+    progress::start_phase("phase: kmers spectra estimation".to_string());
+
+    let shards: Vec<Vec<Mutex<HashMap<u64, u32>>>> = k_candidates
+        .iter()
+        .map(|_| {
+            (0..(1 << SHARDS_COUNT_LOG))
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect()
+        })
+        .collect();
+
+    let Some(&min_k) = k_candidates.iter().min() else {
+        return vec![];
+    };
+
+    let threads_count = threads_count.max(1);
+    let next_block = Mutex::new(input_blocks.iter());
+    let (chunks_sender, chunks_receiver) = crossbeam::channel::bounded(threads_count * 2);
+
+    let merge_sampled_kmers = |sampled_kmers: &mut Vec<HashMap<u64, u32>>| {
+        for (sampled_kmers, shards) in sampled_kmers.iter_mut().zip(shards.iter()) {
+            let mut shards_kmers: Vec<Vec<_>> = (0..shards.len()).map(|_| Vec::new()).collect();
+            for (hash, count) in sampled_kmers.drain() {
+                shards_kmers[H::get_bucket(sampling_log, SHARDS_COUNT_LOG, hash) as usize]
+                    .push((hash, count));
+            }
+
+            for (shard_kmers, shard) in shards_kmers.into_iter().zip(shards.iter()) {
+                let mut shard = shard.lock();
+                for (hash, count) in shard_kmers {
+                    *shard.entry(hash).or_insert(0) += count;
+                }
+            }
+        }
+    };
+
+    let count_chunk = |chunk: &SequencesChunk, sampled_kmers: &mut Vec<HashMap<u64, u32>>| {
+        for sequence in chunk.iter_sequences() {
+            for (&k, sampled_kmers) in k_candidates.iter().zip(sampled_kmers.iter_mut()) {
+                if sequence.len() < k {
+                    continue;
+                }
+
+                for hash in H::new(sequence, k).iter() {
+                    let hash = hash.to_unextendable();
+                    if H::get_bucket(0, sampling_log, hash) == 0 {
+                        *sampled_kmers.entry(hash).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        let local_kmers_count: usize = sampled_kmers.iter().map(|kmers| kmers.len()).sum();
+        if local_kmers_count >= MAX_LOCAL_SAMPLED_KMERS {
+            merge_sampled_kmers(sampled_kmers);
+        }
+    };
+
+    let instance_context = &instance::current();
+    let next_block = &next_block;
+    let merge_sampled_kmers = &merge_sampled_kmers;
+    let count_chunk = &count_chunk;
+
+    // Each thread both reads the input blocks and counts the chunks read by all the threads, so that
+    // the estimation never uses more than threads_count threads. A chunk is counted by the thread
+    // that read it if the other threads are all busy, and each thread counts the chunks left
+    // in the channel before exiting, so all the chunks sent are counted
+    std::thread::scope(|scope| {
+        for _ in 0..threads_count {
+            let chunks_sender = chunks_sender.clone();
+            let chunks_receiver = chunks_receiver.clone();
+            scope.spawn(move || {
+                instance::enter(instance_context);
+                let mut sampled_kmers: Vec<HashMap<u64, u32>> =
+                    k_candidates.iter().map(|_| HashMap::new()).collect();
+
+                let send_or_count =
+                    |chunk: SequencesChunk, sampled_kmers: &mut Vec<HashMap<u64, u32>>| {
+                        if let Err(TrySendError::Full(chunk)) = chunks_sender.try_send(chunk) {
+                            count_chunk(&chunk, sampled_kmers);
+                        }
+                    };
+
+                loop {
+                    if let Ok(chunk) = chunks_receiver.try_recv() {
+                        count_chunk(&chunk, &mut sampled_kmers);
+                        continue;
+                    }
+
+                    let Some(block) = next_block.lock().next() else {
+                        break;
+                    };
+
+                    let mut chunk = SequencesChunk::default();
+                    GeneralSequencesStream::new().read_block(
+                        block,
+                        false,
+                        None,
+                        |sequence, _info| {
+                            for part in sequence
+                                .seq
+                                .split(|base| {
+                                    !matches!(
+                                        *base,
+                                        b'A' | b'C' | b'G' | b'T' | b'a' | b'c' | b'g' | b't'
+                                    )
+                                })
+                                .filter(|part| part.len() >= min_k)
+                            {
+                                let part_start = chunk.bases.len();
+                                chunk.bases.extend_from_slice(part);
+                                chunk.bases[part_start..].make_ascii_uppercase();
+                                chunk.ends.push(chunk.bases.len());
+                            }
+
+                            if chunk.bases.len() >= SEQUENCES_CHUNK_SIZE {
+                                send_or_count(std::mem::take(&mut chunk), &mut sampled_kmers);
+                            }
+                        },
+                    );
+
+                    if !chunk.ends.is_empty() {
+                        send_or_count(chunk, &mut sampled_kmers);
+                    }
+                }
+
+                while let Ok(chunk) = chunks_receiver.try_recv() {
+                    count_chunk(&chunk, &mut sampled_kmers);
+                }
+
+                merge_sampled_kmers(&mut sampled_kmers);
+            });
+        }
+    });
+
+    let scale = 1u64 << sampling_log;
+
+    k_candidates
+        .iter()
+        .zip(shards.into_iter())
+        .map(|(&k, shards)| {
+            let mut distinct_kmers = 0;
+            let mut solid_kmers = 0;
+            for shard in shards {
+                let shard = shard.into_inner();
+                distinct_kmers += shard.len() as u64;
+                solid_kmers += shard
+                    .values()
+                    .filter(|count| **count as usize >= min_multiplicity)
+                    .count() as u64;
+            }
+
+            KmersSpectrumEstimate {
+                k,
+                distinct_kmers: distinct_kmers * scale,
+                solid_kmers: solid_kmers * scale,
+            }
+        })
+        .collect()
+}
+
+/// Returns the k value with the highest estimated number of solid kmers, preferring the smaller k on ties
+pub fn best_k_from_spectra(spectra: &[KmersSpectrumEstimate]) -> Option<usize> {
+    spectra
+        .iter()
+        .rev()
+        .filter(|estimate| estimate.solid_kmers > 0)
+        .max_by_key(|estimate| estimate.solid_kmers)
+        .map(|estimate| estimate.k)
+}
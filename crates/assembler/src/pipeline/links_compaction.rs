@@ -1,6 +1,9 @@
 use crate::structs::link_mapping::{LinkMapping, LinkMappingSerializer};
+use config::cancellation;
+use config::memory_budget::MemoryReservation;
 use config::{
-    get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+    get_memory_mode, remove_temp_files, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
+    DEFAULT_PREFETCH_AMOUNT,
 };
 use io::get_bucket_index;
 use io::structs::unitig_link::{UnitigFlags, UnitigIndex, UnitigLink, UnitigLinkSerializer};
@@ -47,6 +50,10 @@ pub fn links_compaction(
     ));
 
     links_inputs.par_iter().for_each(|input| {
+        if cancellation::is_cancelled() {
+            return;
+        }
+
         let bucket_index = get_bucket_index(input);
 
         let mut link_buffers = link_thread_buffers.get();
@@ -72,7 +79,7 @@ pub fn links_compaction(
         let file_reader = LockFreeBinaryReader::new(
             input,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         );
@@ -93,6 +100,11 @@ pub fn links_compaction(
 
         drop(file_reader);
 
+        let _links_reservation = MemoryReservation::with_size(
+            vec.capacity() * std::mem::size_of::<UnitigLink>()
+                + last_unitigs_vec.capacity() * std::mem::size_of::<UnitigIndex>(),
+        );
+
         struct Compare;
         impl SortKey<UnitigLink> for Compare {
             type KeyType = u32;
@@ -14,9 +14,10 @@ use crate::pipeline::maximal_unitig_links::maximal_unitig_index::{
 };
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::ColorsManager;
+use config::progress;
 use config::{
-    get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
-    DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+    get_compression_level_info, get_memory_mode, remove_temp_files, BucketIndexType, SwapPriority,
+    DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT,
 };
 use hashes::ExtendableHashTraitType;
 use hashes::{HashFunction, HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
@@ -31,7 +32,6 @@ use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBi
 use parallel_processor::buckets::MultiThreadBuckets;
 use parallel_processor::fast_smart_bucket_sort::fast_smart_radix_sort;
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use rayon::prelude::*;
 use std::cmp::max;
@@ -64,9 +64,7 @@ pub fn build_maximal_unitigs_links<
     let (step_1_hash_files, unitigs_count) = {
         let unitigs_count = AtomicU64::new(0);
 
-        PHASES_TIMES_MONITOR
-            .write()
-            .start_phase("phase: maximal unitigs links building [step 1]".to_string());
+        progress::start_phase("phase: maximal unitigs links building [step 1]".to_string());
 
         let maximal_unitigs_reader_step1 =
             CompressedBinaryReader::new(&in_file, RemoveFileMode::Keep, DEFAULT_PREFETCH_AMOUNT);
@@ -213,9 +211,7 @@ pub fn build_maximal_unitigs_links<
 
     // Sort the hashes
     let maximal_unitig_links_data_step2 = {
-        PHASES_TIMES_MONITOR
-            .write()
-            .start_phase("phase: maximal unitigs links building [step 2]".to_string());
+        progress::start_phase("phase: maximal unitigs links building [step 2]".to_string());
 
         let maximal_links_buckets = Arc::new(MultiThreadBuckets::<CompressedBinaryWriter>::new(
             buckets_count,
@@ -244,7 +240,7 @@ pub fn build_maximal_unitigs_links<
             CompressedBinaryReader::new(
                 input,
                 RemoveFileMode::Remove {
-                    remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                    remove_fs: remove_temp_files(),
                 },
                 DEFAULT_PREFETCH_AMOUNT,
             )
@@ -301,9 +297,7 @@ pub fn build_maximal_unitigs_links<
 
     // Rewrite the output file to include found links
     {
-        PHASES_TIMES_MONITOR
-            .write()
-            .start_phase("phase: maximal unitigs links building [step 3]".to_string());
+        progress::start_phase("phase: maximal unitigs links building [step 3]".to_string());
 
         let mappings_loader = MaximalUnitigLinksMappingsLoader::new(
             maximal_unitig_links_data_step2,
@@ -314,7 +308,7 @@ pub fn build_maximal_unitigs_links<
         let maximal_unitigs_reader_step3 = CompressedBinaryReader::new(
             &in_file,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         );
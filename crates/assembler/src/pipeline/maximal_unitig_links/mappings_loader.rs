@@ -1,7 +1,7 @@
 use crate::pipeline::maximal_unitig_links::maximal_unitig_index::{
     DoubleMaximalUnitigLinks, MaximalUnitigIndex, MaximalUnitigLink,
 };
-use config::{DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use config::{remove_temp_files, DEFAULT_PREFETCH_AMOUNT};
 use parallel_processor::buckets::bucket_writer::BucketItemSerializer;
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::memory_fs::RemoveFileMode;
@@ -45,7 +45,7 @@ impl MaximalUnitigLinksMapping {
         let mut reader = CompressedBinaryReader::new(
             bucket,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         );
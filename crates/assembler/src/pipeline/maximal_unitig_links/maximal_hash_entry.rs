@@ -112,7 +112,7 @@ impl<H: HashFunctionFactory> SortKey<MaximalHashEntry<H::HashTypeUnextendable>>
     for MaximalHashCompare<H>
 {
     type KeyType = H::HashTypeUnextendable;
-    const KEY_BITS: usize = size_of::<H::HashTypeUnextendable>() * 8;
+    const KEY_BITS: usize = H::SORTING_KEY_BITS;
 
     #[inline(always)]
     fn compare(
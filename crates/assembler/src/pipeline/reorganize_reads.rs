@@ -1,6 +1,7 @@
+use config::progress;
 use config::{
-    get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
-    DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+    get_compression_level_info, get_memory_mode, remove_temp_files, SwapPriority,
+    DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT,
 };
 use hashes::{HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
 use io::concurrent::temp_reads::creads_utils::{
@@ -27,14 +28,12 @@ use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBi
 use parallel_processor::buckets::MultiThreadBuckets;
 use parallel_processor::fast_smart_bucket_sort::{fast_smart_radix_sort, SortKey};
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
 use std::io::{Read, Write};
 use std::mem::transmute;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 #[derive(Clone, Debug)]
@@ -139,9 +138,7 @@ pub fn reorganize_reads<
     out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, (), BK>,
     buckets_count: usize,
 ) -> (Vec<PathBuf>, PathBuf) {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: reads reorganization".to_string());
+    progress::start_phase("phase: reads reorganization".to_string());
 
     let buckets = Arc::new(MultiThreadBuckets::<CompressedBinaryWriter>::new(
         buckets_count,
@@ -186,7 +183,7 @@ pub fn reorganize_reads<
         LockFreeBinaryReader::new(
             mapping_file,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         )
@@ -208,7 +205,7 @@ pub fn reorganize_reads<
         CompressedBinaryReader::new(
             read_file,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         )
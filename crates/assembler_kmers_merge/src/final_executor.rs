@@ -1,4 +1,5 @@
 use crate::map_processor::ParallelKmersMergeMapPacket;
+use crate::structs::{CountedKmer, CountedKmerSerializer, KmersAbundanceHistogram};
 use crate::{GlobalMergeData, ParallelKmersMergeFactory, ResultsBucket};
 use colors::colors_manager::ColorsMergeManager;
 use colors::colors_manager::{color_types, ColorsManager};
@@ -14,6 +15,7 @@ use io::structs::hash_entry::{Direction, HashEntrySerializer};
 use io::varint::decode_varint;
 use kmers_transform::{KmersTransformExecutorFactory, KmersTransformFinalExecutor};
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
+use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
 use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
 use parallel_processor::execution_manager::packet::Packet;
 use std::marker::PhantomData;
@@ -41,6 +43,10 @@ pub struct ParallelKmersMergeFinalExecutor<
         <color_types::PartialUnitigsColorStructure<H, MH, CX> as SequenceExtraDataTempBufferManagement>::TempBuffer,
     bucket_counter: usize,
     bucket_change_threshold: usize,
+    abundance_histogram: KmersAbundanceHistogram,
+    counted_kmers_tmp: Option<BucketsThreadDispatcher<CompressedBinaryWriter, CountedKmerSerializer>>,
+    counted_kmers_bases: Vec<u8>,
+    counted_kmers_list: Vec<(MH::HashTypeUnextendable, usize, u64)>,
     _phantom: PhantomData<H>,
 }
 
@@ -62,6 +68,18 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 color_types::PartialUnitigsColorStructure::<H, MH, CX>::new_temp_buffer(),
             bucket_counter: 0,
             bucket_change_threshold: 16, // TODO: Parametrize
+            abundance_histogram: KmersAbundanceHistogram::new(),
+            counted_kmers_tmp: global_data.counted_kmers_buckets.as_ref().map(|buckets| {
+                BucketsThreadDispatcher::new(
+                    buckets,
+                    BucketsThreadBuffer::new(
+                        DEFAULT_PER_CPU_BUFFER_SIZE,
+                        global_data.buckets_count,
+                    ),
+                )
+            }),
+            counted_kmers_bases: vec![],
+            counted_kmers_list: vec![],
             _phantom: PhantomData,
         }
     }
@@ -162,6 +180,68 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             }
         }
     }
+
+    /// Writes all the solid kmers of the map with their multiplicity and color subset, in place of the unitigs
+    fn dump_counted_kmers(
+        &mut self,
+        global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
+        map_struct: &mut ParallelKmersMergeMapPacket<H, MH, CX>,
+    ) {
+        let k = global_data.k;
+        let buckets_count_bits = global_data.buckets_count.ilog2() as usize;
+        let kmer_bytes = (k + 3) / 4;
+
+        self.counted_kmers_bases.clear();
+        self.counted_kmers_list.clear();
+
+        let bases_count = map_struct.saved_reads.len() * 4;
+        if MH::INVERTIBLE || bases_count >= k {
+            // The multiplicities must be read before processing the colors, as they can overwrite the counters
+            Self::get_kmers(global_data, map_struct, |hash, cread, rhentry| {
+                let offset = self.counted_kmers_bases.len();
+                cread.copy_to_buffer(&mut self.counted_kmers_bases);
+                self.counted_kmers_list.push((
+                    hash.to_unextendable(),
+                    offset,
+                    rhentry.get_kmer_multiplicity() as u64,
+                ));
+                rhentry.set_used();
+            });
+        }
+
+        if CX::COLORS_ENABLED {
+            CX::ColorsMergeManagerType::<H, MH>::process_colors(
+                &global_data.colors_global_table,
+                &mut map_struct.temp_colors,
+                &mut map_struct.rhash_map,
+                global_data.k,
+                global_data.min_multiplicity,
+            );
+        }
+
+        let counted_kmers_tmp = self.counted_kmers_tmp.as_mut().unwrap();
+
+        for (hash, offset, multiplicity) in self.counted_kmers_list.iter() {
+            let color = map_struct
+                .rhash_map
+                .get(hash)
+                .and_then(|entry| CX::ColorsMergeManagerType::<H, MH>::get_kmer_color_subset(entry))
+                .unwrap_or(0);
+
+            counted_kmers_tmp.add_element(
+                MH::get_bucket(0, buckets_count_bits, *hash),
+                &(),
+                &CountedKmer {
+                    kmer: CompressedRead::new_from_compressed(
+                        &self.counted_kmers_bases[*offset..(*offset + kmer_bytes)],
+                        k,
+                    ),
+                    multiplicity: *multiplicity,
+                    color,
+                },
+            );
+        }
+    }
 }
 
 // static DEBUG_MAPS_HOLDER: Mutex<Vec<Box<dyn Any + Sync + Send>>> = const_mutex(Vec::new());
@@ -191,6 +271,19 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         let current_bucket = self.current_bucket.as_mut().unwrap();
         let bucket_index = current_bucket.get_bucket_index();
 
+        // Must be done before processing the colors, as they can overwrite the counters
+        for rhentry in map_struct.rhash_map.values() {
+            let multiplicity = rhentry.get_kmer_multiplicity();
+            if multiplicity > 0 {
+                self.abundance_histogram.add_kmer(multiplicity);
+            }
+        }
+
+        if self.counted_kmers_tmp.is_some() {
+            self.dump_counted_kmers(global_data, map_struct);
+            return map_struct_packet;
+        }
+
         if CX::COLORS_ENABLED {
             CX::ColorsMergeManagerType::<H, MH>::process_colors(
                 &global_data.colors_global_table,
@@ -416,8 +509,15 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 
     fn finalize(
         self,
-        _global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
+        global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
     ) {
+        global_data
+            .abundance_histogram
+            .lock()
+            .merge(&self.abundance_histogram);
+        if let Some(counted_kmers_tmp) = self.counted_kmers_tmp {
+            counted_kmers_tmp.finalize();
+        }
         self.hashes_tmp.finalize();
     }
 }
@@ -1,12 +1,13 @@
 use crate::final_executor::ParallelKmersMergeFinalExecutor;
 use crate::map_processor::{ParallelKmersMergeMapProcessor, KMERGE_TEMP_DIR};
 use crate::preprocessor::ParallelKmersMergePreprocessor;
-use crate::structs::{ResultsBucket, RetType};
+use crate::structs::{KmersAbundanceHistogram, ResultsBucket, RetType};
 use assembler_minimizer_bucketing::AssemblerMinimizerBucketingExecutorFactory;
 use colors::colors_manager::color_types::{
     GlobalColorsTableWriter, MinimizerBucketingSeqColorDataType,
 };
 use colors::colors_manager::{color_types, ColorsManager};
+use config::progress;
 use config::{
     get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
     MINIMUM_SUBBUCKET_KMERS_COUNT, RESPLITTING_MAX_K_M_DIFFERENCE,
@@ -18,6 +19,7 @@ use io::structs::hash_entry::HashEntry;
 use io::structs::hash_entry::{Direction, HashEntrySerializer};
 use kmers_transform::processor::KmersTransformProcessor;
 use kmers_transform::{KmersTransform, KmersTransformExecutorFactory};
+use log::info;
 use minimizer_bucketing::{MinimizerBucketingCommonData, MinimizerBucketingExecutorFactory};
 use parallel_processor::buckets::bucket_writer::BucketItemSerializer;
 use parallel_processor::buckets::concurrent::BucketsThreadDispatcher;
@@ -28,10 +30,11 @@ use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 #[cfg(feature = "mem-analysis")]
 use parallel_processor::mem_tracker::MemoryInfo;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use parking_lot::Mutex;
 use std::cmp::min;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use utils::owned_drop::OwnedDrop;
 
@@ -57,6 +60,10 @@ pub struct GlobalMergeData<
     sequences_size_total: AtomicU64,
     hasnmap_kmers_total: AtomicU64,
     kmer_batches_count: AtomicU64,
+    abundance_histogram: Mutex<KmersAbundanceHistogram>,
+    counted_kmers_buckets: Option<Arc<MultiThreadBuckets<CompressedBinaryWriter>>>,
+    verify_hash_collisions: bool,
+    hash_collisions_count: AtomicU64,
 }
 
 pub struct ParallelKmersMergeFactory<
@@ -86,8 +93,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         AssemblerMinimizerBucketingExecutorFactory::new(&global_data.global_resplit_data)
     }
 
-    fn new_preprocessor(_global_data: &Arc<Self::GlobalExtraData>) -> Self::PreprocessorType {
-        ParallelKmersMergePreprocessor::new()
+    fn new_preprocessor(global_data: &Arc<Self::GlobalExtraData>) -> Self::PreprocessorType {
+        ParallelKmersMergePreprocessor::new(global_data.k, global_data.m)
     }
 
     fn new_map_processor(
@@ -143,10 +150,10 @@ pub fn kmers_merge<
     k: usize,
     m: usize,
     threads_count: usize,
+    dump_counted_kmers: bool,
+    verify_hash_collisions: bool,
 ) -> RetType {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: kmers merge".to_string());
+    progress::start_phase("phase: kmers merge".to_string());
 
     H::initialize(k);
     MH::initialize(k);
@@ -161,6 +168,21 @@ pub fn kmers_merge<
         ),
     ));
 
+    // When dumping the kmers, the solid kmers are written to these buckets instead of building the unitigs
+    let counted_kmers_buckets = if dump_counted_kmers {
+        Some(Arc::new(MultiThreadBuckets::<CompressedBinaryWriter>::new(
+            buckets_count,
+            out_directory.as_ref().join("counted_kmers"),
+            &(
+                get_memory_mode(SwapPriority::ResultBuckets),
+                CompressedBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
+                get_compression_level_info(),
+            ),
+        )))
+    } else {
+        None
+    };
+
     let mut sequences = Vec::new();
 
     let reads_buckets = MultiThreadBuckets::<CompressedBinaryWriter>::new(
@@ -211,6 +233,11 @@ pub fn kmers_merge<
         sequences_size_total: AtomicU64::new(0),
         hasnmap_kmers_total: AtomicU64::new(0),
         kmer_batches_count: AtomicU64::new(0),
+        abundance_histogram: Mutex::new(KmersAbundanceHistogram::new()),
+        counted_kmers_buckets: counted_kmers_buckets.clone(),
+        // Invertible hashes are exact, so they cannot have collisions
+        verify_hash_collisions: verify_hash_collisions && !MH::INVERTIBLE,
+        hash_collisions_count: AtomicU64::new(0),
     });
 
     KmersTransform::<ParallelKmersMergeFactory<H, MH, CX>>::new(
@@ -218,16 +245,23 @@ pub fn kmers_merge<
         out_directory.as_ref(),
         buckets_counters_path,
         buckets_count,
-        global_data,
+        global_data.clone(),
         threads_count,
         k,
         MINIMUM_SUBBUCKET_KMERS_COUNT as u64,
     )
     .parallel_kmers_transform();
 
+    let abundance_histogram = global_data.abundance_histogram.lock().clone();
+
     RetType {
         sequences,
         hashes: hashes_buckets.finalize(),
+        abundance_histogram: Some(abundance_histogram),
+        counted_kmers: counted_kmers_buckets
+            .map(|buckets| buckets.finalize())
+            .unwrap_or_default(),
+        hash_collisions: global_data.hash_collisions_count.load(Ordering::Relaxed),
     }
 }
 
@@ -235,14 +269,14 @@ pub fn kmers_merge<
 mod tests {
     use colors::colors_manager::{ColorsManager, ColorsMergeManager};
     use colors::non_colored::NonColoredManager;
-    use config::{FLUSH_QUEUE_FACTOR, KEEP_FILES, PREFER_MEMORY};
+    use config::instance::{self, InstanceContext, InstanceSettings};
+    use config::FLUSH_QUEUE_FACTOR;
     use io::generate_bucket_names;
     use parallel_processor::memory_data_size::MemoryDataSize;
     use parallel_processor::memory_fs::MemoryFs;
     use rayon::ThreadPoolBuilder;
     use std::cmp::max;
     use std::path::Path;
-    use std::sync::atomic::Ordering;
     use std::sync::Arc;
 
     #[ignore]
@@ -275,7 +309,7 @@ mod tests {
             > as ColorsMergeManager<
                 hashes::cn_nthash::CanonicalNtHashIteratorFactory,
                 hashes::cn_rkhash::u128::CanonicalRabinKarpHashFactory,
-            >>::create_colors_table("", &[]),
+            >>::create_colors_table("", &[], Default::default()),
         );
 
         let k = 63;
@@ -286,9 +320,12 @@ mod tests {
         // Increase the maximum allowed number of open files
         fdlimit::raise_fd_limit();
 
-        KEEP_FILES.store(true, Ordering::Relaxed);
-
-        PREFER_MEMORY.store(false, Ordering::Relaxed);
+        instance::enter(&InstanceContext::new(InstanceSettings {
+            keep_files: true,
+            prefer_memory: false,
+            threads_count,
+            ..Default::default()
+        }));
 
         ThreadPoolBuilder::new()
             .num_threads(threads_count)
@@ -313,7 +350,7 @@ mod tests {
             32768,
         );
 
-        println!("Using m: {} with k: {}", m, k);
+        info!("Using m: {} with k: {}", m, k);
 
         // #[cfg(feature = "mem-analysis")]
         // debug_print_allocations("/tmp/allocations", Duration::from_secs(5));
@@ -332,6 +369,8 @@ mod tests {
             k,
             m,
             threads_count,
+            false,
+            false,
         );
     }
 }
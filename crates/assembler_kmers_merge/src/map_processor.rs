@@ -2,7 +2,9 @@ use crate::ParallelKmersMergeFactory;
 use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
 use colors::colors_manager::{color_types, ColorsManager};
 use colors::colors_manager::{ColorsMergeManager, MinimizerBucketingSeqColorData};
+use config::memory_budget::MemoryReservation;
 use config::{READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END};
+use hashbrown::hash_map::Entry;
 use hashbrown::HashMap;
 use hashes::ExtendableHashTraitType;
 use hashes::HashFunction;
@@ -42,6 +44,10 @@ pub struct ParallelKmersMergeMapPacket<
     pub saved_reads: Vec<u8>,
     pub encoded_saved_reads_indexes: Vec<u8>,
     pub temp_colors: color_types::ColorsBufferTempStructure<H, MH, CX>,
+    /// First kmer seen for each hash (in canonical orientation, one base per byte) and whether a
+    /// different kmer with the same hash was found, used only when verifying the hash collisions
+    verification_kmers: HashMap<MH::HashTypeUnextendable, (usize, bool)>,
+    verification_bases: Vec<u8>,
     average_hasmap_size: u64,
     average_sequences_size: u64,
 }
@@ -71,6 +77,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             temp_colors: CX::ColorsMergeManagerType::<H, MH>::allocate_temp_buffer_structure(
                 KMERGE_TEMP_DIR.read().deref().as_ref().unwrap(),
             ),
+            verification_kmers: HashMap::new(),
+            verification_bases: vec![],
             average_hasmap_size: 0,
             average_sequences_size: 0,
         }
@@ -96,6 +104,14 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             self.encoded_saved_reads_indexes = Vec::with_capacity(saved_reads_suggested_size)
         }
 
+        if !self.verification_kmers.is_empty() {
+            clear_hashmap(
+                &mut self.verification_kmers,
+                max(8192, self.average_hasmap_size as usize),
+            );
+            self.verification_bases.clear();
+        }
+
         CX::ColorsMergeManagerType::<H, MH>::reinit_temp_buffer_structure(&mut self.temp_colors);
     }
 }
@@ -110,6 +126,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 MapEntry<color_types::HashMapTempColorIndex<H, MH, CX>>,
             )>() + 1)
             + self.saved_reads.len()
+            + self.verification_kmers.len()
+                * (size_of::<(MH::HashTypeUnextendable, (usize, bool))>() + 1)
+            + self.verification_bases.len()
+            + CX::ColorsMergeManagerType::<H, MH>::get_temp_buffer_structure_size(&self.temp_colors)
     }
 }
 
@@ -125,6 +145,7 @@ pub struct ParallelKmersMergeMapProcessor<
     >,
     last_saved_len: usize,
     mem_tracker: MemoryTracker<KmersTransformProcessor<ParallelKmersMergeFactory<H, MH, CX>>>,
+    memory_reservation: MemoryReservation,
 }
 
 impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager>
@@ -137,6 +158,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             map_packet: None,
             last_saved_len: 0,
             mem_tracker,
+            memory_reservation: MemoryReservation::new(),
         }
     }
 }
@@ -176,6 +198,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 
         let mut kmers_count = 0;
         let mut unique_kmers_count = 0;
+        let mut hash_collisions_count = 0;
 
         for (flags, color, read) in batch.iter() {
             let read = read.as_reference(ref_sequences);
@@ -223,6 +246,34 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                     min_idx = min(min_idx, idx / 4);
                     max_idx = max(max_idx, idx);
                 }
+
+                if global_data.verify_hash_collisions {
+                    let kmer_base = |i: usize| unsafe {
+                        if is_forward {
+                            read.get_base_unchecked(idx + i)
+                        } else {
+                            read.get_base_unchecked(idx + k - 1 - i) ^ 2
+                        }
+                    };
+
+                    match map_packet.verification_kmers.entry(hash.to_unextendable()) {
+                        Entry::Vacant(entry) => {
+                            entry.insert((map_packet.verification_bases.len(), false));
+                            map_packet.verification_bases.extend((0..k).map(kmer_base));
+                        }
+                        Entry::Occupied(mut entry) => {
+                            let (offset, collided) = entry.get_mut();
+                            if !*collided
+                                && (0..k).any(|i| {
+                                    map_packet.verification_bases[*offset + i] != kmer_base(i)
+                                })
+                            {
+                                *collided = true;
+                                hash_collisions_count += 1;
+                            }
+                        }
+                    }
+                }
             }
 
             CX::ColorsMergeManagerType::<H, MH>::add_temp_buffer_sequence(
@@ -249,8 +300,15 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             }
         }
 
-        self.mem_tracker
-            .update_memory_usage(&[map_packet.get_size(), 0]);
+        if hash_collisions_count > 0 {
+            global_data
+                .hash_collisions_count
+                .fetch_add(hash_collisions_count, Ordering::Relaxed);
+        }
+
+        let map_size = map_packet.get_size();
+        self.mem_tracker.update_memory_usage(&[map_size, 0]);
+        self.memory_reservation.update(map_size);
 
         GroupProcessStats {
             total_kmers: kmers_count,
@@ -292,6 +350,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         COUNTER_KMERS_MAX.max(all_kmers as i64);
         COUNTER_READS_AVG.add_value(all_kmers as i64);
         self.mem_tracker.update_memory_usage(&[0, 0]);
+        self.memory_reservation.update(0);
 
         map_packet
     }
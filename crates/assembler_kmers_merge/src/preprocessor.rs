@@ -2,8 +2,7 @@ use crate::ParallelKmersMergeFactory;
 use colors::colors_manager::ColorsManager;
 use config::BucketIndexType;
 use config::READ_FLAG_INCL_END;
-use hashes::ExtendableHashTraitType;
-use hashes::HashFunction;
+use hashes::minimizer_scheme::MinimizerSelector;
 use hashes::{HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedRead;
 use kmers_transform::{KmersTransformExecutorFactory, KmersTransformPreprocessor};
@@ -14,14 +13,16 @@ pub struct ParallelKmersMergePreprocessor<
     MH: HashFunctionFactory,
     CX: ColorsManager,
 > {
-    _phantom: PhantomData<(H, MH, CX)>,
+    minimizer_selector: MinimizerSelector<H>,
+    _phantom: PhantomData<(MH, CX)>,
 }
 
 impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager>
     ParallelKmersMergePreprocessor<H, MH, CX>
 {
-    pub fn new() -> Self {
+    pub fn new(k: usize, m: usize) -> Self {
         Self {
+            minimizer_selector: MinimizerSelector::new(k - m),
             _phantom: PhantomData,
         }
     }
@@ -32,7 +33,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     for ParallelKmersMergePreprocessor<H, MH, CX>
 {
     fn get_sequence_bucket<C>(
-        &self,
+        &mut self,
         global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
         seq_data: &(u8, u8, C, CompressedRead),
         used_hash_bits: usize,
@@ -43,20 +44,11 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         let decr_val =
             ((read.bases_count() == global_data.k) && (flags & READ_FLAG_INCL_END) == 0) as usize;
 
-        let hashes = H::new(
+        let minimizer = self.minimizer_selector.window_minimizer(
             read.sub_slice((1 - decr_val)..(global_data.k - decr_val)),
             global_data.m,
         );
 
-        let minimizer = hashes
-            .iter()
-            .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
-            .unwrap();
-
-        H::get_bucket(
-            used_hash_bits,
-            bucket_bits_count,
-            minimizer.to_unextendable(),
-        )
+        H::get_bucket(used_hash_bits, bucket_bits_count, minimizer)
     }
 }
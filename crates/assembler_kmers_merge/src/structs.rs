@@ -1,13 +1,19 @@
-use config::BucketIndexType;
+use byteorder::ReadBytesExt;
+use config::{BucketIndexType, ColorIndexType, KMERS_ABUNDANCE_HISTOGRAM_MAX};
+use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::creads_utils::{
     CompressedReadsBucketData, CompressedReadsBucketDataSerializer,
 };
 use io::concurrent::temp_reads::extra_data::SequenceExtraDataConsecutiveCompression;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
 use parallel_processor::buckets::bucket_writer::BucketItemSerializer;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
 use parallel_processor::buckets::LockFreeBucket;
+use std::cmp::min;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use utils::owned_drop::OwnedDrop;
 
 pub struct ResultsBucket<X: SequenceExtraDataConsecutiveCompression> {
@@ -46,7 +52,213 @@ impl<X: SequenceExtraDataConsecutiveCompression> Drop for ResultsBucket<X> {
     }
 }
 
+/// Counts of distinct kmers for each multiplicity, the last entry holds all the kmers
+/// with multiplicity greater or equal to KMERS_ABUNDANCE_HISTOGRAM_MAX
+#[derive(Clone, Debug)]
+pub struct KmersAbundanceHistogram {
+    counts: Vec<u64>,
+}
+
+impl KmersAbundanceHistogram {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; KMERS_ABUNDANCE_HISTOGRAM_MAX + 1],
+        }
+    }
+
+    #[inline(always)]
+    pub fn add_kmer(&mut self, multiplicity: usize) {
+        self.counts[min(multiplicity, KMERS_ABUNDANCE_HISTOGRAM_MAX)] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += *other_count;
+        }
+    }
+
+    pub fn get_counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Writes the histogram in the two columns format used by jellyfish and GenomeScope,
+    /// skipping the multiplicities without kmers
+    pub fn write_tsv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for (multiplicity, count) in self.counts.iter().enumerate().skip(1) {
+            if *count > 0 {
+                writeln!(writer, "{}\t{}", multiplicity, count)?;
+            }
+        }
+        writer.flush()
+    }
+}
+
+/// A single solid kmer, with its multiplicity and (if colors are enabled) its color subset index
+pub struct CountedKmer<'a> {
+    pub kmer: CompressedRead<'a>,
+    pub multiplicity: u64,
+    pub color: ColorIndexType,
+}
+
+pub struct CountedKmerSerializer;
+
+impl BucketItemSerializer for CountedKmerSerializer {
+    type InputElementType<'a> = CountedKmer<'a>;
+    type ExtraData = ();
+    type ReadBuffer = Vec<u8>;
+    type ExtraDataBuffer = ();
+    type ReadType<'a> = CountedKmer<'a>;
+
+    #[inline(always)]
+    fn new() -> Self {
+        Self
+    }
+
+    #[inline(always)]
+    fn reset(&mut self) {}
+
+    #[inline(always)]
+    fn write_to(
+        &mut self,
+        element: &Self::InputElementType<'_>,
+        bucket: &mut Vec<u8>,
+        _extra_data: &Self::ExtraData,
+        _: &(),
+    ) {
+        encode_varint(|b| bucket.extend_from_slice(b), element.multiplicity);
+        encode_varint(|b| bucket.extend_from_slice(b), element.color as u64);
+        encode_varint(
+            |b| bucket.extend_from_slice(b),
+            element.kmer.get_length() as u64,
+        );
+        element.kmer.copy_to_buffer(bucket);
+    }
+
+    fn read_from<'a, S: Read>(
+        &mut self,
+        mut stream: S,
+        read_buffer: &'a mut Self::ReadBuffer,
+        _: &mut (),
+    ) -> Option<Self::ReadType<'a>> {
+        let multiplicity = decode_varint(|| stream.read_u8().ok())?;
+        let color = decode_varint(|| stream.read_u8().ok())? as ColorIndexType;
+        let bases_count = decode_varint(|| stream.read_u8().ok())? as usize;
+
+        read_buffer.clear();
+        read_buffer.resize((bases_count + 3) / 4, 0);
+        stream.read_exact(&mut read_buffer[..]).ok()?;
+
+        Some(CountedKmer {
+            kmer: CompressedRead::new_from_compressed(&read_buffer[..], bases_count),
+            multiplicity,
+            color,
+        })
+    }
+
+    #[inline(always)]
+    fn get_size(&self, element: &Self::InputElementType<'_>, _: &()) -> usize {
+        VARINT_MAX_SIZE * 3 + (element.kmer.get_length() + 3) / 4
+    }
+}
+
 pub struct RetType {
     pub sequences: Vec<PathBuf>,
     pub hashes: Vec<PathBuf>,
+    pub abundance_histogram: Option<KmersAbundanceHistogram>,
+    /// The solid kmers buckets, present only if the kmers dump was requested
+    pub counted_kmers: Vec<PathBuf>,
+    /// Number of distinct kmers sharing the hash with another kmer, counted only if the verification was requested
+    pub hash_collisions: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::compressed_read::CompressedReadIndipendent;
+
+    #[test]
+    fn counted_kmers_serialization() {
+        let kmers: [(&[u8], u64, ColorIndexType); 4] = [
+            (b"ACGTACGTACGTACGTACGTACGTACGTACG", 2, 0),
+            (b"TTTTTTTTTTTTTTTTTTTTTTTTTTTTTTT", 1 << 40, 12345),
+            (b"GATTACA", 7, ColorIndexType::MAX),
+            (b"CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCA", 300, 1),
+        ];
+
+        let mut serializer = CountedKmerSerializer::new();
+        let mut storage = vec![];
+        let mut bucket = vec![];
+        for (kmer, multiplicity, color) in kmers.iter() {
+            storage.clear();
+            let read = CompressedReadIndipendent::from_plain(kmer, &mut storage);
+            let element = CountedKmer {
+                kmer: read.as_reference(&storage),
+                multiplicity: *multiplicity,
+                color: *color,
+            };
+            let size_before = bucket.len();
+            serializer.write_to(&element, &mut bucket, &(), &());
+            assert!(bucket.len() - size_before <= serializer.get_size(&element, &()));
+        }
+
+        let mut serializer = CountedKmerSerializer::new();
+        let mut stream = &bucket[..];
+        let mut read_buffer = vec![];
+        for (kmer, multiplicity, color) in kmers.iter() {
+            let element = serializer
+                .read_from(&mut stream, &mut read_buffer, &mut ())
+                .unwrap();
+            assert_eq!(element.kmer.to_string().as_bytes(), *kmer);
+            assert_eq!(element.multiplicity, *multiplicity);
+            assert_eq!(element.color, *color);
+        }
+        assert!(serializer
+            .read_from(&mut stream, &mut read_buffer, &mut ())
+            .is_none());
+    }
+
+    #[test]
+    fn abundance_histogram_counts() {
+        let mut histogram = KmersAbundanceHistogram::new();
+        histogram.add_kmer(1);
+        histogram.add_kmer(1);
+        histogram.add_kmer(3);
+        histogram.add_kmer(KMERS_ABUNDANCE_HISTOGRAM_MAX);
+        histogram.add_kmer(KMERS_ABUNDANCE_HISTOGRAM_MAX * 10);
+
+        let mut other = KmersAbundanceHistogram::new();
+        other.add_kmer(3);
+        histogram.merge(&other);
+
+        let counts = histogram.get_counts();
+        assert_eq!(counts.len(), KMERS_ABUNDANCE_HISTOGRAM_MAX + 1);
+        assert_eq!(counts[1], 2);
+        assert_eq!(counts[2], 0);
+        assert_eq!(counts[3], 2);
+        assert_eq!(counts[KMERS_ABUNDANCE_HISTOGRAM_MAX], 2);
+        assert_eq!(counts.iter().sum::<u64>(), 6);
+    }
+
+    #[test]
+    fn abundance_histogram_tsv() {
+        let mut histogram = KmersAbundanceHistogram::new();
+        histogram.add_kmer(2);
+        histogram.add_kmer(2);
+        histogram.add_kmer(5);
+        histogram.add_kmer(KMERS_ABUNDANCE_HISTOGRAM_MAX + 1);
+
+        let path = std::env::temp_dir().join(format!(
+            "ggcat-test-{}-abundance-histogram.tsv",
+            std::process::id()
+        ));
+        histogram.write_tsv(&path).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            contents,
+            format!("2\t2\n5\t1\n{}\t1\n", KMERS_ABUNDANCE_HISTOGRAM_MAX)
+        );
+    }
 }
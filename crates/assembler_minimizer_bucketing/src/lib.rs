@@ -2,21 +2,20 @@ use ::dynamic_dispatch::dynamic_dispatch;
 use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
 use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
 use colors::parsers::{SequenceIdent, SingleSequenceInfo};
+use config::progress;
 use config::{BucketIndexType, ColorIndexType};
 use config::{READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END};
-use hashes::rolling::minqueue::RollingMinQueue;
-use hashes::ExtendableHashTraitType;
-use hashes::HashFunction;
+use hashes::minimizer_scheme::{get_minimizer_scheme, MinimizerScheme, MinimizerSelector};
 use hashes::MinimizerHashFunctionFactory;
 use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
 use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 use io::sequences_stream::general::{GeneralSequenceBlockData, GeneralSequencesStream};
 use io::sequences_stream::SequenceInfo;
+use minimizer_bucketing::frequent_minimizers::sample_frequent_minimizers;
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
     MinimizerBucketingExecutorFactory, MinimizerInputSequence,
 };
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::cmp::max;
 use std::marker::PhantomData;
 use std::ops::Range;
@@ -24,7 +23,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub struct AssemblerMinimizerBucketingExecutor<H: MinimizerHashFunctionFactory, CX: ColorsManager> {
-    minimizer_queue: RollingMinQueue<H>,
+    minimizer_selector: MinimizerSelector<H>,
     global_data: Arc<MinimizerBucketingCommonData<()>>,
     _phantom: PhantomData<CX>,
 }
@@ -75,7 +74,7 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager> MinimizerBucketingExecu
         global_data: &Arc<MinimizerBucketingCommonData<Self::GlobalData>>,
     ) -> Self::ExecutorType {
         Self::ExecutorType {
-            minimizer_queue: RollingMinQueue::new(global_data.k - global_data.m),
+            minimizer_selector: MinimizerSelector::new(global_data.k - global_data.m),
             global_data: global_data.clone(),
             _phantom: PhantomData,
         }
@@ -152,11 +151,9 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
         second_bits: usize,
         mut push_sequence: F,
     ){
-        let hashes = H::new(sequence, self.global_data.m);
-
         let mut rolling_iter = self
-            .minimizer_queue
-            .make_iter(hashes.iter().map(|x| x.to_unextendable()));
+            .minimizer_selector
+            .rolling_minimizers(sequence, self.global_data.m);
 
         let mut last_index = 0;
         let mut last_hash = rolling_iter.next().unwrap();
@@ -226,10 +223,6 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
 ) -> (Vec<PathBuf>, PathBuf) {
     H::initialize(k);
 
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: reads bucketing".to_string());
-
     let mut input_files: Vec<_> = input_blocks
         .into_iter()
         .enumerate()
@@ -246,6 +239,13 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
     input_files.sort_by_cached_key(|(file, _)| file.estimated_bases_count());
     input_files.reverse();
 
+    if get_minimizer_scheme() == MinimizerScheme::FrequencyAware {
+        let blocks: Vec<_> = input_files.iter().map(|(block, _)| block).collect();
+        sample_frequent_minimizers::<H, GeneralSequencesStream>(&blocks, threads_count, k - m, m);
+    }
+
+    progress::start_phase("phase: reads bucketing".to_string());
+
     GenericMinimizerBucketing::do_bucketing::<
         AssemblerMinimizerBucketingExecutorFactory<H, CX>,
         GeneralSequencesStream,
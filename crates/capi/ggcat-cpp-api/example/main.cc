@@ -47,11 +47,21 @@ int main(int argc, char const *argv[])
     config.temp_dir = "/tmp",
     config.memory = 2.0,
     config.prefer_memory = true,
+    config.hard_memory_limit = 0.0,
     config.total_threads_count = 16,
     config.intermediate_compression_level = -1,
 
     config.use_stats_file = false;
     config.stats_file = "";
+    config.minimizer_scheme = MinimizerScheme_Random;
+    config.minimizer_scheme_param = 0;
+    config.progress_callback = [](void *context, ProgressEvent event)
+    {
+        if (event.type == ProgressEventType_PhaseStart)
+        {
+            std::cout << "Started " << std::string(event.phase_name.data, event.phase_name.size) << std::endl;
+        }
+    };
 
     GGCATInstance *instance = GGCATInstance::create(config);
 
@@ -717,13 +717,14 @@ std::size_t align_of() {
 } // namespace rust
 
 struct GGCATConfigFFI;
+struct GGCATErrorFFI;
 struct GGCATInstanceFFI;
 
 #ifndef CXXBRIDGE1_STRUCT_GGCATConfigFFI
 #define CXXBRIDGE1_STRUCT_GGCATConfigFFI
-// Main config of GGCAT. This config is global and should be passed to GGCATInstance::create
+// Main config of GGCAT, owned by the instance created with GGCATInstance::create
 struct GGCATConfigFFI final {
-  // If false, a memory only mode is attempted. May crash for large input data if there is no enough RAM memory.
+  // If false, all the temporary files are kept in memory. Fails with an out of memory error if there is not enough RAM memory.
   bool use_temp_dir;
   // Directory for temporary files
   ::rust::String temp_dir;
@@ -734,6 +735,8 @@ struct GGCATConfigFFI final {
   double memory;
   // Use all the given memory before writing to disk
   bool prefer_memory;
+  // Hard memory limit for the whole process (GB), including the processing steps, 0 to disable it
+  double hard_memory_limit;
   // The total threads to be used
   ::std::size_t total_threads_count;
   // The default lz4 compression level for the intermediate files, -1 to use default values
@@ -742,11 +745,31 @@ struct GGCATConfigFFI final {
   bool use_stats_file;
   // The path to an optional json-formatted real time stats file
   ::rust::String stats_file;
+  // The scheme used to choose the minimizers
+  ::std::size_t minimizer_scheme;
+  // The s-mers length for syncmers or the r parameter for mod-minimizers
+  ::std::size_t minimizer_scheme_param;
+  // Optional function receiving the progress events, 0 to disable it
+  ::std::size_t progress_callback;
+  // The context passed to the progress callback
+  ::std::size_t progress_callback_context;
 
   using IsRelocatable = ::std::true_type;
 };
 #endif // CXXBRIDGE1_STRUCT_GGCATConfigFFI
 
+#ifndef CXXBRIDGE1_STRUCT_GGCATErrorFFI
+#define CXXBRIDGE1_STRUCT_GGCATErrorFFI
+// Error reported by the fallible functions, code is 0 if no error occurred
+struct GGCATErrorFFI final {
+  // 0: none, 1: invalid options, 2: input file not found, 3: invalid colormap, 4: io error, 5: internal error, 6: cancelled
+  ::std::size_t code;
+  ::rust::String message;
+
+  using IsRelocatable = ::std::true_type;
+};
+#endif // CXXBRIDGE1_STRUCT_GGCATErrorFFI
+
 #ifndef CXXBRIDGE1_STRUCT_GGCATInstanceFFI
 #define CXXBRIDGE1_STRUCT_GGCATInstanceFFI
 struct GGCATInstanceFFI final : public ::rust::Opaque {
@@ -761,23 +784,35 @@ private:
 };
 #endif // CXXBRIDGE1_STRUCT_GGCATInstanceFFI
 
-// Creates a new GGCATInstance. If an instance already exists, it will be returned, ignoring the new config.
-const ::GGCATInstanceFFI &ggcat_create(::GGCATConfigFFI config) noexcept;
+// Creates a new independent GGCATInstance, with its own threads pool and settings.
+// Returns the pointer to the instance, or 0 if it cannot be created
+::std::size_t ggcat_create(::GGCATConfigFFI config, ::GGCATErrorFFI &error) noexcept;
+
+// Creates a token that can be used to cancel a running operation from another thread,
+// it must be freed with ggcat_free_cancellation_token
+::std::size_t ggcat_create_cancellation_token() noexcept;
+
+void ggcat_free_cancellation_token(::std::size_t token) noexcept;
+
+// Requests the cancellation of the operations using the token, the cancelled operations report the cancelled error code
+void ggcat_cancel(::std::size_t token) noexcept;
+
+bool ggcat_is_cancelled(::std::size_t token) noexcept;
 
 // Builds a new graph from the given input files, with the specified parameters
-::rust::String ggcat_build_from_files(const ::GGCATInstanceFFI &instance, ::rust::Slice<const ::rust::String> input_files, ::rust::String output_file, ::rust::Slice<const ::rust::String> color_names, ::std::size_t kmer_length, ::std::size_t threads_count, bool forward_only, ::std::size_t minimizer_length, bool colors, ::std::size_t min_multiplicity, ::std::size_t extra_elab) noexcept;
+::rust::String ggcat_build_from_files(const ::GGCATInstanceFFI &instance, ::rust::Slice<const ::rust::String> input_files, ::rust::String output_file, ::rust::Slice<const ::rust::String> color_names, ::std::size_t kmer_length, ::std::size_t threads_count, bool forward_only, ::std::size_t minimizer_length, bool colors, ::std::size_t min_multiplicity, ::std::size_t extra_elab, ::std::size_t cancellation_token, ::GGCATErrorFFI &error) noexcept;
 
 // Queries a (optionally) colored graph with a specific set of sequences as queries
-::rust::String ggcat_query_graph(const ::GGCATInstanceFFI &instance, ::rust::String input_graph, ::rust::String input_query, ::rust::String output_file_prefix, ::std::size_t kmer_length, ::std::size_t threads_count, bool forward_only, ::std::size_t minimizer_length, bool colors, ::std::size_t color_output_format) noexcept;
+::rust::String ggcat_query_graph(const ::GGCATInstanceFFI &instance, ::rust::String input_graph, ::rust::String input_query, ::rust::String output_file_prefix, ::std::size_t kmer_length, ::std::size_t threads_count, bool forward_only, ::std::size_t minimizer_length, bool colors, ::std::size_t color_output_format, ::std::size_t result_callback, ::std::size_t result_callback_context, ::std::size_t cancellation_token, ::GGCATErrorFFI &error) noexcept;
 
 ::rust::String ggcat_get_colormap_file(::rust::String graph_file) noexcept;
 
 // Returns a vector of color names in the given graph.
 // The color indexes returned from the dump_unitigs function
 // can be used to index this vector.
-::rust::Vec<::rust::String> ggcat_dump_colors(::rust::String input_colormap) noexcept;
+::rust::Vec<::rust::String> ggcat_dump_colors(::rust::String input_colormap, ::GGCATErrorFFI &error) noexcept;
 
 // Dumps the unitigs of the given graph, optionally with colors
 // It's not guaranteed that maximal unitigs are returned, as only kmers with the same colors subset
 // are returned as whole unitigs to speedup colormap reading times
-void ggcat_dump_unitigs(const ::GGCATInstanceFFI &instance, ::rust::String graph_input, ::std::size_t kmer_length, ::std::size_t minimizer_length, bool colors, ::std::size_t threads_count, ::std::size_t output_function_context, ::std::size_t output_function_ptr) noexcept;
+void ggcat_dump_unitigs(const ::GGCATInstanceFFI &instance, ::rust::String graph_input, ::std::size_t kmer_length, ::std::size_t minimizer_length, bool colors, ::std::size_t threads_count, ::std::size_t output_function_context, ::std::size_t output_function_ptr, ::GGCATErrorFFI &error) noexcept;
@@ -1,8 +1,11 @@
 #include <cstddef>
+#include <exception>
 #include <string>
 #include <vector>
 #include <memory>
 
+struct GGCATInstanceFFI;
+
 namespace ggcat
 {
 
@@ -33,12 +36,52 @@ namespace ggcat
         ExtraElaborationStep_Eulertigs = 3,
         /// Generate pathtigs
         ExtraElaborationStep_Pathtigs = 4,
+        /// Split the maximal unitigs at each change of colors subset, with links between the pieces (requires colors)
+        ExtraElaborationStep_ColorCompactedUnitigs = 5,
+    };
+
+    enum MinimizerScheme
+    {
+        /// The m-mer with the smallest hash
+        MinimizerScheme_Random = 0,
+        /// Open syncmers are preferred, minimizer_scheme_param is the s-mers length
+        MinimizerScheme_OpenSyncmers = 1,
+        /// Closed syncmers are preferred, minimizer_scheme_param is the s-mers length
+        MinimizerScheme_ClosedSyncmers = 2,
+        /// Mod-minimizers, minimizer_scheme_param is the r parameter
+        MinimizerScheme_ModMinimizers = 3,
+        /// The m-mers that are frequent in a sample of the input are avoided
+        MinimizerScheme_FrequencyAware = 4,
+    };
+
+    enum ProgressEventType
+    {
+        /// A new phase is started, phase_name is set
+        ProgressEventType_PhaseStart = 0,
+        /// The current phase is completed, phase_name is set and value1 is the phase duration in milliseconds
+        ProgressEventType_PhaseEnd = 1,
+        /// Buckets processed by the current phase, value1 is the processed count and value2 the total
+        ProgressEventType_BucketsProcessed = 2,
+        /// A links compaction iteration is completed, value1 is the iteration and value2 the remaining links
+        ProgressEventType_LinksCompaction = 3,
+        /// Bases read from the input, value1 is the bases count, value2 the processed files and value3 the total files
+        ProgressEventType_BytesRead = 4,
+    };
+
+    struct ProgressEvent
+    {
+        ProgressEventType type;
+        Slice<char> phase_name;
+        uint64_t value1;
+        uint64_t value2;
+        uint64_t value3;
     };
 
     enum ColoredQueryOutputFormat
     {
         ColoredQueryOutputFormat_JsonLinesWithNumbers = 0,
         ColoredQueryOutputFormat_JsonLinesWithNames = 1,
+        ColoredQueryOutputFormat_JsonLinesWithNamesAndMetadata = 2,
     };
 
     enum DnaSequencesFileType
@@ -49,6 +92,53 @@ namespace ggcat
         DnaSequencesFileType_BINARY = 3,
     };
 
+    enum GGCATErrorCode
+    {
+        /// The given options are not valid
+        GGCATErrorCode_InvalidOptions = 1,
+        /// An input file does not exist or cannot be read
+        GGCATErrorCode_InputFileNotFound = 2,
+        /// The colormap file is missing or it is not a valid GGCAT colormap
+        GGCATErrorCode_InvalidColormap = 3,
+        /// An input/output error happened while running the operation
+        GGCATErrorCode_Io = 4,
+        /// The operation failed with an unexpected internal error
+        GGCATErrorCode_Internal = 5,
+        /// The operation was stopped by its cancellation token
+        GGCATErrorCode_Cancelled = 6,
+        /// Different kmers share the same hash and no wider hash is available for the kmer length
+        GGCATErrorCode_HashCollisions = 7,
+        /// The temporary files do not fit in the given memory in memory-only mode
+        GGCATErrorCode_OutOfMemory = 8,
+    };
+
+    // Exception thrown by the failed operations, with the error code and its description
+    class GGCATException final : public std::exception
+    {
+    private:
+        GGCATErrorCode error_code;
+        std::string message;
+
+    public:
+        GGCATException(GGCATErrorCode error_code, std::string message) : error_code(error_code), message(std::move(message))
+        {
+        }
+
+        GGCATErrorCode code() const noexcept
+        {
+            return error_code;
+        }
+
+        const char *what() const noexcept override
+        {
+            return message.c_str();
+        }
+    };
+
+    // Function receiving each query result (query index, color, fraction of matched kmers),
+    // the color is always 0 for non colored graphs. It can be called from multiple threads
+    typedef void (*QueryResultCallback)(void *context, size_t query_index, uint32_t color, double fraction);
+
     struct DnaSequence
     {
         Slice<char> ident_data;
@@ -74,10 +164,10 @@ namespace ggcat
         // void estimated_base_count(void *block);
     };
 
-    // Main config of GGCAT. This config is global and should be passed to GGCATInstance::create
+    // Main config of GGCAT, owned by the instance created with GGCATInstance::create
     struct GGCATConfig final
     {
-        // If false, a memory only mode is attempted. May crash for large input data if there is no enough RAM memory.
+        // If false, all the temporary files are kept in memory. Fails with an out of memory error if there is not enough RAM memory.
         bool use_temp_dir;
         // Directory for temporary files
         std::string temp_dir;
@@ -88,6 +178,8 @@ namespace ggcat
         double memory;
         // Use all the given memory before writing to disk
         bool prefer_memory;
+        // Hard memory limit for the whole process (GB), including the processing steps, 0 to disable it
+        double hard_memory_limit = 0.0;
         // The total threads to be used
         std::size_t total_threads_count;
         // The default lz4 compression level for the intermediate files, -1 to use default values
@@ -96,6 +188,35 @@ namespace ggcat
         bool use_stats_file;
         // The path to an optional json-formatted real time stats file
         std::string stats_file;
+        // The scheme used to choose the minimizers
+        MinimizerScheme minimizer_scheme = MinimizerScheme_Random;
+        // The s-mers length for syncmers or the r parameter for mod-minimizers
+        std::size_t minimizer_scheme_param = 0;
+        // Optional function receiving the progress events, it can be called from multiple threads
+        void (*progress_callback)(void *context, ProgressEvent event) = nullptr;
+        // The context passed to the progress callback
+        void *progress_callback_context = nullptr;
+    };
+
+    // Token used to stop a running operation from another thread, the cancelled operations throw a GGCATException with the GGCATErrorCode_Cancelled code
+    class CancellationToken final
+    {
+    private:
+        uintptr_t ffi_token;
+
+        friend class GGCATInstance;
+
+    public:
+        CancellationToken();
+        ~CancellationToken();
+
+        CancellationToken(const CancellationToken &) = delete;
+        CancellationToken &operator=(const CancellationToken &) = delete;
+
+        // Requests the cancellation of the operations using this token, it can be called from any thread
+        void cancel();
+
+        bool is_cancelled() const;
     };
 
     struct __InputStreamBlockData
@@ -118,7 +239,18 @@ namespace ggcat
     {
 
     private:
-        static GGCATInstance instance;
+        GGCATInstanceFFI const *ffi_instance = nullptr;
+        void (*progress_callback)(void *context, ProgressEvent event) = nullptr;
+        void *progress_callback_context = nullptr;
+
+        static void progress_callback_bridge(
+            uintptr_t instance,
+            size_t event_type,
+            uintptr_t name_ptr,
+            size_t name_len,
+            uint64_t value1,
+            uint64_t value2,
+            uint64_t value3);
 
         void dump_unitigs_internal(
             std::string graph_input,
@@ -145,6 +277,41 @@ namespace ggcat
                 same_color);
         }
 
+        template <typename S>
+        static std::vector<__InputStreamBlockData> create_input_stream_blocks(Slice<void *> input_streams)
+        {
+            thread_local std::unique_ptr<StreamReader> stream_reader = nullptr;
+            thread_local std::pair<void (*)(uintptr_t, DnaSequence, SequenceInfo), uintptr_t> callback_data;
+
+            std::vector<__InputStreamBlockData> input_stream_blocks;
+
+            for (int i = 0; i < input_streams.size; i++)
+            {
+                input_stream_blocks.push_back({[](uintptr_t block, bool copy_ident_data, size_t partial_read_copyback, void (*callback)(uintptr_t callback_context, DnaSequence sequence, SequenceInfo info), uintptr_t callback_context)
+                                               {
+                                                   callback_data.first = callback;
+                                                   callback_data.second = callback_context;
+
+                                                   if (stream_reader == nullptr)
+                                                   {
+                                                       stream_reader = std::unique_ptr<S>(new S());
+                                                   }
+
+                                                   StreamReader *local_stream_reader = stream_reader.get();
+
+                                                   local_stream_reader->read_block((void *)block, copy_ident_data, partial_read_copyback, [](DnaSequence sequence, SequenceInfo info)
+                                                                                   { callback_data.first(callback_data.second, sequence, info); });
+                                               },
+                                               [](uintptr_t block) -> uint64_t
+                                               {
+                                                   return S::estimated_base_count((void *)block);
+                                               },
+                                               (uintptr_t)input_streams.data[i]});
+            }
+
+            return input_stream_blocks;
+        }
+
         std::string build_graph_internal_ffi(
             Slice<__InputStreamBlockData> input_streams,
             std::string output_file,
@@ -155,12 +322,30 @@ namespace ggcat
             ExtraElaborationStep extra_elab,
             bool colors,
             Slice<std::string> color_names,
-            size_t minimizer_length);
+            size_t minimizer_length,
+            CancellationToken *cancellation_token);
+
+        std::string query_graph_internal_ffi(
+            std::string input_graph,
+            Slice<__InputStreamBlockData> input_streams,
+            std::string output_file_prefix,
+            size_t kmer_length,
+            size_t threads_count,
+            bool forward_only,
+            bool colors,
+            size_t color_output_format,
+            size_t minimizer_length,
+            CancellationToken *cancellation_token,
+            QueryResultCallback result_callback,
+            void *result_callback_context);
 
     public:
+        // Creates a new independent instance, with its own threads and settings.
+        // Throws a GGCATException if the instance cannot be created
         static GGCATInstance *create(GGCATConfig config);
 
-        /// Builds a new graph from the given input files, with the specified parameters
+        /// Builds a new graph from the given input files, with the specified parameters.
+        /// All the operations throw a GGCATException if they fail
         std::string build_graph_from_files(
             // The input files
             Slice<std::string> input_files,
@@ -190,7 +375,10 @@ namespace ggcat
             Slice<std::string> color_names = Slice<std::string>::empty(),
 
             // Overrides the default m-mers (minimizers) length
-            size_t minimizer_length = -1);
+            size_t minimizer_length = -1,
+
+            // Optional token to cancel the build
+            CancellationToken *cancellation_token = nullptr);
 
         /// Builds a new graph from the given input streams, with the specified parameters
         template <typename S>
@@ -223,37 +411,13 @@ namespace ggcat
             Slice<std::string> color_names = Slice<std::string>::empty(),
 
             // Overrides the default m-mers (minimizers) length
-            size_t minimizer_length = -1)
-        {
+            size_t minimizer_length = -1,
 
-            thread_local std::unique_ptr<StreamReader> stream_reader = nullptr;
-            thread_local std::pair<void (*)(uintptr_t, DnaSequence, SequenceInfo), uintptr_t> callback_data;
-
-            std::vector<__InputStreamBlockData> input_stream_blocks;
-
-            for (int i = 0; i < input_streams.size; i++)
-            {
-                input_stream_blocks.push_back({[](uintptr_t block, bool copy_ident_data, size_t partial_read_copyback, void (*callback)(uintptr_t callback_context, DnaSequence sequence, SequenceInfo info), uintptr_t callback_context)
-                                               {
-                                                   callback_data.first = callback;
-                                                   callback_data.second = callback_context;
-
-                                                   if (stream_reader == nullptr)
-                                                   {
-                                                       stream_reader = std::unique_ptr<S>(new S());
-                                                   }
+            // Optional token to cancel the build
+            CancellationToken *cancellation_token = nullptr)
+        {
 
-                                                   StreamReader *local_stream_reader = stream_reader.get();
-
-                                                   local_stream_reader->read_block((void *)block, copy_ident_data, partial_read_copyback, [](DnaSequence sequence, SequenceInfo info)
-                                                                                   { callback_data.first(callback_data.second, sequence, info); });
-                                               },
-                                               [](uintptr_t block) -> uint64_t
-                                               {
-                                                   return S::estimated_base_count((void *)block);
-                                               },
-                                               (uintptr_t)input_streams.data[i]});
-            }
+            std::vector<__InputStreamBlockData> input_stream_blocks = create_input_stream_blocks<S>(input_streams);
 
             return build_graph_internal_ffi(Slice<__InputStreamBlockData>(input_stream_blocks.data(),
                                                                           input_stream_blocks.size()),
@@ -265,7 +429,8 @@ namespace ggcat
                                             extra_elab,
                                             colors,
                                             color_names,
-                                            minimizer_length);
+                                            minimizer_length,
+                                            cancellation_token);
         }
 
         /// Queries a (optionally) colored graph with a specific set of sequences as queries
@@ -289,7 +454,60 @@ namespace ggcat
             // Query output format
             size_t color_output_format = ColoredQueryOutputFormat_JsonLinesWithNumbers,
             // Overrides the default m-mers (minimizers) length
-            size_t minimizer_length = -1);
+            size_t minimizer_length = -1,
+            // Optional token to cancel the query
+            CancellationToken *cancellation_token = nullptr,
+            // Optional function receiving each query result
+            QueryResultCallback result_callback = nullptr,
+            // The context passed to the result callback
+            void *result_callback_context = nullptr);
+
+        /// Queries a (optionally) colored graph with the sequences of the given streams as queries
+        template <typename S>
+        std::string query_graph_from_streams(
+            // The input graph
+            std::string input_graph,
+            // The input query streams
+            Slice<void *> input_streams,
+
+            // The output file
+            std::string output_file_prefix,
+
+            // Specifies the k-mers length
+            size_t kmer_length,
+            // The threads to be used
+            size_t threads_count,
+            // Treats reverse complementary kmers as different
+            bool forward_only = false,
+            // Enable colors
+            bool colors = false,
+            // Query output format
+            size_t color_output_format = ColoredQueryOutputFormat_JsonLinesWithNumbers,
+            // Overrides the default m-mers (minimizers) length
+            size_t minimizer_length = -1,
+            // Optional token to cancel the query
+            CancellationToken *cancellation_token = nullptr,
+            // Optional function receiving each query result
+            QueryResultCallback result_callback = nullptr,
+            // The context passed to the result callback
+            void *result_callback_context = nullptr)
+        {
+            std::vector<__InputStreamBlockData> input_stream_blocks = create_input_stream_blocks<S>(input_streams);
+
+            return query_graph_internal_ffi(input_graph,
+                                            Slice<__InputStreamBlockData>(input_stream_blocks.data(),
+                                                                          input_stream_blocks.size()),
+                                            output_file_prefix,
+                                            kmer_length,
+                                            threads_count,
+                                            forward_only,
+                                            colors,
+                                            color_output_format,
+                                            minimizer_length,
+                                            cancellation_token,
+                                            result_callback,
+                                            result_callback_context);
+        }
 
         // Obtains the standard colormap file path from a graph file path
         static std::string get_colormap_file(std::string graph_file);
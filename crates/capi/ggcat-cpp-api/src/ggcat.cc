@@ -4,30 +4,103 @@
 
 using namespace ggcat;
 
-GGCATInstanceFFI const *ffi_instance = nullptr;
-GGCATInstance ggcat::GGCATInstance::instance = GGCATInstance();
-
 static_assert(sizeof(size_t) == sizeof(uintptr_t), "size_t and uintptr_t must be the same size");
 
-GGCATInstance *GGCATInstance::create(GGCATConfig config)
+static void throw_if_error(const GGCATErrorFFI &error)
 {
-    if (ffi_instance == nullptr)
+    if (error.code != 0)
     {
+        throw GGCATException((GGCATErrorCode)error.code, std::string(error.message.c_str()));
+    }
+}
+
+static std::vector<InputStreamFFI> get_ffi_input_streams(Slice<__InputStreamBlockData> input_streams)
+{
+    std::vector<InputStreamFFI> ffi_input_streams;
+
+    for (size_t i = 0; i < input_streams.size; i++)
+    {
+        InputStreamFFI ffi_input_stream;
+        ffi_input_stream.block_data = input_streams.data[i].block_data;
+        ffi_input_stream.virtual_read_block = (uintptr_t)input_streams.data[i].read_block;
+        ffi_input_stream.virtual_estimated_base_count = (uintptr_t)input_streams.data[i].estimated_base_count;
+
+        ffi_input_streams.push_back(ffi_input_stream);
+    }
+
+    return ffi_input_streams;
+}
+
+void GGCATInstance::progress_callback_bridge(
+    uintptr_t instance,
+    size_t event_type,
+    uintptr_t name_ptr,
+    size_t name_len,
+    uint64_t value1,
+    uint64_t value2,
+    uint64_t value3)
+{
+    GGCATInstance *self = (GGCATInstance *)instance;
+    self->progress_callback(self->progress_callback_context, ProgressEvent{
+                                                                 (ProgressEventType)event_type,
+                                                                 Slice<char>((char *)name_ptr, name_len),
+                                                                 value1,
+                                                                 value2,
+                                                                 value3,
+                                                             });
+}
+
+CancellationToken::CancellationToken() : ffi_token(ggcat_create_cancellation_token())
+{
+}
+
+CancellationToken::~CancellationToken()
+{
+    ggcat_free_cancellation_token(ffi_token);
+}
+
+void CancellationToken::cancel()
+{
+    ggcat_cancel(ffi_token);
+}
+
+bool CancellationToken::is_cancelled() const
+{
+    return ggcat_is_cancelled(ffi_token);
+}
 
-        GGCATConfigFFI ffi_config;
+GGCATInstance *GGCATInstance::create(GGCATConfig config)
+{
+    GGCATInstance *instance = new GGCATInstance();
+    instance->progress_callback = config.progress_callback;
+    instance->progress_callback_context = config.progress_callback_context;
+
+    GGCATConfigFFI ffi_config;
 
-        ffi_config.use_temp_dir = config.use_temp_dir,
-        ffi_config.temp_dir = rust::String(config.temp_dir.c_str()),
-        ffi_config.memory = config.memory,
-        ffi_config.prefer_memory = config.prefer_memory,
-        ffi_config.total_threads_count = config.total_threads_count,
-        ffi_config.intermediate_compression_level = config.intermediate_compression_level,
-        ffi_config.use_stats_file = config.use_stats_file,
-        ffi_config.stats_file = rust::String(config.stats_file.c_str()),
+    ffi_config.use_temp_dir = config.use_temp_dir,
+    ffi_config.temp_dir = rust::String(config.temp_dir.c_str()),
+    ffi_config.memory = config.memory,
+    ffi_config.prefer_memory = config.prefer_memory,
+    ffi_config.hard_memory_limit = config.hard_memory_limit,
+    ffi_config.total_threads_count = config.total_threads_count,
+    ffi_config.intermediate_compression_level = config.intermediate_compression_level,
+    ffi_config.use_stats_file = config.use_stats_file,
+    ffi_config.stats_file = rust::String(config.stats_file.c_str()),
+    ffi_config.minimizer_scheme = config.minimizer_scheme,
+    ffi_config.minimizer_scheme_param = config.minimizer_scheme_param,
+    ffi_config.progress_callback = config.progress_callback ? reinterpret_cast<uintptr_t>(progress_callback_bridge) : 0,
+    ffi_config.progress_callback_context = reinterpret_cast<uintptr_t>(instance),
 
-        ffi_instance = &ggcat_create(ffi_config);
+    GGCATErrorFFI error;
+    uintptr_t ffi_instance = ggcat_create(ffi_config, error);
+    if (error.code != 0)
+    {
+        delete instance;
+        throw_if_error(error);
     }
-    return &instance;
+
+    instance->ffi_instance = reinterpret_cast<GGCATInstanceFFI const *>(ffi_instance);
+    return instance;
 }
 
 std::string GGCATInstance::build_graph_from_files(
@@ -40,7 +113,8 @@ std::string GGCATInstance::build_graph_from_files(
     ExtraElaborationStep extra_elab,
     bool colors,
     Slice<std::string> color_names,
-    size_t minimizer_length)
+    size_t minimizer_length,
+    CancellationToken *cancellation_token)
 {
     std::vector<rust::String> ffi_input_files;
 
@@ -56,6 +130,7 @@ std::string GGCATInstance::build_graph_from_files(
         ffi_color_names.push_back(rust::String(color_names.data[i].c_str()));
     }
 
+    GGCATErrorFFI error;
     auto rust_str = ggcat_build_from_files(*ffi_instance,
                                            rust::Slice<const rust::String>(ffi_input_files.data(), ffi_input_files.size()),
                                            rust::String(output_file.c_str()),
@@ -66,7 +141,10 @@ std::string GGCATInstance::build_graph_from_files(
                                            minimizer_length,
                                            colors,
                                            min_multiplicity,
-                                           extra_elab);
+                                           extra_elab,
+                                           cancellation_token ? cancellation_token->ffi_token : 0,
+                                           error);
+    throw_if_error(error);
     return std::string(rust_str.c_str());
 }
 
@@ -80,19 +158,10 @@ std::string GGCATInstance::build_graph_internal_ffi(
     ExtraElaborationStep extra_elab,
     bool colors,
     Slice<std::string> color_names,
-    size_t minimizer_length)
+    size_t minimizer_length,
+    CancellationToken *cancellation_token)
 {
-    std::vector<InputStreamFFI> ffi_input_streams;
-
-    for (size_t i = 0; i < input_streams.size; i++)
-    {
-        InputStreamFFI ffi_input_stream;
-        ffi_input_stream.block_data = input_streams.data[i].block_data;
-        ffi_input_stream.virtual_read_block = (uintptr_t)input_streams.data[i].read_block;
-        ffi_input_stream.virtual_estimated_base_count = (uintptr_t)input_streams.data[i].estimated_base_count;
-
-        ffi_input_streams.push_back(ffi_input_stream);
-    }
+    std::vector<InputStreamFFI> ffi_input_streams = get_ffi_input_streams(input_streams);
 
     std::vector<rust::String> ffi_color_names;
 
@@ -101,6 +170,7 @@ std::string GGCATInstance::build_graph_internal_ffi(
         ffi_color_names.push_back(rust::String(color_names.data[i].c_str()));
     }
 
+    GGCATErrorFFI error;
     auto rust_str = ggcat_build_from_streams(*ffi_instance,
                                              rust::Slice<const InputStreamFFI>(ffi_input_streams.data(), ffi_input_streams.size()),
                                              rust::String(output_file.c_str()),
@@ -111,7 +181,10 @@ std::string GGCATInstance::build_graph_internal_ffi(
                                              minimizer_length,
                                              colors,
                                              min_multiplicity,
-                                             extra_elab);
+                                             extra_elab,
+                                             cancellation_token ? cancellation_token->ffi_token : 0,
+                                             error);
+    throw_if_error(error);
     return std::string(rust_str.c_str());
 }
 
@@ -124,8 +197,12 @@ std::string GGCATInstance::query_graph(
     bool forward_only,
     bool colors,
     size_t color_output_format,
-    size_t minimizer_length)
+    size_t minimizer_length,
+    CancellationToken *cancellation_token,
+    QueryResultCallback result_callback,
+    void *result_callback_context)
 {
+    GGCATErrorFFI error;
     auto rust_str = ggcat_query_graph(*ffi_instance,
                                       rust::String(input_graph.c_str()),
                                       rust::String(input_query.c_str()),
@@ -135,7 +212,47 @@ std::string GGCATInstance::query_graph(
                                       forward_only,
                                       minimizer_length,
                                       colors,
-                                      color_output_format);
+                                      color_output_format,
+                                      reinterpret_cast<uintptr_t>(result_callback),
+                                      reinterpret_cast<uintptr_t>(result_callback_context),
+                                      cancellation_token ? cancellation_token->ffi_token : 0,
+                                      error);
+    throw_if_error(error);
+    return std::string(rust_str.c_str());
+}
+
+std::string GGCATInstance::query_graph_internal_ffi(
+    std::string input_graph,
+    Slice<__InputStreamBlockData> input_streams,
+    std::string output_file_prefix,
+    size_t kmer_length,
+    size_t threads_count,
+    bool forward_only,
+    bool colors,
+    size_t color_output_format,
+    size_t minimizer_length,
+    CancellationToken *cancellation_token,
+    QueryResultCallback result_callback,
+    void *result_callback_context)
+{
+    std::vector<InputStreamFFI> ffi_input_streams = get_ffi_input_streams(input_streams);
+
+    GGCATErrorFFI error;
+    auto rust_str = ggcat_query_graph_from_streams(*ffi_instance,
+                                                   rust::String(input_graph.c_str()),
+                                                   rust::Slice<const InputStreamFFI>(ffi_input_streams.data(), ffi_input_streams.size()),
+                                                   rust::String(output_file_prefix.c_str()),
+                                                   kmer_length,
+                                                   threads_count,
+                                                   forward_only,
+                                                   minimizer_length,
+                                                   colors,
+                                                   color_output_format,
+                                                   reinterpret_cast<uintptr_t>(result_callback),
+                                                   reinterpret_cast<uintptr_t>(result_callback_context),
+                                                   cancellation_token ? cancellation_token->ffi_token : 0,
+                                                   error);
+    throw_if_error(error);
     return std::string(rust_str.c_str());
 }
 
@@ -148,7 +265,9 @@ std::string GGCATInstance::get_colormap_file(std::string graph_file)
 std::vector<std::string> GGCATInstance::dump_colors(
     std::string input_colormap)
 {
-    auto colors_vec = ggcat_dump_colors(rust::String(input_colormap.c_str()));
+    GGCATErrorFFI error;
+    auto colors_vec = ggcat_dump_colors(rust::String(input_colormap.c_str()), error);
+    throw_if_error(error);
 
     std::vector<std::string> colors;
 
@@ -171,7 +290,7 @@ void GGCATInstance::dump_unitigs_internal(
     uintptr_t output_function)
 
 {
-
+    GGCATErrorFFI error;
     ggcat_dump_unitigs(*ffi_instance,
                        rust::String(graph_input.c_str()),
                        kmer_length,
@@ -180,5 +299,7 @@ void GGCATInstance::dump_unitigs_internal(
                        threads_count,
                        single_thread_output_function,
                        context,
-                       output_function);
+                       output_function,
+                       error);
+    throw_if_error(error);
 }
\ No newline at end of file
@@ -1,37 +1,233 @@
+use std::panic::AssertUnwindSafe;
 use std::slice::from_raw_parts;
 use std::sync::Arc;
 use std::{mem::transmute, path::PathBuf};
 
+use ggcat_api::{
+    BuildOptions, CancellationToken, ExtraElaboration, GGCATConfig, GGCATError, GGCATInstance,
+    GeneralSequenceBlockData, MinimizerScheme, ProgressCallback, ProgressEvent, QueryOptions,
+};
 use ggcat_api::{
     ColorIndexType, DnaSequence, DnaSequencesFileType, DynamicSequencesStream, SequenceInfo,
 };
-use ggcat_api::{ExtraElaboration, GGCATConfig, GGCATInstance, GeneralSequenceBlockData};
-
-#[repr(transparent)]
-struct GGCATInstanceFFI(GGCATInstance);
-
-fn ggcat_create(config: ffi::GGCATConfigFFI) -> &'static GGCATInstanceFFI {
-    let instance = GGCATInstance::create(GGCATConfig {
-        temp_dir: if config.use_temp_dir {
-            Some(PathBuf::from(config.temp_dir))
-        } else {
-            None
-        },
-        memory: config.memory,
-        prefer_memory: config.prefer_memory,
-        total_threads_count: config.total_threads_count,
-        intermediate_compression_level: if config.intermediate_compression_level != u32::MAX {
-            Some(config.intermediate_compression_level)
-        } else {
-            None
-        },
-        stats_file: if config.use_stats_file {
-            Some(PathBuf::from(config.stats_file))
-        } else {
-            None
-        },
+
+struct GGCATInstanceFFI(Arc<GGCATInstance>);
+
+const PROGRESS_EVENT_PHASE_START: usize = 0;
+const PROGRESS_EVENT_PHASE_END: usize = 1;
+const PROGRESS_EVENT_BUCKETS_PROCESSED: usize = 2;
+const PROGRESS_EVENT_LINKS_COMPACTION: usize = 3;
+const PROGRESS_EVENT_BYTES_READ: usize = 4;
+
+/// Forwards the progress events to the C function, with the arguments:
+/// (context, event_type, name_ptr, name_len, value1, value2, value3)
+/// PhaseStart: (name) PhaseEnd: (name, duration_ms) BucketsProcessed: (processed, total)
+/// LinksCompaction: (iteration, remaining_links) BytesRead: (bytes, processed_files, total_files)
+fn progress_callback_bridge(callback_ptr: usize, callback_context: usize) -> ProgressCallback {
+    let callback: extern "C" fn(usize, usize, usize, usize, u64, u64, u64) =
+        unsafe { transmute(callback_ptr) };
+
+    Arc::new(move |event: &ProgressEvent| {
+        let (event_type, name, values) = match event {
+            ProgressEvent::PhaseStart { name } => {
+                (PROGRESS_EVENT_PHASE_START, name.as_str(), [0; 3])
+            }
+            ProgressEvent::PhaseEnd { name, duration } => (
+                PROGRESS_EVENT_PHASE_END,
+                name.as_str(),
+                [duration.as_millis() as u64, 0, 0],
+            ),
+            ProgressEvent::BucketsProcessed { processed, total } => (
+                PROGRESS_EVENT_BUCKETS_PROCESSED,
+                "",
+                [*processed as u64, *total as u64, 0],
+            ),
+            ProgressEvent::LinksCompaction {
+                iteration,
+                remaining_links,
+            } => (
+                PROGRESS_EVENT_LINKS_COMPACTION,
+                "",
+                [*iteration as u64, *remaining_links, 0],
+            ),
+            ProgressEvent::BytesRead {
+                bytes,
+                processed_files,
+                total_files,
+            } => (
+                PROGRESS_EVENT_BYTES_READ,
+                "",
+                [*bytes, *processed_files as u64, *total_files as u64],
+            ),
+        };
+
+        callback(
+            callback_context,
+            event_type,
+            name.as_ptr() as usize,
+            name.len(),
+            values[0],
+            values[1],
+            values[2],
+        );
+    })
+}
+
+/// Creates the instance, returning the pointer to it or 0 if the creation failed
+fn ggcat_create(config: ffi::GGCATConfigFFI, error: &mut ffi::GGCATErrorFFI) -> usize {
+    const MINIMIZER_SCHEME_RANDOM: usize = 0;
+    const MINIMIZER_SCHEME_OPEN_SYNCMERS: usize = 1;
+    const MINIMIZER_SCHEME_CLOSED_SYNCMERS: usize = 2;
+    const MINIMIZER_SCHEME_MOD_MINIMIZERS: usize = 3;
+    const MINIMIZER_SCHEME_FREQUENCY_AWARE: usize = 4;
+
+    run_reporting_errors(error, || {
+        let minimizer_scheme = match config.minimizer_scheme {
+            MINIMIZER_SCHEME_RANDOM => MinimizerScheme::Random,
+            MINIMIZER_SCHEME_OPEN_SYNCMERS => MinimizerScheme::OpenSyncmers {
+                s: config.minimizer_scheme_param,
+            },
+            MINIMIZER_SCHEME_CLOSED_SYNCMERS => MinimizerScheme::ClosedSyncmers {
+                s: config.minimizer_scheme_param,
+            },
+            MINIMIZER_SCHEME_MOD_MINIMIZERS => MinimizerScheme::ModMinimizers {
+                r: config.minimizer_scheme_param,
+            },
+            MINIMIZER_SCHEME_FREQUENCY_AWARE => MinimizerScheme::FrequencyAware,
+            _ => {
+                return Err(GGCATError::InvalidOptions(format!(
+                    "invalid minimizer_scheme value: {}",
+                    config.minimizer_scheme
+                )))
+            }
+        };
+
+        let instance = GGCATInstance::create(GGCATConfig {
+            temp_dir: if config.use_temp_dir {
+                Some(PathBuf::from(config.temp_dir))
+            } else {
+                None
+            },
+            memory: config.memory,
+            prefer_memory: config.prefer_memory,
+            hard_memory_limit: if config.hard_memory_limit > 0.0 {
+                Some(config.hard_memory_limit)
+            } else {
+                None
+            },
+            total_threads_count: config.total_threads_count,
+            intermediate_compression_level: if config.intermediate_compression_level != u32::MAX {
+                Some(config.intermediate_compression_level)
+            } else {
+                None
+            },
+            stats_file: if config.use_stats_file {
+                Some(PathBuf::from(config.stats_file))
+            } else {
+                None
+            },
+            minimizer_scheme,
+            progress_callback: if config.progress_callback != 0 {
+                Some(progress_callback_bridge(
+                    config.progress_callback,
+                    config.progress_callback_context,
+                ))
+            } else {
+                None
+            },
+        })?;
+        // The instances created from C++ are never freed
+        Ok(Box::leak(Box::new(GGCATInstanceFFI(instance))) as *const GGCATInstanceFFI as usize)
+    })
+}
+
+/// Creates a new cancellation token, returning its pointer that must be freed with ggcat_free_cancellation_token
+fn ggcat_create_cancellation_token() -> usize {
+    Box::into_raw(Box::new(CancellationToken::new())) as usize
+}
+
+fn ggcat_free_cancellation_token(token: usize) {
+    drop(unsafe { Box::from_raw(token as *mut CancellationToken) });
+}
+
+fn ggcat_cancel(token: usize) {
+    unsafe { &*(token as *const CancellationToken) }.cancel();
+}
+
+fn ggcat_is_cancelled(token: usize) -> bool {
+    unsafe { &*(token as *const CancellationToken) }.is_cancelled()
+}
+
+const ERROR_CODE_NONE: usize = 0;
+const ERROR_CODE_INVALID_OPTIONS: usize = 1;
+const ERROR_CODE_INPUT_FILE_NOT_FOUND: usize = 2;
+const ERROR_CODE_INVALID_COLORMAP: usize = 3;
+const ERROR_CODE_IO: usize = 4;
+const ERROR_CODE_INTERNAL: usize = 5;
+const ERROR_CODE_CANCELLED: usize = 6;
+const ERROR_CODE_HASH_COLLISIONS: usize = 7;
+const ERROR_CODE_OUT_OF_MEMORY: usize = 8;
+
+/// Stores the error in the error struct passed from C++, returning the default value
+fn report_error<T: Default>(error: GGCATError, error_ffi: &mut ffi::GGCATErrorFFI) -> T {
+    error_ffi.code = match &error {
+        GGCATError::InvalidOptions(_) => ERROR_CODE_INVALID_OPTIONS,
+        GGCATError::InputFileNotFound(_) => ERROR_CODE_INPUT_FILE_NOT_FOUND,
+        GGCATError::InvalidColormap { .. } => ERROR_CODE_INVALID_COLORMAP,
+        GGCATError::Io(_) => ERROR_CODE_IO,
+        GGCATError::Internal(_) => ERROR_CODE_INTERNAL,
+        GGCATError::Cancelled => ERROR_CODE_CANCELLED,
+        GGCATError::HashCollisions { .. } => ERROR_CODE_HASH_COLLISIONS,
+        GGCATError::OutOfMemory(_) => ERROR_CODE_OUT_OF_MEMORY,
+    };
+    error_ffi.message = error.to_string();
+    T::default()
+}
+
+/// Runs the function reporting its errors and panics to C++, as unwinding across the ffi boundary aborts the process
+fn run_reporting_errors<T: Default>(
+    error_ffi: &mut ffi::GGCATErrorFFI,
+    function: impl FnOnce() -> Result<T, GGCATError>,
+) -> T {
+    error_ffi.code = ERROR_CODE_NONE;
+    error_ffi.message = String::new();
+
+    let result = std::panic::catch_unwind(AssertUnwindSafe(function)).unwrap_or_else(|payload| {
+        Err(GGCATError::Internal(
+            payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown error".to_string()),
+        ))
     });
-    unsafe { std::mem::transmute(instance) }
+
+    match result {
+        Ok(value) => value,
+        Err(error) => report_error(error, error_ffi),
+    }
+}
+
+/// Converts the returned paths to strings. They are built from the utf-8 strings passed from C++,
+/// so the conversion never replaces any character
+fn path_to_string(path: PathBuf) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn get_minimizer_length(minimizer_length: usize) -> Option<usize> {
+    if minimizer_length == usize::MAX {
+        None
+    } else {
+        Some(minimizer_length)
+    }
+}
+
+fn get_cancellation_token(cancellation_token: usize) -> Option<CancellationToken> {
+    if cancellation_token == 0 {
+        None
+    } else {
+        Some(unsafe { &*(cancellation_token as *const CancellationToken) }.clone())
+    }
 }
 
 fn ggcat_build(
@@ -62,45 +258,53 @@ fn ggcat_build(
 
     // Extra elaboration step
     extra_elab: usize,
-) -> String {
+
+    // Optional token to cancel the build, 0 if not used
+    cancellation_token: usize,
+) -> Result<String, GGCATError> {
     const EXTRA_ELABORATION_STEP_NONE: usize = 0;
     const EXTRA_ELABORATION_STEP_UNITIG_LINKS: usize = 1;
     const EXTRA_ELABORATION_STEP_GREEDY_MATCHTIGS: usize = 2;
     const EXTRA_ELABORATION_STEP_EULERTIGS: usize = 3;
     const EXTRA_ELABORATION_STEP_PATHTIGS: usize = 4;
+    const EXTRA_ELABORATION_STEP_COLOR_COMPACTED_UNITIGS: usize = 5;
+
+    let extra_elab = match extra_elab {
+        EXTRA_ELABORATION_STEP_NONE => ExtraElaboration::None,
+        EXTRA_ELABORATION_STEP_UNITIG_LINKS => ExtraElaboration::UnitigLinks,
+        EXTRA_ELABORATION_STEP_GREEDY_MATCHTIGS => ExtraElaboration::GreedyMatchtigs,
+        EXTRA_ELABORATION_STEP_EULERTIGS => ExtraElaboration::Eulertigs,
+        EXTRA_ELABORATION_STEP_PATHTIGS => ExtraElaboration::Pathtigs,
+        EXTRA_ELABORATION_STEP_COLOR_COMPACTED_UNITIGS => ExtraElaboration::ColorCompactedUnitigs,
+        _ => {
+            return Err(GGCATError::InvalidOptions(format!(
+                "invalid extra_elab value: {}",
+                extra_elab
+            )))
+        }
+    };
+
+    let debug_options = instance.0.get_debug_options();
+    let mut options = BuildOptions::builder(kmer_length)
+        .color_names(color_names.to_vec())
+        .threads_count(threads_count)
+        .forward_only(forward_only)
+        .colors(colors)
+        .min_multiplicity(min_multiplicity)
+        .extra_elaboration(extra_elab)
+        .hash_type(debug_options.hash_type)
+        .verify_hash_collisions(debug_options.verify_hash_collisions);
+    if let Some(minimizer_length) = get_minimizer_length(minimizer_length) {
+        options = options.minimizer_length(minimizer_length);
+    }
+    if let Some(cancellation_token) = get_cancellation_token(cancellation_token) {
+        options = options.cancellation_token(cancellation_token);
+    }
 
     instance
         .0
-        .build_graph(
-            input_blocks,
-            PathBuf::from(output_file),
-            if color_names.len() > 0 {
-                Some(color_names)
-            } else {
-                None
-            },
-            kmer_length,
-            threads_count,
-            forward_only,
-            if minimizer_length == usize::MAX {
-                None
-            } else {
-                Some(minimizer_length)
-            },
-            colors,
-            min_multiplicity,
-            match extra_elab {
-                EXTRA_ELABORATION_STEP_NONE => ExtraElaboration::None,
-                EXTRA_ELABORATION_STEP_UNITIG_LINKS => ExtraElaboration::UnitigLinks,
-                EXTRA_ELABORATION_STEP_GREEDY_MATCHTIGS => ExtraElaboration::GreedyMatchtigs,
-                EXTRA_ELABORATION_STEP_EULERTIGS => ExtraElaboration::Eulertigs,
-                EXTRA_ELABORATION_STEP_PATHTIGS => ExtraElaboration::Pathtigs,
-                _ => panic!("Invalid extra_elab value: {}", extra_elab),
-            },
-        )
-        .to_str()
-        .unwrap()
-        .to_string()
+        .build(input_blocks, PathBuf::from(output_file), &options.build()?)
+        .map(path_to_string)
 }
 
 fn ggcat_build_from_files(
@@ -131,30 +335,125 @@ fn ggcat_build_from_files(
 
     // Extra elaboration step
     extra_elab: usize,
+
+    // Optional token to cancel the build, 0 if not used
+    cancellation_token: usize,
+
+    error: &mut ffi::GGCATErrorFFI,
 ) -> String {
-    ggcat_build(
-        instance,
-        input_files
-            .iter()
-            .map(|f| {
-                if f.ends_with(".gfa") {
-                    todo!("GFA support is not implemented yet");
-                    // GeneralSequenceBlockData::GFA() // PathBuf::from(f))
-                } else {
-                    GeneralSequenceBlockData::FASTA(PathBuf::from(f))
-                }
-            })
-            .collect(),
-        output_file,
-        color_names,
-        kmer_length,
-        threads_count,
-        forward_only,
-        minimizer_length,
-        colors,
-        min_multiplicity,
-        extra_elab,
-    )
+    run_reporting_errors(error, || {
+        let mut input_blocks = vec![];
+        for file in input_files {
+            if file.ends_with(".gfa") {
+                return Err(GGCATError::InvalidOptions(
+                    "GFA support is not implemented yet".to_string(),
+                ));
+            }
+            input_blocks.push(GeneralSequenceBlockData::FASTA(PathBuf::from(file)));
+        }
+
+        ggcat_build(
+            instance,
+            input_blocks,
+            output_file,
+            color_names,
+            kmer_length,
+            threads_count,
+            forward_only,
+            minimizer_length,
+            colors,
+            min_multiplicity,
+            extra_elab,
+            cancellation_token,
+        )
+    })
+}
+
+struct SequencesStreamFFI {
+    // extern "C" void (*read_block)(uintptr_t block, bool copy_ident_data, size_t partial_read_copyback, uintptr_t callback, uintptr_t callback_context);
+    //      with void callback(uintptr_t callback_context, DnaSequenceFFI sequence, SequenceInfoFFI info);
+    virtual_read_block: extern "C" fn(
+        block: usize,
+        copy_ident_data: bool,
+        partial_read_copyback: usize,
+        callback: extern "C" fn(
+            callback_context: usize,
+            sequence: DnaSequenceFFI,
+            info: SequenceInfoFFI,
+        ),
+        callback_context: usize,
+    ),
+    // extern "C" uint64_t (*estimated_base_count)(uintptr_t block);
+    virtual_estimated_base_count: extern "C" fn(block: usize) -> u64,
+}
+
+impl DynamicSequencesStream for SequencesStreamFFI {
+    fn read_block(
+        &self,
+        block: usize,
+        copy_ident_data: bool,
+        partial_read_copyback: Option<usize>,
+        mut callback: &mut dyn FnMut(DnaSequence, SequenceInfo),
+    ) {
+        extern "C" fn callback_wrapper(
+            callback_ptr: usize,
+            sequence: DnaSequenceFFI,
+            info: SequenceInfoFFI,
+        ) {
+            let callback =
+                unsafe { &mut *(callback_ptr as *mut &mut dyn FnMut(DnaSequence, SequenceInfo)) };
+            callback(
+                DnaSequence {
+                    ident_data: unsafe {
+                        from_raw_parts(sequence.ident_data, sequence.ident_data_len)
+                    },
+                    seq: unsafe { from_raw_parts(sequence.seq, sequence.seq_len) },
+                    format: match sequence.format {
+                        DnaSequencesFileTypeFFI::FASTA => DnaSequencesFileType::FASTA,
+                        DnaSequencesFileTypeFFI::FASTQ => DnaSequencesFileType::FASTQ,
+                        DnaSequencesFileTypeFFI::GFA => DnaSequencesFileType::GFA,
+                        DnaSequencesFileTypeFFI::BINARY => DnaSequencesFileType::BINARY,
+                    },
+                },
+                SequenceInfo {
+                    color: Some(info.color),
+                },
+            );
+        }
+
+        let callback_ptr = (&mut callback) as *mut _ as usize;
+        (self.virtual_read_block)(
+            block,
+            copy_ident_data,
+            partial_read_copyback.unwrap_or(0),
+            callback_wrapper,
+            callback_ptr,
+        );
+    }
+
+    fn estimated_base_count(&self, block: usize) -> u64 {
+        (self.virtual_estimated_base_count)(block)
+    }
+}
+
+/// Converts the streams passed from C++ to input blocks
+fn get_input_streams_blocks(
+    input_streams: &[ffi::InputStreamFFI],
+) -> Vec<GeneralSequenceBlockData> {
+    input_streams
+        .iter()
+        .map(|s| {
+            GeneralSequenceBlockData::Dynamic((
+                Arc::new(unsafe {
+                    SequencesStreamFFI {
+                        virtual_read_block: transmute(s.virtual_read_block),
+                        virtual_estimated_base_count: transmute(s.virtual_estimated_base_count),
+                    }
+                }),
+                s.block_data,
+            ))
+        })
+        .collect()
 }
 
 fn ggcat_build_from_streams(
@@ -185,101 +484,86 @@ fn ggcat_build_from_streams(
 
     // Extra elaboration step
     extra_elab: usize,
+
+    // Optional token to cancel the build, 0 if not used
+    cancellation_token: usize,
+
+    error: &mut ffi::GGCATErrorFFI,
 ) -> String {
-    struct SequencesStreamFFI {
-        // extern "C" void (*read_block)(uintptr_t block, bool copy_ident_data, size_t partial_read_copyback, uintptr_t callback, uintptr_t callback_context);
-        //      with void callback(uintptr_t callback_context, DnaSequenceFFI sequence, SequenceInfoFFI info);
-        virtual_read_block: extern "C" fn(
-            block: usize,
-            copy_ident_data: bool,
-            partial_read_copyback: usize,
-            callback: extern "C" fn(
-                callback_context: usize,
-                sequence: DnaSequenceFFI,
-                info: SequenceInfoFFI,
-            ),
-            callback_context: usize,
-        ),
-        // extern "C" uint64_t (*estimated_base_count)(uintptr_t block);
-        virtual_estimated_base_count: extern "C" fn(block: usize) -> u64,
-    }
+    run_reporting_errors(error, || {
+        ggcat_build(
+            instance,
+            get_input_streams_blocks(input_streams),
+            output_file,
+            color_names,
+            kmer_length,
+            threads_count,
+            forward_only,
+            minimizer_length,
+            colors,
+            min_multiplicity,
+            extra_elab,
+            cancellation_token,
+        )
+    })
+}
 
-    impl DynamicSequencesStream for SequencesStreamFFI {
-        fn read_block(
-            &self,
-            block: usize,
-            copy_ident_data: bool,
-            partial_read_copyback: Option<usize>,
-            mut callback: &mut dyn FnMut(DnaSequence, SequenceInfo),
-        ) {
-            extern "C" fn callback_wrapper(
-                callback_ptr: usize,
-                sequence: DnaSequenceFFI,
-                info: SequenceInfoFFI,
-            ) {
-                let callback = unsafe {
-                    &mut *(callback_ptr as *mut &mut dyn FnMut(DnaSequence, SequenceInfo))
-                };
-                callback(
-                    DnaSequence {
-                        ident_data: unsafe {
-                            from_raw_parts(sequence.ident_data, sequence.ident_data_len)
-                        },
-                        seq: unsafe { from_raw_parts(sequence.seq, sequence.seq_len) },
-                        format: match sequence.format {
-                            DnaSequencesFileTypeFFI::FASTA => DnaSequencesFileType::FASTA,
-                            DnaSequencesFileTypeFFI::FASTQ => DnaSequencesFileType::FASTQ,
-                            DnaSequencesFileTypeFFI::GFA => DnaSequencesFileType::GFA,
-                            DnaSequencesFileTypeFFI::BINARY => DnaSequencesFileType::BINARY,
-                        },
-                    },
-                    SequenceInfo {
-                        color: Some(info.color),
-                    },
-                );
-            }
+/// Creates the validated query options from the C++ parameters
+fn get_query_options(
+    instance: &'static GGCATInstanceFFI,
+    kmer_length: usize,
+    threads_count: usize,
+    forward_only: bool,
+    minimizer_length: usize,
+    colors: bool,
+    color_output_format: usize,
+    result_callback: usize,
+    result_callback_context: usize,
+    cancellation_token: usize,
+) -> Result<QueryOptions, GGCATError> {
+    const COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NUMBERS: usize = 0;
+    const COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NAMES: usize = 1;
+    const COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NAMES_AND_METADATA: usize = 2;
 
-            let callback_ptr = (&mut callback) as *mut _ as usize;
-            (self.virtual_read_block)(
-                block,
-                copy_ident_data,
-                partial_read_copyback.unwrap_or(0),
-                callback_wrapper,
-                callback_ptr,
-            );
+    let color_output_format = match color_output_format {
+        COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NUMBERS => {
+            ggcat_api::ColoredQueryOutputFormat::JsonLinesWithNumbers
         }
-
-        fn estimated_base_count(&self, block: usize) -> u64 {
-            (self.virtual_estimated_base_count)(block)
+        COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NAMES => {
+            ggcat_api::ColoredQueryOutputFormat::JsonLinesWithNames
+        }
+        COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NAMES_AND_METADATA => {
+            ggcat_api::ColoredQueryOutputFormat::JsonLinesWithNamesAndMetadata
         }
+        _ => {
+            return Err(GGCATError::InvalidOptions(format!(
+                "invalid color_output_format value: {}",
+                color_output_format
+            )))
+        }
+    };
+
+    let mut options = QueryOptions::builder(kmer_length)
+        .threads_count(threads_count)
+        .forward_only(forward_only)
+        .colors(colors)
+        .color_output_format(color_output_format)
+        .hash_type(instance.0.get_debug_options().hash_type);
+    if let Some(minimizer_length) = get_minimizer_length(minimizer_length) {
+        options = options.minimizer_length(minimizer_length);
     }
-
-    ggcat_build(
-        instance,
-        input_streams
-            .iter()
-            .map(|s| {
-                GeneralSequenceBlockData::Dynamic((
-                    Arc::new(unsafe {
-                        SequencesStreamFFI {
-                            virtual_read_block: transmute(s.virtual_read_block),
-                            virtual_estimated_base_count: transmute(s.virtual_estimated_base_count),
-                        }
-                    }),
-                    s.block_data,
-                ))
-            })
-            .collect(),
-        output_file,
-        color_names,
-        kmer_length,
-        threads_count,
-        forward_only,
-        minimizer_length,
-        colors,
-        min_multiplicity,
-        extra_elab,
-    )
+    if let Some(cancellation_token) = get_cancellation_token(cancellation_token) {
+        options = options.cancellation_token(cancellation_token);
+    }
+    if result_callback != 0 {
+        // extern "C" void (*result_callback)(uintptr_t context, size_t query_index, uint32_t color, double fraction);
+        let result_callback: extern "C" fn(usize, usize, u32, f64) =
+            unsafe { transmute(result_callback) };
+        options = options.results_callback(Arc::new(move |query_index, color, fraction| {
+            result_callback(result_callback_context, query_index, color, fraction)
+        }));
+    }
+    options.build()
 }
 
 /// Queries a (optionally) colored graph with a specific set of sequences as queries
@@ -308,56 +592,123 @@ fn ggcat_query_graph(
 
     // Query output format
     color_output_format: usize,
+
+    // Optional function receiving each query result, 0 if not used
+    result_callback: usize,
+    result_callback_context: usize,
+
+    // Optional token to cancel the query, 0 if not used
+    cancellation_token: usize,
+
+    error: &mut ffi::GGCATErrorFFI,
 ) -> String {
-    const COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NUMBERS: usize = 0;
-    const COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NAMES: usize = 1;
+    run_reporting_errors(error, || {
+        let options = get_query_options(
+            instance,
+            kmer_length,
+            threads_count,
+            forward_only,
+            minimizer_length,
+            colors,
+            color_output_format,
+            result_callback,
+            result_callback_context,
+            cancellation_token,
+        )?;
+
+        instance
+            .0
+            .query(
+                PathBuf::from(input_graph),
+                PathBuf::from(input_query),
+                PathBuf::from(output_file_prefix),
+                &options,
+            )
+            .map(path_to_string)
+    })
+}
 
-    instance
-        .0
-        .query_graph(
-            PathBuf::from(input_graph),
-            PathBuf::from(input_query),
-            PathBuf::from(output_file_prefix),
+/// Queries a (optionally) colored graph with the sequences of the given streams as queries
+fn ggcat_query_graph_from_streams(
+    instance: &'static GGCATInstanceFFI,
+
+    // The input graph
+    input_graph: String,
+    // The input query streams
+    input_streams: &[ffi::InputStreamFFI],
+
+    // The output file
+    output_file_prefix: String,
+
+    // Specifies the k-mers length
+    kmer_length: usize,
+    // The threads to be used
+    threads_count: usize,
+    // Treats reverse complementary kmers as different
+    forward_only: bool,
+    // Overrides the default m-mers (minimizers) length
+    minimizer_length: usize,
+
+    // Enable colors
+    colors: bool,
+
+    // Query output format
+    color_output_format: usize,
+
+    // Optional function receiving each query result, 0 if not used
+    result_callback: usize,
+    result_callback_context: usize,
+
+    // Optional token to cancel the query, 0 if not used
+    cancellation_token: usize,
+
+    error: &mut ffi::GGCATErrorFFI,
+) -> String {
+    run_reporting_errors(error, || {
+        let options = get_query_options(
+            instance,
             kmer_length,
             threads_count,
             forward_only,
-            if minimizer_length == usize::MAX {
-                None
-            } else {
-                Some(minimizer_length)
-            },
+            minimizer_length,
             colors,
-            match color_output_format {
-                COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NUMBERS => {
-                    ggcat_api::ColoredQueryOutputFormat::JsonLinesWithNumbers
-                }
-                COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NAMES => {
-                    ggcat_api::ColoredQueryOutputFormat::JsonLinesWithNames
-                }
-                _ => panic!("Invalid color_output_format value: {}", color_output_format),
-            },
-        )
-        .to_str()
-        .unwrap()
-        .to_string()
+            color_output_format,
+            result_callback,
+            result_callback_context,
+            cancellation_token,
+        )?;
+
+        instance
+            .0
+            .query_from_streams(
+                PathBuf::from(input_graph),
+                get_input_streams_blocks(input_streams),
+                PathBuf::from(output_file_prefix),
+                &options,
+            )
+            .map(path_to_string)
+    })
 }
 
 /// Obtains the standard colormap file path from a graph file path
 pub fn ggcat_get_colormap_file(graph_file: String) -> String {
-    GGCATInstance::get_colormap_file(PathBuf::from(graph_file))
-        .to_str()
-        .unwrap()
-        .to_string()
+    path_to_string(GGCATInstance::get_colormap_file(PathBuf::from(graph_file)))
 }
 
 /// Returns a vector of color names in the given graph.
 /// The color indexes returned from the dump_unitigs function
 /// can be used to index this vector.
-pub fn ggcat_dump_colors(
+fn ggcat_dump_colors(
     // The input colormap
     input_colormap: String,
+    error: &mut ffi::GGCATErrorFFI,
 ) -> Vec<String> {
-    GGCATInstance::dump_colors(input_colormap).collect()
+    run_reporting_errors(error, || {
+        if !PathBuf::from(&input_colormap).is_file() {
+            return Err(GGCATError::InputFileNotFound(PathBuf::from(input_colormap)));
+        }
+        Ok(GGCATInstance::dump_colors(input_colormap).collect())
+    })
 }
 
 /// Dumps the unitigs of the given graph, optionally with colors
@@ -382,32 +733,38 @@ fn ggcat_dump_unitigs(
 
     output_function_context: usize,
     output_function_ptr: usize,
+
+    error: &mut ffi::GGCATErrorFFI,
 ) {
     let output_function: extern "C" fn(usize, usize, usize, usize, usize, bool) =
         unsafe { transmute(output_function_ptr) };
 
-    instance.0.dump_unitigs(
-        PathBuf::from(graph_input),
-        kmer_length,
-        if minimizer_length == usize::MAX {
-            None
-        } else {
-            Some(minimizer_length)
-        },
-        colors,
-        threads_count,
-        single_thread_output_function,
-        |sequence, colors, same_colors| {
-            output_function(
-                output_function_context,
-                sequence.as_ptr() as usize,
-                sequence.len(),
-                colors.as_ptr() as usize,
-                colors.len(),
-                same_colors,
-            );
-        },
-    )
+    run_reporting_errors(error, || {
+        let graph_input = PathBuf::from(graph_input);
+        if !graph_input.is_file() {
+            return Err(GGCATError::InputFileNotFound(graph_input));
+        }
+
+        instance.0.dump_unitigs(
+            graph_input,
+            kmer_length,
+            get_minimizer_length(minimizer_length),
+            colors,
+            threads_count,
+            single_thread_output_function,
+            |sequence, colors, same_colors| {
+                output_function(
+                    output_function_context,
+                    sequence.as_ptr() as usize,
+                    sequence.len(),
+                    colors.as_ptr() as usize,
+                    colors.len(),
+                    same_colors,
+                );
+            },
+        );
+        Ok(())
+    })
 }
 
 static_assertions::assert_eq_size!(ColorIndexType, u32);
@@ -437,9 +794,9 @@ pub struct SequenceInfoFFI {
 
 #[cxx::bridge]
 mod ffi {
-    /// Main config of GGCAT. This config is global and should be passed to GGCATInstance::create
+    /// Main config of GGCAT, owned by the instance created with GGCATInstance::create
     pub struct GGCATConfigFFI {
-        /// If false, a memory only mode is attempted. May crash for large input data if there is no enough RAM memory.
+        /// If false, all the temporary files are kept in memory. Fails with an out of memory error if there is not enough RAM memory.
         pub use_temp_dir: bool,
 
         /// Directory for temporary files
@@ -454,6 +811,9 @@ mod ffi {
         /// Use all the given memory before writing to disk
         pub prefer_memory: bool,
 
+        /// Hard memory limit for the whole process (GB), including the processing steps, 0 to disable it
+        pub hard_memory_limit: f64,
+
         /// The total threads to be used
         pub total_threads_count: usize,
 
@@ -464,6 +824,24 @@ mod ffi {
         pub use_stats_file: bool,
         /// The path to an optional json-formatted real time stats file
         pub stats_file: String,
+
+        /// The scheme used to choose the minimizers
+        pub minimizer_scheme: usize,
+        /// The s-mers length for syncmers or the r parameter for mod-minimizers
+        pub minimizer_scheme_param: usize,
+
+        // extern "C" void (*progress_callback)(uintptr_t context, size_t event_type, uintptr_t name_ptr, size_t name_len, uint64_t value1, uint64_t value2, uint64_t value3);
+        /// Optional function receiving the progress events, 0 to disable it
+        pub progress_callback: usize,
+        /// The context passed to the progress callback
+        pub progress_callback_context: usize,
+    }
+
+    /// Error reported by the fallible functions, code is 0 if no error occurred
+    pub struct GGCATErrorFFI {
+        /// 0: none, 1: invalid options, 2: input file not found, 3: invalid colormap, 4: io error, 5: internal error, 6: cancelled
+        pub code: usize,
+        pub message: String,
     }
 
     pub struct InputStreamFFI {
@@ -478,8 +856,20 @@ mod ffi {
     extern "Rust" {
         type GGCATInstanceFFI;
 
-        /// Creates a new GGCATInstance. If an instance already exists, it will be returned, ignoring the new config.
-        fn ggcat_create(config: GGCATConfigFFI) -> &'static GGCATInstanceFFI;
+        /// Creates a new independent GGCATInstance, with its own threads pool and settings.
+        /// Returns the pointer to the instance, or 0 if it cannot be created
+        fn ggcat_create(config: GGCATConfigFFI, error: &mut GGCATErrorFFI) -> usize;
+
+        /// Creates a token that can be used to cancel a running operation from another thread,
+        /// it must be freed with ggcat_free_cancellation_token
+        fn ggcat_create_cancellation_token() -> usize;
+
+        fn ggcat_free_cancellation_token(token: usize);
+
+        /// Requests the cancellation of the operations using the token, the cancelled operations report the cancelled error code
+        fn ggcat_cancel(token: usize);
+
+        fn ggcat_is_cancelled(token: usize) -> bool;
 
         /// Builds a new graph from the given input files, with the specified parameters
         fn ggcat_build_from_files(
@@ -510,6 +900,11 @@ mod ffi {
 
             // Extra elaboration step
             extra_elab: usize,
+
+            // Optional token to cancel the build, created with ggcat_create_cancellation_token, 0 if not used
+            cancellation_token: usize,
+
+            error: &mut GGCATErrorFFI,
         ) -> String;
 
         /// Builds a new graph from the given input streams, with the specified parameters
@@ -541,6 +936,11 @@ mod ffi {
 
             // Extra elaboration step
             extra_elab: usize,
+
+            // Optional token to cancel the build, created with ggcat_create_cancellation_token, 0 if not used
+            cancellation_token: usize,
+
+            error: &mut GGCATErrorFFI,
         ) -> String;
 
         /// Queries a (optionally) colored graph with a specific set of sequences as queries
@@ -569,6 +969,54 @@ mod ffi {
 
             // Query output format
             color_output_format: usize,
+
+            // extern "C" void (*result_callback)(uintptr_t context, size_t query_index, uint32_t color, double fraction);
+            // Optional function receiving each query result, 0 if not used, can be called concurrently
+            result_callback: usize,
+            result_callback_context: usize,
+
+            // Optional token to cancel the query, created with ggcat_create_cancellation_token, 0 if not used
+            cancellation_token: usize,
+
+            error: &mut GGCATErrorFFI,
+        ) -> String;
+
+        /// Queries a (optionally) colored graph with the sequences of the given streams as queries
+        fn ggcat_query_graph_from_streams(
+            instance: &'static GGCATInstanceFFI,
+
+            // The input graph
+            input_graph: String,
+            // The input query streams
+            input_streams: &[InputStreamFFI],
+
+            // The output file
+            output_file_prefix: String,
+
+            // Specifies the k-mers length
+            kmer_length: usize,
+            // The threads to be used
+            threads_count: usize,
+            // Treats reverse complementary kmers as different
+            forward_only: bool,
+            // Overrides the default m-mers (minimizers) length
+            minimizer_length: usize,
+
+            // Enable colors
+            colors: bool,
+
+            // Query output format
+            color_output_format: usize,
+
+            // extern "C" void (*result_callback)(uintptr_t context, size_t query_index, uint32_t color, double fraction);
+            // Optional function receiving each query result, 0 if not used, can be called concurrently
+            result_callback: usize,
+            result_callback_context: usize,
+
+            // Optional token to cancel the query, created with ggcat_create_cancellation_token, 0 if not used
+            cancellation_token: usize,
+
+            error: &mut GGCATErrorFFI,
         ) -> String;
 
         fn ggcat_get_colormap_file(graph_file: String) -> String;
@@ -576,9 +1024,10 @@ mod ffi {
         /// Returns a vector of color names in the given graph.
         /// The color indexes returned from the dump_unitigs function
         /// can be used to index this vector.
-        pub fn ggcat_dump_colors(
+        fn ggcat_dump_colors(
             // The input colormap
             input_colormap: String,
+            error: &mut GGCATErrorFFI,
         ) -> Vec<String>;
 
         /// Dumps the unitigs of the given graph, optionally with colors
@@ -603,6 +1052,8 @@ mod ffi {
             output_function_context: usize,
             // extern "C" fn(context: usize, seq_ptr: usize, seq_len: usize, col_ptr: usize, col_len: usize, same_colors: bool),
             output_function_ptr: usize,
+
+            error: &mut GGCATErrorFFI,
         );
     }
 }
@@ -0,0 +1,213 @@
+use crate::inspect::{inspect_colormap, InspectColormapArgs};
+use ggcat_api::{ColormapUpgrade, GGCATInstance};
+use log::{error, info};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub enum ColormapArgs {
+    /// Reorders the colors by similarity to reduce the colormap size
+    Optimize(OptimizeColormapArgs),
+    /// Converts a colormap written by an older version of ggcat to the current format
+    Upgrade(UpgradeColormapArgs),
+    /// Renames the colors of a colormap, without changing the graph
+    Rename(RenameColorsArgs),
+    /// Sets the json metadata of the colors of a colormap, reported by the queries
+    SetMetadata(SetColorsMetadataArgs),
+    /// Reports the sizes and the statistics of a colormap, or the contents of a subset or of a color
+    Inspect(InspectColormapArgs),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct OptimizeColormapArgs {
+    /// The input colormap
+    pub input_colormap: PathBuf,
+
+    /// Write the optimized colormap to this file instead of replacing the input one.
+    /// The input colormap is replaced only if the optimized one is smaller
+    #[structopt(short = "o", long = "output-file")]
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct UpgradeColormapArgs {
+    /// The input colormap
+    pub input_colormap: PathBuf,
+
+    /// Write the upgraded colormap to this file instead of replacing the input one
+    #[structopt(short = "o", long = "output-file")]
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct RenameColorsArgs {
+    /// The input colormap
+    pub input_colormap: PathBuf,
+
+    /// Tab separated file with the old and the new name of each renamed color, one per line
+    pub renames_file: PathBuf,
+
+    /// Write the edited colormap to this file instead of replacing the input one
+    #[structopt(short = "o", long = "output-file")]
+    pub output_file: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct SetColorsMetadataArgs {
+    /// The input colormap
+    pub input_colormap: PathBuf,
+
+    /// Json lines file with the metadata of each color, in the form {"color_name": "name", "metadata": {...}}.
+    /// A null metadata removes the metadata of the color
+    pub metadata_file: PathBuf,
+
+    /// Write the edited colormap to this file instead of replacing the input one
+    #[structopt(short = "o", long = "output-file")]
+    pub output_file: Option<PathBuf>,
+}
+
+/// Parses the non empty lines of a file, reporting the line number of the first invalid one
+fn parse_lines<T>(
+    file: &Path,
+    mut parse: impl FnMut(&str) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    let reader = BufReader::new(
+        File::open(file).map_err(|e| format!("Cannot open {}: {}", file.display(), e))?,
+    );
+
+    let mut values = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        values.push(parse(&line).map_err(|e| format!("{}:{}: {}", file.display(), index + 1, e))?);
+    }
+    Ok(values)
+}
+
+fn parse_rename(line: &str) -> Result<(String, String), String> {
+    match line.split_once('\t') {
+        Some((old_name, new_name)) if !new_name.contains('\t') => {
+            Ok((old_name.to_string(), new_name.to_string()))
+        }
+        _ => Err("expected the old and the new color name separated by a tab".to_string()),
+    }
+}
+
+fn parse_color_metadata(line: &str) -> Result<(String, Option<String>), String> {
+    let value: serde_json::Value =
+        serde_json::from_str(line).map_err(|e| format!("invalid json: {}", e))?;
+
+    let color_name = value
+        .get("color_name")
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| "missing the color_name string".to_string())?;
+
+    let metadata = match value.get("metadata") {
+        None => return Err("missing the metadata field".to_string()),
+        Some(serde_json::Value::Null) => None,
+        Some(metadata) => Some(metadata.to_string()),
+    };
+
+    Ok((color_name.to_string(), metadata))
+}
+
+fn run_optimize_colormap(args: OptimizeColormapArgs) -> bool {
+    let replace_input = args.output_file.is_none();
+
+    match GGCATInstance::optimize_colormap(&args.input_colormap, args.output_file) {
+        Ok(stats) => {
+            info!(
+                "Reordered {} colors in {} subsets: {} => {} bytes ({:.2}% reduction)",
+                stats.colors_count,
+                stats.subsets_count,
+                stats.original_size,
+                stats.reordered_size,
+                stats.size_reduction() * 100.0
+            );
+            if replace_input && stats.reordered_size >= stats.original_size {
+                info!("The reordered colormap is not smaller, the input colormap is unchanged");
+            }
+            true
+        }
+        Err(error) => {
+            error!("{}", error);
+            false
+        }
+    }
+}
+
+fn run_upgrade_colormap(args: UpgradeColormapArgs) -> bool {
+    match GGCATInstance::upgrade_colormap(&args.input_colormap, args.output_file) {
+        Ok(ColormapUpgrade::AlreadyCurrent) => {
+            info!("The colormap already uses the current format, nothing to do");
+            true
+        }
+        Ok(ColormapUpgrade::Upgraded { from_version }) => {
+            info!(
+                "Colormap upgraded from format version {} to the current one",
+                from_version
+            );
+            true
+        }
+        Err(error) => {
+            error!("{}", error);
+            false
+        }
+    }
+}
+
+fn run_rename_colors(args: RenameColorsArgs) -> bool {
+    let renames = match parse_lines(&args.renames_file, parse_rename) {
+        Ok(renames) => renames,
+        Err(error) => {
+            error!("{}", error);
+            return false;
+        }
+    };
+
+    match GGCATInstance::rename_colors(&args.input_colormap, args.output_file, &renames) {
+        Ok(renamed) => {
+            info!("Renamed {} colors", renamed);
+            true
+        }
+        Err(error) => {
+            error!("{}", error);
+            false
+        }
+    }
+}
+
+fn run_set_colors_metadata(args: SetColorsMetadataArgs) -> bool {
+    let metadata = match parse_lines(&args.metadata_file, parse_color_metadata) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            error!("{}", error);
+            return false;
+        }
+    };
+
+    match GGCATInstance::set_colors_metadata(&args.input_colormap, args.output_file, &metadata) {
+        Ok(updated) => {
+            info!("Updated the metadata of {} colors", updated);
+            true
+        }
+        Err(error) => {
+            error!("{}", error);
+            false
+        }
+    }
+}
+
+pub fn run_colormap_command(args: ColormapArgs) -> bool {
+    match args {
+        ColormapArgs::Optimize(args) => run_optimize_colormap(args),
+        ColormapArgs::Upgrade(args) => run_upgrade_colormap(args),
+        ColormapArgs::Rename(args) => run_rename_colors(args),
+        ColormapArgs::SetMetadata(args) => run_set_colors_metadata(args),
+        ColormapArgs::Inspect(args) => inspect_colormap(args),
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{collect_input_files, CommonArgs};
+use config::ColorIndexType;
+use ggcat_api::GGCATInstance;
+use io::sequences_stream::general::GeneralSequenceBlockData;
+use io::varint::encode_varint;
+use log::{error, info};
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::exit;
+use structopt::clap::arg_enum;
+use structopt::StructOpt;
+use utils::Utils;
+
+/// Magic header of the binary kmers dump
+const KMERS_DUMP_MAGIC: &[u8; 16] = b"GGCAT_KMERS_DUMP";
+
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum CountOutputFormat {
+        Text,
+        Binary,
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CountArgs {
+    /// The input files
+    pub input: Vec<PathBuf>,
+
+    /// The lists of input files
+    #[structopt(short = "l", long = "input-lists")]
+    pub input_lists: Vec<PathBuf>,
+
+    /// Enable colors
+    #[structopt(short, long)]
+    pub colors: bool,
+
+    /// Minimum multiplicity required to keep a kmer
+    #[structopt(short = "s", long = "min-multiplicity", default_value = "2")]
+    pub min_multiplicity: usize,
+
+    #[structopt(short = "o", long = "output-file", default_value = "output.kmers.txt")]
+    pub output_file: PathBuf,
+
+    /// Format of the output file, Text writes one `<kmer>\t<count>[\t<colors>]` line for each kmer
+    #[structopt(long = "output-format", default_value = "Text")]
+    pub output_format: CountOutputFormat,
+
+    #[structopt(flatten)]
+    pub common_args: CommonArgs,
+}
+
+/// Writes a single kmer in the binary dump format: the 2-bit packed bases (A=0, C=1, T=2, G=3, first base in the lowest bits),
+/// the multiplicity as a varint and, if colored, the colors count followed by the delta-encoded sorted colors, all as varints
+fn write_binary_kmer(
+    writer: &mut impl Write,
+    kmer: &[u8],
+    multiplicity: u64,
+    colors: Option<&[ColorIndexType]>,
+) -> std::io::Result<()> {
+    for chunk in kmer.chunks(4) {
+        let mut packed = 0;
+        for (index, base) in chunk.iter().enumerate() {
+            packed |= Utils::compress_base(*base) << (index * 2);
+        }
+        writer.write_all(&[packed])?;
+    }
+
+    encode_varint(|b| writer.write_all(b), multiplicity)?;
+
+    if let Some(colors) = colors {
+        encode_varint(|b| writer.write_all(b), colors.len() as u64)?;
+        let mut last_color = 0;
+        for color in colors {
+            encode_varint(|b| writer.write_all(b), (*color - last_color) as u64)?;
+            last_color = *color;
+        }
+    }
+    Ok(())
+}
+
+pub fn run_count_from_args(instance: &GGCATInstance, args: CountArgs) {
+    let inputs = collect_input_files(&args.input, &args.input_lists);
+
+    if inputs.is_empty() {
+        error!("No input files specified!");
+        exit(1);
+    }
+
+    let color_names: Vec<_> = inputs
+        .iter()
+        .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+
+    let inputs = inputs
+        .into_iter()
+        .map(|x| GeneralSequenceBlockData::FASTA(x))
+        .collect();
+
+    let mut output_file = BufWriter::new(File::create(&args.output_file).unwrap_or_else(|err| {
+        error!(
+            "Cannot create output file {}: {}",
+            args.output_file.display(),
+            err
+        );
+        exit(1);
+    }));
+
+    if args.output_format == CountOutputFormat::Binary {
+        output_file.write_all(KMERS_DUMP_MAGIC).unwrap();
+        output_file
+            .write_all(&(args.common_args.kmer_length as u32).to_le_bytes())
+            .unwrap();
+        output_file.write_all(&[args.colors as u8]).unwrap();
+    }
+
+    let output_file = Mutex::new(output_file);
+
+    instance.visit_kmers(
+        inputs,
+        Some(&color_names),
+        args.common_args.kmer_length,
+        args.common_args.threads_count,
+        args.common_args.forward_only,
+        args.common_args.minimizer_length,
+        args.colors,
+        args.min_multiplicity,
+        false,
+        |kmer, multiplicity, colors| {
+            let mut output_file = output_file.lock();
+            match args.output_format {
+                CountOutputFormat::Text => {
+                    output_file.write_all(kmer).unwrap();
+                    write!(output_file, "\t{}", multiplicity).unwrap();
+                    if args.colors {
+                        for (index, color) in colors.iter().enumerate() {
+                            write!(
+                                output_file,
+                                "{}{}",
+                                if index == 0 { '\t' } else { ',' },
+                                color
+                            )
+                            .unwrap();
+                        }
+                    }
+                    writeln!(output_file).unwrap();
+                }
+                CountOutputFormat::Binary => {
+                    write_binary_kmer(
+                        &mut *output_file,
+                        kmer,
+                        multiplicity,
+                        if args.colors { Some(colors) } else { None },
+                    )
+                    .unwrap();
+                }
+            }
+        },
+    );
+
+    output_file.into_inner().flush().unwrap();
+
+    info!("Kmers counts saved to: {}", args.output_file.display());
+}
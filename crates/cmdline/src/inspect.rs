@@ -0,0 +1,401 @@
+use crate::validate::parse_color;
+use colors::colors_manager::ColorMapReader;
+use colors::storage::deserializer::ColorsDeserializer;
+use config::ColorIndexType;
+use io::sequences_reader::SequencesReader;
+use log::error;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub struct InspectColormapArgs {
+    /// The input colormap
+    pub input_colormap: PathBuf,
+
+    /// The graph built together with the colormap, used to count the kmers of each subset and of each color
+    #[structopt(short = "g", long = "graph")]
+    pub graph: Option<PathBuf>,
+
+    /// Print only the colors of this subset, in decimal or in hexadecimal with the 0x prefix (as in the graph)
+    #[structopt(long = "subset")]
+    pub subset: Option<String>,
+
+    /// Print only the subsets containing the color with this name
+    #[structopt(long = "color")]
+    pub color: Option<String>,
+
+    /// Number of most frequent subsets to report, requires the graph
+    #[structopt(long = "top", default_value = "10")]
+    pub top: usize,
+
+    /// Print the report as json
+    #[structopt(long = "json")]
+    pub json: bool,
+}
+
+/// Sums the kmers of each subset from the C:<subset>:<count> annotations of the graph
+fn read_subsets_kmers(graph: &Path, subsets_count: u64) -> Result<Vec<u64>, String> {
+    if !graph.is_file() {
+        return Err(format!("Graph file {} not found", graph.display()));
+    }
+
+    let mut subsets_kmers = vec![0; subsets_count as usize];
+    let mut invalid_annotation = None;
+
+    SequencesReader::new().process_file_extended(
+        graph,
+        |seq| {
+            let ident = std::str::from_utf8(seq.ident_data).unwrap_or("");
+            for field in ident.split_whitespace() {
+                let Some(color) = field.strip_prefix("C:") else {
+                    continue;
+                };
+                match parse_color(color) {
+                    Some((subset, count)) if (subset as u64) < subsets_count => {
+                        subsets_kmers[subset as usize] += count;
+                    }
+                    _ => {
+                        invalid_annotation.get_or_insert_with(|| field.to_string());
+                    }
+                }
+            }
+        },
+        None,
+        true,
+        false,
+    );
+
+    match invalid_annotation {
+        Some(annotation) => Err(format!(
+            "The graph has the color annotation '{}' that does not match the colormap",
+            annotation
+        )),
+        None => Ok(subsets_kmers),
+    }
+}
+
+fn parse_subset(value: &str) -> Option<ColorIndexType> {
+    match value.strip_prefix("0x") {
+        Some(hex) => ColorIndexType::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn print_report(json: bool, report: impl FnOnce() -> Value, print_text: impl FnOnce()) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report()).unwrap());
+    } else {
+        print_text();
+    }
+}
+
+fn inspect_subset(
+    colormap: &mut ColorsDeserializer,
+    subsets_kmers: Option<&[u64]>,
+    subset: &str,
+    json: bool,
+) -> bool {
+    let subset = match parse_subset(subset) {
+        Some(subset) if (subset as u64) < colormap.colors_subsets_count() => subset,
+        _ => {
+            error!(
+                "Invalid subset {}, the colormap has {} subsets",
+                subset,
+                colormap.colors_subsets_count()
+            );
+            return false;
+        }
+    };
+
+    let mut colors = Vec::new();
+    colormap.get_color_mappings(subset, &mut colors);
+    let kmers = subsets_kmers.map(|kmers| kmers[subset as usize]);
+
+    print_report(
+        json,
+        || {
+            json!({
+                "subset": subset,
+                "kmers": kmers,
+                "colors": colors.iter().map(|color| json!({
+                    "index": color,
+                    "name": colormap.get_color_name(*color, false),
+                })).collect::<Vec<_>>(),
+            })
+        },
+        || {
+            match kmers {
+                Some(kmers) => println!(
+                    "Subset {} (0x{:x}): {} colors, {} kmers",
+                    subset,
+                    subset,
+                    colors.len(),
+                    kmers
+                ),
+                None => println!(
+                    "Subset {} (0x{:x}): {} colors",
+                    subset,
+                    subset,
+                    colors.len()
+                ),
+            }
+            for color in &colors {
+                println!("{}\t{}", color, colormap.get_color_name(*color, false));
+            }
+        },
+    );
+    true
+}
+
+fn inspect_color(
+    colormap: &mut ColorsDeserializer,
+    subsets_kmers: Option<&[u64]>,
+    name: &str,
+    json: bool,
+) -> bool {
+    let Some(color) = (0..colormap.colors_count() as ColorIndexType)
+        .find(|color| colormap.get_color_name(*color, false) == name)
+    else {
+        error!("The colormap has no color named '{}'", name);
+        return false;
+    };
+
+    let mut colors = Vec::new();
+    let mut subsets = Vec::new();
+    for subset in 0..colormap.colors_subsets_count() as ColorIndexType {
+        colors.clear();
+        colormap.get_color_mappings(subset, &mut colors);
+        if colors.binary_search(&color).is_ok() {
+            subsets.push(subset);
+        }
+    }
+
+    let subset_kmers = |subset: ColorIndexType| subsets_kmers.map(|kmers| kmers[subset as usize]);
+    let total_kmers = subsets_kmers.map(|_| {
+        subsets
+            .iter()
+            .map(|s| subset_kmers(*s).unwrap())
+            .sum::<u64>()
+    });
+
+    print_report(
+        json,
+        || {
+            json!({
+                "color": color,
+                "name": name,
+                "kmers": total_kmers,
+                "subsets": subsets.iter().map(|subset| json!({
+                    "subset": subset,
+                    "kmers": subset_kmers(*subset),
+                })).collect::<Vec<_>>(),
+            })
+        },
+        || {
+            match total_kmers {
+                Some(kmers) => println!(
+                    "Color {} ({}): {} subsets, {} kmers",
+                    color,
+                    name,
+                    subsets.len(),
+                    kmers
+                ),
+                None => println!("Color {} ({}): {} subsets", color, name, subsets.len()),
+            }
+            for subset in &subsets {
+                match subset_kmers(*subset) {
+                    Some(kmers) => println!("0x{:x}\t{}", subset, kmers),
+                    None => println!("0x{:x}", subset),
+                }
+            }
+        },
+    );
+    true
+}
+
+fn inspect_summary(
+    colormap: &mut ColorsDeserializer,
+    subsets_kmers: Option<&[u64]>,
+    top: usize,
+    json: bool,
+) -> bool {
+    let header = colormap.header_info();
+    let chunks = colormap.chunks_info();
+    let colors_count = colormap.colors_count();
+
+    // The most frequent subsets are known from the graph, their colors are collected while scanning the colormap
+    let mut top_subsets: Vec<(ColorIndexType, Vec<ColorIndexType>)> = match subsets_kmers {
+        Some(kmers) => {
+            let mut subsets: Vec<_> = (0..kmers.len() as ColorIndexType).collect();
+            subsets.sort_by_key(|subset| std::cmp::Reverse(kmers[*subset as usize]));
+            subsets.truncate(top);
+            subsets.sort_unstable();
+            subsets.into_iter().map(|subset| (subset, vec![])).collect()
+        }
+        None => vec![],
+    };
+
+    // Subsets with a number of colors in [2^i, 2^(i+1))
+    let mut size_distribution: Vec<u64> = Vec::new();
+    let mut colors_subsets = vec![0u64; colors_count];
+    let mut colors_kmers = vec![0u64; colors_count];
+
+    let mut colors = Vec::new();
+    let mut next_top_subset = 0;
+    for subset in 0..header.subsets_count as ColorIndexType {
+        colors.clear();
+        colormap.get_color_mappings(subset, &mut colors);
+
+        let size_class = (usize::BITS - colors.len().leading_zeros()) as usize;
+        if size_distribution.len() <= size_class {
+            size_distribution.resize(size_class + 1, 0);
+        }
+        size_distribution[size_class] += 1;
+
+        let kmers = subsets_kmers
+            .map(|kmers| kmers[subset as usize])
+            .unwrap_or(0);
+        for color in &colors {
+            colors_subsets[*color as usize] += 1;
+            colors_kmers[*color as usize] += kmers;
+        }
+
+        if top_subsets.get(next_top_subset).map(|t| t.0) == Some(subset) {
+            top_subsets[next_top_subset].1 = colors.clone();
+            next_top_subset += 1;
+        }
+    }
+
+    if let Some(kmers) = subsets_kmers {
+        top_subsets.sort_by_key(|(subset, _)| std::cmp::Reverse(kmers[*subset as usize]));
+    }
+
+    let size_range = |size_class: usize| match size_class {
+        0 => (0, 0),
+        _ => (1usize << (size_class - 1), (1usize << size_class) - 1),
+    };
+
+    print_report(
+        json,
+        || {
+            json!({
+                "version": header.version,
+                "encoding": format!("{:?}", header.encoding),
+                "colors_count": header.colors_count,
+                "subsets_count": header.subsets_count,
+                "compressed_size": header.total_size,
+                "uncompressed_size": header.total_uncompressed_size,
+                "subsets_size_distribution": size_distribution.iter().enumerate()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(size_class, count)| {
+                        let (min, max) = size_range(size_class);
+                        json!({ "min_colors": min, "max_colors": max, "subsets": count })
+                    }).collect::<Vec<_>>(),
+                "most_frequent_subsets": top_subsets.iter().map(|(subset, colors)| json!({
+                    "subset": subset,
+                    "kmers": subsets_kmers.unwrap()[*subset as usize],
+                    "colors": colors,
+                })).collect::<Vec<_>>(),
+                "colors": (0..colors_count).map(|color| json!({
+                    "index": color,
+                    "name": colormap.get_color_name(color as ColorIndexType, false),
+                    "subsets": colors_subsets[color],
+                    "kmers": subsets_kmers.map(|_| colors_kmers[color]),
+                })).collect::<Vec<_>>(),
+                "chunks": chunks.iter().map(|chunk| json!({
+                    "first_subset": chunk.subsets.start,
+                    "subsets": chunk.subsets.len(),
+                    "file_offset": chunk.file_offset,
+                    "compressed_size": chunk.compressed_size,
+                })).collect::<Vec<_>>(),
+            })
+        },
+        || {
+            println!("Format version: {}", header.version);
+            println!("Encoding: {:?}", header.encoding);
+            println!("Colors: {}", header.colors_count);
+            println!("Subsets: {}", header.subsets_count);
+            println!(
+                "Size: {} bytes compressed, {} bytes uncompressed",
+                header.total_size, header.total_uncompressed_size
+            );
+
+            println!("\nSubsets size distribution (colors: subsets):");
+            for (size_class, count) in size_distribution.iter().enumerate() {
+                if *count > 0 {
+                    let (min, max) = size_range(size_class);
+                    println!("{}-{}: {}", min, max, count);
+                }
+            }
+
+            match subsets_kmers {
+                Some(kmers) => {
+                    println!("\nMost frequent subsets (subset: kmers, colors):");
+                    for (subset, colors) in &top_subsets {
+                        println!("0x{:x}: {}, {:?}", subset, kmers[*subset as usize], colors);
+                    }
+                }
+                None => println!("\nPass the graph with --graph to report the kmers counts"),
+            }
+
+            println!("\nColors (index, name, subsets, kmers):");
+            for color in 0..colors_count {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    color,
+                    colormap.get_color_name(color as ColorIndexType, false),
+                    colors_subsets[color],
+                    subsets_kmers
+                        .map(|_| colors_kmers[color].to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                );
+            }
+
+            println!("\nChunks (first subset, subsets, compressed size):");
+            for chunk in &chunks {
+                println!(
+                    "0x{:x}\t{}\t{}",
+                    chunk.subsets.start,
+                    chunk.subsets.len(),
+                    chunk.compressed_size
+                );
+            }
+        },
+    );
+    true
+}
+
+pub fn inspect_colormap(args: InspectColormapArgs) -> bool {
+    let mut colormap = match ColorsDeserializer::open(&args.input_colormap, true) {
+        Ok(colormap) => colormap,
+        Err(error) => {
+            error!(
+                "Invalid colormap {}: {}",
+                args.input_colormap.display(),
+                error
+            );
+            return false;
+        }
+    };
+
+    let subsets_kmers = match &args.graph {
+        Some(graph) => match read_subsets_kmers(graph, colormap.colors_subsets_count()) {
+            Ok(subsets_kmers) => Some(subsets_kmers),
+            Err(error) => {
+                error!("{}", error);
+                return false;
+            }
+        },
+        None => None,
+    };
+    let subsets_kmers = subsets_kmers.as_deref();
+
+    if let Some(subset) = &args.subset {
+        inspect_subset(&mut colormap, subsets_kmers, subset, args.json)
+    } else if let Some(color) = &args.color {
+        inspect_color(&mut colormap, subsets_kmers, color, args.json)
+    } else {
+        inspect_summary(&mut colormap, subsets_kmers, args.top, args.json)
+    }
+}
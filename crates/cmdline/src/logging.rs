@@ -0,0 +1,63 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Writes the logs to stderr, as plain text or as json lines
+struct CmdlineLogger {
+    json: bool,
+}
+
+impl Log for CmdlineLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let stderr = std::io::stderr();
+        let mut lock = stderr.lock();
+
+        if self.json {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|t| t.as_secs_f64())
+                .unwrap_or(0.0);
+            let _ = writeln!(
+                lock,
+                "{}",
+                serde_json::json!({
+                    "timestamp": timestamp,
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                })
+            );
+        } else if record.level() <= Level::Warn {
+            let _ = writeln!(lock, "{}: {}", record.level(), record.args());
+        } else {
+            let _ = writeln!(lock, "{}", record.args());
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+pub fn init_logging(quiet: bool, verbose: u64, json: bool) {
+    let level = if quiet {
+        LevelFilter::Error
+    } else {
+        match verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    };
+
+    log::set_boxed_logger(Box::new(CmdlineLogger { json })).unwrap();
+    log::set_max_level(level);
+}
@@ -6,15 +6,24 @@ extern crate alloc;
 extern crate test;
 
 mod benchmarks;
+mod colormap;
+mod count;
+mod inspect;
+mod logging;
+mod suggest_k;
+mod validate;
 
 use backtrace::Backtrace;
-use ggcat_api::{ExtraElaboration, GGCATConfig, GGCATInstance};
+use ggcat_api::debug::DebugOptions;
+use ggcat_api::{BuildOptions, ExtraElaboration, GGCATConfig, GGCATInstance};
+use log::{error, info, warn};
+use std::cmp::{max, min};
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Write};
 use std::panic;
 use std::path::PathBuf;
 use std::process::exit;
-use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::Duration;
 use structopt::StructOpt;
 
@@ -52,22 +61,59 @@ arg_enum! {
     }
 }
 
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+    pub enum MinimizerScheme {
+        Random = 0,
+        OpenSyncmers = 1,
+        ClosedSyncmers = 2,
+        ModMinimizers = 3,
+        FrequencyAware = 4
+    }
+}
+
 use ::utils::compute_best_m;
+use colormap::{run_colormap_command, ColormapArgs};
 use colors::colors_manager::ColorMapReader;
 use colors::storage::deserializer::ColorsDeserializer;
-use colors::DefaultColorsSerializer;
 use config::ColorIndexType;
+use count::{run_count_from_args, CountArgs};
 use io::sequences_stream::general::GeneralSequenceBlockData;
 use parallel_processor::memory_fs::MemoryFs;
 use std::io::BufRead;
 use structopt::clap::{arg_enum, ArgGroup};
+use suggest_k::{run_suggest_k_from_args, SuggestKArgs};
+use validate::{validate_graph, ValidateArgs};
+
+#[derive(StructOpt, Debug)]
+struct Cli {
+    /// Only print the errors
+    #[structopt(short = "q", long, global = true)]
+    quiet: bool,
+
+    /// Print more detailed logs (-v for debug, -vv for trace)
+    #[structopt(short = "v", long, parse(from_occurrences), global = true)]
+    verbose: u64,
+
+    /// Print the logs to stderr as json lines
+    #[structopt(long = "log-json", global = true)]
+    log_json: bool,
+
+    #[structopt(subcommand)]
+    command: CliArgs,
+}
 
 #[derive(StructOpt, Debug)]
 enum CliArgs {
     Build(AssemblerArgs),
+    Count(CountArgs),
     Query(QueryArgs),
     DumpColors(DumpColorsArgs),
     Matches(MatchesArgs),
+    Validate(ValidateArgs),
+    SuggestK(SuggestKArgs),
+    /// Tools to inspect and transform colormaps
+    Colormap(ColormapArgs),
     // Utils(CmdUtilsArgs),
 }
 
@@ -94,6 +140,10 @@ struct CommonArgs {
     #[structopt(short = "t", long = "temp-dir", default_value = ".temp_files")]
     pub temp_dir: PathBuf,
 
+    /// Keep all the temporary files in memory, without using the temporary directory
+    #[structopt(long = "memory-only")]
+    pub memory_only: bool,
+
     /// Keep intermediate temporary files for debugging purposes
     #[structopt(long = "keep-temp-files")]
     pub keep_temp_files: bool,
@@ -105,6 +155,20 @@ struct CommonArgs {
     #[structopt(short = "w", long, default_value = "Auto")]
     pub hash_type: HashType,
 
+    /// Scheme used to choose the minimizers of the kmers
+    #[structopt(long = "minimizer-scheme", default_value = "Random")]
+    pub minimizer_scheme: MinimizerScheme,
+
+    /// The s-mers length for syncmers (default m / 2) or the r parameter for mod-minimizers (default 4)
+    #[structopt(long = "minimizer-scheme-param")]
+    pub minimizer_scheme_param: Option<usize>,
+
+    /// Check that different kmers never share the same Rabin-Karp hash, repeating the
+    /// build with a wider (or the exact sequence) hash if any collision is found,
+    /// or failing if no wider hash is available for the kmer length
+    #[structopt(long = "verify-hash-collisions")]
+    pub verify_hash_collisions: bool,
+
     /// Treats reverse complementary kmers as different
     #[structopt(short = "f", long)]
     pub forward_only: bool,
@@ -120,6 +184,12 @@ struct CommonArgs {
     #[structopt(short = "p", long = "prefer-memory")]
     pub prefer_memory: bool,
 
+    /// Hard memory limit for the whole process (GB), including the processing steps.
+    /// The temporary files are written to disk earlier and the processing steps use smaller buffers
+    /// to stay under this limit
+    #[structopt(long = "hard-memory-limit")]
+    pub hard_memory_limit: Option<f64>,
+
     /// The log2 of the number of buckets
     #[structopt(short = "b", long = "buckets-count-log")]
     pub buckets_count_log: Option<usize>,
@@ -185,6 +255,25 @@ struct AssemblerArgs {
     #[structopt(long = "pathtigs", group = "output-mode")]
     pub pathtigs: bool,
 
+    /// Split the maximal unitigs at each change of colors subset, with links between the pieces (requires -c)
+    #[structopt(long = "color-compacted-unitigs", group = "output-mode")]
+    pub color_compacted_unitigs: bool,
+
+    /// Encoding of the colors subsets in the colormap, Roaring is smaller
+    /// when there are many colors shared by most of the kmers
+    #[structopt(long = "colormap-encoding", default_value = "RunLength")]
+    pub colormap_encoding: ColormapEncoding,
+
+    /// Reorder the colors by similarity after the build to reduce the colormap size,
+    /// the color indexes then do not follow the input files order
+    #[structopt(long = "optimize-colormap")]
+    pub optimize_colormap: bool,
+
+    /// Write the CRC32 of the output graph to a file with the .crc32 extension appended,
+    /// that can be checked with the validate command
+    #[structopt(long = "graph-checksum")]
+    pub graph_checksum: bool,
+
     #[structopt(flatten)]
     pub common_args: CommonArgs,
 }
@@ -195,12 +284,21 @@ struct DumpColorsArgs {
     output_file: PathBuf,
 }
 
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum ColormapEncoding {
+        RunLength,
+        Roaring,
+    }
+}
+
 arg_enum! {
     /// Format of the queries output
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub enum ColoredQueryOutputFormat {
         JsonLinesWithNumbers,
         JsonLinesWithNames,
+        JsonLinesWithNamesAndMetadata,
     }
 }
 
@@ -236,28 +334,71 @@ struct QueryArgs {
 // #[cfg(feature = "mem-analysis")]
 // static DEBUG_ALLOCATOR: DebugAllocator = DebugAllocator::new();
 
-fn initialize(args: &CommonArgs, out_file: &PathBuf) -> &'static GGCATInstance {
+fn get_minimizer_scheme(args: &CommonArgs) -> ggcat_api::MinimizerScheme {
+    let m = args
+        .minimizer_length
+        .unwrap_or(compute_best_m(args.kmer_length));
+
+    if args.minimizer_scheme_param == Some(0) || args.minimizer_scheme_param.unwrap_or(0) > m {
+        error!(
+            "The minimizer scheme parameter must be between 1 and m ({})",
+            m
+        );
+        exit(1);
+    }
+
+    match args.minimizer_scheme {
+        MinimizerScheme::Random => ggcat_api::MinimizerScheme::Random,
+        MinimizerScheme::OpenSyncmers => ggcat_api::MinimizerScheme::OpenSyncmers {
+            s: args.minimizer_scheme_param.unwrap_or(max(1, m / 2)),
+        },
+        MinimizerScheme::ClosedSyncmers => ggcat_api::MinimizerScheme::ClosedSyncmers {
+            s: args.minimizer_scheme_param.unwrap_or(max(1, m / 2)),
+        },
+        MinimizerScheme::ModMinimizers => ggcat_api::MinimizerScheme::ModMinimizers {
+            r: args.minimizer_scheme_param.unwrap_or(min(4, m)),
+        },
+        MinimizerScheme::FrequencyAware => ggcat_api::MinimizerScheme::FrequencyAware,
+    }
+}
+
+fn initialize(args: &CommonArgs, out_file: &PathBuf) -> Arc<GGCATInstance> {
     let instance = GGCATInstance::create(GGCATConfig {
-        temp_dir: Some(args.temp_dir.clone()),
+        temp_dir: if args.memory_only {
+            None
+        } else {
+            Some(args.temp_dir.clone())
+        },
         memory: args.memory,
         prefer_memory: args.prefer_memory,
+        hard_memory_limit: args.hard_memory_limit,
         total_threads_count: args.threads_count,
         intermediate_compression_level: args.intermediate_compression_level,
         stats_file: Some(out_file.with_extension("stats.log")),
+        minimizer_scheme: get_minimizer_scheme(args),
+        progress_callback: None,
+    })
+    .unwrap_or_else(|error| {
+        error!("Cannot initialize GGCAT: {}", error);
+        exit(1);
+    });
+
+    instance.set_debug_options(DebugOptions {
+        keep_files: args.keep_temp_files,
+        buckets_count_log_force: args.buckets_count_log,
+        only_bstats: args.only_bstats,
+        hash_type: match args.hash_type {
+            HashType::Auto => ggcat_api::HashType::Auto,
+            HashType::SeqHash => ggcat_api::HashType::SeqHash,
+            HashType::RabinKarp32 => ggcat_api::HashType::RabinKarp32,
+            HashType::RabinKarp64 => ggcat_api::HashType::RabinKarp64,
+            HashType::RabinKarp128 => ggcat_api::HashType::RabinKarp128,
+        },
+        verify_hash_collisions: args.verify_hash_collisions,
+        ..DebugOptions::default()
     });
 
-    ggcat_api::debug::DEBUG_KEEP_FILES.store(args.keep_temp_files, Ordering::Relaxed);
-    *ggcat_api::debug::BUCKETS_COUNT_LOG_FORCE.lock() = args.buckets_count_log;
-    ggcat_api::debug::DEBUG_ONLY_BSTATS.store(args.only_bstats, Ordering::Relaxed);
-    *ggcat_api::debug::DEBUG_HASH_TYPE.lock() = match args.hash_type {
-        HashType::Auto => ggcat_api::HashType::Auto,
-        HashType::SeqHash => ggcat_api::HashType::SeqHash,
-        HashType::RabinKarp32 => ggcat_api::HashType::RabinKarp32,
-        HashType::RabinKarp64 => ggcat_api::HashType::RabinKarp64,
-        HashType::RabinKarp128 => ggcat_api::HashType::RabinKarp128,
-    };
-
-    println!(
+    info!(
         "Using m: {} with k: {}",
         args.minimizer_length
             .unwrap_or(compute_best_m(args.kmer_length)),
@@ -285,19 +426,24 @@ fn convert_assembler_step(step: AssemblerStartingStep) -> assembler::AssemblerSt
     }
 }
 
-fn run_assembler_from_args(instance: &GGCATInstance, args: AssemblerArgs) {
-    let mut inputs = args.input.clone();
+fn collect_input_files(input: &[PathBuf], input_lists: &[PathBuf]) -> Vec<PathBuf> {
+    let mut inputs = input.to_vec();
 
-    for list in args.input_lists {
+    for list in input_lists {
         for input in BufReader::new(File::open(list).unwrap()).lines() {
             if let Ok(input) = input {
                 inputs.push(PathBuf::from(input));
             }
         }
     }
+    inputs
+}
+
+fn run_assembler_from_args(instance: &GGCATInstance, args: AssemblerArgs) {
+    let inputs = collect_input_files(&args.input, &args.input_lists);
 
     if inputs.is_empty() {
-        println!("ERROR: No input files specified!");
+        error!("No input files specified!");
         exit(1);
     }
 
@@ -311,21 +457,21 @@ fn run_assembler_from_args(instance: &GGCATInstance, args: AssemblerArgs) {
         .map(|x| GeneralSequenceBlockData::FASTA(x))
         .collect();
 
-    *ggcat_api::debug::DEBUG_ASSEMBLER_FIRST_STEP.lock() = convert_assembler_step(args.step);
-    *ggcat_api::debug::DEBUG_ASSEMBLER_LAST_STEP.lock() = convert_assembler_step(args.last_step);
-    ggcat_api::debug::DEBUG_LINK_PHASE_ITERATION_START_STEP.store(args.number, Ordering::Relaxed);
+    instance.set_debug_options(DebugOptions {
+        assembler_first_step: convert_assembler_step(args.step),
+        assembler_last_step: convert_assembler_step(args.last_step),
+        link_phase_iteration_start_step: args.number,
+        ..instance.get_debug_options()
+    });
 
-    let output_file = instance.build_graph(
-        inputs,
-        args.output_file,
-        Some(&color_names),
-        args.common_args.kmer_length,
-        args.common_args.threads_count,
-        args.common_args.forward_only,
-        args.common_args.minimizer_length,
-        args.colors,
-        args.min_multiplicity,
-        if args.generate_maximal_unitigs_links {
+    let debug_options = instance.get_debug_options();
+
+    let mut options = BuildOptions::builder(args.common_args.kmer_length)
+        .threads_count(args.common_args.threads_count)
+        .forward_only(args.common_args.forward_only)
+        .colors(args.colors)
+        .min_multiplicity(args.min_multiplicity)
+        .extra_elaboration(if args.generate_maximal_unitigs_links {
             ExtraElaboration::UnitigLinks
         } else if args.greedy_matchtigs {
             ExtraElaboration::GreedyMatchtigs
@@ -333,12 +479,38 @@ fn run_assembler_from_args(instance: &GGCATInstance, args: AssemblerArgs) {
             ExtraElaboration::Eulertigs
         } else if args.pathtigs {
             ExtraElaboration::Pathtigs
+        } else if args.color_compacted_unitigs {
+            ExtraElaboration::ColorCompactedUnitigs
         } else {
             ExtraElaboration::None
-        },
-    );
+        })
+        .hash_type(debug_options.hash_type)
+        .verify_hash_collisions(debug_options.verify_hash_collisions)
+        .colormap_encoding(match args.colormap_encoding {
+            ColormapEncoding::RunLength => ggcat_api::ColormapEncoding::RunLength,
+            ColormapEncoding::Roaring => ggcat_api::ColormapEncoding::Roaring,
+        })
+        .optimize_colormap(args.optimize_colormap)
+        .graph_checksum(args.graph_checksum);
+
+    if args.colors {
+        options = options.color_names(color_names);
+    }
+    if let Some(minimizer_length) = args.common_args.minimizer_length {
+        options = options.minimizer_length(minimizer_length);
+    }
+
+    let result = options
+        .build()
+        .and_then(|options| instance.build(inputs, args.output_file, &options));
 
-    println!("Final output saved to: {}", output_file.display());
+    match result {
+        Ok(output_file) => info!("Final output saved to: {}", output_file.display()),
+        Err(error) => {
+            error!("{}", error);
+            exit(1);
+        }
+    }
 }
 
 fn convert_querier_step(step: QuerierStartingStep) -> querier::QuerierStartingStep {
@@ -351,7 +523,10 @@ fn convert_querier_step(step: QuerierStartingStep) -> querier::QuerierStartingSt
 }
 
 fn run_querier_from_args(instance: &GGCATInstance, args: QueryArgs) -> PathBuf {
-    *ggcat_api::debug::DEBUG_QUERIER_FIRST_STEP.lock() = convert_querier_step(args.step);
+    instance.set_debug_options(DebugOptions {
+        querier_first_step: convert_querier_step(args.step),
+        ..instance.get_debug_options()
+    });
 
     instance.query_graph(
         args.input_graph,
@@ -372,6 +547,9 @@ fn run_querier_from_args(instance: &GGCATInstance, args: QueryArgs) -> PathBuf {
             ColoredQueryOutputFormat::JsonLinesWithNames => {
                 querier::ColoredQueryOutputFormat::JsonLinesWithNames
             }
+            ColoredQueryOutputFormat::JsonLinesWithNamesAndMetadata => {
+                querier::ColoredQueryOutputFormat::JsonLinesWithNamesAndMetadata
+            }
         },
     )
 }
@@ -379,7 +557,9 @@ fn run_querier_from_args(instance: &GGCATInstance, args: QueryArgs) -> PathBuf {
 instrumenter::global_setup_instrumenter!();
 
 fn main() {
-    let args: CliArgs = CliArgs::from_args();
+    let cli = Cli::from_args();
+    logging::init_logging(cli.quiet, cli.verbose, cli.log_json);
+    let args = cli.command;
 
     #[cfg(feature = "mem-analysis")]
     {
@@ -420,10 +600,14 @@ fn main() {
 
             run_assembler_from_args(&instance, args);
         }
+        CliArgs::Count(args) => {
+            let instance = initialize(&args.common_args, &args.output_file);
+
+            run_count_from_args(&instance, args);
+        }
         CliArgs::Matches(args) => {
             let colors_file = args.input_file.with_extension("colors.dat");
-            let mut colors_deserializer =
-                ColorsDeserializer::<DefaultColorsSerializer>::new(colors_file, true);
+            let mut colors_deserializer = ColorsDeserializer::new(colors_file, true);
 
             let mut colors = Vec::new();
 
@@ -440,11 +624,24 @@ fn main() {
             }
             return; // Skip final memory deallocation
         }
+        CliArgs::SuggestK(args) => {
+            run_suggest_k_from_args(args);
+        }
+        CliArgs::Colormap(args) => {
+            if !run_colormap_command(args) {
+                exit(1);
+            }
+            return; // Skip final memory deallocation
+        }
+        CliArgs::Validate(args) => {
+            if !validate_graph(args) {
+                exit(1);
+            }
+            return; // Skip final memory deallocation
+        }
         CliArgs::Query(args) => {
-            initialize(&args.common_args, &args.output_file_prefix);
-
             if !args.colors && args.colored_query_output_format.is_some() {
-                println!("Warning: colored query output format is specified, but the graph is not colored");
+                warn!("Colored query output format is specified, but the graph is not colored");
             }
 
             let _guard = instrumenter::initialize_tracing(
@@ -455,7 +652,7 @@ fn main() {
             let instance = initialize(&args.common_args, &args.output_file_prefix);
 
             let output_file_name = run_querier_from_args(&instance, args);
-            println!("Final output saved to: {}", output_file_name.display());
+            info!("Final output saved to: {}", output_file_name.display());
         }
         CliArgs::DumpColors(args) => {
             let output_file_name = args.output_file.with_extension("jsonl");
@@ -474,7 +671,7 @@ fn main() {
             }
 
             drop(output_file);
-            println!("Colors written to {}", output_file_name.display());
+            info!("Colors written to {}", output_file_name.display());
 
             return; // Skip final memory deallocation
         }
@@ -0,0 +1,133 @@
+use crate::collect_input_files;
+use ggcat_api::{GGCATConfig, GGCATInstance, MinimizerScheme};
+use io::sequences_stream::general::GeneralSequenceBlockData;
+use log::error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::exit;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub struct SuggestKArgs {
+    /// The input files
+    pub input: Vec<PathBuf>,
+
+    /// The lists of input files
+    #[structopt(short = "l", long = "input-lists")]
+    pub input_lists: Vec<PathBuf>,
+
+    /// Minimum multiplicity required to consider a kmer solid
+    #[structopt(short = "s", long = "min-multiplicity", default_value = "2")]
+    pub min_multiplicity: usize,
+
+    /// The smallest k value to be evaluated
+    #[structopt(long = "min-k", default_value = "21")]
+    pub min_k: usize,
+
+    /// The largest k value to be evaluated
+    #[structopt(long = "max-k", default_value = "121")]
+    pub max_k: usize,
+
+    /// The distance between two consecutive evaluated k values
+    #[structopt(long = "k-step", default_value = "10")]
+    pub k_step: usize,
+
+    /// Count only one out of 2^sampling-log kmers, higher values are faster but less precise (default 6)
+    #[structopt(long = "sampling-log")]
+    pub sampling_log: Option<usize>,
+
+    /// Treats reverse complementary kmers as different
+    #[structopt(short = "f", long)]
+    pub forward_only: bool,
+
+    /// Optional file where the estimated curve is written, in tsv format
+    #[structopt(short = "o", long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Directory for temporary files (default .temp_files)
+    #[structopt(short = "t", long = "temp-dir", default_value = ".temp_files")]
+    pub temp_dir: PathBuf,
+
+    #[structopt(short = "j", long, default_value = "16")]
+    pub threads_count: usize,
+}
+
+pub fn run_suggest_k_from_args(args: SuggestKArgs) {
+    let inputs = collect_input_files(&args.input, &args.input_lists);
+
+    if inputs.is_empty() {
+        error!("No input files specified!");
+        exit(1);
+    }
+
+    if args.min_k < 2 || args.min_k > args.max_k || args.k_step == 0 {
+        error!("Invalid k range!");
+        exit(1);
+    }
+
+    let instance = GGCATInstance::create(GGCATConfig {
+        temp_dir: Some(args.temp_dir.clone()),
+        memory: 2.0,
+        prefer_memory: false,
+        hard_memory_limit: None,
+        total_threads_count: args.threads_count,
+        intermediate_compression_level: None,
+        stats_file: None,
+        minimizer_scheme: MinimizerScheme::Random,
+        progress_callback: None,
+    })
+    .unwrap_or_else(|error| {
+        error!("Cannot initialize GGCAT: {}", error);
+        exit(1);
+    });
+
+    let inputs: Vec<_> = inputs
+        .into_iter()
+        .map(|x| GeneralSequenceBlockData::FASTA(x))
+        .collect();
+
+    let k_candidates: Vec<_> = (args.min_k..=args.max_k).step_by(args.k_step).collect();
+
+    let (best_k, spectra) = instance.suggest_k(
+        &inputs,
+        &k_candidates,
+        args.forward_only,
+        args.min_multiplicity,
+        args.sampling_log,
+    );
+
+    println!("k\tdistinct_kmers\tsolid_kmers");
+    for estimate in spectra.iter() {
+        println!(
+            "{}\t{}\t{}",
+            estimate.k, estimate.distinct_kmers, estimate.solid_kmers
+        );
+    }
+
+    if let Some(output_file) = &args.output_file {
+        let mut writer = BufWriter::new(File::create(output_file).unwrap_or_else(|err| {
+            error!(
+                "Cannot create output file {}: {}",
+                output_file.display(),
+                err
+            );
+            exit(1);
+        }));
+        writeln!(writer, "k\tdistinct_kmers\tsolid_kmers").unwrap();
+        for estimate in spectra.iter() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}",
+                estimate.k, estimate.distinct_kmers, estimate.solid_kmers
+            )
+            .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    match best_k {
+        Some(best_k) => println!("Suggested k: {}", best_k),
+        None => println!("No kmers found in the input files!"),
+    }
+}
@@ -0,0 +1,644 @@
+use colors::colors_manager::ColorMapReader;
+use colors::storage::deserializer::ColorsDeserializer;
+use colors::storage::format::verify_colormap_checksums;
+use config::ColorIndexType;
+use hashbrown::HashMap;
+use io::checksum::{verify_file_checksum, FileChecksumStatus};
+use io::sequences_reader::SequencesReader;
+use log::{error, warn};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use utils::Utils;
+
+#[derive(StructOpt, Debug)]
+pub struct ValidateArgs {
+    /// The input graph
+    pub input_graph: PathBuf,
+
+    /// Specifies the k-mers length
+    #[structopt(short, long = "kmer-length")]
+    pub kmer_length: usize,
+
+    /// Treats reverse complementary kmers as different
+    #[structopt(short = "f", long)]
+    pub forward_only: bool,
+
+    /// Check the symmetry and the overlaps of the maximal unitigs links
+    #[structopt(long)]
+    pub links: bool,
+
+    /// Check that the colors referenced by each unitig can be decoded from the colormap
+    #[structopt(long)]
+    pub colors: bool,
+
+    /// Check the graph against its checksum file and the colormap chunks against their checksums
+    #[structopt(long)]
+    pub checksums: bool,
+
+    /// Maximum number of violations printed for each kind of check
+    #[structopt(long = "max-reported", default_value = "100")]
+    pub max_reported: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum ViolationKind {
+    InvalidSequence,
+    DuplicatedKmer,
+    NotMaximal,
+    MissingLink,
+    AsymmetricLink,
+    InconsistentLinkOverlap,
+    InvalidColor,
+    ColorsCountMismatch,
+    ChecksumMismatch,
+}
+
+struct ValidationReport {
+    counts: HashMap<ViolationKind, u64>,
+    max_reported: u64,
+}
+
+impl ValidationReport {
+    fn report(&mut self, kind: ViolationKind, message: impl FnOnce() -> String) {
+        let count = self.counts.entry(kind).or_insert(0);
+        *count += 1;
+        if *count <= self.max_reported {
+            println!("VIOLATION [{:?}]: {}", kind, message());
+        } else if *count == self.max_reported + 1 {
+            println!(
+                "VIOLATION [{:?}]: too many violations, skipping further reports",
+                kind
+            );
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+}
+
+/// A kmer packed as a big integer (2 bits per base, first base is the most significant),
+/// along with its reverse complement
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct OrientedKmer<const N: usize> {
+    fw: [u64; N],
+    rc: [u64; N],
+}
+
+impl<const N: usize> OrientedKmer<N> {
+    fn flipped(&self) -> Self {
+        Self {
+            fw: self.rc,
+            rc: self.fw,
+        }
+    }
+}
+
+struct KmersCodec<const N: usize> {
+    k: usize,
+    mask: [u64; N],
+    forward_only: bool,
+}
+
+impl<const N: usize> KmersCodec<N> {
+    fn new(k: usize, forward_only: bool) -> Self {
+        let mut mask = [0; N];
+        for pos in 0..k {
+            mask[pos / 32] |= 3 << (2 * (pos % 32));
+        }
+        Self {
+            k,
+            mask,
+            forward_only,
+        }
+    }
+
+    #[inline(always)]
+    fn shift_left(&self, value: &[u64; N]) -> [u64; N] {
+        let mut result = [0; N];
+        for i in (0..N).rev() {
+            result[i] = (value[i] << 2) | if i > 0 { value[i - 1] >> 62 } else { 0 };
+            result[i] &= self.mask[i];
+        }
+        result
+    }
+
+    #[inline(always)]
+    fn shift_right(value: &[u64; N]) -> [u64; N] {
+        let mut result = [0; N];
+        for i in 0..N {
+            result[i] = (value[i] >> 2) | if i + 1 < N { value[i + 1] << 62 } else { 0 };
+        }
+        result
+    }
+
+    #[inline(always)]
+    fn set_base(value: &mut [u64; N], pos: usize, cbase: u8) {
+        value[pos / 32] |= (cbase as u64) << (2 * (pos % 32));
+    }
+
+    fn empty() -> OrientedKmer<N> {
+        OrientedKmer {
+            fw: [0; N],
+            rc: [0; N],
+        }
+    }
+
+    /// Appends a base at the end of the kmer, dropping the first one
+    fn push_back(&self, kmer: &OrientedKmer<N>, cbase: u8) -> OrientedKmer<N> {
+        let mut fw = self.shift_left(&kmer.fw);
+        Self::set_base(&mut fw, 0, cbase);
+        let mut rc = Self::shift_right(&kmer.rc);
+        Self::set_base(&mut rc, self.k - 1, Utils::conditional_rc_base(cbase, true));
+        OrientedKmer { fw, rc }
+    }
+
+    /// Prepends a base at the start of the kmer, dropping the last one
+    fn push_front(&self, kmer: &OrientedKmer<N>, cbase: u8) -> OrientedKmer<N> {
+        let mut fw = Self::shift_right(&kmer.fw);
+        Self::set_base(&mut fw, self.k - 1, cbase);
+        let mut rc = self.shift_left(&kmer.rc);
+        Self::set_base(&mut rc, 0, Utils::conditional_rc_base(cbase, true));
+        OrientedKmer { fw, rc }
+    }
+
+    fn canonical(&self, kmer: &OrientedKmer<N>) -> [u64; N] {
+        if self.forward_only {
+            kmer.fw
+        } else {
+            std::cmp::min(kmer.fw, kmer.rc)
+        }
+    }
+
+    /// Checks that the last k - 1 bases of first are equal to the first k - 1 bases of second
+    fn overlaps(&self, first: &OrientedKmer<N>, second: &OrientedKmer<N>) -> bool {
+        let mut second_prefix = second.fw;
+        second_prefix[0] &= !3;
+        self.shift_left(&first.fw) == second_prefix
+    }
+
+    fn to_string(&self, kmer: &OrientedKmer<N>) -> String {
+        (0..self.k)
+            .rev()
+            .map(|pos| {
+                Utils::decompress_base(((kmer.fw[pos / 32] >> (2 * (pos % 32))) & 3) as u8) as char
+            })
+            .collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct UnitigLinkDesc {
+    flip_current: bool,
+    other: u64,
+    flip_other: bool,
+}
+
+struct UnitigDesc<const N: usize> {
+    id: u64,
+    first: OrientedKmer<N>,
+    last: OrientedKmer<N>,
+    links: Vec<UnitigLinkDesc>,
+}
+
+fn parse_sign(sign: &str) -> Option<bool> {
+    match sign {
+        "+" => Some(false),
+        "-" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_link(value: &str) -> Option<UnitigLinkDesc> {
+    let mut parts = value.split(':');
+    let flip_current = parse_sign(parts.next()?)?;
+    let other = parts.next()?.parse().ok()?;
+    let flip_other = parse_sign(parts.next()?)?;
+    Some(UnitigLinkDesc {
+        flip_current,
+        other,
+        flip_other,
+    })
+}
+
+/// Position of the first base that is not one of ACGTacgt, if any
+fn find_invalid_base(sequence: &[u8]) -> Option<usize> {
+    sequence
+        .iter()
+        .position(|base| !matches!(base, b'A' | b'C' | b'G' | b'T' | b'a' | b'c' | b'g' | b't'))
+}
+
+pub(crate) fn parse_color(value: &str) -> Option<(ColorIndexType, u64)> {
+    let (color, count) = value.split_once(':')?;
+    Some((
+        ColorIndexType::from_str_radix(color, 16).ok()?,
+        count.parse().ok()?,
+    ))
+}
+
+fn validate_graph_impl<const N: usize>(
+    args: &ValidateArgs,
+    report: &mut ValidationReport,
+) -> (u64, u64) {
+    let k = args.kmer_length;
+    let codec = KmersCodec::<N>::new(k, args.forward_only);
+
+    let mut colormap = if args.colors {
+        Some(ColorsDeserializer::new(
+            args.input_graph.with_extension("colors.dat"),
+            true,
+        ))
+    } else {
+        None
+    };
+
+    let mut kmers: HashMap<[u64; N], u64> = HashMap::new();
+    let mut unitigs: Vec<UnitigDesc<N>> = Vec::new();
+    let mut colors_buffer = Vec::new();
+
+    SequencesReader::new().process_file_extended(
+        &args.input_graph,
+        |seq| {
+            let ident = std::str::from_utf8(seq.ident_data).unwrap_or("");
+            let mut fields = ident.trim_start_matches('>').split_whitespace();
+            let id = match fields.next().map(|id| id.parse::<u64>()) {
+                Some(Ok(id)) => id,
+                _ => {
+                    report.report(ViolationKind::InvalidSequence, || {
+                        format!("cannot parse unitig id from header '{}'", ident)
+                    });
+                    return;
+                }
+            };
+
+            if seq.seq.len() < k {
+                report.report(ViolationKind::InvalidSequence, || {
+                    format!("unitig {} is shorter than k ({} < {})", id, seq.seq.len(), k)
+                });
+                return;
+            }
+
+            if let Some(position) = find_invalid_base(seq.seq) {
+                report.report(ViolationKind::InvalidSequence, || {
+                    format!(
+                        "unitig {} contains the non-ACGT base '{}' at position {}",
+                        id,
+                        seq.seq[position].escape_ascii(),
+                        position
+                    )
+                });
+                return;
+            }
+
+            let mut links = Vec::new();
+            let mut colors_kmers_count = 0;
+            let mut has_colors = false;
+
+            for field in fields {
+                if let Some(link) = field.strip_prefix("L:") {
+                    match parse_link(link) {
+                        Some(link) => links.push(link),
+                        None => report.report(ViolationKind::MissingLink, || {
+                            format!("unitig {} has a malformed link '{}'", id, field)
+                        }),
+                    }
+                } else if let Some(color) = field.strip_prefix("C:") {
+                    let Some(colormap) = &mut colormap else {
+                        continue;
+                    };
+                    has_colors = true;
+
+                    let Some((color, count)) = parse_color(color) else {
+                        report.report(ViolationKind::InvalidColor, || {
+                            format!("unitig {} has a malformed color '{}'", id, field)
+                        });
+                        continue;
+                    };
+                    colors_kmers_count += count;
+
+                    if color as u64 >= colormap.colors_subsets_count() {
+                        report.report(ViolationKind::InvalidColor, || {
+                            format!(
+                                "unitig {} references color subset {:x} but the colormap contains only {} subsets",
+                                id,
+                                color,
+                                colormap.colors_subsets_count()
+                            )
+                        });
+                        continue;
+                    }
+
+                    colors_buffer.clear();
+                    colormap.get_color_mappings(color, &mut colors_buffer);
+                    let colors_count = colormap.colors_count();
+
+                    if colors_buffer.is_empty()
+                        || colors_buffer.windows(2).any(|w| w[0] >= w[1])
+                        || colors_buffer.iter().any(|c| *c as usize >= colors_count)
+                    {
+                        report.report(ViolationKind::InvalidColor, || {
+                            format!(
+                                "unitig {} references color subset {:x} that decodes to an invalid set {:?} ({} colors)",
+                                id, color, colors_buffer, colors_count
+                            )
+                        });
+                    }
+                }
+            }
+
+            let kmers_count = (seq.seq.len() - k + 1) as u64;
+
+            if args.colors && !has_colors {
+                report.report(ViolationKind::InvalidColor, || {
+                    format!("unitig {} has no colors", id)
+                });
+            } else if has_colors && colors_kmers_count != kmers_count {
+                report.report(ViolationKind::ColorsCountMismatch, || {
+                    format!(
+                        "unitig {} has {} kmers but its colors cover {} kmers",
+                        id, kmers_count, colors_kmers_count
+                    )
+                });
+            }
+
+            let mut kmer = KmersCodec::<N>::empty();
+            for base in &seq.seq[..(k - 1)] {
+                kmer = codec.push_back(&kmer, Utils::compress_base(*base));
+            }
+
+            let mut first = None;
+            for base in &seq.seq[(k - 1)..] {
+                kmer = codec.push_back(&kmer, Utils::compress_base(*base));
+                if first.is_none() {
+                    first = Some(kmer);
+                }
+
+                if let Some(other) = kmers.insert(codec.canonical(&kmer), id) {
+                    report.report(ViolationKind::DuplicatedKmer, || {
+                        format!(
+                            "kmer {} is present both in unitig {} and in unitig {}",
+                            codec.to_string(&kmer),
+                            other,
+                            id
+                        )
+                    });
+                }
+            }
+
+            unitigs.push(UnitigDesc {
+                id,
+                first: first.unwrap(),
+                last: kmer,
+                links,
+            });
+        },
+        None,
+        true,
+        false,
+    );
+
+    // Returns the only kmer that follows the given one in the graph, if it is unique
+    let unique_successor = |kmer: &OrientedKmer<N>| {
+        let mut successor = None;
+        for cbase in 0..4 {
+            let next = codec.push_back(kmer, cbase);
+            if kmers.contains_key(&codec.canonical(&next)) {
+                if successor.is_some() {
+                    return None;
+                }
+                successor = Some(next);
+            }
+        }
+        successor
+    };
+
+    let predecessors_count = |kmer: &OrientedKmer<N>| {
+        (0..4)
+            .filter(|cbase| kmers.contains_key(&codec.canonical(&codec.push_front(kmer, *cbase))))
+            .count()
+    };
+
+    for unitig in &unitigs {
+        for (end, direction) in [(unitig.last, "forward"), (unitig.first.flipped(), "backward")] {
+            let Some(successor) = unique_successor(&end) else {
+                continue;
+            };
+
+            let other = kmers[&codec.canonical(&successor)];
+            if other != unitig.id && predecessors_count(&successor) == 1 {
+                report.report(ViolationKind::NotMaximal, || {
+                    format!(
+                        "unitig {} can be extended {} with unitig {}",
+                        unitig.id, direction, other
+                    )
+                });
+            }
+        }
+    }
+
+    if args.links {
+        let unitigs_map: HashMap<_, _> = unitigs
+            .iter()
+            .enumerate()
+            .map(|(index, unitig)| (unitig.id, index))
+            .collect();
+
+        for unitig in &unitigs {
+            for link in &unitig.links {
+                let Some(other) = unitigs_map.get(&link.other).map(|i| &unitigs[*i]) else {
+                    report.report(ViolationKind::MissingLink, || {
+                        format!(
+                            "unitig {} links to the missing unitig {}",
+                            unitig.id, link.other
+                        )
+                    });
+                    continue;
+                };
+
+                let current_end = if link.flip_current {
+                    unitig.first.flipped()
+                } else {
+                    unitig.last
+                };
+
+                let other_start = if link.flip_other {
+                    other.last.flipped()
+                } else {
+                    other.first
+                };
+
+                if !codec.overlaps(&current_end, &other_start) {
+                    report.report(ViolationKind::InconsistentLinkOverlap, || {
+                        format!(
+                            "link {} -> {} does not have a k-1 overlap ({} / {})",
+                            unitig.id,
+                            other.id,
+                            codec.to_string(&current_end),
+                            codec.to_string(&other_start)
+                        )
+                    });
+                }
+
+                let reverse_link = UnitigLinkDesc {
+                    flip_current: !link.flip_other,
+                    other: unitig.id,
+                    flip_other: !link.flip_current,
+                };
+
+                if !other.links.contains(&reverse_link) {
+                    report.report(ViolationKind::AsymmetricLink, || {
+                        format!(
+                            "link {} -> {} has no matching reverse link in unitig {}",
+                            unitig.id, other.id, other.id
+                        )
+                    });
+                }
+            }
+        }
+    }
+
+    (unitigs.len() as u64, kmers.len() as u64)
+}
+
+/// Verifies the checksums of the graph and of its colormap, if present
+fn validate_checksums(args: &ValidateArgs, report: &mut ValidationReport) {
+    match verify_file_checksum(&args.input_graph) {
+        Ok(FileChecksumStatus::Valid) => println!("The graph checksum is valid"),
+        Ok(FileChecksumStatus::Missing) => warn!(
+            "No checksum file found for the graph {}, build it with --graph-checksum",
+            args.input_graph.display()
+        ),
+        Ok(FileChecksumStatus::Mismatch { expected, found }) => {
+            report.report(ViolationKind::ChecksumMismatch, || {
+                format!(
+                    "the graph {} is corrupted, its checksum is {:08x} instead of {:08x}",
+                    args.input_graph.display(),
+                    found,
+                    expected
+                )
+            })
+        }
+        Err(error) => report.report(ViolationKind::ChecksumMismatch, || {
+            format!("cannot check the graph checksum: {}", error)
+        }),
+    }
+
+    let colormap_file = args.input_graph.with_extension("colors.dat");
+    if !colormap_file.exists() {
+        return;
+    }
+
+    match verify_colormap_checksums(&colormap_file) {
+        Ok(Some(mismatches)) => {
+            if mismatches.is_empty() {
+                println!("The colormap checksums are valid");
+            }
+            for mismatch in mismatches {
+                report.report(ViolationKind::ChecksumMismatch, || {
+                    format!("colormap {}: {}", colormap_file.display(), mismatch)
+                });
+            }
+        }
+        Ok(None) => warn!(
+            "The colormap {} has no checksums, add them with 'ggcat colormap upgrade'",
+            colormap_file.display()
+        ),
+        Err(error) => report.report(ViolationKind::ChecksumMismatch, || {
+            format!("colormap {}: {}", colormap_file.display(), error)
+        }),
+    }
+}
+
+/// Validates a graph produced by ggcat, returning true if no violations were found
+pub fn validate_graph(args: ValidateArgs) -> bool {
+    let mut report = ValidationReport {
+        counts: HashMap::new(),
+        max_reported: args.max_reported,
+    };
+
+    if args.checksums {
+        validate_checksums(&args, &mut report);
+    }
+
+    if args.colors {
+        let colormap_file = args.input_graph.with_extension("colors.dat");
+        if let Err(error) = ColorsDeserializer::check_colormap_file(&colormap_file) {
+            error!("Invalid colormap {}: {}", colormap_file.display(), error);
+            return false;
+        }
+    }
+
+    let (unitigs_count, kmers_count) = match args.kmer_length {
+        0 => {
+            error!("k must be greater than 0!");
+            return false;
+        }
+        1..=32 => validate_graph_impl::<1>(&args, &mut report),
+        33..=64 => validate_graph_impl::<2>(&args, &mut report),
+        65..=128 => validate_graph_impl::<4>(&args, &mut report),
+        129..=256 => validate_graph_impl::<8>(&args, &mut report),
+        _ => {
+            error!("k greater than 256 is not supported!");
+            return false;
+        }
+    };
+
+    println!(
+        "Checked {} unitigs with {} distinct kmers",
+        unitigs_count, kmers_count
+    );
+
+    let mut counts: Vec<_> = report.counts.iter().collect();
+    counts.sort();
+    for (kind, count) in counts {
+        println!("{:?}: {} violations", kind, count);
+    }
+
+    let total = report.total();
+    if total == 0 {
+        println!("The graph is valid!");
+    }
+    total == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_bases() {
+        assert_eq!(find_invalid_base(b"ACGTacgt"), None);
+        assert_eq!(find_invalid_base(b"ACGNT"), Some(3));
+        assert_eq!(find_invalid_base(b"acgtu"), Some(4));
+        assert_eq!(find_invalid_base(b"AC-GT"), Some(2));
+        assert_eq!(find_invalid_base(b"ACG\rT"), Some(3));
+    }
+
+    #[test]
+    fn kmers_codec_roll() {
+        let codec = KmersCodec::<2>::new(35, false);
+        let sequence = b"ACGTTGCATGCAAGTCCATGGACTTAGCAGTCGTAGCTTGCA";
+
+        let mut kmer = KmersCodec::<2>::empty();
+        for base in &sequence[..35] {
+            kmer = codec.push_back(&kmer, Utils::compress_base(*base));
+        }
+
+        let mut kmer_rc = KmersCodec::<2>::empty();
+        for base in sequence[..35].iter().rev() {
+            kmer_rc = codec.push_back(&kmer_rc, Utils::compress_base(*base) ^ 2);
+        }
+        assert_eq!(kmer.flipped(), kmer_rc);
+
+        let next = codec.push_back(&kmer, Utils::compress_base(sequence[35]));
+        assert!(codec.overlaps(&kmer, &next));
+        assert_eq!(
+            codec.push_front(&next, Utils::compress_base(sequence[0])),
+            kmer
+        );
+        assert_eq!(
+            codec.to_string(&next),
+            std::str::from_utf8(&sequence[1..36]).unwrap()
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::parsers::SingleSequenceInfo;
+use crate::storage::ColormapEncoding;
 use config::{BucketIndexType, ColorCounterType, ColorIndexType};
 use dynamic_dispatch::dynamic_dispatch;
 use hashbrown::HashMap;
@@ -9,6 +10,7 @@ use io::concurrent::temp_reads::extra_data::{
     SequenceExtraDataConsecutiveCompression, SequenceExtraDataTempBufferManagement,
 };
 use parallel_processor::fast_smart_bucket_sort::FastSortable;
+use std::borrow::Cow;
 use std::cmp::min;
 use std::hash::Hash;
 use std::ops::Range;
@@ -68,14 +70,20 @@ pub trait MinimizerBucketingSeqColorData:
 }
 
 pub trait ColorMapReader {
-    fn get_color_name(&self, index: ColorIndexType, json_escaped: bool) -> &str;
+    fn get_color_name(&self, index: ColorIndexType, json_escaped: bool) -> Cow<'_, str>;
+    /// The metadata of the color as a json string, if it was set
+    fn get_color_metadata(&self, index: ColorIndexType) -> Option<&str>;
     fn colors_count(&self) -> usize;
     fn colors_subsets_count(&self) -> u64;
 }
 
 impl ColorMapReader for () {
-    fn get_color_name(&self, _index: ColorIndexType, _json_escaped: bool) -> &str {
-        ""
+    fn get_color_name(&self, _index: ColorIndexType, _json_escaped: bool) -> Cow<'_, str> {
+        Cow::Borrowed("")
+    }
+
+    fn get_color_metadata(&self, _index: ColorIndexType) -> Option<&str> {
+        None
     }
 
     fn colors_count(&self) -> usize {
@@ -125,10 +133,11 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
     type GlobalColorsTableWriter: Sync + Send + 'static;
     type GlobalColorsTableReader: ColorMapReader + Sync + Send + 'static;
 
-    /// Creates a new colors table at the given path
+    /// Creates a new colors table at the given path, storing the colors subsets with the given encoding
     fn create_colors_table(
         path: impl AsRef<Path>,
         color_names: &[String],
+        encoding: ColormapEncoding,
     ) -> Self::GlobalColorsTableWriter;
 
     /// Creates a new colors table at the given path
@@ -141,6 +150,8 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
     type ColorsBufferTempStructure: 'static + Send + Sync;
     fn allocate_temp_buffer_structure(temp_dir: &Path) -> Self::ColorsBufferTempStructure;
     fn reinit_temp_buffer_structure(data: &mut Self::ColorsBufferTempStructure);
+    /// Memory used by the temporary buffer, accounted in the hard memory budget
+    fn get_temp_buffer_structure_size(data: &Self::ColorsBufferTempStructure) -> usize;
     fn add_temp_buffer_structure_el(
         data: &mut Self::ColorsBufferTempStructure,
         kmer_color: &Self::SingleKmerColorDataType,
@@ -169,6 +180,11 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
         min_multiplicity: usize,
     );
 
+    /// Returns the color subset index assigned to a single kmer by process_colors, if colors are enabled
+    fn get_kmer_color_subset(
+        entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType>;
+
     /// Struct used to hold color information about unitigs
     type PartialUnitigsColorStructure: IdentSequenceWriter + Clone + 'static;
     /// Struct holding the result of joining multiple partial unitigs to build a final unitig
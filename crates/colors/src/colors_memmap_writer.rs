@@ -1,6 +1,7 @@
-// use crate::storage::roaring::ColorsStorage;
+use crate::storage::roaring::RoaringColorsSerializer;
+use crate::storage::run_length::RunLengthColorsSerializer;
 use crate::storage::serializer::ColorsSerializer;
-use crate::storage::ColorsSerializerTrait;
+use crate::storage::ColormapEncoding;
 use config::ColorIndexType;
 use dashmap::DashMap;
 use hashes::dummy_hasher::DummyHasherBuilder;
@@ -9,18 +10,47 @@ use siphasher::sip128::{Hasher128, SipHasher13};
 use std::hash::Hash;
 use std::path::Path;
 
-pub struct ColorsMemMapWriter<C: ColorsSerializerTrait> {
+/// Colors subsets storage with the encoding selected when the graph is built
+enum ColorsStorage {
+    RunLength(ColorsSerializer<RunLengthColorsSerializer>),
+    Roaring(ColorsSerializer<RoaringColorsSerializer>),
+}
+
+impl ColorsStorage {
+    fn serialize_colors(&self, colors: &[ColorIndexType]) -> ColorIndexType {
+        match self {
+            ColorsStorage::RunLength(storage) => storage.serialize_colors(colors),
+            ColorsStorage::Roaring(storage) => storage.serialize_colors(colors),
+        }
+    }
+
+    fn print_stats(&self) {
+        match self {
+            ColorsStorage::RunLength(storage) => storage.print_stats(),
+            ColorsStorage::Roaring(storage) => storage.print_stats(),
+        }
+    }
+}
+
+pub struct ColorsMemMapWriter {
     colors: DashMap<u128, ColorIndexType, DummyHasherBuilder>,
-    colors_storage: ColorsSerializer<C>,
+    colors_storage: ColorsStorage,
     hash_keys: (u64, u64),
 }
 
-impl<C: ColorsSerializerTrait> ColorsMemMapWriter<C> {
-    pub fn new(file: impl AsRef<Path>, color_names: &[String]) -> Self {
+impl ColorsMemMapWriter {
+    pub fn new(file: impl AsRef<Path>, color_names: &[String], encoding: ColormapEncoding) -> Self {
         let mut rng = thread_rng();
         Self {
             colors: DashMap::with_hasher(DummyHasherBuilder),
-            colors_storage: ColorsSerializer::new(file, color_names),
+            colors_storage: match encoding {
+                ColormapEncoding::RunLength => {
+                    ColorsStorage::RunLength(ColorsSerializer::new(file, color_names))
+                }
+                ColormapEncoding::Roaring => {
+                    ColorsStorage::Roaring(ColorsSerializer::new(file, color_names))
+                }
+            },
             hash_keys: (rng.next_u64(), rng.next_u64()),
         }
     }
@@ -4,8 +4,7 @@
 #![feature(const_type_id)]
 #![feature(int_roundings)]
 #![feature(let_chains)]
-
-use crate::storage::run_length::RunLengthColorsSerializer;
+#![feature(slice_group_by)]
 
 pub mod bundles;
 pub mod colors_manager;
@@ -16,5 +15,3 @@ pub mod parsers;
 pub mod storage;
 
 pub(crate) mod async_slice_queue;
-
-pub type DefaultColorsSerializer = RunLengthColorsSerializer;
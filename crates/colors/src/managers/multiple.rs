@@ -1,9 +1,11 @@
+use log::{error, warn};
 use crate::colors_manager::ColorsMergeManager;
 use crate::colors_memmap_writer::ColorsMemMapWriter;
-use crate::DefaultColorsSerializer;
+use crate::storage::ColormapEncoding;
 use atoi::{FromRadix10, FromRadix16};
 use bstr::ByteSlice;
 use byteorder::ReadBytesExt;
+use config::memory_budget::{self, MemoryReservation};
 use config::{
     get_compression_level_info, get_memory_mode, ColorCounterType, ColorIndexType, MinimizerType,
     SwapPriority, PARTIAL_VECS_CHECKPOINT_SIZE, READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END,
@@ -38,6 +40,7 @@ const COLOR_SEQUENCES_SUBBUKETS: usize = 32;
 struct SequencesStorage {
     buffer: Vec<u8>,
     file: Option<CompressedBinaryWriter>,
+    memory_reservation: MemoryReservation,
 }
 
 struct SequencesStorageStream<'a> {
@@ -64,6 +67,7 @@ impl SequencesStorage {
         Self {
             buffer: Vec::with_capacity(READS_BUFFERS_MAX_CAPACITY),
             file: None,
+            memory_reservation: MemoryReservation::with_size(READS_BUFFERS_MAX_CAPACITY),
         }
     }
 
@@ -85,7 +89,11 @@ impl SequencesStorage {
     }
 
     pub fn flush(&mut self, temp_dir: &PathBuf) {
-        if self.buffer.len() >= READS_BUFFERS_MAX_CAPACITY {
+        // Flush earlier if the memory is close to the hard limit
+        if self.buffer.len() >= READS_BUFFERS_MAX_CAPACITY
+            || (self.buffer.len() >= READS_BUFFERS_MIN_FLUSH_SIZE
+                && memory_budget::is_under_pressure())
+        {
             if self.file.is_none() {
                 static COLOR_STORAGE_INDEX: AtomicUsize = AtomicUsize::new(0);
                 self.file = Some(CompressedBinaryWriter::new(
@@ -102,6 +110,7 @@ impl SequencesStorage {
             self.file.as_ref().unwrap().write_data(&self.buffer);
             self.buffer.clear();
         }
+        self.memory_reservation.update(self.buffer.capacity());
     }
 }
 
@@ -118,19 +127,21 @@ pub struct MultipleColorsManager<H: MinimizerHashFunctionFactory, MH: HashFuncti
 const VISITED_BIT: usize = 1 << (COUNTER_BITS - 1);
 const TEMP_BUFFER_START_SIZE: usize = 1024 * 64;
 const READS_BUFFERS_MAX_CAPACITY: usize = 1024 * 32;
+const READS_BUFFERS_MIN_FLUSH_SIZE: usize = 1024 * 4;
 
 impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManager<H, MH>
     for MultipleColorsManager<H, MH>
 {
     type SingleKmerColorDataType = ColorIndexType;
-    type GlobalColorsTableWriter = ColorsMemMapWriter<DefaultColorsSerializer>;
+    type GlobalColorsTableWriter = ColorsMemMapWriter;
     type GlobalColorsTableReader = ();
 
     fn create_colors_table(
         path: impl AsRef<Path>,
         color_names: &[String],
+        encoding: ColormapEncoding,
     ) -> Self::GlobalColorsTableWriter {
-        ColorsMemMapWriter::new(path, color_names)
+        ColorsMemMapWriter::new(path, color_names, encoding)
     }
 
     fn open_colors_table(_path: impl AsRef<Path>) -> Self::GlobalColorsTableReader {
@@ -165,6 +176,14 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         data.sequences_count = 0;
     }
 
+    fn get_temp_buffer_structure_size(data: &Self::ColorsBufferTempStructure) -> usize {
+        data.sequences
+            .iter()
+            .map(|s| s.buffer.capacity())
+            .sum::<usize>()
+            + data.temp_colors_buffer.capacity() * size_of::<ColorIndexType>()
+    }
+
     fn add_temp_buffer_structure_el(
         data: &mut Self::ColorsBufferTempStructure,
         kmer_color: &ColorIndexType,
@@ -343,6 +362,12 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         }
     }
 
+    fn get_kmer_color_subset(
+        entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType> {
+        Some((entry.get_counter() & !VISITED_BIT) as ColorIndexType)
+    }
+
     type PartialUnitigsColorStructure = UnitigColorData;
     type TempUnitigColorStructure = DefaultUnitigsTempColorData;
 
@@ -455,8 +480,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
             .sum::<ColorCounterType>()
             + 30;
         if sum != seq.len() {
-            println!("Temp values: {} {}", sum as usize, seq.len());
-            println!("Dbg: {:?}", str.colors);
+            error!("Temp values: {} {}", sum as usize, seq.len());
+            error!("Dbg: {:?}", str.colors);
             assert_eq!(sum as usize, seq.len());
         }
     }
@@ -485,7 +510,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
             let kmer_color = (entry.get_counter() & !VISITED_BIT) as ColorIndexType;
             if kmer_color != color {
                 let hashes = MH::new(read, 31);
-                println!(
+                error!(
                     "Err: {:?}",
                     hashes
                         .iter()
@@ -635,7 +660,7 @@ impl IdentSequenceWriter for UnitigColorData {
             colors_count += kmers_count
         }
         if colors_count == 0 {
-            println!("Warn: 0 colors for {:?}", std::str::from_utf8(ident));
+            warn!("0 colors for {:?}", std::str::from_utf8(ident));
         }
 
         Some(UnitigColorData {
@@ -660,7 +685,7 @@ impl IdentSequenceWriter for UnitigColorData {
             }
         }
         if colors_count == 0 {
-            println!("Warn: 0 colors for {:?}", std::str::from_utf8(ident));
+            warn!("0 colors for {:?}", std::str::from_utf8(ident));
         }
 
         Some(UnitigColorData {
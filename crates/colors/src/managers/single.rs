@@ -1,6 +1,6 @@
 use crate::colors_manager::ColorsMergeManager;
 use crate::storage::deserializer::ColorsDeserializer;
-use crate::DefaultColorsSerializer;
+use crate::storage::ColormapEncoding;
 use byteorder::ReadBytesExt;
 use config::{ColorCounterType, ColorIndexType};
 use hashbrown::HashMap;
@@ -27,11 +27,12 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
 {
     type SingleKmerColorDataType = ColorIndexType;
     type GlobalColorsTableWriter = ();
-    type GlobalColorsTableReader = ColorsDeserializer<DefaultColorsSerializer>;
+    type GlobalColorsTableReader = ColorsDeserializer;
 
     fn create_colors_table(
         _path: impl AsRef<Path>,
         _color_names: &[String],
+        _encoding: ColormapEncoding,
     ) -> Self::GlobalColorsTableWriter {
         ()
     }
@@ -50,6 +51,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
 
     fn reinit_temp_buffer_structure(_data: &mut Self::ColorsBufferTempStructure) {}
 
+    fn get_temp_buffer_structure_size(_data: &Self::ColorsBufferTempStructure) -> usize {
+        0
+    }
+
     fn add_temp_buffer_structure_el(
         _data: &mut Self::ColorsBufferTempStructure,
         kmer_color: &ColorIndexType,
@@ -90,6 +95,12 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     ) {
     }
 
+    fn get_kmer_color_subset(
+        entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType> {
+        Some(entry.color_index.color_index)
+    }
+
     type PartialUnitigsColorStructure = UnitigColorDataSerializer;
     type TempUnitigColorStructure = DefaultUnitigsTempColorData;
 
@@ -2,7 +2,8 @@ use crate::colors_manager::{
     ColorsManager, ColorsMergeManager, ColorsParser, MinimizerBucketingSeqColorData,
 };
 use crate::parsers::SingleSequenceInfo;
-use config::{BucketIndexType, ColorCounterType};
+use crate::storage::ColormapEncoding;
+use config::{BucketIndexType, ColorCounterType, ColorIndexType};
 use dynamic_dispatch::dynamic_dispatch;
 use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
@@ -120,6 +121,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     fn create_colors_table(
         _path: impl AsRef<Path>,
         _color_names: &[String],
+        _encoding: ColormapEncoding,
     ) -> Self::GlobalColorsTableWriter {
         ()
     }
@@ -140,6 +142,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     #[inline(always)]
     fn reinit_temp_buffer_structure(_data: &mut Self::ColorsBufferTempStructure) {}
 
+    fn get_temp_buffer_structure_size(_data: &Self::ColorsBufferTempStructure) -> usize {
+        0
+    }
+
     #[inline(always)]
     fn add_temp_buffer_structure_el(
         _data: &mut Self::ColorsBufferTempStructure,
@@ -180,6 +186,13 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         unreachable!()
     }
 
+    #[inline(always)]
+    fn get_kmer_color_subset(
+        _entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType> {
+        None
+    }
+
     type PartialUnitigsColorStructure = NonColoredManager;
     type TempUnitigColorStructure = NonColoredManager;
 
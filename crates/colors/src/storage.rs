@@ -1,16 +1,29 @@
+use crate::storage::roaring::RoaringColorsSerializer;
+use crate::storage::run_length::RunLengthColorsSerializer;
 use crate::storage::serializer::ColorsFlushProcessing;
 use config::ColorIndexType;
 use std::io::Read;
 
 pub mod deserializer;
+pub mod editing;
+pub mod format;
+pub mod mmap_reader;
+pub mod names_table;
+pub mod reordering;
 pub mod roaring;
 pub mod run_length;
 pub mod serializer;
+pub mod upgrade;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
 
 pub trait ColorsSerializerTrait: 'static {
     const MAGIC: [u8; 16];
 
-    fn decode_color(reader: impl Read, out_vec: Option<&mut Vec<ColorIndexType>>);
+    /// Decodes (or skips, if out_vec is None) the next subset, returning None if its data is not valid.
+    /// On failure out_vec is left empty
+    fn decode_color(reader: impl Read, out_vec: Option<&mut Vec<ColorIndexType>>) -> Option<()>;
     // fn decode_colors(reader: impl Read) -> ;
 
     fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, colors_count: u64) -> Self;
@@ -19,3 +32,42 @@ pub trait ColorsSerializerTrait: 'static {
     fn print_stats(&self);
     fn finalize(self) -> ColorsFlushProcessing;
 }
+
+/// Encoding of the colors subsets in the colormap, chosen when the graph is built
+/// and detected from the colormap header when it is read
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColormapEncoding {
+    /// Run-length encoded varints of the colors differences, compact for small or sparse subsets
+    #[default]
+    RunLength,
+    /// Roaring bitmaps, compact for dense subsets of many colors
+    Roaring,
+}
+
+impl ColormapEncoding {
+    pub fn magic(&self) -> [u8; 16] {
+        match self {
+            ColormapEncoding::RunLength => RunLengthColorsSerializer::MAGIC,
+            ColormapEncoding::Roaring => RoaringColorsSerializer::MAGIC,
+        }
+    }
+
+    /// Returns the encoding of a colormap from the magic of its header, if it is known
+    pub fn from_magic(magic: &[u8; 16]) -> Option<Self> {
+        [ColormapEncoding::RunLength, ColormapEncoding::Roaring]
+            .into_iter()
+            .find(|encoding| &encoding.magic() == magic)
+    }
+
+    #[inline(always)]
+    pub fn decode_color(
+        &self,
+        reader: impl Read,
+        out_vec: Option<&mut Vec<ColorIndexType>>,
+    ) -> Option<()> {
+        match self {
+            ColormapEncoding::RunLength => RunLengthColorsSerializer::decode_color(reader, out_vec),
+            ColormapEncoding::Roaring => RoaringColorsSerializer::decode_color(reader, out_vec),
+        }
+    }
+}
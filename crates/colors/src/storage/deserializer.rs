@@ -1,80 +1,109 @@
 use crate::colors_manager::ColorMapReader;
-use crate::storage::serializer::{ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap};
-use crate::storage::ColorsSerializerTrait;
+use crate::storage::format::{
+    ColormapChunkInfo, ColormapError, ColormapHeaderInfo, ColormapLayout,
+};
+use crate::storage::names_table::ColorNamesTable;
+use crate::storage::serializer::ColorsIndexEntry;
+use crate::storage::ColormapEncoding;
 use config::ColorIndexType;
-use desse::Desse;
-use desse::DesseSized;
+use memmap2::Mmap;
 use replace_with::replace_with_or_abort;
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
-use std::marker::PhantomData;
-use std::path::Path;
+use std::io::{BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-pub struct ColorsDeserializer<DS: ColorsSerializerTrait> {
+pub struct ColorsDeserializer {
+    path: PathBuf,
+    encoding: ColormapEncoding,
     colormap_file: lz4::Decoder<BufReader<File>>,
-    color_names: Vec<String>,
-    json_escaped_color_names: Vec<String>,
-    colors_index: ColorsIndexMap,
+    color_names: ColorNamesTable,
+    layout: ColormapLayout,
     current_chunk: ColorsIndexEntry,
     current_chunk_size: ColorIndexType,
     current_index: ColorIndexType,
-    _phantom: PhantomData<DS>,
 }
 
-unsafe impl<DS: ColorsSerializerTrait> Sync for ColorsDeserializer<DS> {}
-unsafe impl<DS: ColorsSerializerTrait> Send for ColorsDeserializer<DS> {}
+unsafe impl Sync for ColorsDeserializer {}
+unsafe impl Send for ColorsDeserializer {}
 
-impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
+impl ColorsDeserializer {
+    /// Checks that the file is a valid colormap with a known encoding and a supported version,
+    /// reading only its header and its index
+    pub fn check_colormap_file(file: impl AsRef<Path>) -> Result<ColormapEncoding, ColormapError> {
+        let file = File::open(file)?;
+        Ok(ColormapLayout::read(BufReader::new(file))?.encoding)
+    }
+
+    /// Opens a colormap, panicking if it is not valid. Use open to handle the errors
     pub fn new(file: impl AsRef<Path>, read_color_names: bool) -> Self {
-        let mut file = File::open(file).unwrap();
+        let path = file.as_ref();
+        Self::open(path, read_color_names)
+            .unwrap_or_else(|error| panic!("Cannot open colormap {}: {}", path.display(), error))
+    }
 
-        let mut header_buffer = [0; ColorsFileHeader::SIZE];
-        file.read_exact(&mut header_buffer).unwrap();
+    /// Opens a colormap, checking its header and its index. The data of each chunk is checked
+    /// against its checksum when it is first read, panicking if it is corrupted
+    pub fn open(file: impl AsRef<Path>, read_color_names: bool) -> Result<Self, ColormapError> {
+        let path = file.as_ref().to_path_buf();
+        let mut file = BufReader::new(File::open(&path)?);
 
-        let header: ColorsFileHeader = ColorsFileHeader::deserialize_from(&header_buffer);
-        assert_eq!(header.magic, DS::MAGIC);
+        let layout = ColormapLayout::read(&mut file)?;
 
         let color_names = if read_color_names {
-            let mut compressed_stream = lz4::Decoder::new(BufReader::new(file)).unwrap();
-
-            let color_names: Vec<String> =
-                bincode::deserialize_from(&mut compressed_stream).unwrap();
-            file = compressed_stream.finish().0.into_inner();
-            color_names
+            let mmap = unsafe { Mmap::map(file.get_ref()) }?;
+            ColorNamesTable::open(&path, &layout, Arc::new(mmap))?
         } else {
-            Vec::new()
+            ColorNamesTable::empty()
         };
 
-        let colors_index: ColorsIndexMap = {
-            file.seek(SeekFrom::Start(header.index_offset)).unwrap();
-            bincode::deserialize_from(&mut file).unwrap()
+        let first_chunk = match layout.index.pairs.first() {
+            Some(first_chunk) => {
+                layout.read_and_verify_chunk(&mut file, 0)?;
+                *first_chunk
+            }
+            None => ColorsIndexEntry {
+                start_index: 0,
+                file_offset: layout.header.index_offset,
+                checksum: 0,
+            },
         };
+        file.seek(SeekFrom::Start(first_chunk.file_offset))?;
 
-        let first_chunk = colors_index.pairs[0];
-        file.seek(SeekFrom::Start(first_chunk.file_offset)).unwrap();
-
-        let current_chunk_size = colors_index
+        let current_chunk_size = layout
+            .index
             .pairs
             .get(1)
             .map(|p| p.start_index)
-            .unwrap_or(colors_index.subsets_count as ColorIndexType)
+            .unwrap_or(layout.index.subsets_count as ColorIndexType)
             - first_chunk.start_index;
 
-        let json_escaped_color_names = color_names
-            .iter()
-            .map(|s| s.replace("\"", "\\\"").replace("\\", "\\\\"))
-            .collect();
-
-        Self {
-            colormap_file: lz4::Decoder::new(BufReader::new(file)).unwrap(),
+        Ok(Self {
+            path,
+            encoding: layout.encoding,
+            colormap_file: lz4::Decoder::new(file)?,
             color_names,
-            json_escaped_color_names,
-            colors_index,
+            layout,
             current_chunk: first_chunk,
             current_chunk_size,
             current_index: first_chunk.start_index,
-            _phantom: Default::default(),
-        }
+        })
+    }
+
+    /// The encoding of the colors subsets, detected from the colormap header
+    pub fn encoding(&self) -> ColormapEncoding {
+        self.encoding
+    }
+
+    /// The version, the counts and the sizes recorded in the colormap header
+    pub fn header_info(&self) -> ColormapHeaderInfo {
+        self.layout.header_info()
+    }
+
+    /// The compressed chunks of the colormap, in the order of their subsets
+    pub fn chunks_info(&self) -> Vec<ColormapChunkInfo> {
+        self.layout.chunks_info()
     }
 
     fn maybe_change_block(&mut self, target_color: ColorIndexType) {
@@ -91,29 +120,39 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
             // );
             // Requested color is outside of chunk range, update the current chunk
             let new_chunk_index = self
-                .colors_index
+                .layout
+                .index
                 .pairs
                 .partition_point(|x| x.start_index <= target_color)
                 - 1;
 
-            self.current_chunk = self.colors_index.pairs[new_chunk_index];
+            self.current_chunk = self.layout.index.pairs[new_chunk_index];
             self.current_chunk_size = self
-                .colors_index
+                .layout
+                .index
                 .pairs
                 .get(new_chunk_index + 1)
                 .map(|p| p.start_index)
-                .unwrap_or(self.colors_index.subsets_count as ColorIndexType)
+                .unwrap_or(self.layout.index.subsets_count as ColorIndexType)
                 - self.current_chunk.start_index;
             self.current_index = self.current_chunk.start_index;
 
+            let mut chunk_check = Ok(());
             replace_with_or_abort(&mut self.colormap_file, |colormap_file| {
                 let mut buffered_file = colormap_file.finish().0;
                 assert_ne!(self.current_chunk.file_offset, 0);
+                chunk_check = self
+                    .layout
+                    .read_and_verify_chunk(&mut buffered_file, new_chunk_index);
                 buffered_file
                     .seek(SeekFrom::Start(self.current_chunk.file_offset))
                     .unwrap();
                 lz4::Decoder::new(buffered_file).unwrap()
             });
+
+            if let Err(error) = chunk_check {
+                panic!("Corrupted colormap {}: {}", self.path.display(), error);
+            }
         }
     }
 
@@ -122,23 +161,43 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
 
         while self.current_index < color {
             // Skip the colors
-            DS::decode_color(&mut self.colormap_file, None);
+            if self
+                .encoding
+                .decode_color(&mut self.colormap_file, None)
+                .is_none()
+            {
+                self.invalid_subset(self.current_index);
+            }
             self.current_index += 1;
         }
 
         // Decode the requested color
-        DS::decode_color(&mut self.colormap_file, Some(out_vec));
+        if self
+            .encoding
+            .decode_color(&mut self.colormap_file, Some(out_vec))
+            .is_none()
+        {
+            self.invalid_subset(color);
+        }
         self.current_index += 1;
     }
+
+    fn invalid_subset(&self, subset: ColorIndexType) -> ! {
+        panic!(
+            "Corrupted colormap {}: {}",
+            self.path.display(),
+            ColormapError::InvalidSubset { subset }
+        );
+    }
 }
 
-impl<DS: ColorsSerializerTrait> ColorMapReader for ColorsDeserializer<DS> {
-    fn get_color_name(&self, index: ColorIndexType, json_escaped: bool) -> &str {
-        if json_escaped {
-            &self.json_escaped_color_names[index as usize]
-        } else {
-            &self.color_names[index as usize]
-        }
+impl ColorMapReader for ColorsDeserializer {
+    fn get_color_name(&self, index: ColorIndexType, json_escaped: bool) -> Cow<'_, str> {
+        self.color_names.get_name(index, json_escaped)
+    }
+
+    fn get_color_metadata(&self, index: ColorIndexType) -> Option<&str> {
+        self.color_names.metadata(index)
     }
 
     fn colors_count(&self) -> usize {
@@ -146,6 +205,6 @@ impl<DS: ColorsSerializerTrait> ColorMapReader for ColorsDeserializer<DS> {
     }
 
     fn colors_subsets_count(&self) -> u64 {
-        self.colors_index.subsets_count as u64
+        self.layout.index.subsets_count as u64
     }
 }
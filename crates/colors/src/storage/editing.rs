@@ -0,0 +1,273 @@
+use crate::storage::format::{ColormapError, ColormapLayout};
+use crate::storage::serializer::{
+    write_colors_names_section, ColorsFileHeader, ColorsIndexMap, STORAGE_VERSION,
+};
+use desse::{Desse, DesseSized};
+use hashbrown::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+fn bincode_to_io_error(error: bincode::Error) -> ColormapError {
+    ColormapError::Io(std::io::Error::new(std::io::ErrorKind::Other, error))
+}
+
+/// Rewrites a colormap with new color names and metadata, copying the compressed chunks without decoding them.
+/// The chunks are checked against their checksums, and the output is written with the current format version
+fn rewrite_colors_names(
+    input: &Path,
+    output: &Path,
+    color_names: &[String],
+    colors_metadata: &[String],
+) -> Result<(), ColormapError> {
+    let mut input_file = BufReader::new(File::open(input)?);
+    let layout = ColormapLayout::read(&mut input_file)?;
+
+    let mut output_file = File::create(output)?;
+    output_file.write_all(&ColorsFileHeader::default().serialize()[..])?;
+    output_file = write_colors_names_section(output_file, color_names, colors_metadata)?;
+
+    let mut offset = output_file.stream_position()?;
+    let mut output_file = BufWriter::new(output_file);
+
+    let mut chunks_order: Vec<_> = (0..layout.index.pairs.len()).collect();
+    chunks_order.sort_by_key(|chunk| layout.index.pairs[*chunk].file_offset);
+
+    let mut pairs = layout.index.pairs.clone();
+    let mut data = Vec::new();
+    for chunk in chunks_order {
+        let start = layout.index.pairs[chunk].file_offset;
+        data.resize((layout.chunks_end_offsets[chunk] - start) as usize, 0);
+        input_file.seek(SeekFrom::Start(start))?;
+        input_file.read_exact(&mut data)?;
+        layout
+            .verify_chunk(chunk, &data)
+            .map_err(ColormapError::ChecksumMismatch)?;
+
+        output_file.write_all(&data)?;
+        pairs[chunk].file_offset = offset;
+        pairs[chunk].checksum = crc32fast::hash(&data);
+        offset += data.len() as u64;
+    }
+
+    let index_offset = offset;
+    bincode::serialize_into(
+        &mut output_file,
+        &ColorsIndexMap {
+            pairs,
+            subsets_count: layout.index.subsets_count,
+        },
+    )
+    .map_err(bincode_to_io_error)?;
+
+    let mut output_file = output_file.into_inner().map_err(|e| e.into_error())?;
+    let total_size = output_file.stream_position()?;
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.write_all(
+        &ColorsFileHeader {
+            magic: layout.header.magic,
+            version: STORAGE_VERSION,
+            index_offset,
+            colors_count: color_names.len() as u64,
+            subsets_count: layout.header.subsets_count,
+            total_size,
+            total_uncompressed_size: layout.header.total_uncompressed_size,
+        }
+        .serialize()[..],
+    )?;
+
+    Ok(())
+}
+
+/// Reads the names and the metadata of the colors, applies the edit and writes the updated colormap to output
+fn edit_colors_names(
+    input: &Path,
+    output: &Path,
+    edit: impl FnOnce(&mut Vec<String>, &mut Vec<String>) -> Result<usize, ColormapError>,
+) -> Result<usize, ColormapError> {
+    let (mut color_names, mut colors_metadata) = {
+        let mut input_file = BufReader::new(File::open(input)?);
+        let layout = ColormapLayout::read(&mut input_file)?;
+        layout.read_color_names(&mut input_file)?
+    };
+
+    let edited = edit(&mut color_names, &mut colors_metadata)?;
+    rewrite_colors_names(input, output, &color_names, &colors_metadata)?;
+    Ok(edited)
+}
+
+fn find_colors<'a>(
+    color_names: &[String],
+    requested: impl Iterator<Item = &'a String>,
+) -> Result<Vec<Vec<usize>>, ColormapError> {
+    let mut names_map: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, name) in color_names.iter().enumerate() {
+        names_map.entry(name.as_str()).or_default().push(index);
+    }
+
+    requested
+        .map(|name| {
+            names_map
+                .get(name.as_str())
+                .cloned()
+                .ok_or_else(|| ColormapError::UnknownColor(name.clone()))
+        })
+        .collect()
+}
+
+/// Renames the colors of a colormap, given pairs of old and new names, writing the result to output.
+/// All the colors with the same old name are renamed. Returns the number of renamed colors
+pub fn rename_colors(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    renames: &[(String, String)],
+) -> Result<usize, ColormapError> {
+    edit_colors_names(input.as_ref(), output.as_ref(), |color_names, _| {
+        let indexes = find_colors(color_names, renames.iter().map(|(old, _)| old))?;
+
+        let mut renamed = 0;
+        for (colors, (_, new_name)) in indexes.into_iter().zip(renames) {
+            for color in colors {
+                color_names[color] = new_name.clone();
+                renamed += 1;
+            }
+        }
+        Ok(renamed)
+    })
+}
+
+/// Checks that the metadata of a color is valid json, returning it in its compact form,
+/// so that it can be written as it is in the json lines outputs
+fn compact_json_metadata(color: &str, metadata: &str) -> Result<String, ColormapError> {
+    serde_json::from_str::<serde_json::Value>(metadata)
+        .map(|value| value.to_string())
+        .map_err(|error| ColormapError::InvalidMetadata {
+            color: color.to_string(),
+            reason: error.to_string(),
+        })
+}
+
+/// Sets the metadata of the colors of a colormap, given pairs of color names and json metadata
+/// (None removes it), writing the result to output. Returns the number of updated colors,
+/// or an error without writing anything if a metadata is not valid json
+pub fn set_colors_metadata(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    metadata: &[(String, Option<String>)],
+) -> Result<usize, ColormapError> {
+    let metadata = metadata
+        .iter()
+        .map(|(color, color_metadata)| {
+            Ok((
+                color.clone(),
+                color_metadata
+                    .as_deref()
+                    .map(|m| compact_json_metadata(color, m))
+                    .transpose()?,
+            ))
+        })
+        .collect::<Result<Vec<_>, ColormapError>>()?;
+
+    edit_colors_names(
+        input.as_ref(),
+        output.as_ref(),
+        |color_names, colors_metadata| {
+            let indexes = find_colors(color_names, metadata.iter().map(|(name, _)| name))?;
+            colors_metadata.resize(color_names.len(), String::new());
+
+            let mut updated = 0;
+            for (colors, (_, color_metadata)) in indexes.into_iter().zip(&metadata) {
+                for color in colors {
+                    colors_metadata[color] = color_metadata.clone().unwrap_or_default();
+                    updated += 1;
+                }
+            }
+            Ok(updated)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rename_colors, set_colors_metadata};
+    use crate::colors_manager::ColorMapReader;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::format::{verify_colormap_checksums, ColormapError};
+    use crate::storage::roaring::RoaringColorsSerializer;
+    use crate::storage::test_utils::{test_color_names, write_test_colormap, TempFile};
+    use config::ColorIndexType;
+
+    #[test]
+    fn colors_names_editing() {
+        let input = TempFile::new("editing-input.colors.dat");
+        let renamed = TempFile::new("editing-renamed.colors.dat");
+        let output = TempFile::new("editing-output.colors.dat");
+
+        let color_names = test_color_names(20);
+        let subsets: Vec<Vec<ColorIndexType>> = (0..50000u32)
+            .map(|i| (0..20).filter(|c| (i + c) % 3 == 0).collect())
+            .collect();
+        write_test_colormap::<RoaringColorsSerializer>(&input, &color_names, &[], &subsets);
+
+        assert!(matches!(
+            rename_colors(
+                &input,
+                &renamed,
+                &[("missing".to_string(), "x".to_string())]
+            ),
+            Err(ColormapError::UnknownColor(_))
+        ));
+
+        let renames = [("color3".to_string(), "sample \"3\"".to_string())];
+        assert_eq!(rename_colors(&input, &renamed, &renames).unwrap(), 1);
+
+        let metadata = [
+            (
+                "sample \"3\"".to_string(),
+                Some("{\"species\":\"E. coli\"}".to_string()),
+            ),
+            (
+                "color7".to_string(),
+                Some("{\n  \"date\": \"2023-01-01\"\n}".to_string()),
+            ),
+        ];
+
+        assert!(matches!(
+            set_colors_metadata(
+                &renamed,
+                &output,
+                &[("color7".to_string(), Some("{\"date\":".to_string()))]
+            ),
+            Err(ColormapError::InvalidMetadata { .. })
+        ));
+        assert!(!output.exists());
+
+        assert_eq!(
+            set_colors_metadata(&renamed, &output, &metadata).unwrap(),
+            2
+        );
+        assert_eq!(
+            verify_colormap_checksums(&output).unwrap().unwrap().len(),
+            0
+        );
+
+        let mut deserializer = ColorsDeserializer::open(&output, true).unwrap();
+        assert_eq!(deserializer.get_color_name(3, false), "sample \"3\"");
+        assert_eq!(deserializer.get_color_name(4, false), "color4");
+        assert_eq!(
+            deserializer.get_color_metadata(3),
+            Some("{\"species\":\"E. coli\"}")
+        );
+        assert_eq!(deserializer.get_color_metadata(4), None);
+        assert_eq!(
+            deserializer.get_color_metadata(7),
+            Some("{\"date\":\"2023-01-01\"}")
+        );
+
+        let mut colors = Vec::new();
+        for (index, subset) in subsets.iter().enumerate().step_by(101) {
+            deserializer.get_color_mappings(index as ColorIndexType, &mut colors);
+            assert_eq!(&colors, subset);
+        }
+    }
+}
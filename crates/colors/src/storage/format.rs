@@ -0,0 +1,630 @@
+use crate::storage::names_table::StringsTable;
+use crate::storage::serializer::{
+    ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap, ColorsIndexMapV1, MIN_STORAGE_VERSION,
+    STORAGE_VERSION,
+};
+use crate::storage::ColormapEncoding;
+use config::ColorIndexType;
+use desse::{Desse, DesseSized};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+/// Errors found while opening or editing a colormap file
+#[derive(Debug)]
+pub enum ColormapError {
+    /// The file cannot be read
+    Io(std::io::Error),
+    /// The file does not start with the magic of a known colormap encoding
+    WrongMagic,
+    /// The colormap was written with a format version not supported by this build
+    UnsupportedVersion { version: u64 },
+    /// The file is shorter than the size recorded in its header
+    Truncated { expected_size: u64, file_size: u64 },
+    /// The index of the chunks cannot be decoded or it is not consistent with the header
+    InvalidIndex(String),
+    /// A chunk of the index points outside of the colors data section
+    OffsetOutOfRange { chunk: usize, offset: u64 },
+    /// The color names section cannot be decoded
+    InvalidColorNames(String),
+    /// The data of a chunk does not match its checksum
+    ChecksumMismatch(ChecksumMismatch),
+    /// No color has the requested name
+    UnknownColor(String),
+    /// The data of a colors subset cannot be decoded
+    InvalidSubset { subset: ColorIndexType },
+    /// The metadata given for a color is not valid json
+    InvalidMetadata { color: String, reason: String },
+    /// The requested subset is not in the colormap, usually because the graph does not match it
+    SubsetOutOfRange {
+        subset: ColorIndexType,
+        subsets_count: u64,
+    },
+}
+
+/// Chunk of a colormap whose compressed data does not match the checksum stored in the index
+#[derive(Copy, Clone, Debug)]
+pub struct ChecksumMismatch {
+    pub chunk: usize,
+    pub subsets: Range<ColorIndexType>,
+    pub expected: u32,
+    pub found: u32,
+}
+
+impl Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the chunk {} (subsets {}..{}) is corrupted, its checksum is {:08x} instead of {:08x}",
+            self.chunk, self.subsets.start, self.subsets.end, self.found, self.expected
+        )
+    }
+}
+
+impl Display for ColormapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColormapError::Io(error) => write!(f, "cannot read the colormap: {}", error),
+            ColormapError::WrongMagic => write!(
+                f,
+                "wrong magic, the file is not a colormap of a known format or it was not completely written"
+            ),
+            ColormapError::UnsupportedVersion { version } if *version < MIN_STORAGE_VERSION => {
+                write!(
+                    f,
+                    "the colormap format version {} is older than the oldest supported version {}",
+                    version, MIN_STORAGE_VERSION
+                )
+            }
+            ColormapError::UnsupportedVersion { version } => write!(
+                f,
+                "the colormap format version {} is newer than the supported version {}, \
+                please update ggcat to read it",
+                version, STORAGE_VERSION
+            ),
+            ColormapError::Truncated {
+                expected_size,
+                file_size,
+            } => write!(
+                f,
+                "the file is truncated, its size is {} bytes but the header expects {} bytes",
+                file_size, expected_size
+            ),
+            ColormapError::InvalidIndex(reason) => {
+                write!(f, "invalid chunks index: {}", reason)
+            }
+            ColormapError::OffsetOutOfRange { chunk, offset } => write!(
+                f,
+                "the offset {} of the chunk {} is outside of the colors data",
+                offset, chunk
+            ),
+            ColormapError::InvalidColorNames(reason) => {
+                write!(f, "invalid color names: {}", reason)
+            }
+            ColormapError::ChecksumMismatch(mismatch) => write!(f, "{}", mismatch),
+            ColormapError::UnknownColor(name) => {
+                write!(f, "the colormap has no color named '{}'", name)
+            }
+            ColormapError::InvalidSubset { subset } => {
+                write!(f, "the colors of the subset {} cannot be decoded", subset)
+            }
+            ColormapError::InvalidMetadata { color, reason } => write!(
+                f,
+                "the metadata of the color '{}' is not valid json: {}",
+                color, reason
+            ),
+            ColormapError::SubsetOutOfRange {
+                subset,
+                subsets_count,
+            } => write!(
+                f,
+                "the subset {} is out of range, the colormap has {} subsets",
+                subset, subsets_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ColormapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ColormapError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ColormapError {
+    fn from(error: std::io::Error) -> Self {
+        ColormapError::Io(error)
+    }
+}
+
+/// Summary of the colormap header
+#[derive(Copy, Clone, Debug)]
+pub struct ColormapHeaderInfo {
+    pub version: u64,
+    pub encoding: ColormapEncoding,
+    pub colors_count: u64,
+    pub subsets_count: u64,
+    /// Size of the whole colormap file
+    pub total_size: u64,
+    /// Size of the colors subsets before the compression
+    pub total_uncompressed_size: u64,
+}
+
+/// Position and compressed size of a chunk of the colormap
+#[derive(Clone, Debug)]
+pub struct ColormapChunkInfo {
+    pub subsets: Range<ColorIndexType>,
+    pub file_offset: u64,
+    pub compressed_size: u64,
+}
+
+/// Header and chunks index of a colormap, checked for consistency with the file
+pub(crate) struct ColormapLayout {
+    pub header: ColorsFileHeader,
+    pub encoding: ColormapEncoding,
+    pub index: ColorsIndexMap,
+    /// End of the compressed data of each chunk, in the same order of the index
+    pub chunks_end_offsets: Vec<u64>,
+}
+
+impl ColormapLayout {
+    /// Reads and validates the header and the index of a colormap
+    pub fn read(mut file: impl Read + Seek) -> Result<Self, ColormapError> {
+        let file_size = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(0))?;
+
+        if file_size < ColorsFileHeader::SIZE as u64 {
+            return Err(ColormapError::Truncated {
+                expected_size: ColorsFileHeader::SIZE as u64,
+                file_size,
+            });
+        }
+
+        let mut header_buffer = [0; ColorsFileHeader::SIZE];
+        file.read_exact(&mut header_buffer)?;
+        let header: ColorsFileHeader = ColorsFileHeader::deserialize_from(&header_buffer);
+
+        let encoding =
+            ColormapEncoding::from_magic(&header.magic).ok_or(ColormapError::WrongMagic)?;
+
+        if !(MIN_STORAGE_VERSION..=STORAGE_VERSION).contains(&header.version) {
+            return Err(ColormapError::UnsupportedVersion {
+                version: header.version,
+            });
+        }
+
+        if file_size < header.total_size {
+            return Err(ColormapError::Truncated {
+                expected_size: header.total_size,
+                file_size,
+            });
+        }
+
+        if header.index_offset < ColorsFileHeader::SIZE as u64 || header.index_offset >= file_size {
+            return Err(ColormapError::InvalidIndex(format!(
+                "the index offset {} is outside of the file",
+                header.index_offset
+            )));
+        }
+
+        file.seek(SeekFrom::Start(header.index_offset))?;
+        let index_error = |e: bincode::Error| {
+            ColormapError::InvalidIndex(format!("the index is truncated or corrupted ({})", e))
+        };
+        let index: ColorsIndexMap = if header.version == 1 {
+            let index: ColorsIndexMapV1 =
+                bincode::deserialize_from(&mut file).map_err(index_error)?;
+            ColorsIndexMap {
+                pairs: index
+                    .pairs
+                    .into_iter()
+                    .map(|entry| ColorsIndexEntry {
+                        start_index: entry.start_index,
+                        file_offset: entry.file_offset,
+                        checksum: 0,
+                    })
+                    .collect(),
+                subsets_count: index.subsets_count,
+            }
+        } else {
+            bincode::deserialize_from(&mut file).map_err(index_error)?
+        };
+
+        if index.subsets_count != header.subsets_count {
+            return Err(ColormapError::InvalidIndex(format!(
+                "the index has {} subsets while the header has {}",
+                index.subsets_count, header.subsets_count
+            )));
+        }
+
+        if index.pairs.is_empty() != (index.subsets_count == 0) {
+            return Err(ColormapError::InvalidIndex(format!(
+                "{} chunks found for {} subsets",
+                index.pairs.len(),
+                index.subsets_count
+            )));
+        }
+
+        for (chunk, entry) in index.pairs.iter().enumerate() {
+            if entry.file_offset <= ColorsFileHeader::SIZE as u64
+                || entry.file_offset >= header.index_offset
+            {
+                return Err(ColormapError::OffsetOutOfRange {
+                    chunk,
+                    offset: entry.file_offset,
+                });
+            }
+
+            let valid_start = match chunk {
+                0 => entry.start_index == 0,
+                _ => entry.start_index > index.pairs[chunk - 1].start_index,
+            };
+            if !valid_start || entry.start_index as u64 >= index.subsets_count {
+                return Err(ColormapError::InvalidIndex(format!(
+                    "the chunk {} has an invalid start subset {}",
+                    chunk, entry.start_index
+                )));
+            }
+        }
+
+        // The chunks are written in completion order, so the end of each one is the start of the next in the file
+        let mut sorted_offsets: Vec<_> = index.pairs.iter().map(|p| p.file_offset).collect();
+        sorted_offsets.sort_unstable();
+        let chunks_end_offsets = index
+            .pairs
+            .iter()
+            .map(|p| {
+                let next = sorted_offsets.partition_point(|o| *o <= p.file_offset);
+                sorted_offsets
+                    .get(next)
+                    .copied()
+                    .unwrap_or(header.index_offset)
+            })
+            .collect();
+
+        Ok(Self {
+            header,
+            encoding,
+            index,
+            chunks_end_offsets,
+        })
+    }
+
+    pub fn header_info(&self) -> ColormapHeaderInfo {
+        ColormapHeaderInfo {
+            version: self.header.version,
+            encoding: self.encoding,
+            colors_count: self.header.colors_count,
+            subsets_count: self.header.subsets_count,
+            total_size: self.header.total_size,
+            total_uncompressed_size: self.header.total_uncompressed_size,
+        }
+    }
+
+    /// The chunks of the colormap, in the order of their subsets
+    pub fn chunks_info(&self) -> Vec<ColormapChunkInfo> {
+        (0..self.index.pairs.len())
+            .map(|chunk| ColormapChunkInfo {
+                subsets: self.chunk_subsets(chunk),
+                file_offset: self.index.pairs[chunk].file_offset,
+                compressed_size: self.chunks_end_offsets[chunk]
+                    - self.index.pairs[chunk].file_offset,
+            })
+            .collect()
+    }
+
+    /// Chunks checksums are stored starting from the version 2 of the format
+    pub fn has_checksums(&self) -> bool {
+        self.header.version >= 2
+    }
+
+    pub fn chunk_subsets(&self, chunk: usize) -> Range<ColorIndexType> {
+        let start = self.index.pairs[chunk].start_index;
+        let end = self
+            .index
+            .pairs
+            .get(chunk + 1)
+            .map(|p| p.start_index)
+            .unwrap_or(self.index.subsets_count as ColorIndexType);
+        start..end
+    }
+
+    /// Checks the compressed data of a chunk against its checksum, if the colormap has checksums
+    pub fn verify_chunk(&self, chunk: usize, data: &[u8]) -> Result<(), ChecksumMismatch> {
+        if !self.has_checksums() {
+            return Ok(());
+        }
+
+        let expected = self.index.pairs[chunk].checksum;
+
+        let found = crc32fast::hash(data);
+        if found != expected {
+            return Err(ChecksumMismatch {
+                chunk,
+                subsets: self.chunk_subsets(chunk),
+                expected,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads the compressed data of a chunk from the file and checks it against its checksum
+    pub fn read_and_verify_chunk(
+        &self,
+        mut file: impl Read + Seek,
+        chunk: usize,
+    ) -> Result<(), ColormapError> {
+        let start = self.index.pairs[chunk].file_offset;
+        let mut data = vec![0; (self.chunks_end_offsets[chunk] - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut data)?;
+        self.verify_chunk(chunk, &data)
+            .map_err(ColormapError::ChecksumMismatch)
+    }
+
+    /// Color names and metadata are stored in indexed tables, that can be read lazily,
+    /// starting from the version 2 of the format
+    pub fn has_indexed_color_names(&self) -> bool {
+        self.header.version >= 2
+    }
+
+    /// Position in the file of the color names section, between the header and the first chunk
+    pub fn names_section(&self) -> Range<u64> {
+        let end = self
+            .index
+            .pairs
+            .iter()
+            .map(|p| p.file_offset)
+            .min()
+            .unwrap_or(self.header.index_offset);
+        ColorsFileHeader::SIZE as u64..end
+    }
+
+    /// Reads the positions of the names and of the metadata tables from the indexed color names section
+    pub fn parse_color_names_tables(
+        &self,
+        section: &[u8],
+    ) -> Result<(StringsTable, StringsTable), ColormapError> {
+        let names = StringsTable::parse(section, 0)?;
+        if names.len() as u64 != self.header.colors_count {
+            return Err(ColormapError::InvalidColorNames(format!(
+                "{} names found for {} colors",
+                names.len(),
+                self.header.colors_count
+            )));
+        }
+
+        let metadata = StringsTable::parse(section, names.end())?;
+        if !metadata.is_empty() && metadata.len() != names.len() {
+            return Err(ColormapError::InvalidColorNames(format!(
+                "{} metadata found for {} colors",
+                metadata.len(),
+                names.len()
+            )));
+        }
+
+        Ok((names, metadata))
+    }
+
+    /// Decodes the whole color names section, returning the names and the metadata
+    /// of the colors (empty if the colormap has no metadata)
+    pub fn read_color_names(
+        &self,
+        mut file: impl Read + Seek,
+    ) -> Result<(Vec<String>, Vec<String>), ColormapError> {
+        if self.has_indexed_color_names() {
+            let section = self.names_section();
+            let mut data = vec![0; (section.end - section.start) as usize];
+            file.seek(SeekFrom::Start(section.start))?;
+            file.read_exact(&mut data)?;
+
+            let (names, metadata) = self.parse_color_names_tables(&data)?;
+            let read_table = |table: StringsTable| {
+                (0..table.len())
+                    .map(|index| table.get(&data, index).map(|s| s.to_string()))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            return Ok((read_table(names)?, read_table(metadata)?));
+        }
+
+        file.seek(SeekFrom::Start(ColorsFileHeader::SIZE as u64))?;
+        let mut compressed_stream =
+            lz4::Decoder::new(file).map_err(|e| ColormapError::InvalidColorNames(e.to_string()))?;
+        let color_names: Vec<String> = bincode::deserialize_from(&mut compressed_stream)
+            .map_err(|e| ColormapError::InvalidColorNames(e.to_string()))?;
+
+        if color_names.len() as u64 != self.header.colors_count {
+            return Err(ColormapError::InvalidColorNames(format!(
+                "{} names found for {} colors",
+                color_names.len(),
+                self.header.colors_count
+            )));
+        }
+
+        // The version 1 of the format has no metadata
+        Ok((color_names, Vec::new()))
+    }
+}
+
+/// Checks the data of all the chunks of a colormap against their checksums.
+/// Returns None if the colormap was written with a format version without checksums
+pub fn verify_colormap_checksums(
+    file: impl AsRef<Path>,
+) -> Result<Option<Vec<ChecksumMismatch>>, ColormapError> {
+    let mut file = BufReader::new(File::open(file)?);
+    let layout = ColormapLayout::read(&mut file)?;
+
+    if !layout.has_checksums() {
+        return Ok(None);
+    }
+
+    let mut mismatches = vec![];
+    for chunk in 0..layout.index.pairs.len() {
+        match layout.read_and_verify_chunk(&mut file, chunk) {
+            Ok(()) => {}
+            Err(ColormapError::ChecksumMismatch(mismatch)) => mismatches.push(mismatch),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(Some(mismatches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_colormap_checksums, ColormapError, ColormapLayout};
+    use crate::colors_manager::ColorMapReader;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::run_length::RunLengthColorsSerializer;
+    use crate::storage::serializer::{
+        ColorsFileHeader, ColorsIndexEntryV1, ColorsIndexMapV1, STORAGE_VERSION,
+    };
+    use crate::storage::test_utils::{test_color_names, write_test_colormap, TempFile};
+    use crate::storage::upgrade::{upgrade_colormap, ColormapUpgrade};
+    use desse::{Desse, DesseSized};
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    fn rewrite_header(file: &Path, update: impl FnOnce(&mut ColorsFileHeader)) {
+        let mut data = std::fs::read(file).unwrap();
+        let mut header =
+            ColorsFileHeader::deserialize_from(data[..ColorsFileHeader::SIZE].try_into().unwrap());
+        update(&mut header);
+        data[..ColorsFileHeader::SIZE].copy_from_slice(&header.serialize());
+        std::fs::write(file, data).unwrap();
+    }
+
+    fn read_layout(file: &Path) -> ColormapLayout {
+        ColormapLayout::read(BufReader::new(File::open(file).unwrap())).unwrap()
+    }
+
+    /// Rewrites the color names section and the index as in the version 1 of the format,
+    /// with the compressed color names and without the chunks checksums
+    fn downgrade_to_v1(file: &Path, color_names: &[String]) {
+        let layout = read_layout(file);
+        let names_section = layout.names_section();
+        let current_data = std::fs::read(file).unwrap();
+
+        let mut names_stream = lz4::EncoderBuilder::new()
+            .build(current_data[..ColorsFileHeader::SIZE].to_vec())
+            .unwrap();
+        bincode::serialize_into(&mut names_stream, color_names).unwrap();
+        let (mut data, result) = names_stream.finish();
+        result.unwrap();
+
+        let chunks_start = data.len() as u64;
+        data.extend_from_slice(
+            &current_data[names_section.end as usize..layout.header.index_offset as usize],
+        );
+        let index_offset = data.len() as u64;
+
+        bincode::serialize_into(
+            &mut data,
+            &ColorsIndexMapV1 {
+                pairs: layout
+                    .index
+                    .pairs
+                    .iter()
+                    .map(|entry| ColorsIndexEntryV1 {
+                        start_index: entry.start_index,
+                        file_offset: entry.file_offset - names_section.end + chunks_start,
+                    })
+                    .collect(),
+                subsets_count: layout.index.subsets_count,
+            },
+        )
+        .unwrap();
+        let total_size = data.len() as u64;
+        std::fs::write(file, data).unwrap();
+        rewrite_header(file, |header| {
+            header.version = 1;
+            header.index_offset = index_offset;
+            header.total_size = total_size;
+        });
+    }
+
+    #[test]
+    fn colormap_validation_errors() {
+        let file = TempFile::new("validation.colors.dat");
+        let upgraded = TempFile::new("validation-upgraded.colors.dat");
+        let color_names = test_color_names(10);
+        let subsets: Vec<_> = (0..1000u32).map(|i| vec![i % 9, 9]).collect();
+
+        let write_colormap = || {
+            write_test_colormap::<RunLengthColorsSerializer>(&file, &color_names, &[], &subsets);
+        };
+
+        write_colormap();
+        assert!(ColorsDeserializer::open(&file, true).is_ok());
+        assert_eq!(verify_colormap_checksums(&file).unwrap().unwrap().len(), 0);
+
+        let data = std::fs::read(&file).unwrap();
+        std::fs::write(&file, &data[..data.len() - 5]).unwrap();
+        assert!(matches!(
+            ColorsDeserializer::open(&file, true),
+            Err(ColormapError::Truncated { .. })
+        ));
+
+        write_colormap();
+        rewrite_header(&file, |header| header.magic = *b"NOT_A_COLORMAP!!");
+        assert!(matches!(
+            ColorsDeserializer::open(&file, true),
+            Err(ColormapError::WrongMagic)
+        ));
+
+        write_colormap();
+        rewrite_header(&file, |header| header.index_offset = header.total_size + 10);
+        assert!(matches!(
+            ColorsDeserializer::open(&file, true),
+            Err(ColormapError::InvalidIndex(_))
+        ));
+
+        write_colormap();
+        rewrite_header(&file, |header| header.version = STORAGE_VERSION + 1);
+        assert!(matches!(
+            ColorsDeserializer::open(&file, true),
+            Err(ColormapError::UnsupportedVersion { .. })
+        ));
+
+        write_colormap();
+        let chunk_offset = read_layout(&file).index.pairs[0].file_offset as usize;
+        let mut data = std::fs::read(&file).unwrap();
+        data[chunk_offset + 8] ^= 0xFF;
+        std::fs::write(&file, data).unwrap();
+        assert!(matches!(
+            ColorsDeserializer::open(&file, true),
+            Err(ColormapError::ChecksumMismatch(_))
+        ));
+        let mismatches = verify_colormap_checksums(&file).unwrap().unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].chunk, 0);
+
+        write_colormap();
+        downgrade_to_v1(&file, &color_names);
+        assert!(verify_colormap_checksums(&file).unwrap().is_none());
+        assert_eq!(
+            upgrade_colormap(&file, &upgraded).unwrap(),
+            ColormapUpgrade::Upgraded { from_version: 1 }
+        );
+        assert_eq!(
+            verify_colormap_checksums(&upgraded).unwrap().unwrap().len(),
+            0
+        );
+
+        let mut deserializer = ColorsDeserializer::open(&upgraded, true).unwrap();
+        let mut colors = Vec::new();
+        deserializer.get_color_mappings(123, &mut colors);
+        assert_eq!(colors, vec![6, 9]);
+        assert_eq!(deserializer.get_color_name(3, false), "color3");
+        assert_eq!(
+            upgrade_colormap(&upgraded, &file).unwrap(),
+            ColormapUpgrade::AlreadyCurrent
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use crate::colors_manager::ColorMapReader;
+use crate::storage::format::{ColormapError, ColormapLayout};
+use crate::storage::names_table::ColorNamesTable;
+use crate::storage::ColormapEncoding;
+use config::ColorIndexType;
+use memmap2::Mmap;
+use parking_lot::Mutex;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Decoded chunks kept in memory by default
+pub const DEFAULT_CACHED_CHUNKS: usize = 16;
+
+/// Decompressed chunk of the colormap, with the start position of each subset
+struct DecodedChunk {
+    data: Vec<u8>,
+    subsets_offsets: Vec<usize>,
+}
+
+/// Thread-safe colormap reader, that maps the file in memory and decompresses only the chunks
+/// containing the requested subsets, keeping the most recently used ones in a small cache.
+/// Unlike ColorsDeserializer it can be shared between threads and it is efficient for random lookups
+pub struct ColorsMmapReader {
+    mmap: Arc<Mmap>,
+    encoding: ColormapEncoding,
+    color_names: ColorNamesTable,
+    layout: ColormapLayout,
+    cached_chunks: Mutex<VecDeque<(usize, Arc<DecodedChunk>)>>,
+    cache_capacity: usize,
+}
+
+impl ColorsMmapReader {
+    pub fn new(
+        file: impl AsRef<Path>,
+        read_color_names: bool,
+        cache_capacity: usize,
+    ) -> Result<Self, ColormapError> {
+        let path = file.as_ref();
+        let file = File::open(path)?;
+        let mmap = Arc::new(unsafe { Mmap::map(&file) }?);
+
+        let layout = ColormapLayout::read(Cursor::new(&mmap[..]))?;
+
+        let color_names = if read_color_names {
+            ColorNamesTable::open(path, &layout, mmap.clone())?
+        } else {
+            ColorNamesTable::empty()
+        };
+
+        Ok(Self {
+            mmap,
+            encoding: layout.encoding,
+            color_names,
+            layout,
+            cached_chunks: Mutex::new(VecDeque::with_capacity(cache_capacity)),
+            cache_capacity,
+        })
+    }
+
+    pub fn encoding(&self) -> ColormapEncoding {
+        self.encoding
+    }
+
+    fn find_chunk(&self, subset: ColorIndexType) -> Result<usize, ColormapError> {
+        if (subset as u64) >= self.layout.index.subsets_count {
+            return Err(ColormapError::SubsetOutOfRange {
+                subset,
+                subsets_count: self.layout.index.subsets_count,
+            });
+        }
+        Ok(self
+            .layout
+            .index
+            .pairs
+            .partition_point(|x| x.start_index <= subset)
+            - 1)
+    }
+
+    fn decode_chunk(&self, chunk_index: usize) -> Result<DecodedChunk, ColormapError> {
+        let subsets = self.layout.chunk_subsets(chunk_index);
+        let compressed = &self.mmap[self.layout.index.pairs[chunk_index].file_offset as usize
+            ..self.layout.chunks_end_offsets[chunk_index] as usize];
+
+        self.layout
+            .verify_chunk(chunk_index, compressed)
+            .map_err(ColormapError::ChecksumMismatch)?;
+
+        let mut data = Vec::new();
+        lz4::Decoder::new(compressed)?.read_to_end(&mut data)?;
+
+        let mut subsets_offsets = Vec::with_capacity(subsets.len() + 1);
+        let mut cursor = Cursor::new(data.as_slice());
+        for subset in subsets {
+            subsets_offsets.push(cursor.position() as usize);
+            self.encoding
+                .decode_color(&mut cursor, None)
+                .ok_or(ColormapError::InvalidSubset { subset })?;
+        }
+        subsets_offsets.push(cursor.position() as usize);
+
+        Ok(DecodedChunk {
+            data,
+            subsets_offsets,
+        })
+    }
+
+    fn get_chunk(&self, chunk_index: usize) -> Result<Arc<DecodedChunk>, ColormapError> {
+        {
+            let mut cached_chunks = self.cached_chunks.lock();
+            if let Some(position) = cached_chunks.iter().position(|(i, _)| *i == chunk_index) {
+                let entry = cached_chunks.remove(position).unwrap();
+                let chunk = entry.1.clone();
+                cached_chunks.push_front(entry);
+                return Ok(chunk);
+            }
+        }
+
+        // Decode without holding the lock, so that other threads can read the cached chunks
+        let chunk = Arc::new(self.decode_chunk(chunk_index)?);
+
+        if self.cache_capacity > 0 {
+            let mut cached_chunks = self.cached_chunks.lock();
+            if !cached_chunks.iter().any(|(i, _)| *i == chunk_index) {
+                if cached_chunks.len() >= self.cache_capacity {
+                    cached_chunks.pop_back();
+                }
+                cached_chunks.push_front((chunk_index, chunk.clone()));
+            }
+        }
+        Ok(chunk)
+    }
+
+    fn decode_subset(
+        &self,
+        chunk: &DecodedChunk,
+        chunk_start: ColorIndexType,
+        subset: ColorIndexType,
+        out_vec: &mut Vec<ColorIndexType>,
+    ) -> Result<(), ColormapError> {
+        let local_index = (subset - chunk_start) as usize;
+        let subset_data =
+            &chunk.data[chunk.subsets_offsets[local_index]..chunk.subsets_offsets[local_index + 1]];
+        self.encoding
+            .decode_color(subset_data, Some(out_vec))
+            .ok_or(ColormapError::InvalidSubset { subset })
+    }
+
+    /// Decodes the colors of a single subset, returning an error if the subset is not in the colormap
+    /// or if its chunk is corrupted
+    pub fn get_color_mappings(
+        &self,
+        subset: ColorIndexType,
+        out_vec: &mut Vec<ColorIndexType>,
+    ) -> Result<(), ColormapError> {
+        let chunk_index = self.find_chunk(subset)?;
+        let chunk = self.get_chunk(chunk_index)?;
+        self.decode_subset(
+            &chunk,
+            self.layout.index.pairs[chunk_index].start_index,
+            subset,
+            out_vec,
+        )
+    }
+
+    /// Decodes the colors of many subsets, in any order, decompressing each needed chunk only once.
+    /// The results are returned in the same order of the requested subsets
+    pub fn get_many_color_mappings(
+        &self,
+        subsets: &[ColorIndexType],
+    ) -> Result<Vec<Vec<ColorIndexType>>, ColormapError> {
+        let mut requests = subsets
+            .iter()
+            .enumerate()
+            .map(|(position, subset)| Ok((self.find_chunk(*subset)?, *subset, position)))
+            .collect::<Result<Vec<_>, ColormapError>>()?;
+        requests.sort_unstable();
+
+        let mut results = vec![Vec::new(); subsets.len()];
+
+        for chunk_requests in requests.group_by(|a, b| a.0 == b.0) {
+            let chunk_index = chunk_requests[0].0;
+            let chunk = self.get_chunk(chunk_index)?;
+            let chunk_start = self.layout.index.pairs[chunk_index].start_index;
+
+            for (_, subset, position) in chunk_requests {
+                self.decode_subset(&chunk, chunk_start, *subset, &mut results[*position])?;
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+impl ColorMapReader for ColorsMmapReader {
+    fn get_color_name(&self, index: ColorIndexType, json_escaped: bool) -> Cow<'_, str> {
+        self.color_names.get_name(index, json_escaped)
+    }
+
+    fn get_color_metadata(&self, index: ColorIndexType) -> Option<&str> {
+        self.color_names.metadata(index)
+    }
+
+    fn colors_count(&self) -> usize {
+        self.color_names.len()
+    }
+
+    fn colors_subsets_count(&self) -> u64 {
+        self.layout.index.subsets_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColorsMmapReader;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::format::ColormapError;
+    use crate::storage::roaring::RoaringColorsSerializer;
+    use crate::storage::run_length::RunLengthColorsSerializer;
+    use crate::storage::test_utils::{test_color_names, write_test_colormap, TempFile};
+    use crate::storage::ColorsSerializerTrait;
+    use config::ColorIndexType;
+
+    fn random_access_reading<S: ColorsSerializerTrait>() {
+        let file = TempFile::new("mmap-reader.colors.dat");
+        let color_names = test_color_names(100);
+
+        // Enough subsets to be split in multiple chunks
+        let subsets: Vec<Vec<ColorIndexType>> = (0..50000u32)
+            .map(|i| (0..100).filter(|c| (i + c) % (i % 7 + 2) == 0).collect())
+            .filter(|s: &Vec<_>| !s.is_empty())
+            .collect();
+
+        let indexes = write_test_colormap::<S>(&file, &color_names, &[], &subsets);
+
+        let reader = ColorsMmapReader::new(&file, true, 2).unwrap();
+        let mut colors = Vec::new();
+        for position in (0..subsets.len()).rev().step_by(97) {
+            reader
+                .get_color_mappings(indexes[position], &mut colors)
+                .unwrap();
+            assert_eq!(colors, subsets[position]);
+        }
+
+        let positions: Vec<_> = (0..subsets.len()).step_by(13).collect();
+        let requested: Vec<_> = positions.iter().map(|p| indexes[*p]).collect();
+        for (position, colors) in positions
+            .iter()
+            .zip(reader.get_many_color_mappings(&requested).unwrap())
+        {
+            assert_eq!(colors, subsets[*position]);
+        }
+
+        let subsets_count = subsets.len() as ColorIndexType;
+        assert!(matches!(
+            reader.get_color_mappings(subsets_count, &mut colors),
+            Err(ColormapError::SubsetOutOfRange { .. })
+        ));
+        assert!(reader
+            .get_many_color_mappings(&[0, subsets_count + 10])
+            .is_err());
+
+        // Corrupt the data of the last chunk, that is not cached yet
+        drop(reader);
+        let last_chunk = ColorsDeserializer::new(&file, false)
+            .chunks_info()
+            .pop()
+            .unwrap();
+        let mut data = std::fs::read(&file).unwrap();
+        data[last_chunk.file_offset as usize + 4] ^= 0xFF;
+        std::fs::write(&file, data).unwrap();
+
+        let reader = ColorsMmapReader::new(&file, false, 2).unwrap();
+        assert!(matches!(
+            reader.get_color_mappings(last_chunk.subsets.start, &mut colors),
+            Err(ColormapError::ChecksumMismatch(_))
+        ));
+        assert!(reader.get_color_mappings(0, &mut colors).is_ok());
+    }
+
+    #[test]
+    fn mmap_reader_random_access() {
+        random_access_reading::<RunLengthColorsSerializer>();
+        random_access_reading::<RoaringColorsSerializer>();
+    }
+}
@@ -0,0 +1,314 @@
+use crate::storage::format::{ColormapError, ColormapLayout};
+use config::ColorIndexType;
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::io::{Cursor, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Escapes a string to be written inside a json string literal
+pub fn json_escape(value: &str) -> Cow<'_, str> {
+    if !value
+        .chars()
+        .any(|c| c == '"' || c == '\\' || c.is_control())
+    {
+        return Cow::Borrowed(value);
+    }
+
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Writes a table of strings in the indexed format of the color names section, used starting from the version 2
+/// of the format: the count of strings, the end offset of each string in the data and then the concatenated
+/// utf-8 strings, so that each one can be read without decoding the others. The integers are u64 little endian
+pub(crate) fn write_strings_table(
+    mut writer: impl Write,
+    strings: &[String],
+) -> std::io::Result<()> {
+    writer.write_all(&(strings.len() as u64).to_le_bytes())?;
+    let mut offset = 0u64;
+    for string in strings {
+        offset += string.len() as u64;
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    for string in strings {
+        writer.write_all(string.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u64(section: &[u8], position: usize) -> Result<u64, ColormapError> {
+    section
+        .get(position..position + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| ColormapError::InvalidColorNames("the names table is truncated".to_string()))
+}
+
+/// Position of a strings table inside the color names section
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct StringsTable {
+    count: usize,
+    offsets_start: usize,
+    data_start: usize,
+    end: usize,
+}
+
+impl StringsTable {
+    /// Reads the header of a strings table starting at the given position of the section,
+    /// checking that the whole table fits in the section
+    pub fn parse(section: &[u8], start: usize) -> Result<Self, ColormapError> {
+        let count = read_u64(section, start)? as usize;
+        let offsets_start = start + 8;
+
+        let data_start = count
+            .checked_mul(8)
+            .and_then(|size| size.checked_add(offsets_start))
+            .filter(|data_start| *data_start <= section.len())
+            .ok_or_else(|| {
+                ColormapError::InvalidColorNames(format!(
+                    "the offsets of {} strings do not fit in the names section",
+                    count
+                ))
+            })?;
+
+        let data_size = match count {
+            0 => 0,
+            _ => read_u64(section, data_start - 8)? as usize,
+        };
+
+        let end = data_start
+            .checked_add(data_size)
+            .filter(|end| *end <= section.len())
+            .ok_or_else(|| {
+                ColormapError::InvalidColorNames(format!(
+                    "{} bytes of strings do not fit in the names section",
+                    data_size
+                ))
+            })?;
+
+        Ok(Self {
+            count,
+            offsets_start,
+            data_start,
+            end,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Position of the first byte after the table
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get<'a>(&self, section: &'a [u8], index: usize) -> Result<&'a str, ColormapError> {
+        assert!(index < self.count);
+
+        let start = match index {
+            0 => 0,
+            _ => read_u64(section, self.offsets_start + (index - 1) * 8)? as usize,
+        };
+        let end = read_u64(section, self.offsets_start + index * 8)? as usize;
+
+        if start > end || end > self.end - self.data_start {
+            return Err(ColormapError::InvalidColorNames(format!(
+                "the string {} has invalid offsets {}..{}",
+                index, start, end
+            )));
+        }
+
+        std::str::from_utf8(&section[self.data_start + start..self.data_start + end]).map_err(|e| {
+            ColormapError::InvalidColorNames(format!(
+                "the string {} is not valid utf-8: {}",
+                index, e
+            ))
+        })
+    }
+}
+
+enum ColorNamesData {
+    /// Names decoded at once from the compressed section of the colormaps of version 1
+    Decoded {
+        names: Vec<String>,
+        metadata: Vec<String>,
+    },
+    /// Names read on demand from the indexed section of the memory mapped colormap
+    Indexed {
+        mmap: Arc<Mmap>,
+        section: Range<usize>,
+        names: StringsTable,
+        metadata: StringsTable,
+    },
+}
+
+/// Names and metadata of the colors of a colormap. Starting from the version 2 of the format they are read lazily
+/// from the memory mapped file, so opening a colormap with millions of colors does not decode all of them
+pub(crate) struct ColorNamesTable {
+    path: PathBuf,
+    data: ColorNamesData,
+}
+
+impl ColorNamesTable {
+    /// Table without any color, used when the color names are not requested
+    pub fn empty() -> Self {
+        Self {
+            path: PathBuf::new(),
+            data: ColorNamesData::Decoded {
+                names: Vec::new(),
+                metadata: Vec::new(),
+            },
+        }
+    }
+
+    pub fn open(
+        path: &Path,
+        layout: &ColormapLayout,
+        mmap: Arc<Mmap>,
+    ) -> Result<Self, ColormapError> {
+        let data = if layout.has_indexed_color_names() {
+            let section = layout.names_section();
+            let section = section.start as usize..section.end as usize;
+            let (names, metadata) = layout.parse_color_names_tables(&mmap[section.clone()])?;
+            ColorNamesData::Indexed {
+                mmap,
+                section,
+                names,
+                metadata,
+            }
+        } else {
+            let (names, metadata) = layout.read_color_names(Cursor::new(&mmap[..]))?;
+            ColorNamesData::Decoded { names, metadata }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            data,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.data {
+            ColorNamesData::Decoded { names, .. } => names.len(),
+            ColorNamesData::Indexed { names, .. } => names.len(),
+        }
+    }
+
+    fn get_indexed<'a>(
+        &self,
+        mmap: &'a Mmap,
+        section: &Range<usize>,
+        table: &StringsTable,
+        index: usize,
+    ) -> &'a str {
+        table
+            .get(&mmap[section.clone()], index)
+            .unwrap_or_else(|error| panic!("Corrupted colormap {}: {}", self.path.display(), error))
+    }
+
+    pub fn name(&self, index: ColorIndexType) -> &str {
+        match &self.data {
+            ColorNamesData::Decoded { names, .. } => &names[index as usize],
+            ColorNamesData::Indexed {
+                mmap,
+                section,
+                names,
+                ..
+            } => self.get_indexed(mmap, section, names, index as usize),
+        }
+    }
+
+    /// The metadata of the color, if it was set
+    pub fn metadata(&self, index: ColorIndexType) -> Option<&str> {
+        let metadata = match &self.data {
+            ColorNamesData::Decoded { metadata, .. } => {
+                metadata.get(index as usize).map(|m| m.as_str())
+            }
+            ColorNamesData::Indexed {
+                mmap,
+                section,
+                metadata,
+                ..
+            } => ((index as usize) < metadata.len())
+                .then(|| self.get_indexed(mmap, section, metadata, index as usize)),
+        };
+        metadata.filter(|metadata| !metadata.is_empty())
+    }
+
+    pub fn get_name(&self, index: ColorIndexType, json_escaped: bool) -> Cow<'_, str> {
+        let name = self.name(index);
+        if json_escaped {
+            json_escape(name)
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_escape;
+    use crate::colors_manager::ColorMapReader;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::mmap_reader::{ColorsMmapReader, DEFAULT_CACHED_CHUNKS};
+    use crate::storage::run_length::RunLengthColorsSerializer;
+    use crate::storage::test_utils::{test_color_names, write_test_colormap, TempFile};
+    use config::ColorIndexType;
+
+    #[test]
+    fn indexed_color_names() {
+        assert_eq!(json_escape("sample.fa"), "sample.fa");
+        assert_eq!(json_escape("a\\\"b\""), "a\\\\\\\"b\\\"");
+        assert_eq!(json_escape("dir\\name\n"), "dir\\\\name\\n");
+
+        let file = TempFile::new("names-table.colors.dat");
+        let mut color_names = test_color_names(1000);
+        color_names[1] = String::new();
+        color_names[2] = "C:\\genomes\\\"quoted\".fa".to_string();
+        let mut colors_metadata = vec![String::new(); color_names.len()];
+        colors_metadata[999] = "{\"id\":999}".to_string();
+
+        let subsets: Vec<Vec<ColorIndexType>> = (0..100u32).map(|i| vec![i, i + 1]).collect();
+        write_test_colormap::<RunLengthColorsSerializer>(
+            &file,
+            &color_names,
+            &colors_metadata,
+            &subsets,
+        );
+
+        let deserializer = ColorsDeserializer::new(&file, true);
+        let reader = ColorsMmapReader::new(&file, true, DEFAULT_CACHED_CHUNKS).unwrap();
+        let readers: [&dyn ColorMapReader; 2] = [&deserializer, &reader];
+
+        for reader in readers {
+            assert_eq!(reader.colors_count(), color_names.len());
+            for (index, name) in color_names.iter().enumerate().rev() {
+                assert_eq!(reader.get_color_name(index as _, false), name.as_str());
+            }
+            assert_eq!(
+                reader.get_color_name(2, true),
+                "C:\\\\genomes\\\\\\\"quoted\\\".fa"
+            );
+            assert_eq!(reader.get_color_metadata(999), Some("{\"id\":999}"));
+            assert_eq!(reader.get_color_metadata(998), None);
+        }
+    }
+}
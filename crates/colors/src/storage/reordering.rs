@@ -0,0 +1,246 @@
+use crate::colors_manager::ColorMapReader;
+use crate::storage::deserializer::ColorsDeserializer;
+use crate::storage::serializer::rewrite_colormap;
+use config::ColorIndexType;
+use hashbrown::HashMap;
+use std::path::Path;
+
+/// Number of minhash values computed for each color over the subsets containing it
+const SIGNATURE_SIZE: usize = 16;
+/// Consecutive minhash values that must all match to consider two colors as neighbour candidates
+const BAND_SIZE: usize = 2;
+/// Maximum number of not yet placed candidates evaluated at each step of the greedy ordering
+const MAX_EVALUATED_CANDIDATES: usize = 256;
+
+/// Sizes of a colormap before and after reordering its colors
+#[derive(Copy, Clone, Debug)]
+pub struct ColormapReorderingStats {
+    pub colors_count: usize,
+    pub subsets_count: u64,
+    pub original_size: u64,
+    pub reordered_size: u64,
+}
+
+impl ColormapReorderingStats {
+    /// Size reduction of the colormap, as a fraction of the original size (negative if it grew)
+    pub fn size_reduction(&self) -> f64 {
+        1.0 - (self.reordered_size as f64 / self.original_size.max(1) as f64)
+    }
+}
+
+#[inline(always)]
+fn subset_hash(subset: ColorIndexType, seed: usize) -> u64 {
+    // splitmix64 finalizer
+    let mut x = (subset as u64) ^ ((seed as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15));
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+/// Computes a new order of the colors that places next to each other the colors that appear in similar subsets,
+/// with a greedy nearest neighbour walk over their minhash signatures.
+/// Returns for each old color index its new index
+pub fn compute_similarity_order(
+    colors_count: usize,
+    subsets: impl Iterator<Item = (ColorIndexType, Vec<ColorIndexType>)>,
+) -> Vec<ColorIndexType> {
+    let mut signatures = vec![[u64::MAX; SIGNATURE_SIZE]; colors_count];
+    let mut frequencies = vec![0u64; colors_count];
+
+    for (subset, colors) in subsets {
+        let hashes: [u64; SIGNATURE_SIZE] = std::array::from_fn(|seed| subset_hash(subset, seed));
+        for color in colors {
+            let signature = &mut signatures[color as usize];
+            for (value, hash) in signature.iter_mut().zip(hashes.iter()) {
+                *value = (*value).min(*hash);
+            }
+            frequencies[color as usize] += 1;
+        }
+    }
+
+    let similarity = |a: usize, b: usize| {
+        signatures[a]
+            .iter()
+            .zip(signatures[b].iter())
+            .filter(|(x, y)| x == y)
+            .count()
+    };
+
+    // Colors sharing all the values of a band are likely to be similar
+    let mut buckets: HashMap<(usize, &[u64]), Vec<usize>> = HashMap::new();
+    for (color, signature) in signatures.iter().enumerate() {
+        for (band, values) in signature.chunks(BAND_SIZE).enumerate() {
+            buckets.entry((band, values)).or_default().push(color);
+        }
+    }
+
+    // Fallback order used when no candidate is left, groups the colors with equal signature prefixes
+    let mut fallback_order: Vec<usize> = (0..colors_count).collect();
+    fallback_order.sort_by(|a, b| {
+        signatures[*a]
+            .cmp(&signatures[*b])
+            .then(frequencies[*b].cmp(&frequencies[*a]))
+    });
+    let mut fallback_position = 0;
+
+    let mut new_indexes = vec![0; colors_count];
+    let mut placed = vec![false; colors_count];
+
+    let mut current = match (0..colors_count).max_by_key(|c| (frequencies[*c], colors_count - *c)) {
+        None => return new_indexes,
+        Some(first) => first,
+    };
+
+    for new_index in 0..colors_count {
+        placed[current] = true;
+        new_indexes[current] = new_index as ColorIndexType;
+
+        if new_index + 1 == colors_count {
+            break;
+        }
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut evaluated = 0;
+
+        'bands: for (band, values) in signatures[current].chunks(BAND_SIZE).enumerate() {
+            let bucket = buckets.get_mut(&(band, values)).unwrap();
+            let mut position = 0;
+            while position < bucket.len() {
+                let candidate = bucket[position];
+                if placed[candidate] {
+                    // Each color is removed at most once from each bucket
+                    bucket.swap_remove(position);
+                    continue;
+                }
+                position += 1;
+
+                let score = similarity(current, candidate);
+                if best
+                    .map(|(_, best_score)| score > best_score)
+                    .unwrap_or(true)
+                {
+                    best = Some((candidate, score));
+                }
+
+                evaluated += 1;
+                if evaluated >= MAX_EVALUATED_CANDIDATES || score == SIGNATURE_SIZE {
+                    break 'bands;
+                }
+            }
+        }
+
+        current = match best {
+            Some((candidate, _)) => candidate,
+            None => {
+                while placed[fallback_order[fallback_position]] {
+                    fallback_position += 1;
+                }
+                fallback_order[fallback_position]
+            }
+        };
+    }
+
+    new_indexes
+}
+
+/// Reorders the colors of a colormap by similarity to improve its compression, writing the result to output.
+/// The subsets keep their indexes, so the graph referencing them does not change, while the color indexes
+/// and the color names are permuted consistently. The output keeps the encoding of the input colormap
+pub fn reorder_colormap(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+) -> ColormapReorderingStats {
+    let input = input.as_ref();
+    let output = output.as_ref();
+
+    let mut deserializer = ColorsDeserializer::new(input, true);
+    let colors_count = deserializer.colors_count();
+    let subsets_count = deserializer.colors_subsets_count();
+
+    let new_indexes = compute_similarity_order(
+        colors_count,
+        (0..subsets_count as ColorIndexType).map(|subset| {
+            let mut colors = Vec::new();
+            deserializer.get_color_mappings(subset, &mut colors);
+            (subset, colors)
+        }),
+    );
+
+    let mut color_names = vec![String::new(); colors_count];
+    for (old_index, new_index) in new_indexes.iter().enumerate() {
+        color_names[*new_index as usize] = deserializer
+            .get_color_name(old_index as ColorIndexType, false)
+            .to_string();
+    }
+
+    let mut colors_metadata = vec![String::new(); colors_count];
+    for (old_index, new_index) in new_indexes.iter().enumerate() {
+        if let Some(metadata) = deserializer.get_color_metadata(old_index as ColorIndexType) {
+            colors_metadata[*new_index as usize] = metadata.to_string();
+        }
+    }
+
+    rewrite_colormap(
+        deserializer,
+        output,
+        &color_names,
+        &colors_metadata,
+        |color| new_indexes[color as usize],
+    );
+
+    ColormapReorderingStats {
+        colors_count,
+        subsets_count,
+        original_size: std::fs::metadata(input).unwrap().len(),
+        reordered_size: std::fs::metadata(output).unwrap().len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reorder_colormap;
+    use crate::colors_manager::ColorMapReader;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::run_length::RunLengthColorsSerializer;
+    use crate::storage::test_utils::{test_color_names, write_test_colormap, TempFile};
+    use config::ColorIndexType;
+
+    #[test]
+    fn reordering_preserves_subsets() {
+        let input = TempFile::new("reorder-input.colors.dat");
+        let output = TempFile::new("reorder-output.colors.dat");
+
+        // Interleaved colors of two groups, that always appear together in the same group
+        let color_names = test_color_names(64);
+        let subsets: Vec<Vec<ColorIndexType>> = (0..30000u32)
+            .map(|i| {
+                let group = i % 2;
+                (0..64)
+                    .filter(|c| c % 2 == group && (c / 2 + i) % 3 != 0)
+                    .collect()
+            })
+            .collect();
+
+        write_test_colormap::<RunLengthColorsSerializer>(&input, &color_names, &[], &subsets);
+
+        let stats = reorder_colormap(&input, &output);
+        assert_eq!(stats.subsets_count, subsets.len() as u64);
+
+        let mut reordered = ColorsDeserializer::new(&output, true);
+        let mut colors = Vec::new();
+        for (index, subset) in subsets.iter().enumerate() {
+            reordered.get_color_mappings(index as ColorIndexType, &mut colors);
+            let mut names: Vec<_> = colors
+                .iter()
+                .map(|c| reordered.get_color_name(*c, false).to_string())
+                .collect();
+            names.sort();
+            let mut expected: Vec<_> = subset
+                .iter()
+                .map(|c| color_names[*c as usize].clone())
+                .collect();
+            expected.sort();
+            assert_eq!(names, expected);
+        }
+    }
+}
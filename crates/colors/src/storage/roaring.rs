@@ -1,155 +1,165 @@
-#![allow(warnings)]
+use crate::async_slice_queue::AsyncSliceQueue;
 use crate::storage::serializer::ColorsFlushProcessing;
 use crate::storage::ColorsSerializerTrait;
+use byteorder::ReadBytesExt;
 use config::ColorIndexType;
-use io::chunks_writer::ChunksWriter;
-use parking_lot::Mutex;
+use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use io::varint::{decode_varint, encode_varint};
+use log::info;
 use roaring::RoaringBitmap;
-use std::io::Read;
-use std::sync::atomic::{AtomicU32, Ordering};
-
-struct RoaringBitmapInstance {
-    bitmap: RoaringBitmap,
-    offset: ColorIndexType,
-    colors_count: u64,
-    checkpoint_distance: u64,
-    stride: ColorIndexType,
-    last_color: ColorIndexType,
-}
-
-impl RoaringBitmapInstance {
-    fn new(
-        colors_count: u64,
-        checkpoint_distance: u64,
-        offset: ColorIndexType,
-        stride: ColorIndexType,
-    ) -> Self {
-        todo!("Fix meaning of 'stride'!");
-        Self {
-            bitmap: RoaringBitmap::new(),
-            offset,
-            colors_count,
-            checkpoint_distance,
-            stride,
-            last_color: 0,
-        }
+use std::io::{Read, Write};
+
+/// Encodes each colors subset as a serialized roaring bitmap, prefixed by its size to allow skipping it
+/// without decoding. Dense subsets with many colors are much smaller than with the run-length encoding
+pub struct RoaringColorIndexSerializer;
+impl RoaringColorIndexSerializer {
+    pub fn serialize_colors(mut writer: impl Write, colors: &[ColorIndexType]) {
+        let bitmap = RoaringBitmap::from_sorted_iter(colors.iter().copied())
+            .expect("The colors subset must be sorted and without duplicates");
+
+        encode_varint(|b| writer.write_all(b), bitmap.serialized_size() as u64).unwrap();
+        bitmap.serialize_into(writer).unwrap();
     }
 
-    fn try_append(
-        &mut self,
-        color_index: ColorIndexType,
-        colors: impl Iterator<Item = ColorIndexType>,
-        writer: &ColorsFlushProcessing,
-    ) -> bool {
-        let base_color = color_index - self.offset;
-
-        // Another append is in queue and the current is not the first one
-        if base_color > self.last_color + self.stride {
-            return false;
-        }
-
-        self.last_color = base_color;
-
-        assert_eq!(base_color % self.stride, 0);
-        let strided_color = base_color / self.stride;
-
-        let local_position = strided_color * (self.colors_count as u32);
-
-        self.bitmap
-            .append(colors.map(|c| local_position + c))
-            .unwrap();
-
-        // Flush the partial bitmap
-        if strided_color >= self.checkpoint_distance as u32 {
-            println!("Flushing with offset: {}", self.offset);
-            self.flush(writer);
-        }
-
-        true
+    pub fn skip_colors(mut reader: impl Read) -> Option<()> {
+        let size = decode_varint(|| reader.read_u8().ok())?;
+        let skipped = std::io::copy(&mut (&mut reader).take(size), &mut std::io::sink()).ok()?;
+        (skipped == size).then_some(())
     }
 
-    fn flush(&mut self, writer: &ColorsFlushProcessing) {
-        let mut pdata = writer.start_processing();
-        self.bitmap
-            .serialize_into(writer.get_stream(&mut pdata))
-            .unwrap();
-        writer.end_processing(pdata, self.offset);
-        self.offset += self.last_color;
-        self.last_color = 0;
-        self.bitmap.clear();
+    pub fn deserialize_colors(
+        mut reader: impl Read,
+        colors: &mut Vec<ColorIndexType>,
+    ) -> Option<()> {
+        let size = decode_varint(|| reader.read_u8().ok())?;
+        let bitmap = RoaringBitmap::deserialize_from((&mut reader).take(size)).ok()?;
+
+        colors.clear();
+        colors.extend(bitmap.iter());
+        Some(())
     }
 }
 
 pub struct RoaringColorsSerializer {
+    async_buffer: AsyncSliceQueue<u8, ColorsFlushProcessing>,
     colors_count: u64,
-    roaring_bitmaps: Vec<Mutex<RoaringBitmapInstance>>,
-    writer: ColorsFlushProcessing,
-    colors_index: AtomicU32,
 }
 
+#[thread_local]
+static mut TEMP_BITMAP_BUFFER: Vec<u8> = Vec::new();
+
 impl ColorsSerializerTrait for RoaringColorsSerializer {
     const MAGIC: [u8; 16] = *b"GGCAT_CMAP_ROARG";
 
-    // FIXME: Implement!
-    fn decode_color(_reader: impl Read, _out_vec: Option<&mut Vec<u32>>) {
-        todo!()
+    fn decode_color(mut reader: impl Read, out_vec: Option<&mut Vec<u32>>) -> Option<()> {
+        match out_vec {
+            None => RoaringColorIndexSerializer::skip_colors(&mut reader),
+            Some(out_vec) => {
+                let result = RoaringColorIndexSerializer::deserialize_colors(&mut reader, out_vec);
+                if result.is_none() {
+                    out_vec.clear();
+                }
+                result
+            }
+        }
     }
 
     fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, colors_count: u64) -> Self {
-        todo!("Fix meaning of 'stride'!");
-        let stride = rayon::current_num_threads() as ColorIndexType;
-
         Self {
-            roaring_bitmaps: (0..stride)
-                .map(|off| {
-                    Mutex::new(RoaringBitmapInstance::new(
-                        colors_count,
-                        checkpoint_distance as u64,
-                        off,
-                        stride,
-                    ))
-                })
-                .collect(),
-            writer,
-            colors_index: AtomicU32::new(0),
+            async_buffer: AsyncSliceQueue::new(
+                DEFAULT_OUTPUT_BUFFER_SIZE,
+                rayon::current_num_threads(),
+                checkpoint_distance,
+                writer,
+            ),
             colors_count,
         }
     }
 
     fn serialize_colors(&self, colors: &[ColorIndexType]) -> ColorIndexType {
-        let color_index = self.colors_index.fetch_add(1, Ordering::Relaxed);
-
-        let target_bitmap = color_index % self.roaring_bitmaps.len() as ColorIndexType;
-
-        loop {
-            let mut bitmap_lock = self.roaring_bitmaps[target_bitmap as usize].lock();
-            if bitmap_lock.try_append(color_index, colors.iter().copied(), &self.writer) {
-                break;
-            }
-            drop(bitmap_lock);
-            std::thread::yield_now();
+        unsafe {
+            TEMP_BITMAP_BUFFER.clear();
+            RoaringColorIndexSerializer::serialize_colors(&mut TEMP_BITMAP_BUFFER, colors);
+            self.async_buffer.add_data(TEMP_BITMAP_BUFFER.as_slice()) as ColorIndexType
         }
-
-        color_index
     }
 
     fn get_subsets_count(&self) -> u64 {
-        self.colors_index.load(Ordering::Relaxed) as u64
+        self.async_buffer.get_counter()
     }
 
     fn print_stats(&self) {
-        println!(
-            "Subsets count: {} witn {} colors",
+        info!(
+            "Total color subsets: {} with {} colors (roaring encoding)",
             self.get_subsets_count(),
             self.colors_count
         );
     }
 
-    fn finalize(mut self) -> ColorsFlushProcessing {
-        for bitmap in self.roaring_bitmaps {
-            bitmap.lock().flush(&mut self.writer);
+    fn finalize(self) -> ColorsFlushProcessing {
+        self.async_buffer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RoaringColorIndexSerializer, RoaringColorsSerializer};
+    use crate::storage::ColorsSerializerTrait;
+    use config::ColorIndexType;
+    use std::io::Cursor;
+
+    fn color_subset_encoding(subsets: &[&[ColorIndexType]]) {
+        let mut buffer = Vec::new();
+
+        for colors in subsets {
+            RoaringColorIndexSerializer::serialize_colors(&mut buffer, colors);
         }
 
-        self.writer
+        let mut cursor = Cursor::new(buffer);
+        let mut des_colors = Vec::new();
+
+        for (index, colors) in subsets.iter().enumerate() {
+            // Skip the odd subsets, checking that the next ones are still decoded correctly
+            if index % 2 == 1 {
+                RoaringColorIndexSerializer::skip_colors(&mut cursor).unwrap();
+            } else {
+                RoaringColorIndexSerializer::deserialize_colors(&mut cursor, &mut des_colors)
+                    .unwrap();
+                assert_eq!(*colors, des_colors.as_slice());
+            }
+        }
+    }
+
+    #[test]
+    fn roaring_subset_encoding_test() {
+        let dense: Vec<_> = (0..50000).filter(|c| c % 7 != 0).collect();
+        let sparse: Vec<_> = (0..200).map(|c| c * 1000 + 3).collect();
+
+        color_subset_encoding(&[
+            &[0],
+            &[1, 2, 5, 10, 15, 30, 45],
+            &dense,
+            &[70000, 70001, 140000],
+            &sparse,
+            &[3, 6, 9, 12, 62, 63, 64, 70, 71, 88, 95, 100],
+        ]);
+    }
+
+    #[test]
+    fn roaring_invalid_subset() {
+        let mut buffer = Vec::new();
+        RoaringColorIndexSerializer::serialize_colors(&mut buffer, &[1, 5, 9, 4000]);
+        let truncated = &buffer[..buffer.len() - 3];
+
+        // The colors of the previous subset must not be returned when the decoding fails
+        let mut colors = vec![7, 8];
+        assert!(RoaringColorsSerializer::decode_color(truncated, Some(&mut colors)).is_none());
+        assert!(colors.is_empty());
+        assert!(RoaringColorsSerializer::decode_color(truncated, None).is_none());
+
+        assert!(
+            RoaringColorsSerializer::decode_color(buffer.as_slice(), Some(&mut colors)).is_some()
+        );
+        assert_eq!(colors, vec![1, 5, 9, 4000]);
     }
 }
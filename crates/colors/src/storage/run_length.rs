@@ -5,6 +5,7 @@ use byteorder::ReadBytesExt;
 use config::ColorIndexType;
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
 use io::varint::{decode_varint, encode_varint};
+use log::{debug, info};
 use std::io::{Read, Write};
 
 pub struct ColorIndexSerializer;
@@ -63,7 +64,7 @@ impl ColorIndexSerializer {
         mut reader: impl Read,
         mut add_color: impl FnMut(ColorIndexType),
     ) -> Option<()> {
-        add_color((decode_varint(|| reader.read_u8().ok())? - 2) as ColorIndexType);
+        add_color(decode_varint(|| reader.read_u8().ok())?.checked_sub(2)? as ColorIndexType);
         loop {
             let result = decode_varint(|| reader.read_u8().ok())? as ColorIndexType;
             if result == 0 {
@@ -107,13 +108,15 @@ static mut TEMP_COLOR_BUFFER: Vec<u8> = Vec::new();
 impl ColorsSerializerTrait for RunLengthColorsSerializer {
     const MAGIC: [u8; 16] = *b"GGCAT_CMAP_RNLEN";
 
-    fn decode_color(mut reader: impl Read, out_vec: Option<&mut Vec<u32>>) {
+    fn decode_color(mut reader: impl Read, out_vec: Option<&mut Vec<u32>>) -> Option<()> {
         match out_vec {
-            None => {
-                ColorIndexSerializer::deserialize_colors_diffs(&mut reader, |_| {});
-            }
+            None => ColorIndexSerializer::deserialize_colors_diffs(&mut reader, |_| {}),
             Some(out_vec) => {
-                ColorIndexSerializer::deserialize_colors(&mut reader, out_vec);
+                let result = ColorIndexSerializer::deserialize_colors(&mut reader, out_vec);
+                if result.is_none() {
+                    out_vec.clear();
+                }
+                result
             }
         }
     }
@@ -142,7 +145,7 @@ impl ColorsSerializerTrait for RunLengthColorsSerializer {
     }
 
     fn print_stats(&self) {
-        println!("Total color subsets: {}", self.async_buffer.get_counter())
+        info!("Total color subsets: {}", self.async_buffer.get_counter())
     }
 
     fn finalize(self) -> ColorsFlushProcessing {
@@ -161,7 +164,7 @@ mod tests {
 
         ColorIndexSerializer::serialize_colors(&mut buffer, colors);
 
-        println!("Buffer size: {}", buffer.len());
+        debug!("Buffer size: {}", buffer.len());
         let mut cursor = Cursor::new(buffer);
 
         let mut des_colors = Vec::new();
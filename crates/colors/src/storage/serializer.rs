@@ -1,4 +1,9 @@
-use crate::storage::ColorsSerializerTrait;
+use crate::colors_manager::ColorMapReader;
+use crate::storage::deserializer::ColorsDeserializer;
+use crate::storage::names_table::write_strings_table;
+use crate::storage::roaring::RoaringColorsSerializer;
+use crate::storage::run_length::RunLengthColorsSerializer;
+use crate::storage::{ColormapEncoding, ColorsSerializerTrait};
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
 use config::{ColorIndexType, COLORS_SINGLE_BATCH_SIZE};
 use desse::{Desse, DesseSized};
@@ -12,7 +17,10 @@ use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-const STORAGE_VERSION: u64 = 1;
+/// Version of the colormap format written by this build
+pub const STORAGE_VERSION: u64 = 2;
+/// Oldest colormap format version that can still be read, older ones are not supported anymore
+pub const MIN_STORAGE_VERSION: u64 = 1;
 
 #[derive(Debug, Desse, DesseSized, Default)]
 pub(crate) struct ColorsFileHeader {
@@ -29,6 +37,21 @@ pub(crate) struct ColorsFileHeader {
 pub struct ColorsIndexEntry {
     pub start_index: ColorIndexType,
     pub file_offset: u64,
+    /// CRC32 of the compressed chunk data, zero in colormaps of version 1
+    pub checksum: u32,
+}
+
+/// Index entry of the version 1 colormaps, without the chunk checksum
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct ColorsIndexEntryV1 {
+    pub start_index: ColorIndexType,
+    pub file_offset: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ColorsIndexMapV1 {
+    pub pairs: Vec<ColorsIndexEntryV1>,
+    pub subsets_count: u64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -42,25 +65,42 @@ pub struct ColorsSerializer<SI: ColorsSerializerTrait> {
     serializer_impl: ManuallyDrop<SI>,
 }
 
+/// Writes the indexed color names section, with the table of the color names followed by the table
+/// of the metadata of each color (empty if there is no metadata for any color)
+pub(crate) fn write_colors_names_section(
+    colormap_file: File,
+    color_names: &[String],
+    colors_metadata: &[String],
+) -> std::io::Result<File> {
+    let mut writer = BufWriter::new(colormap_file);
+    write_strings_table(&mut writer, color_names)?;
+    if colors_metadata.iter().all(|metadata| metadata.is_empty()) {
+        write_strings_table(&mut writer, &[])?;
+    } else {
+        write_strings_table(&mut writer, colors_metadata)?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())
+}
+
 impl<SI: ColorsSerializerTrait> ColorsSerializer<SI> {
     pub fn new(file: impl AsRef<Path>, color_names: &[String]) -> Self {
+        Self::new_with_metadata(file, color_names, &[])
+    }
+
+    /// Creates a colormap storing also the metadata of each color, as a json string (empty if missing)
+    pub fn new_with_metadata(
+        file: impl AsRef<Path>,
+        color_names: &[String],
+        colors_metadata: &[String],
+    ) -> Self {
         let mut colormap_file = File::create(file).unwrap();
 
         colormap_file
             .write_all(&ColorsFileHeader::default().serialize()[..])
             .unwrap();
 
-        colormap_file = {
-            let mut color_names_stream = lz4::EncoderBuilder::new()
-                .level(4)
-                .build(colormap_file)
-                .unwrap();
-            bincode::serialize_into(&mut color_names_stream, color_names).unwrap();
-
-            let (cf, res) = color_names_stream.finish();
-            res.unwrap();
-            cf
-        };
+        colormap_file =
+            write_colors_names_section(colormap_file, color_names, colors_metadata).unwrap();
 
         let file_offset = colormap_file.stream_position().unwrap();
 
@@ -206,6 +246,59 @@ impl ChunksWriter for ColorsFlushProcessing {
         file_lock.1.pairs.push(ColorsIndexEntry {
             start_index,
             file_offset,
+            checksum: crc32fast::hash(&data),
         });
     }
 }
+
+fn rewrite_colormap_with<SI: ColorsSerializerTrait>(
+    mut deserializer: ColorsDeserializer,
+    output: &Path,
+    color_names: &[String],
+    colors_metadata: &[String],
+    map_color: impl Fn(ColorIndexType) -> ColorIndexType,
+) {
+    let serializer =
+        ColorsSerializer::<SI>::new_with_metadata(output, color_names, colors_metadata);
+
+    let mut colors = Vec::new();
+    for subset in 0..deserializer.colors_subsets_count() as ColorIndexType {
+        deserializer.get_color_mappings(subset, &mut colors);
+        for color in colors.iter_mut() {
+            *color = map_color(*color);
+        }
+        colors.sort_unstable();
+
+        // The subsets are written sequentially, so they keep the indexes referenced by the graph
+        let new_subset = serializer.serialize_colors(&colors);
+        assert_eq!(new_subset, subset);
+    }
+}
+
+/// Writes all the subsets of a colormap to a new file in the current format, with the same encoding,
+/// mapping each color with the given function. The subsets keep their indexes
+pub(crate) fn rewrite_colormap(
+    deserializer: ColorsDeserializer,
+    output: impl AsRef<Path>,
+    color_names: &[String],
+    colors_metadata: &[String],
+    map_color: impl Fn(ColorIndexType) -> ColorIndexType,
+) {
+    let output = output.as_ref();
+    match deserializer.encoding() {
+        ColormapEncoding::RunLength => rewrite_colormap_with::<RunLengthColorsSerializer>(
+            deserializer,
+            output,
+            color_names,
+            colors_metadata,
+            map_color,
+        ),
+        ColormapEncoding::Roaring => rewrite_colormap_with::<RoaringColorsSerializer>(
+            deserializer,
+            output,
+            color_names,
+            colors_metadata,
+            map_color,
+        ),
+    }
+}
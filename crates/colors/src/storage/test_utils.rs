@@ -0,0 +1,64 @@
+use crate::storage::serializer::ColorsSerializer;
+use crate::storage::ColorsSerializerTrait;
+use config::ColorIndexType;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_FILES_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Path of a temporary file unique to the test that created it, the file is removed on drop
+pub(crate) struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            path: std::env::temp_dir().join(format!(
+                "ggcat-test-{}-{}-{}",
+                std::process::id(),
+                TEMP_FILES_COUNTER.fetch_add(1, Ordering::Relaxed),
+                name
+            )),
+        }
+    }
+}
+
+impl Deref for TempFile {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempFile {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub(crate) fn test_color_names(count: usize) -> Vec<String> {
+    (0..count).map(|c| format!("color{}", c)).collect()
+}
+
+/// Writes a colormap with the given colors and subsets, returning the index assigned to each subset
+pub(crate) fn write_test_colormap<S: ColorsSerializerTrait>(
+    file: &Path,
+    color_names: &[String],
+    colors_metadata: &[String],
+    subsets: &[Vec<ColorIndexType>],
+) -> Vec<ColorIndexType> {
+    let serializer = ColorsSerializer::<S>::new_with_metadata(file, color_names, colors_metadata);
+    subsets
+        .iter()
+        .map(|subset| serializer.serialize_colors(subset))
+        .collect()
+}
@@ -0,0 +1,69 @@
+use crate::colors_manager::ColorMapReader;
+use crate::storage::deserializer::ColorsDeserializer;
+use crate::storage::format::{ColormapError, ColormapLayout};
+use crate::storage::serializer::{rewrite_colormap, STORAGE_VERSION};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Outcome of a colormap upgrade
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColormapUpgrade {
+    /// The colormap already uses the current format version, nothing was written
+    AlreadyCurrent,
+    /// The colormap was rewritten from the given format version to the current one
+    Upgraded { from_version: u64 },
+}
+
+/// Returns the format version of a colormap, also if it is not supported by this build
+pub fn colormap_version(file: impl AsRef<Path>) -> Result<u64, ColormapError> {
+    let file = BufReader::new(File::open(file)?);
+    match ColormapLayout::read(file) {
+        Ok(layout) => Ok(layout.header.version),
+        Err(ColormapError::UnsupportedVersion { version }) => Ok(version),
+        Err(error) => Err(error),
+    }
+}
+
+/// Converts a colormap written with an older format version to the current one, writing it to output.
+/// Each format change must keep ColormapLayout and ColorsDeserializer able to read the previous versions,
+/// so that they can be rewritten here with the current serializer (e.g. adding the chunks checksums to version 1)
+pub fn upgrade_colormap(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+) -> Result<ColormapUpgrade, ColormapError> {
+    let input = input.as_ref();
+
+    let from_version = colormap_version(input)?;
+    if from_version == STORAGE_VERSION {
+        return Ok(ColormapUpgrade::AlreadyCurrent);
+    }
+    if from_version > STORAGE_VERSION {
+        return Err(ColormapError::UnsupportedVersion {
+            version: from_version,
+        });
+    }
+
+    let deserializer = ColorsDeserializer::open(input, true)?;
+    let color_names: Vec<_> = (0..deserializer.colors_count())
+        .map(|color| deserializer.get_color_name(color as _, false).to_string())
+        .collect();
+    let colors_metadata: Vec<_> = (0..deserializer.colors_count())
+        .map(|color| {
+            deserializer
+                .get_color_metadata(color as _)
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect();
+
+    rewrite_colormap(
+        deserializer,
+        output,
+        &color_names,
+        &colors_metadata,
+        |color| color,
+    );
+
+    Ok(ColormapUpgrade::Upgraded { from_version })
+}
@@ -0,0 +1,43 @@
+//! Cooperative cancellation of the running operations. The executors skip the remaining buckets
+//! once the token of the current operation is cancelled (or the operation failed), while the pipelines drivers
+//! stop between the phases by unwinding with a `Cancelled` payload, that is converted to an error by the api.
+
+use crate::instance;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Token shared with the caller of an operation, that can be used to request its cancellation from any thread
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests the cancellation of the operations using this token
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Unwinding payload used to stop a cancelled operation
+#[derive(Debug)]
+pub struct Cancelled;
+
+/// Returns true if the current operation was cancelled
+#[inline]
+pub fn is_cancelled() -> bool {
+    instance::with_current(|context| context.is_cancelled())
+}
+
+/// Stops the current operation if it was cancelled, must be called only outside of the executors
+pub fn check_cancelled() {
+    if is_cancelled() {
+        std::panic::resume_unwind(Box::new(Cancelled));
+    }
+}
@@ -0,0 +1,377 @@
+//! Settings and runtime state owned by each GGCAT instance, so that different instances can run side by side.
+//! Each operation runs with its own context, created from the one of its instance, that holds its cancellation token
+//! and state, so that the operations running at the same time on an instance are also independent.
+//! The pipelines read the context from the current thread: the threads pool of each operation enters its context
+//! in all of its threads, while the executors threads created by the pipelines enter the context
+//! captured when the pipeline is started.
+
+use crate::cancellation::CancellationToken;
+use crate::memory_budget::MemoryBudget;
+use crate::progress::{ProgressCallback, ProgressState};
+use crate::MinimizerScheme;
+use parallel_processor::buckets::writers::compressed_binary_writer::CompressionLevelInfo;
+use parallel_processor::memory_data_size::MemoryDataSize;
+use parallel_processor::memory_fs::file::internal::MemoryFileMode;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+const DEFAULT_COMPRESSION_LEVEL_SLOW: u32 = 3;
+const DEFAULT_COMPRESSION_LEVEL_FAST: u32 = 0;
+
+#[derive(Clone)]
+pub struct InstanceSettings {
+    /// Use all the given memory before writing the temporary files to disk
+    pub prefer_memory: bool,
+    /// Keep all the temporary files in memory, never writing them to disk
+    pub memory_only: bool,
+    /// Do not remove the temporary files after they are used
+    pub keep_files: bool,
+    /// The lz4 compression level for the intermediate files, if None a slower level is used for the files on disk
+    pub intermediate_compression_level: Option<u32>,
+    pub minimizer_scheme: MinimizerScheme,
+    pub hard_memory_limit: Option<MemoryDataSize>,
+    pub threads_count: usize,
+    /// Budget shared with the other instances, if None the instance gets its own budget with the hard limit
+    pub memory_budget: Option<Arc<MemoryBudget>>,
+    pub progress_callback: Option<ProgressCallback>,
+}
+
+impl Default for InstanceSettings {
+    fn default() -> Self {
+        Self {
+            prefer_memory: false,
+            memory_only: false,
+            keep_files: false,
+            intermediate_compression_level: None,
+            minimizer_scheme: MinimizerScheme::Random,
+            hard_memory_limit: None,
+            threads_count: 1,
+            memory_budget: None,
+            progress_callback: None,
+        }
+    }
+}
+
+pub struct InstanceContext {
+    prefer_memory: bool,
+    memory_only: bool,
+    keep_files: AtomicBool,
+    compression_level_slow: AtomicU32,
+    compression_level_fast: AtomicU32,
+    minimizer_scheme: MinimizerScheme,
+    /// Filter of the frequent minimizers used by the FrequencyAware scheme, its type is defined by the hashes crate
+    minimizers_filter: RwLock<Option<Arc<dyn Any + Send + Sync>>>,
+    /// Token of the operation using this context
+    cancellation_token: Option<CancellationToken>,
+    /// Reason of the failure of the operation, set from threads that cannot return an error
+    failure: Mutex<Option<String>>,
+    failed: AtomicBool,
+    /// Budget shared by all the operations of the instance, and by the other instances using the same budget
+    pub(crate) memory_budget: Arc<MemoryBudget>,
+    pub(crate) progress: ProgressState,
+}
+
+impl InstanceContext {
+    pub fn new(settings: InstanceSettings) -> Arc<Self> {
+        let context = Self {
+            prefer_memory: settings.prefer_memory,
+            memory_only: settings.memory_only,
+            keep_files: AtomicBool::new(settings.keep_files),
+            compression_level_slow: AtomicU32::new(DEFAULT_COMPRESSION_LEVEL_SLOW),
+            compression_level_fast: AtomicU32::new(DEFAULT_COMPRESSION_LEVEL_FAST),
+            minimizer_scheme: settings.minimizer_scheme,
+            minimizers_filter: RwLock::new(None),
+            cancellation_token: None,
+            failure: Mutex::new(None),
+            failed: AtomicBool::new(false),
+            memory_budget: settings.memory_budget.unwrap_or_else(|| {
+                Arc::new(MemoryBudget::new(
+                    settings.hard_memory_limit,
+                    settings.threads_count,
+                ))
+            }),
+            progress: ProgressState::new(settings.progress_callback),
+        };
+
+        if let Some(level) = settings.intermediate_compression_level {
+            context.set_compression_level(level);
+        }
+
+        Arc::new(context)
+    }
+
+    /// Creates the context of a single operation, with the settings, the memory budget and the progress callback
+    /// of this one, but with its own cancellation token, compression levels, minimizers filter and phases
+    pub fn new_operation(&self, cancellation_token: Option<CancellationToken>) -> Arc<Self> {
+        Arc::new(Self {
+            prefer_memory: self.prefer_memory,
+            memory_only: self.memory_only,
+            keep_files: AtomicBool::new(self.keep_files()),
+            compression_level_slow: AtomicU32::new(
+                self.compression_level_slow.load(Ordering::Relaxed),
+            ),
+            compression_level_fast: AtomicU32::new(
+                self.compression_level_fast.load(Ordering::Relaxed),
+            ),
+            minimizer_scheme: self.minimizer_scheme,
+            minimizers_filter: RwLock::new(None),
+            cancellation_token,
+            failure: Mutex::new(None),
+            failed: AtomicBool::new(false),
+            memory_budget: self.memory_budget.clone(),
+            progress: self.progress.new_operation(),
+        })
+    }
+
+    pub fn keep_files(&self) -> bool {
+        self.keep_files.load(Ordering::Relaxed)
+    }
+
+    pub fn set_keep_files(&self, keep_files: bool) {
+        self.keep_files.store(keep_files, Ordering::Relaxed);
+    }
+
+    /// Uses the same compression level both for the fast and the slow disk files
+    pub fn set_compression_level(&self, level: u32) {
+        self.compression_level_slow.store(level, Ordering::Relaxed);
+        self.compression_level_fast.store(level, Ordering::Relaxed);
+    }
+
+    pub fn compression_level_info(&self) -> CompressionLevelInfo {
+        CompressionLevelInfo {
+            fast_disk: self.compression_level_fast.load(Ordering::Relaxed),
+            slow_disk: self.compression_level_slow.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn memory_mode(&self, swap_priority: usize) -> MemoryFileMode {
+        if self.memory_only {
+            MemoryFileMode::AlwaysMemory
+        } else if self.prefer_memory {
+            MemoryFileMode::PreferMemory { swap_priority }
+        } else {
+            MemoryFileMode::DiskOnly
+        }
+    }
+
+    pub fn minimizer_scheme(&self) -> MinimizerScheme {
+        self.minimizer_scheme
+    }
+
+    pub fn set_minimizers_filter(&self, filter: Option<Arc<dyn Any + Send + Sync>>) {
+        *self.minimizers_filter.write().unwrap() = filter;
+    }
+
+    pub fn get_minimizers_filter(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.minimizers_filter.read().unwrap().clone()
+    }
+
+    /// Returns true if the operation was cancelled or it failed, so that its remaining work is skipped
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .map(|token| token.is_cancelled())
+            .unwrap_or(false)
+            || self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Fails the operation from a thread that cannot return an error, stopping it as if it was cancelled.
+    /// Only the first failure is kept
+    pub fn fail(&self, reason: String) {
+        let mut failure = self.failure.lock();
+        if failure.is_none() {
+            *failure = Some(reason);
+            self.failed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get_failure(&self) -> Option<String> {
+        self.failure.lock().clone()
+    }
+
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory_budget
+    }
+}
+
+/// Operations running in the whole process, on any instance
+static RUNNING_OPERATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks an operation as running until dropped
+#[must_use]
+pub struct RunningOperation(());
+
+impl RunningOperation {
+    pub fn start() -> Self {
+        RUNNING_OPERATIONS.fetch_add(1, Ordering::Relaxed);
+        Self(())
+    }
+}
+
+impl Drop for RunningOperation {
+    fn drop(&mut self) {
+        RUNNING_OPERATIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of operations running in the whole process
+pub fn running_operations_count() -> usize {
+    RUNNING_OPERATIONS.load(Ordering::Relaxed)
+}
+
+thread_local! {
+    static CURRENT_CONTEXT: RefCell<Option<Arc<InstanceContext>>> = RefCell::new(None);
+}
+
+/// Context used by the threads that do not belong to any instance
+fn default_context() -> &'static Arc<InstanceContext> {
+    static DEFAULT_CONTEXT: OnceLock<Arc<InstanceContext>> = OnceLock::new();
+    DEFAULT_CONTEXT.get_or_init(|| InstanceContext::new(InstanceSettings::default()))
+}
+
+/// Makes the given context the current one for the calling thread
+pub fn enter(context: &Arc<InstanceContext>) {
+    CURRENT_CONTEXT.with(|current| {
+        let mut current = current.borrow_mut();
+        if !current
+            .as_ref()
+            .map(|c| Arc::ptr_eq(c, context))
+            .unwrap_or(false)
+        {
+            *current = Some(context.clone());
+        }
+    });
+}
+
+/// Fails the operation of a context if dropped while its thread is panicking. The executors threads are not owned
+/// by the threads pool of the operation, so their panics would otherwise never reach the caller of the operation
+#[must_use]
+pub struct OperationPanicGuard(Arc<InstanceContext>);
+
+impl Drop for OperationPanicGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0
+                .fail("an executor thread of the operation panicked".to_string());
+        }
+    }
+}
+
+/// Makes the given context the current one for an executor thread, failing its operation if the executor panics
+pub fn enter_executor(context: &Arc<InstanceContext>) -> OperationPanicGuard {
+    enter(context);
+    OperationPanicGuard(context.clone())
+}
+
+/// Returns the context of the calling thread
+pub fn current() -> Arc<InstanceContext> {
+    with_current(|context| context.clone())
+}
+
+/// Calls the function with the context of the calling thread, without cloning it
+#[inline]
+pub fn with_current<R>(function: impl FnOnce(&Arc<InstanceContext>) -> R) -> R {
+    CURRENT_CONTEXT.with(|current| match current.borrow().as_ref() {
+        Some(context) => function(context),
+        None => function(default_context()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancellation::{self, CancellationToken};
+    use std::sync::Barrier;
+
+    #[test]
+    fn operations_cancellation() {
+        let context = InstanceContext::new(InstanceSettings::default());
+        let cancelled_token = CancellationToken::new();
+        let running_token = CancellationToken::new();
+
+        let cancelled_operation = context.new_operation(Some(cancelled_token.clone()));
+        let running_operation = context.new_operation(Some(running_token.clone()));
+        assert!(Arc::ptr_eq(
+            &cancelled_operation.memory_budget,
+            &running_operation.memory_budget
+        ));
+
+        // Both the operations are running when one of them is cancelled
+        let started = Barrier::new(3);
+        let cancel_sent = Barrier::new(3);
+
+        std::thread::scope(|scope| {
+            let cancelled = scope.spawn(|| {
+                enter(&cancelled_operation);
+                assert!(!cancellation::is_cancelled());
+                started.wait();
+                cancel_sent.wait();
+                cancellation::is_cancelled()
+            });
+            let running = scope.spawn(|| {
+                enter(&running_operation);
+                assert!(!cancellation::is_cancelled());
+                started.wait();
+                cancel_sent.wait();
+                cancellation::is_cancelled()
+            });
+
+            started.wait();
+            cancelled_token.cancel();
+            cancel_sent.wait();
+
+            assert!(cancelled.join().unwrap());
+            assert!(!running.join().unwrap());
+        });
+
+        assert!(!running_token.is_cancelled());
+        assert!(!context.is_cancelled());
+    }
+
+    #[test]
+    fn operations_state() {
+        let context = InstanceContext::new(InstanceSettings::default());
+        context.set_compression_level(5);
+
+        let first = context.new_operation(None);
+        let second = context.new_operation(None);
+        first.set_compression_level(9);
+        first.set_minimizers_filter(Some(Arc::new(0u32)));
+
+        assert_eq!(second.compression_level_info().slow_disk, 5);
+        assert_eq!(context.compression_level_info().slow_disk, 5);
+        assert_eq!(first.compression_level_info().slow_disk, 9);
+        assert!(second.get_minimizers_filter().is_none());
+    }
+
+    #[test]
+    fn executor_panic_fails_operation() {
+        let context = InstanceContext::new(InstanceSettings::default());
+        let operation = context.new_operation(None);
+
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _panic_guard = enter_executor(&operation);
+                })
+                .join()
+                .unwrap();
+            assert!(operation.get_failure().is_none());
+
+            let panicked = scope
+                .spawn(|| {
+                    let _panic_guard = enter_executor(&operation);
+                    panic!("executor failure");
+                })
+                .join();
+            assert!(panicked.is_err());
+        });
+
+        assert!(operation.get_failure().is_some());
+        assert!(operation.is_cancelled());
+        assert!(context.get_failure().is_none());
+    }
+}
@@ -4,9 +4,15 @@ use parallel_processor::buckets::writers::compressed_binary_writer::{
 };
 use parallel_processor::memory_data_size::MemoryDataSize;
 use parallel_processor::memory_fs::file::internal::MemoryFileMode;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use parallel_processor::memory_fs::MemoryFs;
+use std::sync::atomic::AtomicUsize;
 use std::time::Duration;
 
+pub mod cancellation;
+pub mod instance;
+pub mod memory_budget;
+pub mod progress;
+
 pub type BucketIndexType = u16;
 pub type MinimizerType = u32;
 pub type ColorIndexType = u32;
@@ -37,6 +43,8 @@ pub const MINIMIZER_BUCKETS_CHECKPOINT_SIZE: CompressedCheckpointSize =
     CompressedCheckpointSize::new_from_size(MemoryDataSize::from_mebioctets(8));
 
 pub const DEFAULT_OUTPUT_BUFFER_SIZE: usize = 1024 * 1024 * 4;
+/// Compression level of the output graph, when its extension is lz4 or gz
+pub const OUTPUT_COMPRESSION_LEVEL: u32 = 2;
 pub const DEFAULT_PER_CPU_BUFFER_SIZE: MemoryDataSize = MemoryDataSize::from_kibioctets(4);
 
 pub const MINIMUM_LOG_DELTA_TIME: Duration = Duration::from_secs(10);
@@ -64,6 +72,9 @@ pub const MAX_INTERMEDIATE_MAP_SIZE: u64 = 1024 * 1024 * 32;
 pub const READ_FLAG_INCL_BEGIN: u8 = 1 << 0;
 pub const READ_FLAG_INCL_END: u8 = 1 << 1;
 
+// Multiplicities greater or equal to this value are grouped in the last histogram entry
+pub const KMERS_ABUNDANCE_HISTOGRAM_MAX: usize = 10000;
+
 pub const COLORS_SINGLE_BATCH_SIZE: u64 = 20000;
 pub const QUERIES_COUNT_MIN_BATCH: u64 = 1000;
 
@@ -83,23 +94,42 @@ impl SwapPriority {
     pub const KmersMergeBuckets: usize = 6;
 }
 
-// Functions depending on global config parameters set at runtime
-pub static KEEP_FILES: AtomicBool = AtomicBool::new(false);
-pub static INTERMEDIATE_COMPRESSION_LEVEL_SLOW: AtomicU32 = AtomicU32::new(3);
-pub static INTERMEDIATE_COMPRESSION_LEVEL_FAST: AtomicU32 = AtomicU32::new(0);
-pub static PREFER_MEMORY: AtomicBool = AtomicBool::new(false);
+/// Scheme used to choose the minimizer of each window of m-mers.
+/// The s and r parameters are clamped to the range 1..=m
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MinimizerScheme {
+    /// The m-mer with the smallest hash is chosen
+    Random,
+    /// Open syncmers (m-mers whose smallest s-mer is the first one) are chosen before the other m-mers
+    OpenSyncmers { s: usize },
+    /// Closed syncmers (m-mers whose smallest s-mer is the first or the last one) are chosen before the other m-mers
+    ClosedSyncmers { s: usize },
+    /// Mod-minimizers: the smallest t-mer of the window is found, with t = r + ((m - r) mod w),
+    /// and the m-mer at its position modulo the window size w is chosen
+    ModMinimizers { r: usize },
+    /// The m-mers found to be very frequent while sampling the input are chosen only if there is no other choice
+    FrequencyAware,
+}
 
+// Functions depending on the settings of the instance running on the current thread
 pub fn get_memory_mode(swap_priority: usize) -> MemoryFileMode {
-    if PREFER_MEMORY.load(Ordering::Relaxed) {
-        MemoryFileMode::PreferMemory { swap_priority }
-    } else {
-        MemoryFileMode::DiskOnly
-    }
+    instance::with_current(|context| context.memory_mode(swap_priority))
 }
 
 pub fn get_compression_level_info() -> CompressionLevelInfo {
-    CompressionLevelInfo {
-        fast_disk: INTERMEDIATE_COMPRESSION_LEVEL_FAST.load(Ordering::Relaxed),
-        slow_disk: INTERMEDIATE_COMPRESSION_LEVEL_SLOW.load(Ordering::Relaxed),
+    instance::with_current(|context| context.compression_level_info())
+}
+
+/// Returns true if the temporary files should be removed after they are used
+pub fn remove_temp_files() -> bool {
+    instance::with_current(|context| !context.keep_files())
+}
+
+/// Writes the pending temporary files to disk and gives back the unused memory at the end of a phase.
+/// The in-memory file system is shared by the whole process, so it is skipped while other operations are running
+pub fn flush_temp_files() {
+    if instance::running_operations_count() <= 1 {
+        MemoryFs::flush_all_to_disk();
+        MemoryFs::free_memory();
     }
 }
@@ -0,0 +1,393 @@
+//! Hard memory budget of the process, shared by all the instances alive at the same time so that the limit
+//! holds for their total usage.
+//! When a limit is set, the temporary files kept in memory get a fixed share of it, while the processing
+//! structures (kmers hashmaps, colors buffers, bucketing buffers, packets pools, hashes sorting and links compaction
+//! vectors, querier maps and counters, dumper sequences) register their usage with a MemoryReservation.
+//! The pipeline reads the current pressure to use smaller maps (more resplitting),
+//! smaller packets and to flush the buffers earlier, so that the total usage stays under the limit.
+//! A new map is started only when the usage is under the limit, failing the operation if the memory is never released.
+//! The smaller structures are not tracked: the input reading buffers, the per thread buckets buffers of the phases
+//! without resplitting, the colormap decoders and their caches, the output writers and the threads stacks,
+//! so the limit should leave some room for them.
+
+use crate::instance::{self, InstanceContext};
+use crate::{MAXIMUM_JIT_PROCESSED_BUCKETS, MAX_INTERMEDIATE_MAP_SIZE};
+use parallel_processor::memory_data_size::MemoryDataSize;
+use std::cmp::{max, min};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Share of the limit that can be used to keep the temporary files in memory
+const TEMP_FILES_BUDGET_FRACTION: f64 = 0.4;
+/// Share of the processing budget that can be used by the kmers maps
+const MAPS_BUDGET_FRACTION: f64 = 0.6;
+/// Processing memory usage over which the budget is considered under pressure
+const PRESSURE_THRESHOLD: f64 = 0.8;
+/// Reference processing memory for each thread, below it the packets are made smaller
+const PACKETS_REFERENCE_MEMORY_PER_THREAD: usize = 256 * 1024 * 1024;
+const MIN_INTERMEDIATE_MAP_SIZE: u64 = 1024 * 1024;
+/// Time without any memory being released after which waiting for the memory fails
+const MEMORY_WAIT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+const MEMORY_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The processing memory stayed over the hard limit without being released
+#[derive(Debug)]
+pub struct MemoryLimitExceeded {
+    pub tracked: MemoryDataSize,
+    pub limit: MemoryDataSize,
+}
+
+impl Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the processing memory ({:.2}) is over the hard memory limit ({:.2}) and it is not being released",
+            self.tracked, self.limit
+        )
+    }
+}
+
+pub struct MemoryBudget {
+    hard_limit: usize,
+    /// Threads of all the instances sharing the budget
+    threads_count: AtomicUsize,
+    tracked_memory: AtomicUsize,
+    peak_tracked_memory: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Creates a new budget with the given hard limit, None to disable it
+    pub fn new(limit: Option<MemoryDataSize>, threads_count: usize) -> Self {
+        Self {
+            hard_limit: limit.map(|l| l.as_bytes()).unwrap_or(0),
+            threads_count: AtomicUsize::new(threads_count),
+            tracked_memory: AtomicUsize::new(0),
+            peak_tracked_memory: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds the threads of an instance that starts sharing the budget
+    pub fn add_threads(&self, threads_count: usize) {
+        self.threads_count
+            .fetch_add(threads_count, Ordering::Relaxed);
+    }
+
+    /// Removes the threads of an instance that stops sharing the budget
+    pub fn remove_threads(&self, threads_count: usize) {
+        self.threads_count
+            .fetch_sub(threads_count, Ordering::Relaxed);
+    }
+
+    fn get_threads_count(&self) -> usize {
+        max(1, self.threads_count.load(Ordering::Relaxed))
+    }
+
+    pub fn get_hard_memory_limit(&self) -> Option<MemoryDataSize> {
+        match self.hard_limit {
+            0 => None,
+            limit => Some(MemoryDataSize::from_bytes(limit)),
+        }
+    }
+
+    /// Memory that can be used to keep the temporary files in memory, capped by the hard limit
+    pub fn get_temp_files_memory(&self, requested: MemoryDataSize) -> MemoryDataSize {
+        match self.hard_limit {
+            0 => requested,
+            limit => MemoryDataSize::from_bytes(min(
+                requested.as_bytes(),
+                (limit as f64 * TEMP_FILES_BUDGET_FRACTION) as usize,
+            )),
+        }
+    }
+
+    fn get_processing_budget(limit: usize) -> usize {
+        limit - (limit as f64 * TEMP_FILES_BUDGET_FRACTION) as usize
+    }
+
+    /// Returns true if the processing memory is close to the hard limit
+    pub fn is_under_pressure(&self) -> bool {
+        match self.hard_limit {
+            0 => false,
+            limit => {
+                self.tracked_memory.load(Ordering::Relaxed) as f64
+                    > Self::get_processing_budget(limit) as f64 * PRESSURE_THRESHOLD
+            }
+        }
+    }
+
+    fn is_over_limit(&self) -> bool {
+        match self.hard_limit {
+            0 => false,
+            limit => {
+                self.tracked_memory.load(Ordering::Relaxed) > Self::get_processing_budget(limit)
+            }
+        }
+    }
+
+    /// Maximum size of a kmers map, buckets expected to be bigger than this are resplitted
+    pub fn get_max_intermediate_map_size(&self) -> u64 {
+        match self.hard_limit {
+            0 => MAX_INTERMEDIATE_MAP_SIZE,
+            limit => {
+                let concurrent_maps =
+                    max(MAXIMUM_JIT_PROCESSED_BUCKETS, self.get_threads_count()) + 2;
+                let maps_budget = Self::get_processing_budget(limit) as f64 * MAPS_BUDGET_FRACTION;
+                let mut map_size = min(
+                    MAX_INTERMEDIATE_MAP_SIZE,
+                    (maps_budget / concurrent_maps as f64) as u64,
+                );
+                if self.is_under_pressure() {
+                    map_size /= 4;
+                }
+                max(MIN_INTERMEDIATE_MAP_SIZE, map_size)
+            }
+        }
+    }
+
+    /// Scales down a packet size if the processing budget of each thread is small
+    pub fn get_packets_size(&self, default_size: usize) -> usize {
+        match self.hard_limit {
+            0 => default_size,
+            limit => {
+                let thread_budget = Self::get_processing_budget(limit) / self.get_threads_count();
+                let factor = (thread_budget as f64 / PACKETS_REFERENCE_MEMORY_PER_THREAD as f64)
+                    .clamp(1.0 / 8.0, 1.0);
+                max(1, (default_size as f64 * factor) as usize)
+            }
+        }
+    }
+
+    /// Waits until the tracked memory goes back under the limit, or the operation is cancelled.
+    /// Fails if no memory is released for a while, as the other structures are not going to be freed
+    pub fn wait_for_available_memory(
+        &self,
+        is_cancelled: impl Fn() -> bool,
+        stall_timeout: Duration,
+    ) -> Result<(), MemoryLimitExceeded> {
+        let mut last_tracked = self.tracked_memory.load(Ordering::Relaxed);
+        let mut last_release = Instant::now();
+
+        while self.is_over_limit() && !is_cancelled() {
+            let tracked = self.tracked_memory.load(Ordering::Relaxed);
+            if tracked < last_tracked {
+                last_release = Instant::now();
+            } else if last_release.elapsed() >= stall_timeout {
+                return Err(MemoryLimitExceeded {
+                    tracked: MemoryDataSize::from_bytes(tracked),
+                    limit: MemoryDataSize::from_bytes(Self::get_processing_budget(self.hard_limit)),
+                });
+            }
+            last_tracked = tracked;
+            std::thread::sleep(MEMORY_WAIT_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    pub fn get_tracked_memory(&self) -> MemoryDataSize {
+        MemoryDataSize::from_bytes(self.tracked_memory.load(Ordering::Relaxed))
+    }
+
+    pub fn get_peak_tracked_memory(&self) -> MemoryDataSize {
+        MemoryDataSize::from_bytes(self.peak_tracked_memory.load(Ordering::Relaxed))
+    }
+}
+
+/// Returns true if the processing memory of the budget of the current instance is close to its hard limit
+pub fn is_under_pressure() -> bool {
+    instance::with_current(|context| context.memory_budget.is_under_pressure())
+}
+
+/// Maximum size of a kmers map for the current instance
+pub fn get_max_intermediate_map_size() -> u64 {
+    instance::with_current(|context| context.memory_budget.get_max_intermediate_map_size())
+}
+
+/// Scales down a packet size according to the budget of the current instance
+pub fn get_packets_size(default_size: usize) -> usize {
+    instance::with_current(|context| context.memory_budget.get_packets_size(default_size))
+}
+
+/// Waits until the processing memory of the current instance goes back under the limit,
+/// to avoid allocating new structures while the others are still being freed
+pub fn wait_for_available_memory() -> Result<(), MemoryLimitExceeded> {
+    let context = instance::current();
+    context
+        .memory_budget
+        .wait_for_available_memory(|| context.is_cancelled(), MEMORY_WAIT_STALL_TIMEOUT)
+}
+
+/// Memory used by a processing structure, accounted in the budget of the current instance until dropped.
+/// The budget is shared with the other instances alive, so the reservations of all of them count for the limit
+pub struct MemoryReservation {
+    context: Arc<InstanceContext>,
+    size: usize,
+}
+
+impl MemoryReservation {
+    pub fn new() -> Self {
+        Self {
+            context: instance::current(),
+            size: 0,
+        }
+    }
+
+    /// Reserves a fixed size, used by the buffers allocated once and kept until the reservation is dropped
+    pub fn with_size(size: usize) -> Self {
+        let mut reservation = Self::new();
+        reservation.update(size);
+        reservation
+    }
+
+    pub fn update(&mut self, size: usize) {
+        let budget = &self.context.memory_budget;
+        if size > self.size {
+            let total = budget
+                .tracked_memory
+                .fetch_add(size - self.size, Ordering::Relaxed)
+                + size
+                - self.size;
+            budget
+                .peak_tracked_memory
+                .fetch_max(total, Ordering::Relaxed);
+        } else {
+            budget
+                .tracked_memory
+                .fetch_sub(self.size - size, Ordering::Relaxed);
+        }
+        self.size = size;
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.update(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancellation::CancellationToken;
+    use crate::instance::InstanceSettings;
+
+    const MIB: usize = 1024 * 1024;
+
+    fn limited_operation(
+        limit: usize,
+        cancellation_token: Option<CancellationToken>,
+    ) -> Arc<InstanceContext> {
+        InstanceContext::new(InstanceSettings {
+            hard_memory_limit: Some(MemoryDataSize::from_bytes(limit)),
+            ..InstanceSettings::default()
+        })
+        .new_operation(cancellation_token)
+    }
+
+    #[test]
+    fn reservations_accounting() {
+        let operation = limited_operation(100 * MIB, None);
+        instance::enter(&operation);
+        let budget = operation.memory_budget();
+
+        let mut reservation = MemoryReservation::new();
+        reservation.update(10 * MIB);
+        let fixed = MemoryReservation::with_size(20 * MIB);
+        assert_eq!(budget.get_tracked_memory().as_bytes(), 30 * MIB);
+
+        reservation.update(5 * MIB);
+        drop(fixed);
+        assert_eq!(budget.get_tracked_memory().as_bytes(), 5 * MIB);
+        assert_eq!(budget.get_peak_tracked_memory().as_bytes(), 30 * MIB);
+
+        drop(reservation);
+        assert_eq!(budget.get_tracked_memory().as_bytes(), 0);
+    }
+
+    #[test]
+    fn memory_limit_pressure() {
+        let operation = limited_operation(100 * MIB, None);
+        instance::enter(&operation);
+        let budget = operation.memory_budget();
+        let processing_budget = MemoryBudget::get_processing_budget(100 * MIB);
+
+        let mut reservation = MemoryReservation::new();
+        assert!(!budget.is_under_pressure());
+        let free_map_size = budget.get_max_intermediate_map_size();
+
+        reservation.update(processing_budget - 1);
+        assert!(budget.is_under_pressure() && !budget.is_over_limit());
+        assert!(budget.get_max_intermediate_map_size() < free_map_size);
+
+        reservation.update(processing_budget + 1);
+        assert!(budget.is_over_limit());
+
+        assert!(!MemoryBudget::new(None, 4).is_under_pressure());
+    }
+
+    #[test]
+    fn wait_until_memory_is_released() {
+        let operation = limited_operation(100 * MIB, None);
+        instance::enter(&operation);
+
+        let reservation = MemoryReservation::with_size(100 * MIB);
+        let start = Instant::now();
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                std::thread::sleep(Duration::from_millis(200));
+                drop(reservation);
+            });
+            operation
+                .memory_budget()
+                .wait_for_available_memory(|| false, Duration::from_secs(10))
+                .unwrap();
+        });
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(!operation.memory_budget().is_over_limit());
+    }
+
+    #[test]
+    fn wait_fails_if_memory_is_not_released() {
+        let operation = limited_operation(100 * MIB, None);
+        instance::enter(&operation);
+
+        let _reservation = MemoryReservation::with_size(100 * MIB);
+        let error = operation
+            .memory_budget()
+            .wait_for_available_memory(|| false, Duration::from_millis(100))
+            .unwrap_err();
+
+        assert_eq!(error.tracked.as_bytes(), 100 * MIB);
+        assert_eq!(
+            error.limit.as_bytes(),
+            MemoryBudget::get_processing_budget(100 * MIB)
+        );
+    }
+
+    #[test]
+    fn wait_stops_when_cancelled() {
+        let cancellation_token = CancellationToken::new();
+        let operation = limited_operation(100 * MIB, Some(cancellation_token.clone()));
+        instance::enter(&operation);
+
+        let _reservation = MemoryReservation::with_size(100 * MIB);
+        cancellation_token.cancel();
+
+        assert!(wait_for_available_memory().is_ok());
+    }
+
+    #[test]
+    fn failed_operation_is_stopped() {
+        let operation = limited_operation(100 * MIB, None);
+        assert!(!operation.is_cancelled());
+
+        operation.fail("first".to_string());
+        operation.fail("second".to_string());
+
+        assert!(operation.is_cancelled());
+        assert_eq!(operation.get_failure().as_deref(), Some("first"));
+    }
+}
@@ -0,0 +1,167 @@
+//! Structured progress events, reported to the callback registered in each instance by the library users.
+
+use crate::instance::{self, InstanceContext};
+use log::info;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub enum ProgressEvent {
+    /// A new phase of the current operation is started
+    PhaseStart { name: String },
+    /// The current phase has been completed
+    PhaseEnd { name: String, duration: Duration },
+    /// Buckets processed by the current phase, the total can grow if some buckets are resplitted
+    BucketsProcessed { processed: usize, total: usize },
+    /// A links compaction iteration has been completed
+    LinksCompaction {
+        iteration: usize,
+        remaining_links: u64,
+    },
+    /// Sequence bases read from the input files
+    BytesRead {
+        bytes: u64,
+        processed_files: usize,
+        total_files: usize,
+    },
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+/// Progress callback and phases of the current operation of an instance
+pub(crate) struct ProgressState {
+    callback: Option<ProgressCallback>,
+    operation_start: Mutex<Instant>,
+    current_phase: Mutex<Option<(String, Instant)>>,
+    completed_phases: Mutex<Vec<(String, Duration)>>,
+    /// Last phase started by any operation of the instance, shared with the operations states
+    last_started_phase: Arc<Mutex<String>>,
+}
+
+impl ProgressState {
+    pub(crate) fn new(callback: Option<ProgressCallback>) -> Self {
+        Self {
+            callback,
+            operation_start: Mutex::new(Instant::now()),
+            current_phase: Mutex::new(None),
+            completed_phases: Mutex::new(Vec::new()),
+            last_started_phase: Arc::new(Mutex::new(String::new())),
+        }
+    }
+
+    /// State of a new operation, reporting to the same callback
+    pub(crate) fn new_operation(&self) -> Self {
+        Self {
+            last_started_phase: self.last_started_phase.clone(),
+            ..Self::new(self.callback.clone())
+        }
+    }
+}
+
+/// Reports the event to the callback of the current instance, the event is built only if there is a callback
+#[inline]
+pub fn report_progress(event: impl FnOnce() -> ProgressEvent) {
+    instance::with_current(|context| {
+        if let Some(callback) = context.progress.callback.as_ref() {
+            callback(&event());
+        }
+    })
+}
+
+/// Starts a new operation, clearing the phases times of the previous one
+pub fn start_operation() {
+    instance::with_current(|context| {
+        *context.progress.operation_start.lock() = Instant::now();
+        *context.progress.current_phase.lock() = None;
+        context.progress.completed_phases.lock().clear();
+    });
+}
+
+/// Starts a new phase of the current operation, ending the previous one
+pub fn start_phase(name: String) {
+    end_phase();
+    report_progress(|| ProgressEvent::PhaseStart { name: name.clone() });
+    instance::with_current(|context| {
+        *context.progress.last_started_phase.lock() = name.clone();
+        *context.progress.current_phase.lock() = Some((name, Instant::now()));
+    });
+}
+
+/// Elapsed time of the current phase of the current operation
+pub fn get_phase_time() -> Duration {
+    instance::with_current(|context| {
+        context
+            .progress
+            .current_phase
+            .lock()
+            .as_ref()
+            .map(|(_, start_time)| start_time.elapsed())
+            .unwrap_or_default()
+    })
+}
+
+/// Describes the elapsed times of the current phase and of the whole current operation, used in the periodic logs
+pub fn get_times_desc() -> String {
+    instance::with_current(|context| {
+        let total_time = context.progress.operation_start.lock().elapsed();
+        match context.progress.current_phase.lock().as_ref() {
+            Some((name, start_time)) => format!(
+                "[{}: {:.2?}, total: {:.2?}]",
+                name,
+                start_time.elapsed(),
+                total_time
+            ),
+            None => format!("[total: {:.2?}]", total_time),
+        }
+    })
+}
+
+/// Returns the last phase started by the operations of the given instance
+pub fn get_last_started_phase(context: &InstanceContext) -> String {
+    context.progress.last_started_phase.lock().clone()
+}
+
+/// Ends the current phase, if any
+pub fn end_phase() {
+    let current_phase =
+        instance::with_current(|context| context.progress.current_phase.lock().take());
+    if let Some((name, start_time)) = current_phase {
+        let duration = start_time.elapsed();
+        report_progress(|| ProgressEvent::PhaseEnd {
+            name: name.clone(),
+            duration,
+        });
+        instance::with_current(|context| {
+            context
+                .progress
+                .completed_phases
+                .lock()
+                .push((name, duration))
+        });
+    }
+}
+
+/// Logs the times of the phases completed by the current operation
+pub fn print_phases_stats(message: String) {
+    end_phase();
+
+    if !log::log_enabled!(log::Level::Info) {
+        return;
+    }
+
+    let completed_phases =
+        instance::with_current(|context| context.progress.completed_phases.lock().clone());
+
+    info!("{}", message);
+    for (name, duration) in completed_phases.iter() {
+        info!("{} took {:.2?}", name, duration);
+    }
+    info!(
+        "Total time: {:.2?}",
+        completed_phases
+            .iter()
+            .map(|(_, duration)| *duration)
+            .sum::<Duration>()
+    );
+}
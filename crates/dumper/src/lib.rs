@@ -4,18 +4,12 @@
 use crate::pipeline::dumper_minimizer_bucketing::minimizer_bucketing;
 use colors::bundles::graph_querying::ColorBundleGraphQuerying;
 use colors::colors_manager::{ColorMapReader, ColorsManager, ColorsMergeManager};
-use colors::DefaultColorsSerializer;
-use config::{
-    ColorIndexType, INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW,
-};
+use config::{flush_temp_files, instance, progress, ColorIndexType};
 use io::compute_stats_from_input_blocks;
 use io::sequences_stream::general::GeneralSequenceBlockData;
-use parallel_processor::memory_fs::MemoryFs;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use parallel_processor::memory_fs::{MemoryFs, RemoveFileMode};
 use pipeline::dumper_colormap_reading::colormap_reading;
-use std::fs::remove_file;
 use std::path::PathBuf;
-use std::sync::atomic::Ordering;
 
 mod pipeline;
 
@@ -46,7 +40,7 @@ pub fn dump_unitigs(
 ) {
     let temp_dir = temp_dir.unwrap_or(PathBuf::new());
 
-    PHASES_TIMES_MONITOR.write().init();
+    progress::start_operation();
 
     let color_map = <ColorBundleGraphQuerying as ColorsManager>::ColorsMergeManagerType::<
         hashes::cn_nthash::CanonicalNtHashIteratorFactory,
@@ -60,8 +54,7 @@ pub fn dump_unitigs(
     let buckets_count_log = buckets_count_log.unwrap_or_else(|| file_stats.best_buckets_count_log);
 
     if let Some(default_compression_level) = default_compression_level {
-        INTERMEDIATE_COMPRESSION_LEVEL_SLOW.store(default_compression_level, Ordering::Relaxed);
-        INTERMEDIATE_COMPRESSION_LEVEL_FAST.store(default_compression_level, Ordering::Relaxed);
+        instance::with_current(|context| context.set_compression_level(default_compression_level));
     }
 
     let buckets_count = 1 << buckets_count_log;
@@ -75,13 +68,12 @@ pub fn dump_unitigs(
         m,
         color_map.colors_subsets_count(),
     );
-    let _ = remove_file(buckets_stats);
+    let _ = MemoryFs::remove_file(&buckets_stats, RemoveFileMode::Remove { remove_fs: true });
 
-    MemoryFs::flush_all_to_disk();
-    MemoryFs::free_memory();
+    flush_temp_files();
 
     let colormap_file = graph_input.with_extension("colors.dat");
-    colormap_reading::<ColorBundleGraphQuerying, DefaultColorsSerializer>(
+    colormap_reading::<ColorBundleGraphQuerying>(
         colormap_file,
         reorganized_unitigs,
         single_thread_output_function,
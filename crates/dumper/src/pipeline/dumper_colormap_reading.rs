@@ -2,37 +2,31 @@ use crate::pipeline::dumper_minimizer_bucketing::DumperKmersReferenceData;
 use colors::colors_manager::color_types::SingleKmerColorDataType;
 use colors::colors_manager::ColorsManager;
 use colors::storage::deserializer::ColorsDeserializer;
-use colors::storage::ColorsSerializerTrait;
-use config::{ColorIndexType, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use config::memory_budget::MemoryReservation;
+use config::progress;
+use config::{remove_temp_files, ColorIndexType, DEFAULT_PREFETCH_AMOUNT};
 use io::compressed_read::CompressedReadIndipendent;
 use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketDataSerializer;
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::buckets::readers::BucketReader;
 use parallel_processor::fast_smart_bucket_sort::{fast_smart_radix_sort, FastSortable, SortKey};
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use parking_lot::Mutex;
 use rayon::prelude::*;
 use std::marker::PhantomData;
 use std::path::PathBuf;
-use std::sync::atomic::Ordering;
 
-pub fn colormap_reading<
-    CX: ColorsManager<SingleKmerColorDataType = ColorIndexType>,
-    CD: ColorsSerializerTrait,
->(
+pub fn colormap_reading<CX: ColorsManager<SingleKmerColorDataType = ColorIndexType>>(
     colormap_file: PathBuf,
     colored_unitigs_buckets: Vec<PathBuf>,
     single_thread_output_function: bool,
     output_function: impl Fn(&[u8], &[ColorIndexType], bool) + Send + Sync,
 ) {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: colormap reading".to_string());
+    progress::start_phase("phase: colormap reading".to_string());
 
     let tlocal_colormap_decoder =
-        ScopedThreadLocal::new(move || ColorsDeserializer::<CD>::new(&colormap_file, false));
+        ScopedThreadLocal::new(move || ColorsDeserializer::new(&colormap_file, false));
 
     let single_thread_lock = Mutex::new(());
 
@@ -47,7 +41,7 @@ pub fn colormap_reading<
         CompressedBinaryReader::new(
             input,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         )
@@ -59,6 +53,14 @@ pub fn colormap_reading<
             let new_read = CompressedReadIndipendent::from_read(&read, &mut temp_bases);
             temp_sequences.push((new_read, color_extra));
         });
+        let _sequences_reservation = MemoryReservation::with_size(
+            temp_bases.capacity()
+                + temp_sequences.capacity()
+                    * std::mem::size_of::<(
+                        CompressedReadIndipendent,
+                        DumperKmersReferenceData<SingleKmerColorDataType<CX>>,
+                    )>(),
+        );
 
         struct ColoredUnitigsCompare<CX: ColorsManager>(PhantomData<&'static CX>);
         impl<CX: ColorsManager>
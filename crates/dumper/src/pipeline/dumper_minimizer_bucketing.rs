@@ -3,6 +3,7 @@ use colors::colors_manager::color_types::{
 };
 use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
 use colors::parsers::{SequenceIdent, SingleSequenceInfo};
+use config::progress;
 use config::BucketIndexType;
 use io::concurrent::temp_reads::extra_data::{
     HasEmptyExtraBuffer, SequenceExtraDataConsecutiveCompression,
@@ -11,12 +12,12 @@ use io::concurrent::temp_reads::extra_data::{
 use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 use io::sequences_stream::fasta::FastaFileSequencesStream;
 use io::sequences_stream::SequenceInfo;
+use log::warn;
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
     MinimizerBucketingExecutorFactory, MinimizerInputSequence,
 };
 use parallel_processor::fast_smart_bucket_sort::FastSortable;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::ops::Range;
@@ -166,8 +167,8 @@ impl<CX: ColorsManager> MinimizerBucketingExecutor<DumperMinimizerBucketingExecu
                 if CX::COLORS_ENABLED
                     && (color.debug_count() != sequence.seq.len() - self.global_data.k + 1)
                 {
-                    println!(
-                        "WARN: Sequence does not have enough colors, please check matching k size:\n{}\n{}",
+                    warn!(
+                        "Sequence does not have enough colors, please check matching k size:\n{}\n{}",
                         std::str::from_utf8(sequence.ident_data).unwrap(),
                         std::str::from_utf8(sequence.seq).unwrap()
                     );
@@ -255,9 +256,7 @@ pub fn minimizer_bucketing<CX: ColorsManager>(
     m: usize,
     colors_count: u64,
 ) -> (Vec<PathBuf>, PathBuf) {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: unitigs reorganization".to_string());
+    progress::start_phase("phase: unitigs reorganization".to_string());
 
     let input_files = vec![(graph_file, ())];
 
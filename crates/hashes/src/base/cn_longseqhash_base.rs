@@ -0,0 +1,229 @@
+use crate::{ExtendableHashTraitType, HashFunction, HashFunctionFactory, HashableSequence};
+use config::BucketIndexType;
+use dynamic_dispatch::dynamic_dispatch;
+use std::cmp::Ordering;
+
+pub struct CanonicalSeqHashIterator<N: HashableSequence> {
+    seq: N,
+    fh: HashIntegerType,
+    rc: HashIntegerType,
+    k_minus1: usize,
+}
+
+impl<N: HashableSequence> CanonicalSeqHashIterator<N> {
+    pub fn new(seq: N, k: usize) -> Result<CanonicalSeqHashIterator<N>, &'static str> {
+        if k > seq.bases_count() || k > HashIntegerType::MAX_BASES {
+            return Err("K out of range!");
+        }
+
+        let mut fh = HashIntegerType::default();
+        let mut bw = HashIntegerType::default();
+        for i in 0..(k - 1) {
+            fh = fh.with_base(i, unsafe { seq.get_unchecked_cbase(i) });
+            bw = bw
+                .shift_bases_left()
+                .with_base(0, xrc(unsafe { seq.get_unchecked_cbase(i) }));
+        }
+
+        Ok(CanonicalSeqHashIterator {
+            seq,
+            fh: fh.shift_bases_left(),
+            rc: bw.masked(k),
+            k_minus1: k - 1,
+        })
+    }
+
+    #[inline(always)]
+    fn roll_hash(&mut self, index: usize) -> ExtCanonicalSeqHash {
+        let base = unsafe { self.seq.get_unchecked_cbase(index) };
+        assert!(base < 4);
+
+        self.fh = self.fh.shift_bases_right().with_base(self.k_minus1, base);
+
+        self.rc = self
+            .rc
+            .shift_bases_left()
+            .with_base(0, xrc(base))
+            .masked(self.k_minus1 + 1);
+
+        ExtCanonicalSeqHash(self.fh, self.rc)
+    }
+}
+
+impl<N: HashableSequence> HashFunction<CanonicalSeqHashFactory> for CanonicalSeqHashIterator<N> {
+    type IteratorType =
+        impl Iterator<Item = <CanonicalSeqHashFactory as HashFunctionFactory>::HashTypeExtendable>;
+    type EnumerableIteratorType = impl Iterator<
+        Item = (
+            usize,
+            <CanonicalSeqHashFactory as HashFunctionFactory>::HashTypeExtendable,
+        ),
+    >;
+
+    fn iter(mut self) -> Self::IteratorType {
+        (self.k_minus1..self.seq.bases_count()).map(move |idx| self.roll_hash(idx))
+    }
+
+    fn iter_enumerate(mut self) -> Self::EnumerableIteratorType {
+        (self.k_minus1..self.seq.bases_count())
+            .map(move |idx| (idx - self.k_minus1, self.roll_hash(idx)))
+    }
+}
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
+pub struct CanonicalSeqHashFactory;
+
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub struct ExtCanonicalSeqHash(HashIntegerType, HashIntegerType);
+
+impl ExtendableHashTraitType for ExtCanonicalSeqHash {
+    type HashTypeUnextendable = HashIntegerType;
+
+    #[inline(always)]
+    fn to_unextendable(self) -> Self::HashTypeUnextendable {
+        if self.is_forward() {
+            self.0
+        } else {
+            self.1
+        }
+    }
+
+    #[inline(always)]
+    fn is_forward(&self) -> bool {
+        self.0.numeric_cmp(&self.1) == Ordering::Less
+    }
+
+    fn is_rc_symmetric(&self) -> bool {
+        self.0 == self.1
+    }
+}
+
+#[dynamic_dispatch]
+impl HashFunctionFactory for CanonicalSeqHashFactory {
+    type HashTypeUnextendable = HashIntegerType;
+    type HashTypeExtendable = ExtCanonicalSeqHash;
+    type HashIterator<N: HashableSequence> = CanonicalSeqHashIterator<N>;
+    type PreferredRandomState = ahash::RandomState;
+
+    #[inline(always)]
+    fn get_random_state() -> Self::PreferredRandomState {
+        ahash::RandomState::new()
+    }
+
+    const NULL_BASE: u8 = 0;
+    // Buckets and sorting keys are computed from the 64 bit fingerprint
+    const USABLE_HASH_BITS: usize = 64;
+    const SORTING_KEY_BITS: usize = 64;
+
+    fn initialize(_k: usize) {}
+
+    fn new<N: HashableSequence>(seq: N, k: usize) -> Self::HashIterator<N> {
+        CanonicalSeqHashIterator::new(seq, k).unwrap()
+    }
+
+    #[inline(always)]
+    fn get_bucket(
+        used_bits: usize,
+        requested_bits: usize,
+        hash: Self::HashTypeUnextendable,
+    ) -> BucketIndexType {
+        ((hash.fingerprint() >> used_bits) % (1 << requested_bits)) as BucketIndexType
+    }
+
+    fn get_shifted(hash: Self::HashTypeUnextendable, shift: u8) -> u8 {
+        (hash.fingerprint() >> shift) as u8
+    }
+
+    #[inline(always)]
+    fn get_u64(hash: Self::HashTypeUnextendable) -> u64 {
+        hash.fingerprint()
+    }
+
+    fn debug_eq_to_u128(hash: Self::HashTypeUnextendable, value: u128) -> bool {
+        (hash.0[0] as u128 | ((hash.0[1] as u128) << 64)) == value
+            && hash.0[2..].iter().all(|word| *word == 0)
+    }
+
+    fn manual_roll_forward(
+        hash: Self::HashTypeExtendable,
+        k: usize,
+        _out_base: u8,
+        in_base: u8,
+    ) -> Self::HashTypeExtendable {
+        assert!(in_base < 4);
+        ExtCanonicalSeqHash(
+            hash.0.shift_bases_right().with_base(k - 1, in_base),
+            hash.1
+                .shift_bases_left()
+                .with_base(0, xrc(in_base))
+                .masked(k),
+        )
+    }
+
+    fn manual_roll_reverse(
+        hash: Self::HashTypeExtendable,
+        k: usize,
+        _out_base: u8,
+        in_base: u8,
+    ) -> Self::HashTypeExtendable {
+        assert!(in_base < 4);
+        ExtCanonicalSeqHash(
+            hash.0.shift_bases_left().with_base(0, in_base).masked(k),
+            hash.1.shift_bases_right().with_base(k - 1, xrc(in_base)),
+        )
+    }
+
+    fn manual_remove_only_forward(
+        hash: Self::HashTypeExtendable,
+        k: usize,
+        _out_base: u8,
+    ) -> Self::HashTypeExtendable {
+        ExtCanonicalSeqHash(hash.0.shift_bases_right(), hash.1.masked(k - 1))
+    }
+
+    fn manual_remove_only_reverse(
+        hash: Self::HashTypeExtendable,
+        k: usize,
+        _out_base: u8,
+    ) -> Self::HashTypeExtendable {
+        ExtCanonicalSeqHash(hash.0.masked(k - 1), hash.1.shift_bases_right())
+    }
+
+    const INVERTIBLE: bool = true;
+    type SeqType = [u8; HashIntegerType::BYTES];
+
+    fn invert(hash: Self::HashTypeUnextendable) -> Self::SeqType {
+        hash.to_le_bytes()
+    }
+}
+
+// Returns the complement of a compressed format base
+#[inline(always)]
+fn xrc(base: u8) -> u8 {
+    base ^ 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CanonicalSeqHashFactory;
+    use super::HashIntegerType;
+    use crate::tests::test_hash_function;
+
+    #[test]
+    fn cn_longseqhash_test() {
+        test_hash_function::<CanonicalSeqHashFactory>(
+            &[
+                2,
+                31,
+                32,
+                33,
+                64,
+                65,
+                101,
+                127,
+                HashIntegerType::MAX_BASES - 1,
+            ],
+            true,
+        );
+    }
+}
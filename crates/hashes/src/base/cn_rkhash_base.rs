@@ -1,6 +1,5 @@
 use crate::{
-    init_rmmult, ExtendableHashTraitType, HashFunction, HashFunctionFactory, HashableSequence,
-    RMMULT_CACHE_SIZE,
+    ExtendableHashTraitType, HashFunction, HashFunctionFactory, HashableSequence, RmmultCache,
 };
 use config::BucketIndexType;
 use dynamic_dispatch::dynamic_dispatch;
@@ -156,10 +155,10 @@ impl ExtendableHashTraitType for ExtCanonicalRabinKarpHash {
     }
 }
 
-static mut RMMULT_CACHE: [u128; RMMULT_CACHE_SIZE] = [0; RMMULT_CACHE_SIZE];
+static RMMULT_CACHE: RmmultCache = RmmultCache::new();
 #[inline(always)]
 fn get_rmmult(k: usize) -> u128 {
-    unsafe { RMMULT_CACHE[k % RMMULT_CACHE_SIZE] }
+    RMMULT_CACHE.get(k, MULTIPLIER as u128)
 }
 
 #[dynamic_dispatch]
@@ -178,9 +177,7 @@ impl HashFunctionFactory for CanonicalRabinKarpHashFactory {
     const USABLE_HASH_BITS: usize = size_of::<Self::HashTypeUnextendable>() * 8 - 1; // -1 because the hash is always odd
 
     fn initialize(k: usize) {
-        unsafe {
-            RMMULT_CACHE = init_rmmult(k, MULTIPLIER as u128);
-        }
+        get_rmmult(k);
     }
 
     fn new<N: HashableSequence>(seq: N, k: usize) -> Self::HashIterator<N> {
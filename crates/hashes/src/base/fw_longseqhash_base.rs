@@ -0,0 +1,201 @@
+use crate::{ExtendableHashTraitType, HashFunction, HashFunctionFactory, HashableSequence};
+use config::BucketIndexType;
+use dynamic_dispatch::dynamic_dispatch;
+
+pub struct ForwardSeqHashIterator<N: HashableSequence> {
+    seq: N,
+    fh: HashIntegerType,
+    k_minus1: usize,
+}
+
+impl<N: HashableSequence> ForwardSeqHashIterator<N> {
+    pub fn new(seq: N, k: usize) -> Result<ForwardSeqHashIterator<N>, &'static str> {
+        if k > seq.bases_count() || k > HashIntegerType::MAX_BASES {
+            return Err("K out of range!");
+        }
+
+        let mut fh = HashIntegerType::default();
+        for i in 0..(k - 1) {
+            fh = fh.with_base(i, unsafe { seq.get_unchecked_cbase(i) });
+        }
+
+        Ok(ForwardSeqHashIterator {
+            seq,
+            fh: fh.shift_bases_left(),
+            k_minus1: k - 1,
+        })
+    }
+
+    #[inline(always)]
+    fn roll_hash(&mut self, index: usize) -> ExtForwardSeqHash {
+        assert!(unsafe { self.seq.get_unchecked_cbase(index) } < 4);
+
+        self.fh = self
+            .fh
+            .shift_bases_right()
+            .with_base(self.k_minus1, unsafe {
+                self.seq.get_unchecked_cbase(index)
+            });
+
+        ExtForwardSeqHash(self.fh)
+    }
+}
+
+impl<N: HashableSequence> HashFunction<ForwardSeqHashFactory> for ForwardSeqHashIterator<N> {
+    type IteratorType =
+        impl Iterator<Item = <ForwardSeqHashFactory as HashFunctionFactory>::HashTypeExtendable>;
+    type EnumerableIteratorType = impl Iterator<
+        Item = (
+            usize,
+            <ForwardSeqHashFactory as HashFunctionFactory>::HashTypeExtendable,
+        ),
+    >;
+
+    fn iter(mut self) -> Self::IteratorType {
+        (self.k_minus1..self.seq.bases_count()).map(move |idx| self.roll_hash(idx))
+    }
+
+    fn iter_enumerate(mut self) -> Self::EnumerableIteratorType {
+        (self.k_minus1..self.seq.bases_count())
+            .map(move |idx| (idx - self.k_minus1, self.roll_hash(idx)))
+    }
+}
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
+pub struct ForwardSeqHashFactory;
+
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub struct ExtForwardSeqHash(HashIntegerType);
+
+impl ExtendableHashTraitType for ExtForwardSeqHash {
+    type HashTypeUnextendable = HashIntegerType;
+
+    #[inline(always)]
+    fn to_unextendable(self) -> Self::HashTypeUnextendable {
+        self.0
+    }
+
+    #[inline(always)]
+    fn is_forward(&self) -> bool {
+        true
+    }
+
+    fn is_rc_symmetric(&self) -> bool {
+        false
+    }
+}
+
+#[dynamic_dispatch]
+impl HashFunctionFactory for ForwardSeqHashFactory {
+    type HashTypeUnextendable = HashIntegerType;
+    type HashTypeExtendable = ExtForwardSeqHash;
+    type HashIterator<N: HashableSequence> = ForwardSeqHashIterator<N>;
+    type PreferredRandomState = ahash::RandomState;
+
+    #[inline(always)]
+    fn get_random_state() -> Self::PreferredRandomState {
+        ahash::RandomState::new()
+    }
+
+    const NULL_BASE: u8 = 0;
+    // Buckets and sorting keys are computed from the 64 bit fingerprint
+    const USABLE_HASH_BITS: usize = 64;
+    const SORTING_KEY_BITS: usize = 64;
+
+    fn initialize(_k: usize) {}
+
+    fn new<N: HashableSequence>(seq: N, k: usize) -> Self::HashIterator<N> {
+        ForwardSeqHashIterator::new(seq, k).unwrap()
+    }
+
+    #[inline(always)]
+    fn get_bucket(
+        used_bits: usize,
+        requested_bits: usize,
+        hash: Self::HashTypeUnextendable,
+    ) -> BucketIndexType {
+        ((hash.fingerprint() >> used_bits) % (1 << requested_bits)) as BucketIndexType
+    }
+
+    fn get_shifted(hash: Self::HashTypeUnextendable, shift: u8) -> u8 {
+        (hash.fingerprint() >> shift) as u8
+    }
+
+    #[inline(always)]
+    fn get_u64(hash: Self::HashTypeUnextendable) -> u64 {
+        hash.fingerprint()
+    }
+
+    fn debug_eq_to_u128(hash: Self::HashTypeUnextendable, value: u128) -> bool {
+        (hash.0[0] as u128 | ((hash.0[1] as u128) << 64)) == value
+            && hash.0[2..].iter().all(|word| *word == 0)
+    }
+
+    fn manual_roll_forward(
+        hash: Self::HashTypeExtendable,
+        k: usize,
+        _out_base: u8,
+        in_base: u8,
+    ) -> Self::HashTypeExtendable {
+        assert!(in_base < 4);
+        ExtForwardSeqHash(hash.0.shift_bases_right().with_base(k - 1, in_base))
+    }
+
+    fn manual_roll_reverse(
+        hash: Self::HashTypeExtendable,
+        k: usize,
+        _out_base: u8,
+        in_base: u8,
+    ) -> Self::HashTypeExtendable {
+        assert!(in_base < 4);
+        ExtForwardSeqHash(hash.0.shift_bases_left().with_base(0, in_base).masked(k))
+    }
+
+    fn manual_remove_only_forward(
+        hash: Self::HashTypeExtendable,
+        _k: usize,
+        _out_base: u8,
+    ) -> Self::HashTypeExtendable {
+        ExtForwardSeqHash(hash.0.shift_bases_right())
+    }
+
+    fn manual_remove_only_reverse(
+        hash: Self::HashTypeExtendable,
+        k: usize,
+        _out_base: u8,
+    ) -> Self::HashTypeExtendable {
+        ExtForwardSeqHash(hash.0.masked(k - 1))
+    }
+
+    const INVERTIBLE: bool = true;
+    type SeqType = [u8; HashIntegerType::BYTES];
+    fn invert(hash: Self::HashTypeUnextendable) -> Self::SeqType {
+        hash.to_le_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ForwardSeqHashFactory;
+    use super::HashIntegerType;
+    use crate::tests::test_hash_function;
+
+    #[test]
+    fn fw_longseqhash_test() {
+        test_hash_function::<ForwardSeqHashFactory>(
+            &[
+                2,
+                31,
+                32,
+                33,
+                64,
+                65,
+                101,
+                127,
+                HashIntegerType::MAX_BASES - 1,
+            ],
+            false,
+        );
+    }
+}
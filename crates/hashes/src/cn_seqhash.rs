@@ -17,3 +17,13 @@ pub mod u128 {
     type HashIntegerType = u128;
     include!("base/cn_seqhash_base.rs");
 }
+
+pub mod u256 {
+    type HashIntegerType = crate::long_hash::LongHash256;
+    include!("base/cn_longseqhash_base.rs");
+}
+
+pub mod u512 {
+    type HashIntegerType = crate::long_hash::LongHash512;
+    include!("base/cn_longseqhash_base.rs");
+}
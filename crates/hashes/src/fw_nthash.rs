@@ -193,6 +193,19 @@ impl crate::MinimizerHashFunctionFactory for ForwardNtHashIteratorFactory {
     ) -> MinimizerType {
         hash as MinimizerType
     }
+
+    #[inline(always)]
+    fn set_minimizer_priority(
+        hash: <Self as HashFunctionFactory>::HashTypeUnextendable,
+        high_priority: bool,
+    ) -> <Self as HashFunctionFactory>::HashTypeUnextendable {
+        const PRIORITY_BIT: u64 = 1 << (MinimizerType::BITS - 1);
+        if high_priority {
+            hash & !PRIORITY_BIT
+        } else {
+            hash | PRIORITY_BIT
+        }
+    }
 }
 
 #[inline(always)]
@@ -8,6 +8,8 @@ pub mod cn_nthash;
 pub mod cn_seqhash;
 pub mod fw_nthash;
 pub mod fw_seqhash;
+pub mod long_hash;
+pub mod minimizer_scheme;
 mod nthash_base;
 
 pub mod cn_rkhash;
@@ -17,6 +19,7 @@ pub mod rolling;
 
 use std::fmt::{Debug, Display};
 use std::hash::{BuildHasher, Hash};
+use std::sync::OnceLock;
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -78,6 +81,8 @@ pub trait HashFunctionFactory: Sized + Clone + Debug + Send + Sync + 'static {
 
     const NULL_BASE: u8;
     const USABLE_HASH_BITS: usize;
+    /// Number of bits of the hash used by get_shifted to radix sort the hashes
+    const SORTING_KEY_BITS: usize = std::mem::size_of::<Self::HashTypeUnextendable>() * 8;
 
     fn initialize(k: usize);
     fn new<N: HashableSequence>(seq: N, k: usize) -> Self::HashIterator<N>;
@@ -135,6 +140,13 @@ pub trait MinimizerHashFunctionFactory: HashFunctionFactory {
     fn get_full_minimizer(
         hash: <Self as HashFunctionFactory>::HashTypeUnextendable,
     ) -> MinimizerType;
+
+    /// Sets or clears the highest bit of the full minimizer, so that the low priority hashes are
+    /// always greater than the high priority ones without changing the bits used for the buckets
+    fn set_minimizer_priority(
+        hash: <Self as HashFunctionFactory>::HashTypeUnextendable,
+        high_priority: bool,
+    ) -> <Self as HashFunctionFactory>::HashTypeUnextendable;
 }
 
 pub trait HashFunction<HF: HashFunctionFactory> {
@@ -162,30 +174,43 @@ impl HashableSequence for &[u8] {
     }
 }
 
-const RMMULT_CACHE_SIZE: usize = 8;
+/// Number of k values whose Rabin-Karp multiplier power is cached, bigger k values compute it on each use
+const RMMULT_CACHE_SIZE: usize = 1024;
 
-fn init_rmmult(k: usize, multiplier: u128) -> [u128; RMMULT_CACHE_SIZE] {
-    fn fastexp(base: u128, mut exp: usize) -> u128 {
-        let mut result: u128 = 1;
-        let mut sqv = base;
+fn compute_rmmult(k: usize, multiplier: u128) -> u128 {
+    let mut exp = k.saturating_sub(1);
+    let mut result: u128 = 1;
+    let mut sqv = multiplier;
 
-        while exp > 0 {
-            if exp & 0x1 == 1 {
-                result = result.wrapping_mul(sqv);
-            }
-            exp /= 2;
-            sqv = sqv.wrapping_mul(sqv);
+    while exp > 0 {
+        if exp & 0x1 == 1 {
+            result = result.wrapping_mul(sqv);
         }
-
-        result
+        exp /= 2;
+        sqv = sqv.wrapping_mul(sqv);
     }
 
-    let mut cache = [0; RMMULT_CACHE_SIZE];
-    for k in k.saturating_sub(RMMULT_CACHE_SIZE / 2)..(k + RMMULT_CACHE_SIZE / 2) {
-        cache[k % RMMULT_CACHE_SIZE] = fastexp(multiplier, k.saturating_sub(1));
+    result
+}
+
+/// Powers of a Rabin-Karp multiplier used to remove the first base of a kmer, cached for each k.
+/// Every entry is computed only once, so that instances using different k values can share the cache
+struct RmmultCache([OnceLock<u128>; RMMULT_CACHE_SIZE]);
+
+impl RmmultCache {
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: OnceLock<u128> = OnceLock::new();
+        Self([EMPTY; RMMULT_CACHE_SIZE])
     }
 
-    cache
+    #[inline(always)]
+    fn get(&self, k: usize, multiplier: u128) -> u128 {
+        match self.0.get(k) {
+            Some(rmmult) => *rmmult.get_or_init(|| compute_rmmult(k, multiplier)),
+            None => compute_rmmult(k, multiplier),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -444,4 +469,30 @@ pub mod tests {
             }
         }
     }
+
+    #[test]
+    fn rmmult_cache_per_k() {
+        use super::{compute_rmmult, RmmultCache, RMMULT_CACHE_SIZE};
+
+        const MULTIPLIER: u128 = 0x5be9ad5f4cd6b75b;
+        static CACHE: RmmultCache = RmmultCache::new();
+
+        let kvalues = [1, 15, 31, 63, RMMULT_CACHE_SIZE + 5];
+
+        std::thread::scope(|scope| {
+            for k in kvalues {
+                scope.spawn(move || {
+                    for _ in 0..1000 {
+                        assert_eq!(CACHE.get(k, MULTIPLIER), compute_rmmult(k, MULTIPLIER));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(CACHE.get(1, MULTIPLIER), 1);
+        assert_eq!(
+            CACHE.get(3, MULTIPLIER),
+            MULTIPLIER.wrapping_mul(MULTIPLIER)
+        );
+    }
 }
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+
+macro_rules! long_hash_type {
+    ($name:ident, $words:expr) => {
+        /// Multi-word integer holding up to 32 * WORDS 2-bit encoded bases,
+        /// the first base is stored in the lowest bits of the first word
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+        pub struct $name(pub [u64; $words]);
+
+        impl $name {
+            pub const WORDS: usize = $words;
+            pub const BYTES: usize = $words * 8;
+            pub const MAX_BASES: usize = $words * 32;
+
+            /// Removes the first base, moving all the other ones one position back
+            #[inline(always)]
+            pub fn shift_bases_right(mut self) -> Self {
+                for i in 0..(Self::WORDS - 1) {
+                    self.0[i] = (self.0[i] >> 2) | (self.0[i + 1] << 62);
+                }
+                self.0[Self::WORDS - 1] >>= 2;
+                self
+            }
+
+            /// Moves all the bases one position forward, leaving the first position empty
+            #[inline(always)]
+            pub fn shift_bases_left(mut self) -> Self {
+                for i in (1..Self::WORDS).rev() {
+                    self.0[i] = (self.0[i] << 2) | (self.0[i - 1] >> 62);
+                }
+                self.0[0] <<= 2;
+                self
+            }
+
+            /// Sets the base at the given (empty) position
+            #[inline(always)]
+            pub fn with_base(mut self, index: usize, base: u8) -> Self {
+                self.0[index / 32] |= (base as u64) << ((index % 32) * 2);
+                self
+            }
+
+            /// Keeps only the first bases_count bases
+            #[inline(always)]
+            pub fn masked(mut self, bases_count: usize) -> Self {
+                for (i, word) in self.0.iter_mut().enumerate() {
+                    let word_start = i * 32;
+                    if bases_count <= word_start {
+                        *word = 0;
+                    } else if bases_count - word_start < 32 {
+                        *word &= (1 << ((bases_count - word_start) * 2)) - 1;
+                    }
+                }
+                self
+            }
+
+            /// Compares the values as if they were a single big integer
+            #[inline(always)]
+            pub fn numeric_cmp(&self, other: &Self) -> Ordering {
+                self.0.iter().rev().cmp(other.0.iter().rev())
+            }
+
+            /// Mix of all the words, used to assign the buckets and to sort the hashes
+            #[inline(always)]
+            pub fn fingerprint(&self) -> u64 {
+                let mut result = 0u64;
+                for word in self.0 {
+                    result = (result ^ word)
+                        .wrapping_mul(0x9E3779B97F4A7C15)
+                        .rotate_left(31);
+                }
+                result ^= result >> 33;
+                result = result.wrapping_mul(0xFF51AFD7ED558CCD);
+                result ^ (result >> 29)
+            }
+
+            pub fn to_le_bytes(self) -> [u8; $words * 8] {
+                let mut bytes = [0; $words * 8];
+                for (i, word) in self.0.iter().enumerate() {
+                    bytes[(i * 8)..((i + 1) * 8)].copy_from_slice(&word.to_le_bytes());
+                }
+                bytes
+            }
+        }
+
+        // The ordering must be consistent with the bits returned by get_shifted, used for radix sorting
+        impl Ord for $name {
+            #[inline(always)]
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.fingerprint()
+                    .cmp(&other.fingerprint())
+                    .then_with(|| self.numeric_cmp(other))
+            }
+        }
+
+        impl PartialOrd for $name {
+            #[inline(always)]
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                for word in self.0.iter().rev() {
+                    write!(f, "{:016x}", word)?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+long_hash_type!(LongHash256, 4);
+long_hash_type!(LongHash512, 8);
+
+#[cfg(test)]
+mod tests {
+    use super::LongHash256;
+
+    #[test]
+    fn long_hash_shifts() {
+        let value = (0..100).fold(LongHash256::default(), |value, index| {
+            value.with_base(index, (index % 4) as u8)
+        });
+
+        assert_eq!(value.shift_bases_left().shift_bases_right(), value);
+        assert_eq!(value.shift_bases_right().0[0] & 0x3, 1);
+        assert_eq!(value.shift_bases_left().0[1] & 0x3, 3);
+        assert_eq!(value.masked(34).0[0], value.0[0]);
+        assert_eq!(value.masked(34).0[1], 1 << 2);
+        assert_eq!(value.masked(34).0[2], 0);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::rolling::minqueue::RollingMinQueue;
+use crate::{
+    ExtendableHashTraitType, HashFunction, HashableSequence, MinimizerHashFunctionFactory,
+};
+use config::MinimizerType;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+pub use config::MinimizerScheme;
+
+/// Returns the minimizer scheme of the instance running on the current thread
+pub fn get_minimizer_scheme() -> MinimizerScheme {
+    config::instance::with_current(|context| context.minimizer_scheme())
+}
+
+/// Sets the minimizers that should be avoided by the FrequencyAware scheme in the current instance
+pub fn set_frequent_minimizers(filter: Option<FrequentMinimizersFilter>) {
+    config::instance::with_current(|context| {
+        context.set_minimizers_filter(filter.map(|f| Arc::new(f) as Arc<dyn Any + Send + Sync>))
+    });
+}
+
+pub fn get_frequent_minimizers() -> Option<Arc<FrequentMinimizersFilter>> {
+    config::instance::with_current(|context| context.get_minimizers_filter())
+        .and_then(|filter| filter.downcast::<FrequentMinimizersFilter>().ok())
+}
+
+const FILTER_BITS_LOG: u32 = 24;
+
+/// Bitmap of the frequent minimizers, indexed by the highest bits of the minimizer.
+/// False positives only lower the priority of some more minimizers, so they do not affect correctness
+pub struct FrequentMinimizersFilter {
+    bits: Vec<u64>,
+}
+
+impl FrequentMinimizersFilter {
+    #[inline(always)]
+    fn index(minimizer: MinimizerType) -> usize {
+        (minimizer >> (MinimizerType::BITS - FILTER_BITS_LOG)) as usize
+    }
+
+    pub fn new(frequent_minimizers: impl Iterator<Item = MinimizerType>) -> Self {
+        let mut bits = vec![0; (1 << FILTER_BITS_LOG) / 64];
+        for minimizer in frequent_minimizers {
+            let index = Self::index(minimizer);
+            bits[index / 64] |= 1 << (index % 64);
+        }
+        Self { bits }
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, minimizer: MinimizerType) -> bool {
+        let index = Self::index(minimizer);
+        (self.bits[index / 64] >> (index % 64)) & 1 != 0
+    }
+}
+
+/// Calls out(window_start, minimum_position) for each window of window_size consecutive keys,
+/// choosing the leftmost position if there are multiple minimums
+fn sliding_leftmost_min(
+    keys: &[MinimizerType],
+    window_size: usize,
+    deque: &mut VecDeque<usize>,
+    mut out: impl FnMut(usize, usize),
+) {
+    deque.clear();
+    for (index, key) in keys.iter().enumerate() {
+        while deque.back().map(|last| keys[*last] > *key).unwrap_or(false) {
+            deque.pop_back();
+        }
+        deque.push_back(index);
+
+        if index + 1 >= window_size {
+            let start = index + 1 - window_size;
+            while *deque.front().unwrap() < start {
+                deque.pop_front();
+            }
+            out(start, *deque.front().unwrap());
+        }
+    }
+}
+
+struct SchemeBuffers<H: MinimizerHashFunctionFactory> {
+    mmers: Vec<H::HashTypeUnextendable>,
+    keys: Vec<MinimizerType>,
+    deque: VecDeque<usize>,
+}
+
+impl<H: MinimizerHashFunctionFactory> SchemeBuffers<H> {
+    fn new() -> Self {
+        Self {
+            mmers: Vec::new(),
+            keys: Vec::new(),
+            deque: VecDeque::new(),
+        }
+    }
+
+    fn compute_mmers<S: HashableSequence>(&mut self, sequence: S, m: usize) {
+        self.mmers.clear();
+        self.mmers
+            .extend(H::new(sequence, m).iter().map(|h| h.to_unextendable()));
+    }
+
+    fn compute_keys<S: HashableSequence>(&mut self, sequence: S, length: usize) {
+        self.keys.clear();
+        self.keys.extend(
+            H::new(sequence, length)
+                .iter()
+                .map(|h| H::get_full_minimizer(h.to_unextendable())),
+        );
+    }
+
+    /// Fills mmers with the m-mers hashes, lowering the priority of the ones that should be avoided
+    fn compute_ranked_mmers<S: HashableSequence>(
+        &mut self,
+        scheme: MinimizerScheme,
+        frequent_minimizers: Option<&FrequentMinimizersFilter>,
+        sequence: S,
+        m: usize,
+    ) {
+        self.compute_mmers(sequence.clone(), m);
+        match scheme {
+            MinimizerScheme::OpenSyncmers { s } | MinimizerScheme::ClosedSyncmers { s } => {
+                let s = s.clamp(1, m);
+                let closed = matches!(scheme, MinimizerScheme::ClosedSyncmers { .. });
+                self.compute_keys(sequence, s);
+                let mmers = &mut self.mmers;
+                sliding_leftmost_min(&self.keys, m - s + 1, &mut self.deque, |start, pos| {
+                    let is_syncmer = pos == start || (closed && pos == start + m - s);
+                    mmers[start] = H::set_minimizer_priority(mmers[start], is_syncmer);
+                });
+            }
+            MinimizerScheme::FrequencyAware => {
+                if let Some(frequent_minimizers) = frequent_minimizers {
+                    for mmer in self.mmers.iter_mut() {
+                        let is_frequent =
+                            frequent_minimizers.contains(H::get_full_minimizer(*mmer));
+                        *mmer = H::set_minimizer_priority(*mmer, !is_frequent);
+                    }
+                }
+            }
+            MinimizerScheme::Random | MinimizerScheme::ModMinimizers { .. } => {}
+        }
+    }
+
+    /// Calls out with the mod-minimizer of each window of window_size m-mers
+    fn compute_mod_minimizers<S: HashableSequence>(
+        &mut self,
+        sequence: S,
+        m: usize,
+        r: usize,
+        window_size: usize,
+        mut out: impl FnMut(H::HashTypeUnextendable),
+    ) {
+        let r = r.clamp(1, m);
+        let t = r + ((m - r) % window_size);
+        self.compute_mmers(sequence.clone(), m);
+        self.compute_keys(sequence, t);
+        let mmers = &self.mmers;
+        sliding_leftmost_min(
+            &self.keys,
+            window_size + m - t,
+            &mut self.deque,
+            |start, pos| out(mmers[start + (pos - start) % window_size]),
+        );
+    }
+}
+
+enum MinimizersIterator<R, K, M> {
+    Random(R),
+    Ranked(K),
+    Mod(M),
+}
+
+impl<T, R: Iterator<Item = T>, K: Iterator<Item = T>, M: Iterator<Item = T>> Iterator
+    for MinimizersIterator<R, K, M>
+{
+    type Item = T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            MinimizersIterator::Random(iter) => iter.next(),
+            MinimizersIterator::Ranked(iter) => iter.next(),
+            MinimizersIterator::Mod(iter) => iter.next(),
+        }
+    }
+}
+
+/// Computes the minimizers of sequences according to the global minimizer scheme
+pub struct MinimizerSelector<H: MinimizerHashFunctionFactory> {
+    scheme: MinimizerScheme,
+    frequent_minimizers: Option<Arc<FrequentMinimizersFilter>>,
+    window_size: usize,
+    minimizer_queue: RollingMinQueue<H>,
+    buffers: SchemeBuffers<H>,
+    minimizers: Vec<H::HashTypeUnextendable>,
+}
+
+impl<H: MinimizerHashFunctionFactory> MinimizerSelector<H> {
+    /// Creates a selector for windows of window_size m-mers
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            scheme: get_minimizer_scheme(),
+            frequent_minimizers: get_frequent_minimizers(),
+            window_size,
+            minimizer_queue: RollingMinQueue::new(window_size),
+            buffers: SchemeBuffers::new(),
+            minimizers: Vec::new(),
+        }
+    }
+
+    /// Returns the minimizer of each window of the sequence, that must contain at least one window
+    pub fn rolling_minimizers<'a, S: HashableSequence + 'a>(
+        &'a mut self,
+        sequence: S,
+        m: usize,
+    ) -> impl Iterator<Item = H::HashTypeUnextendable> + 'a {
+        match self.scheme {
+            MinimizerScheme::Random => MinimizersIterator::Random(
+                self.minimizer_queue
+                    .make_iter(H::new(sequence, m).iter().map(|x| x.to_unextendable())),
+            ),
+            MinimizerScheme::ModMinimizers { r } => {
+                self.minimizers.clear();
+                let minimizers = &mut self.minimizers;
+                self.buffers.compute_mod_minimizers(
+                    sequence,
+                    m,
+                    r,
+                    self.window_size,
+                    |minimizer| minimizers.push(minimizer),
+                );
+                MinimizersIterator::Mod(self.minimizers.iter().copied())
+            }
+            scheme => {
+                self.buffers.compute_ranked_mmers(
+                    scheme,
+                    self.frequent_minimizers.as_deref(),
+                    sequence,
+                    m,
+                );
+                MinimizersIterator::Ranked(
+                    self.minimizer_queue
+                        .make_iter(self.buffers.mmers.iter().copied()),
+                )
+            }
+        }
+    }
+
+    /// Returns the minimizer of a sequence made of exactly one window,
+    /// consistent with the ones returned by rolling_minimizers
+    pub fn window_minimizer<S: HashableSequence>(
+        &mut self,
+        sequence: S,
+        m: usize,
+    ) -> H::HashTypeUnextendable {
+        match self.scheme {
+            MinimizerScheme::Random => H::new(sequence, m)
+                .iter()
+                .map(|h| h.to_unextendable())
+                .min_by_key(|h| H::get_full_minimizer(*h))
+                .unwrap(),
+            MinimizerScheme::ModMinimizers { r } => {
+                let window_size = sequence.bases_count() - m + 1;
+                let mut window_minimizer = None;
+                self.buffers
+                    .compute_mod_minimizers(sequence, m, r, window_size, |minimizer| {
+                        window_minimizer = Some(minimizer)
+                    });
+                window_minimizer.unwrap()
+            }
+            scheme => {
+                self.buffers.compute_ranked_mmers(
+                    scheme,
+                    self.frequent_minimizers.as_deref(),
+                    sequence,
+                    m,
+                );
+                self.buffers
+                    .mmers
+                    .iter()
+                    .copied()
+                    .min_by_key(|h| H::get_full_minimizer(*h))
+                    .unwrap()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fw_nthash::ForwardNtHashIteratorFactory;
+    use crate::minimizer_scheme::{MinimizerScheme, MinimizerSelector};
+    use crate::MinimizerHashFunctionFactory;
+    use config::instance::{self, InstanceContext, InstanceSettings};
+    use rand::{RngCore, SeedableRng};
+
+    #[test]
+    fn minimizer_schemes_consistency() {
+        const M: usize = 12;
+        const WINDOW: usize = 20;
+
+        let mut random = pcg_rand::Pcg64::seed_from_u64(2);
+        let sequence: Vec<u8> = (0..2000)
+            .map(|_| b"ACGT"[(random.next_u32() % 4) as usize])
+            .collect();
+
+        for scheme in [
+            MinimizerScheme::Random,
+            MinimizerScheme::OpenSyncmers { s: 5 },
+            MinimizerScheme::ClosedSyncmers { s: 5 },
+            MinimizerScheme::ModMinimizers { r: 4 },
+        ] {
+            instance::enter(&InstanceContext::new(InstanceSettings {
+                minimizer_scheme: scheme,
+                ..Default::default()
+            }));
+            let mut selector = MinimizerSelector::<ForwardNtHashIteratorFactory>::new(WINDOW);
+            let rolling: Vec<_> = selector.rolling_minimizers(&sequence[..], M).collect();
+            assert_eq!(rolling.len(), sequence.len() - (WINDOW + M - 1) + 1);
+
+            for (start, minimizer) in rolling.into_iter().enumerate() {
+                let window_minimizer =
+                    selector.window_minimizer(&sequence[start..start + WINDOW + M - 1], M);
+                assert_eq!(
+                    ForwardNtHashIteratorFactory::get_full_minimizer(minimizer),
+                    ForwardNtHashIteratorFactory::get_full_minimizer(window_minimizer),
+                    "Scheme: {:?} window: {}",
+                    scheme,
+                    start
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::compressed_read::CompressedRead;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use utils::Utils;
+
+const BUCKET_CHECKSUM_OFFSET: u64 = 0xcbf29ce484222325;
+const BUCKET_CHECKSUM_PRIME: u64 = 0x100000001b3;
+
+#[inline]
+fn bucket_sequence_checksum(encoded_bases: impl Iterator<Item = u8>, flags: u8) -> u64 {
+    encoded_bases.fold(BUCKET_CHECKSUM_OFFSET ^ flags as u64, |hash, base| {
+        (hash ^ base as u64).wrapping_mul(BUCKET_CHECKSUM_PRIME)
+    })
+}
+
+/// Checksum of a sequence written to the temporary buckets, computed on the 2-bit encoded bases so that
+/// it is the same of the packed sequence that is read back. The checksums of the sequences of a bucket
+/// are summed (wrapping), as their order is not preserved when the bucket is read
+#[inline]
+pub fn plain_sequence_checksum(sequence: &[u8], flags: u8) -> u64 {
+    bucket_sequence_checksum(sequence.iter().map(|b| Utils::compress_base(*b)), flags)
+}
+
+/// Checksum of a sequence read from the temporary buckets, see plain_sequence_checksum
+#[inline]
+pub fn packed_sequence_checksum(read: &CompressedRead, flags: u8) -> u64 {
+    bucket_sequence_checksum(read.as_bases_iter().map(Utils::compress_base), flags)
+}
+
+/// Result of checking a file against its checksum file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileChecksumStatus {
+    /// The file has no checksum file
+    Missing,
+    Valid,
+    Mismatch {
+        expected: u32,
+        found: u32,
+    },
+}
+
+/// Path of the file holding the checksum of the given file, with the .crc32 extension appended
+pub fn checksum_file_path(file: impl AsRef<Path>) -> PathBuf {
+    let mut path = file.as_ref().as_os_str().to_owned();
+    path.push(".crc32");
+    PathBuf::from(path)
+}
+
+/// Computes the CRC32 of the whole content of a file
+pub fn compute_file_checksum(file: impl AsRef<Path>) -> std::io::Result<u32> {
+    let mut reader = BufReader::with_capacity(1024 * 1024, File::open(file)?);
+    let mut hasher = crc32fast::Hasher::new();
+
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        hasher.update(buffer);
+        let length = buffer.len();
+        reader.consume(length);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Computes the checksum of a file and writes it next to the file, in a line with the checksum
+/// in hexadecimal followed by the file name
+pub fn write_file_checksum(file: impl AsRef<Path>) -> std::io::Result<u32> {
+    let file = file.as_ref();
+    let checksum = compute_file_checksum(file)?;
+
+    let mut checksum_file = File::create(checksum_file_path(file))?;
+    writeln!(
+        checksum_file,
+        "{:08x}  {}",
+        checksum,
+        file.file_name().unwrap_or_default().to_string_lossy()
+    )?;
+
+    Ok(checksum)
+}
+
+/// Checks a file against the checksum written by write_file_checksum
+pub fn verify_file_checksum(file: impl AsRef<Path>) -> std::io::Result<FileChecksumStatus> {
+    let file = file.as_ref();
+    let checksum_file = checksum_file_path(file);
+
+    if !checksum_file.exists() {
+        return Ok(FileChecksumStatus::Missing);
+    }
+
+    let content = std::fs::read_to_string(&checksum_file)?;
+    let expected = content
+        .split_whitespace()
+        .next()
+        .and_then(|checksum| u32::from_str_radix(checksum, 16).ok())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid checksum file {}", checksum_file.display()),
+            )
+        })?;
+
+    let found = compute_file_checksum(file)?;
+    Ok(if found == expected {
+        FileChecksumStatus::Valid
+    } else {
+        FileChecksumStatus::Mismatch { expected, found }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checksum::{packed_sequence_checksum, plain_sequence_checksum};
+    use crate::compressed_read::CompressedReadIndipendent;
+
+    #[test]
+    fn bucket_checksums() {
+        let sequences: [&[u8]; 3] = [
+            b"ACGTTGCAACGTAGGCTAGGAT",
+            b"acgtAC",
+            b"GGGGGGGGGGGGGGGGGGGGC",
+        ];
+
+        let mut written = 0u64;
+        let mut read = 0u64;
+        let mut storage = Vec::new();
+        for (flags, sequence) in sequences.iter().enumerate() {
+            written = written.wrapping_add(plain_sequence_checksum(sequence, flags as u8));
+
+            storage.clear();
+            let packed = CompressedReadIndipendent::from_plain(sequence, &mut storage);
+            let packed = packed.as_reference(&storage);
+            read = read.wrapping_add(packed_sequence_checksum(&packed, flags as u8));
+        }
+        assert_eq!(written, read);
+
+        // The order of the sequences does not change the sum
+        let reversed = sequences
+            .iter()
+            .enumerate()
+            .rev()
+            .fold(0u64, |sum, (flags, sequence)| {
+                sum.wrapping_add(plain_sequence_checksum(sequence, flags as u8))
+            });
+        assert_eq!(reversed, written);
+
+        assert_ne!(
+            plain_sequence_checksum(b"ACGT", 0),
+            plain_sequence_checksum(b"ACGA", 0)
+        );
+        assert_ne!(
+            plain_sequence_checksum(b"ACGT", 0),
+            plain_sequence_checksum(b"ACGT", 1)
+        );
+        assert_ne!(
+            plain_sequence_checksum(b"ACGT", 0),
+            plain_sequence_checksum(b"ACGTA", 0)
+        );
+    }
+}
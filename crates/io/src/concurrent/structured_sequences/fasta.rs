@@ -65,6 +65,18 @@ impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>
         }
     }
 
+    /// Creates a writer compressed with lz4 or gzip if the path has the corresponding extension, or a plain one otherwise
+    pub fn new_from_extension(path: impl AsRef<Path>, level: u32) -> Self {
+        match path.as_ref().extension() {
+            Some(ext) => match ext.to_string_lossy().to_string().as_str() {
+                "lz4" => Self::new_compressed_lz4(path, level),
+                "gz" => Self::new_compressed_gzip(path, level),
+                _ => Self::new_plain(path),
+            },
+            None => Self::new_plain(path),
+        }
+    }
+
     pub fn new_plain(path: impl AsRef<Path>) -> Self {
         FastaWriter {
             writer: Box::new(BufWriter::with_capacity(
@@ -5,6 +5,7 @@ use config::{BucketIndexType, MAX_BUCKETS_COUNT_LOG, MAX_BUCKET_SIZE, MIN_BUCKET
 use std::cmp::{max, min};
 use std::path::{Path, PathBuf};
 
+pub mod checksum;
 pub mod chunks_writer;
 pub mod compressed_read;
 pub mod concurrent;
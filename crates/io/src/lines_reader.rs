@@ -1,5 +1,6 @@
 use bstr::ByteSlice;
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use log::warn;
 use parallel_processor::mt_debug_counters::counter::{AtomicCounter, AvgMode, SumMode};
 use parallel_processor::mt_debug_counters::{declare_avg_counter_i64, declare_counter_i64};
 use std::fs::File;
@@ -70,10 +71,7 @@ impl LinesReader {
                 },
                 DEFAULT_OUTPUT_BUFFER_SIZE,
             ) {
-                println!(
-                    "WARNING: Error while reading file {}",
-                    path.as_ref().display()
-                );
+                warn!("Error while reading file {}", path.as_ref().display());
             }
             callback(&[]);
         } else if path.as_ref().extension().filter(|x| *x == "lz4").is_some() {
@@ -83,20 +81,14 @@ impl LinesReader {
             .unwrap();
             self.read_stream_buffered(file, callback)
                 .unwrap_or_else(|_| {
-                    println!(
-                        "WARNING: Error while reading file {}",
-                        path.as_ref().display()
-                    );
+                    warn!("Error while reading file {}", path.as_ref().display());
                 });
         } else {
             let file =
                 File::open(&path).expect(&format!("Cannot open file {}", path.as_ref().display()));
             self.read_stream_buffered(file, callback)
                 .unwrap_or_else(|_| {
-                    println!(
-                        "WARNING: Error while reading file {}",
-                        path.as_ref().display()
-                    );
+                    warn!("Error while reading file {}", path.as_ref().display());
                 });
         }
 
@@ -152,10 +144,7 @@ impl LinesReader {
                 // File finished
                 if buffer.len() == 0 {
                     if line_pending {
-                        eprintln!(
-                            "WARNING: No newline at ending of file '{}'",
-                            file.as_ref().display()
-                        );
+                        warn!("No newline at ending of file '{}'", file.as_ref().display());
                     }
                     callback(&[], false, true);
                     return;
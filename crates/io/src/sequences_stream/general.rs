@@ -15,6 +15,7 @@ pub trait DynamicSequencesStream: Sync + Send + 'static {
     fn estimated_base_count(&self, block: usize) -> u64;
 }
 
+#[derive(Clone)]
 pub enum GeneralSequenceBlockData {
     FASTA(<FastaFileSequencesStream as GenericSequencesStream>::SequenceBlockData),
     GFA(),
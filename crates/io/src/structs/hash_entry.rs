@@ -108,7 +108,7 @@ pub struct HashCompare<H: HashFunctionFactory> {
 
 impl<H: HashFunctionFactory> SortKey<HashEntry<H::HashTypeUnextendable>> for HashCompare<H> {
     type KeyType = H::HashTypeUnextendable;
-    const KEY_BITS: usize = size_of::<H::HashTypeUnextendable>() * 8;
+    const KEY_BITS: usize = H::SORTING_KEY_BITS;
 
     #[inline(always)]
     fn compare(
@@ -2,12 +2,14 @@ use config::{
     BucketIndexType, DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, READ_FLAG_INCL_END,
     USE_SECOND_BUCKET,
 };
+use hashes::minimizer_scheme::MinimizerSelector;
 use hashes::{
     ExtendableHashTraitType, HashFunction, HashFunctionFactory, HashableSequence,
     MinimizerHashFunctionFactory,
 };
 use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketDataSerializer;
+use log::debug;
 use parallel_processor::buckets::readers::async_binary_reader::{
     AsyncBinaryReader, AsyncReaderThread,
 };
@@ -16,6 +18,7 @@ use std::collections::HashSet;
 use std::path::PathBuf;
 
 fn get_sequence_bucket<C, H: MinimizerHashFunctionFactory>(
+    minimizer_selector: &mut MinimizerSelector<H>,
     k: usize,
     m: usize,
     seq_data: &(u8, u8, C, CompressedRead),
@@ -26,18 +29,10 @@ fn get_sequence_bucket<C, H: MinimizerHashFunctionFactory>(
     let flags = seq_data.0;
     let decr_val = ((read.bases_count() == k) && (flags & READ_FLAG_INCL_END) == 0) as usize;
 
-    let hashes = H::new(read.sub_slice((1 - decr_val)..(k - decr_val)), m);
+    let minimizer =
+        minimizer_selector.window_minimizer(read.sub_slice((1 - decr_val)..(k - decr_val)), m);
 
-    let minimizer = hashes
-        .iter()
-        .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
-        .unwrap();
-
-    H::get_bucket(
-        used_hash_bits,
-        bucket_bits_count,
-        minimizer.to_unextendable(),
-    )
+    H::get_bucket(used_hash_bits, bucket_bits_count, minimizer)
 }
 
 pub fn compute_stats_for_bucket<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory>(
@@ -72,9 +67,11 @@ pub fn compute_stats_for_bucket<H: MinimizerHashFunctionFactory, MH: HashFunctio
     >>(reader_thread.clone(), Vec::new(), ());
 
     let mut total_counters = vec![0; second_buckets_max];
+    let mut minimizer_selector = MinimizerSelector::<H>::new(k - m);
 
     while let Some((read_info, _)) = items_iterator.next() {
         let orig_bucket = get_sequence_bucket::<(), H>(
+            &mut minimizer_selector,
             k,
             m,
             &read_info,
@@ -100,8 +97,8 @@ pub fn compute_stats_for_bucket<H: MinimizerHashFunctionFactory, MH: HashFunctio
     let tot_seqs = total_counters.iter().sum::<usize>();
     let uniq_seqs = hash_maps.iter().map(|h| h.len()).sum::<usize>();
 
-    println!("Stats for bucket: {}", bucket_index);
-    println!(
+    debug!("Stats for bucket: {}", bucket_index);
+    debug!(
         "FSIZE: {} SEQUENCES: {}/{} UNIQUE_RATIO: {} COMPR_RATIO: {} ",
         file_size,
         tot_seqs,
@@ -109,5 +106,5 @@ pub fn compute_stats_for_bucket<H: MinimizerHashFunctionFactory, MH: HashFunctio
         (tot_seqs as f64 / uniq_seqs as f64),
         (file_size as f64 / tot_seqs as f64)
     );
-    println!("Results: {}", counters_string);
+    debug!("Results: {}", counters_string);
 }
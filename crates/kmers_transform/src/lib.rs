@@ -7,15 +7,20 @@ mod reader;
 use crate::processor::KmersTransformProcessor;
 use crate::reader::{InputBucketDesc, KmersTransformReader};
 use crate::resplitter::KmersTransformResplitter;
+use config::instance::{self, InstanceContext};
+use config::memory_budget::{self, MemoryReservation};
+use config::progress;
 use config::{
-    BucketIndexType, KEEP_FILES, KMERS_TRANSFORM_READS_CHUNKS_SIZE, MAXIMUM_JIT_PROCESSED_BUCKETS,
-    MAXIMUM_SECOND_BUCKETS_COUNT, MINIMUM_LOG_DELTA_TIME, PACKETS_PRIORITY_FILES,
+    remove_temp_files, BucketIndexType, KMERS_TRANSFORM_READS_CHUNKS_SIZE,
+    MAXIMUM_JIT_PROCESSED_BUCKETS, MAXIMUM_SECOND_BUCKETS_COUNT, MINIMUM_LOG_DELTA_TIME,
+    PACKETS_PRIORITY_FILES,
 };
 use io::compressed_read::{CompressedRead, CompressedReadIndipendent};
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraDataConsecutiveCompression, SequenceExtraDataTempBufferManagement,
 };
 use io::get_bucket_index;
+use log::info;
 use minimizer_bucketing::counters_analyzer::CountersAnalyzer;
 use minimizer_bucketing::MinimizerBucketingExecutorFactory;
 use parallel_processor::execution_manager::execution_context::{ExecutionContext, PoolAllocMode};
@@ -25,7 +30,6 @@ use parallel_processor::execution_manager::packet::{Packet, PacketTrait};
 use parallel_processor::execution_manager::thread_pool::ExecThreadPool;
 use parallel_processor::execution_manager::units_io::{ExecutorInput, ExecutorInputAddressMode};
 use parallel_processor::memory_fs::MemoryFs;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parking_lot::Mutex;
 use std::cmp::{max, min};
 use std::marker::PhantomData;
@@ -74,7 +78,7 @@ pub trait KmersTransformPreprocessor<F: KmersTransformExecutorFactory>:
     Sized + 'static + Sync + Send
 {
     fn get_sequence_bucket<C>(
-        &self,
+        &mut self,
         global_data: &F::GlobalExtraData,
         seq_data: &(u8, u8, C, CompressedRead),
         used_hash_bits: usize,
@@ -160,6 +164,9 @@ pub struct KmersTransformContext<F: KmersTransformExecutorFactory> {
     unique_kmers: AtomicU64,
 
     reader_init_lock: tokio::sync::Mutex<()>,
+
+    /// Context of the instance running the transform, entered by the executors threads
+    instance: Arc<InstanceContext>,
 }
 
 impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
@@ -173,10 +180,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
         k: usize,
         min_bucket_size: u64,
     ) -> Self {
-        let counters = CountersAnalyzer::load_from_file(
-            buckets_counters_path,
-            !KEEP_FILES.load(Ordering::Relaxed),
-        );
+        let counters = CountersAnalyzer::load_from_file(buckets_counters_path, remove_temp_files());
 
         let mut total_buckets_size = 0;
 
@@ -212,6 +216,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
                     resplitted: false,
                     rewritten: false,
                     used_hash_bits: buckets_count.ilog2() as usize,
+                    checksum: Some(counters.get_checksum_for_bucket(bucket_index)),
                 });
             }
 
@@ -238,6 +243,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
                     resplitted: false,
                     rewritten: false,
                     used_hash_bits: buckets_count.ilog2() as usize,
+                    checksum: Some(counters.get_checksum_for_bucket(bucket_index)),
                 })
             }
             buckets_list
@@ -269,6 +275,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
             total_kmers: AtomicU64::new(0),
             unique_kmers: AtomicU64::new(0),
             reader_init_lock: tokio::sync::Mutex::new(()),
+            instance: instance::current(),
         });
 
         Self {
@@ -301,15 +308,17 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
         //     ExecutorInputAddressMode::Multiple,
         // );
 
+        let packets_size = memory_budget::get_packets_size(KMERS_TRANSFORM_READS_CHUNKS_SIZE);
+        // The readers packets are kept in their pools until the end of the transform
+        let _packets_reservation =
+            MemoryReservation::with_size(self.global_context.max_buckets * packets_size);
+
         let bucket_readers = disk_thread_pool.register_executors::<KmersTransformReader<F>>(
             read_threads_count,
             PoolAllocMode::Distinct {
                 capacity: self.global_context.max_buckets,
             },
-            max(
-                16,
-                KMERS_TRANSFORM_READS_CHUNKS_SIZE / self.global_context.k,
-            ),
+            max(16, packets_size / self.global_context.k),
             &self.global_context,
         );
 
@@ -386,7 +395,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
 
             extra_debug();
 
-            let monitor = PHASES_TIMES_MONITOR.read();
+            let phase_time = progress::get_phase_time();
 
             let buckets_count = self.global_context.buckets_count;
             let extra_buckets_count = self
@@ -429,16 +438,16 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
                 self.global_context.total_buckets_size + eta_extra_processed_size;
 
             let eta = Duration::from_secs(
-                (monitor.get_phase_timer().as_secs_f64() / (eta_processed_size as f64)
+                (phase_time.as_secs_f64() / (eta_processed_size as f64)
                     * (eta_remaining_size as f64)) as u64,
             );
 
             let est_tot = Duration::from_secs(
-                (monitor.get_phase_timer().as_secs_f64() / (eta_processed_size as f64)
+                (phase_time.as_secs_f64() / (eta_processed_size as f64)
                     * (eta_total_buckets_size as f64)) as u64,
             );
 
-            println!(
+            info!(
                 "Processing bucket {}{} of [{}{}[R:{}]] {} phase eta: {:.0?} est. tot: {:.0?}",
                 processed_count,
                 if extra_processed_buckets_count > 0 {
@@ -453,7 +462,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
                     String::new()
                 },
                 rewritten_buckets_count,
-                monitor.get_formatted_counter_without_memory(),
+                progress::get_times_desc(),
                 eta,
                 est_tot
             );
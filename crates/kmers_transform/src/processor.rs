@@ -3,6 +3,8 @@ use crate::{
     KmersTransformContext, KmersTransformExecutorFactory, KmersTransformFinalExecutor,
     KmersTransformMapProcessor,
 };
+use config::{instance, memory_budget};
+use log::debug;
 use parallel_processor::execution_manager::executor::{AsyncExecutor, ExecutorReceiver};
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::objects_pool::PoolObjectTrait;
@@ -50,6 +52,7 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
         memory_tracker: MemoryTracker<Self>,
     ) -> Self::AsyncExecutorFuture<'a> {
         async move {
+            let _panic_guard = instance::enter_executor(&global_context.instance);
             let mut map_processor =
                 F::new_map_processor(&global_context.global_extra_data, memory_tracker.clone());
             let mut final_executor = F::new_final_executor(&global_context.global_extra_data);
@@ -61,6 +64,10 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
             while let Ok((address, proc_info)) =
                 track!(receiver.obtain_address().await, ADDR_WAITING_COUNTER)
             {
+                // Avoid starting a new map while the processing memory is over the hard limit
+                if let Err(error) = memory_budget::wait_for_available_memory() {
+                    global_context.instance.fail(error.to_string());
+                }
                 map_processor.process_group_start(packet, &global_context.global_extra_data);
 
                 let mut real_size = 0;
@@ -70,6 +77,9 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
                 while let Some(input_packet) =
                     track!(address.receive_packet().await, PACKET_WAITING_COUNTER)
                 {
+                    if global_context.instance.is_cancelled() {
+                        continue;
+                    }
                     real_size += input_packet.reads.len();
                     let stats = map_processor.process_group_batch_sequences(
                         &global_context.global_extra_data,
@@ -100,7 +110,7 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
 
                 if real_size != proc_info.sequences_count {
                     //MAX_PACKET_SIZE.fetch_max(current_size, Ordering::Relaxed) < current_size {
-                    println!(
+                    debug!(
                         "Found bucket with max size {} ==> {} // EXPECTED_SIZE: {} REAL_SIZE: {} SUB: {}",
                         current_size,
                         proc_info.bucket_path.display(),
@@ -5,14 +5,16 @@ use crate::{
     KmersTransformContext, KmersTransformExecutorFactory, KmersTransformMapProcessor,
     KmersTransformPreprocessor,
 };
+use config::progress::{self, ProgressEvent};
 use config::{
-    get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_OUTPUT_BUFFER_SIZE,
-    DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
-    MAXIMUM_JIT_PROCESSED_BUCKETS, MAX_INTERMEDIATE_MAP_SIZE, MIN_BUCKET_CHUNKS_FOR_READING_THREAD,
-    PACKETS_PRIORITY_DEFAULT, PACKETS_PRIORITY_REWRITTEN, PARTIAL_VECS_CHECKPOINT_SIZE,
-    USE_SECOND_BUCKET,
+    get_compression_level_info, get_memory_mode, remove_temp_files, SwapPriority,
+    DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT,
+    MAXIMUM_JIT_PROCESSED_BUCKETS, MIN_BUCKET_CHUNKS_FOR_READING_THREAD, PACKETS_PRIORITY_DEFAULT,
+    PACKETS_PRIORITY_REWRITTEN, PARTIAL_VECS_CHECKPOINT_SIZE, USE_SECOND_BUCKET,
 };
+use config::{instance, memory_budget};
 use instrumenter::local_setup_instrumenter;
+use io::checksum::packed_sequence_checksum;
 use io::compressed_read::CompressedReadIndipendent;
 use io::concurrent::temp_reads::creads_utils::{
     CompressedReadsBucketData, CompressedReadsBucketDataSerializer,
@@ -58,6 +60,8 @@ pub struct InputBucketDesc {
     pub(crate) resplitted: bool,
     pub(crate) rewritten: bool,
     pub(crate) used_hash_bits: usize,
+    /// Sum of the checksums of the sequences written to the bucket, known only for the minimizer buckets
+    pub(crate) checksum: Option<u64>,
 }
 
 impl PoolObjectTrait for InputBucketDesc {
@@ -70,12 +74,14 @@ impl PoolObjectTrait for InputBucketDesc {
             resplitted: false,
             rewritten: false,
             used_hash_bits: 0,
+            checksum: None,
         }
     }
 
     fn reset(&mut self) {
         self.resplitted = false;
         self.sub_bucket_counters.clear();
+        self.checksum = None;
     }
 }
 impl PacketTrait for InputBucketDesc {
@@ -108,6 +114,7 @@ enum AddressMode {
 }
 
 struct BucketsInfo {
+    path: PathBuf,
     reader: AsyncBinaryReader,
     concurrency: usize,
     addresses: Vec<AddressMode>,
@@ -116,6 +123,8 @@ struct BucketsInfo {
     second_buckets_log_max: usize,
     file_size: usize,
     used_hash_bits: usize,
+    expected_checksum: Option<u64>,
+    read_checksum: AtomicU64,
 }
 
 impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
@@ -132,7 +141,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             &file.path,
             true,
             RemoveFileMode::Remove {
-                remove_fs: file.rewritten || !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: file.rewritten || remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         );
@@ -174,6 +183,9 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             global_context.k as f64 / 2.0
         };
 
+        // Smaller when a hard memory limit is set, to resplit more buckets
+        let max_intermediate_map_size = memory_budget::get_max_intermediate_map_size();
+
         while bucket_sizes.len() > 0 {
             let buckets_count = queue.len();
             let mut smallest_bucket = queue.pop().unwrap();
@@ -183,7 +195,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             let is_outlier = !file.resplitted
                 && (total_sequences > 0)
                 && (biggest_sub_bucket.0.count as f64 * unique_estimator_factor
-                    >= (MAX_INTERMEDIATE_MAP_SIZE / F::MapProcessorType::MAP_SIZE as u64) as f64);
+                    >= (max_intermediate_map_size / F::MapProcessorType::MAP_SIZE as u64) as f64);
 
             // if is_outlier {
             //     println!(
@@ -305,6 +317,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
         // );
 
         BucketsInfo {
+            path: file.path.clone(),
             reader,
             concurrency,
             addresses,
@@ -313,6 +326,8 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             second_buckets_log_max,
             file_size,
             used_hash_bits: file.used_hash_bits,
+            expected_checksum: file.checksum,
+            read_checksum: AtomicU64::new(0),
         }
     }
 
@@ -372,7 +387,8 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
         async_reader_thread: Arc<AsyncReaderThread>,
         packets_pool: Arc<PoolObject<PacketsPool<ReadsBuffer<F::AssociatedExtraData>>>>,
     ) {
-        if bucket_info.reader.is_finished() {
+        // Skip the remaining buckets if the operation was cancelled
+        if bucket_info.reader.is_finished() || global_context.instance.is_cancelled() {
             return;
         }
 
@@ -389,7 +405,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             START_PACKET_ALLOC_COUNTER
         );
 
-        let preprocessor = F::new_preprocessor(&global_context.global_extra_data);
+        let mut preprocessor = F::new_preprocessor(&global_context.global_extra_data);
 
         let global_extra_data = &global_context.global_extra_data;
 
@@ -407,6 +423,8 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                 F::AssociatedExtraData::new_temp_buffer(),
             );
 
+        let mut read_checksum = 0u64;
+
         while let Some((read_info, extra_buffer)) = items_iterator.next() {
             let bucket = if has_single_addr {
                 0
@@ -423,6 +441,10 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
 
             let (flags, _second_bucket, mut extra_data, read) = read_info;
 
+            if bucket_info.expected_checksum.is_some() {
+                read_checksum = read_checksum.wrapping_add(packed_sequence_checksum(&read, flags));
+            }
+
             let ind_read =
                 CompressedReadIndipendent::from_read(&read, &mut buffers[bucket].reads_buffer);
             extra_data = F::AssociatedExtraData::copy_extra_from(
@@ -457,6 +479,10 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             F::AssociatedExtraData::clear_temp_buffer(extra_buffer);
         }
 
+        bucket_info
+            .read_checksum
+            .fetch_add(read_checksum, Ordering::Relaxed);
+
         for (bucket, (mut packet, address)) in buffers
             .drain(..)
             .zip(bucket_info.addresses.iter())
@@ -503,6 +529,7 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
         _memory_tracker: MemoryTracker<Self>,
     ) -> Self::AsyncExecutorFuture<'a> {
         async move {
+            let _panic_guard = instance::enter_executor(&global_context.instance);
             let mut async_threads = Vec::new();
 
             while let Ok((address, _)) =
@@ -565,6 +592,19 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                 spawner.executors_await().await;
                 drop(spawner);
 
+                // The checksum is not complete if the reading was stopped by a cancellation
+                if let Some(expected) = buckets_info.expected_checksum {
+                    let found = buckets_info.read_checksum.load(Ordering::Relaxed);
+                    if found != expected && !global_context.instance.is_cancelled() {
+                        panic!(
+                            "Corrupted temporary bucket {}: the checksum of its sequences is {:016x}, expected {:016x}",
+                            buckets_info.path.display(),
+                            found,
+                            expected
+                        );
+                    }
+                }
+
                 for addr in buckets_info.addresses {
                     if let AddressMode::Rewrite(writer, seq_count, init_data) = addr {
                         let new_bucket_address =
@@ -593,6 +633,7 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                                 rewritten: true,
                                 used_hash_bits: init_data.used_hash_bits
                                     + init_data.buckets_hash_bits,
+                                checksum: None,
                             }),
                         );
                     }
@@ -614,6 +655,19 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                         .fetch_add(buckets_info.file_size, Ordering::Relaxed);
                 }
 
+                if is_main_bucket || is_resplitted {
+                    progress::report_progress(|| ProgressEvent::BucketsProcessed {
+                        processed: global_context
+                            .processed_buckets_count
+                            .load(Ordering::Relaxed)
+                            + global_context
+                                .processed_extra_buckets_count
+                                .load(Ordering::Relaxed),
+                        total: global_context.buckets_count
+                            + global_context.extra_buckets_count.load(Ordering::Relaxed),
+                    });
+                }
+
                 assert!(track!(
                     address.receive_packet().await.is_none(),
                     PACKET_WAITING_COUNTER
@@ -1,6 +1,8 @@
 use crate::reader::{InputBucketDesc, KmersTransformReader};
 use crate::reads_buffer::ReadsBuffer;
 use crate::{KmersTransformContext, KmersTransformExecutorFactory};
+use config::instance;
+use config::memory_budget::MemoryReservation;
 use config::{
     get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
     DEFAULT_PER_CPU_BUFFER_SIZE, MAXIMUM_JIT_PROCESSED_BUCKETS, MAX_RESPLIT_BUCKETS_COUNT_LOG,
@@ -131,9 +133,9 @@ impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
 
         let mut local_counters = vec![0u8; resplit_info.global_counters.len()];
 
-        // mem_tracker.update_memory_usage(&[
-        //     DEFAULT_PER_CPU_BUFFER_SIZE.octets as usize * mt_buckets.count()
-        // ]);
+        let _buffers_reservation = MemoryReservation::with_size(
+            DEFAULT_PER_CPU_BUFFER_SIZE.as_bytes() * resplit_info.buckets.count(),
+        );
 
         while let Some(input_packet) = track!(ops.receive_packet().await, PACKET_WAITING_COUNTER) {
             let input_packet = input_packet.deref();
@@ -209,6 +211,7 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformResplitte
         _memory_tracker: MemoryTracker<Self>,
     ) -> Self::AsyncExecutorFuture<'a> {
         async move {
+            let _panic_guard = instance::enter_executor(&global_context.instance);
             while let Ok((address, init_data)) =
                 track!(receiver.obtain_address().await, ADDR_WAITING_COUNTER)
             {
@@ -251,6 +254,7 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformResplitte
                             resplitted: true,
                             rewritten: false,
                             used_hash_bits: 0,
+                            checksum: None,
                         }),
                     );
                 }
@@ -1,6 +1,9 @@
-use config::BucketIndexType;
+use config::{get_memory_mode, BucketIndexType, SwapPriority};
+use log::debug;
+use parallel_processor::memory_fs::file::reader::FileReader;
+use parallel_processor::memory_fs::file::writer::FileWriter;
+use parallel_processor::memory_fs::{MemoryFs, RemoveFileMode};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::Path;
 use std::sync::atomic::AtomicU64;
@@ -13,11 +16,12 @@ pub struct BucketCounter {
 #[derive(Serialize, Deserialize)]
 pub struct CountersAnalyzer {
     counters: Vec<Vec<BucketCounter>>,
+    checksums: Vec<u64>,
     median: u64,
 }
 
 impl CountersAnalyzer {
-    pub fn new(counters: Vec<Vec<AtomicU64>>) -> Self {
+    pub fn new(counters: Vec<Vec<AtomicU64>>, checksums: Vec<AtomicU64>) -> Self {
         let mut sorted_counters: Vec<(u64, usize, usize)> = Vec::new();
 
         let counters: Vec<Vec<BucketCounter>> = counters
@@ -44,15 +48,24 @@ impl CountersAnalyzer {
             0
         };
 
-        Self { counters, median }
+        Self {
+            counters,
+            checksums: checksums.into_iter().map(|c| c.into_inner()).collect(),
+            median,
+        }
     }
 
     pub fn get_counters_for_bucket(&self, bucket: BucketIndexType) -> &Vec<BucketCounter> {
         &self.counters[bucket as usize]
     }
 
+    /// Sum of the checksums of the sequences written to the bucket
+    pub fn get_checksum_for_bucket(&self, bucket: BucketIndexType) -> u64 {
+        self.checksums[bucket as usize]
+    }
+
     pub fn print_debug(&self) {
-        println!("************** BUCKETS DEBUG: **************");
+        debug!("************** BUCKETS DEBUG: **************");
         // for (i, cnt_bucket) in self.counters.iter().enumerate() {
         //     let mut buffer = String::new();
         //     for cnt_sub_bucket in cnt_bucket.iter() {
@@ -64,8 +77,8 @@ impl CountersAnalyzer {
         //     }
         //     println!("{} SIZES: {}", i, buffer);
         // }
-        println!("Sub-bucket median: {}", self.median);
-        println!(
+        debug!("Sub-bucket median: {}", self.median);
+        debug!(
             "Sub-bucket maximum: {}",
             self.counters
                 .iter()
@@ -76,7 +89,10 @@ impl CountersAnalyzer {
     }
 
     pub fn load_from_file(path: impl AsRef<Path>, remove: bool) -> Self {
-        let file = BufReader::new(File::open(&path).unwrap());
+        let file = BufReader::new(
+            FileReader::open(&path, None)
+                .expect(&format!("Cannot open file {}", path.as_ref().display())),
+        );
         let rval: CountersAnalyzer = bincode::deserialize_from(file).unwrap();
 
         // rval.counters.iter_mut().enumerate().for_each(|(bn, x)| {
@@ -91,16 +107,17 @@ impl CountersAnalyzer {
         // rval.print_debug();
 
         if remove {
-            let _ = std::fs::remove_file(path);
+            let _ = MemoryFs::remove_file(&path, RemoveFileMode::Remove { remove_fs: true });
         }
         rval
     }
 
     pub fn serialize_to_file(&self, path: impl AsRef<Path>) {
-        let file = BufWriter::new(
-            File::create(path.as_ref())
-                .expect(&format!("Cannot open file {}", path.as_ref().display())),
-        );
+        // Written through the memory fs, so that it is never stored on disk in memory-only mode
+        let file = BufWriter::new(FileWriter::create(
+            path.as_ref(),
+            get_memory_mode(SwapPriority::MinimizerBuckets),
+        ));
         bincode::serialize_into(file, self).unwrap();
     }
 }
@@ -0,0 +1,99 @@
+use config::MinimizerType;
+use config::{instance, progress};
+use hashes::minimizer_scheme::{set_frequent_minimizers, FrequentMinimizersFilter};
+use hashes::rolling::minqueue::RollingMinQueue;
+use hashes::{ExtendableHashTraitType, HashFunction, MinimizerHashFunctionFactory};
+use io::sequences_stream::GenericSequencesStream;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+const MAX_SAMPLED_BLOCKS: usize = 8;
+const MAX_SAMPLED_BASES_PER_BLOCK: usize = 1024 * 1024 * 32;
+
+/// A minimizer is considered frequent if it starts this many times the average number of super-kmers
+const FREQUENT_MINIMIZER_FACTOR: u64 = 16;
+const FREQUENT_MINIMIZER_MIN_COUNT: u64 = 64;
+
+/// Samples the minimizers of the first bases of some input blocks, and sets as frequent
+/// the ones that start many more super-kmers than the average, used by the FrequencyAware scheme
+pub fn sample_frequent_minimizers<H: MinimizerHashFunctionFactory, S: GenericSequencesStream>(
+    input_blocks: &[&S::SequenceBlockData],
+    threads_count: usize,
+    window_size: usize,
+    m: usize,
+) {
+    progress::start_phase("phase: frequent minimizers sampling".to_string());
+
+    let step = (input_blocks.len() + MAX_SAMPLED_BLOCKS - 1) / MAX_SAMPLED_BLOCKS;
+    let sampled_blocks: Vec<_> = input_blocks.iter().step_by(step.max(1)).collect();
+    let next_block = Mutex::new(sampled_blocks.into_iter());
+    let counts = Mutex::new(HashMap::<MinimizerType, u64>::new());
+
+    let instance_context = instance::current();
+    std::thread::scope(|scope| {
+        for _ in 0..threads_count.max(1).min(MAX_SAMPLED_BLOCKS) {
+            scope.spawn(|| {
+                instance::enter(&instance_context);
+                let mut stream = S::new();
+                let mut minimizer_queue = RollingMinQueue::<H>::new(window_size);
+                let mut uppercase_part = Vec::new();
+
+                loop {
+                    let Some(block) = next_block.lock().next() else {
+                        break;
+                    };
+                    let mut block_counts = HashMap::new();
+                    let mut sampled_bases = 0;
+
+                    stream.read_block(block, false, None, |sequence, _info| {
+                        if sampled_bases >= MAX_SAMPLED_BASES_PER_BLOCK {
+                            return;
+                        }
+                        sampled_bases += sequence.seq.len();
+
+                        for part in sequence.seq.split(|base| {
+                            !matches!(*base, b'A' | b'C' | b'G' | b'T' | b'a' | b'c' | b'g' | b't')
+                        }) {
+                            if part.len() < window_size + m - 1 {
+                                continue;
+                            }
+
+                            uppercase_part.clear();
+                            uppercase_part.extend_from_slice(part);
+                            uppercase_part.make_ascii_uppercase();
+                            let part = &uppercase_part[..];
+
+                            let mut last_minimizer = None;
+                            for minimizer in minimizer_queue
+                                .make_iter(H::new(part, m).iter().map(|h| h.to_unextendable()))
+                                .map(H::get_full_minimizer)
+                            {
+                                if last_minimizer != Some(minimizer) {
+                                    *block_counts.entry(minimizer).or_insert(0) += 1;
+                                    last_minimizer = Some(minimizer);
+                                }
+                            }
+                        }
+                    });
+
+                    let mut counts = counts.lock();
+                    for (minimizer, count) in block_counts {
+                        *counts.entry(minimizer).or_insert(0) += count;
+                    }
+                }
+            });
+        }
+    });
+
+    let counts = counts.into_inner();
+    let total_count: u64 = counts.values().sum();
+    let threshold = (total_count / counts.len().max(1) as u64 * FREQUENT_MINIMIZER_FACTOR)
+        .max(FREQUENT_MINIMIZER_MIN_COUNT);
+
+    set_frequent_minimizers(Some(FrequentMinimizersFilter::new(
+        counts
+            .into_iter()
+            .filter(|(_, count)| *count >= threshold)
+            .map(|(minimizer, _)| minimizer),
+    )));
+}
@@ -2,6 +2,7 @@
 #![feature(impl_trait_in_assoc_type)]
 
 pub mod counters_analyzer;
+pub mod frequent_minimizers;
 mod queue_data;
 mod reader;
 mod sequences_splitter;
@@ -10,6 +11,9 @@ use crate::counters_analyzer::CountersAnalyzer;
 use crate::queue_data::MinimizerBucketingQueueData;
 use crate::reader::MinimizerBucketingFilesReader;
 use crate::sequences_splitter::SequencesSplitter;
+use config::instance::{self, InstanceContext};
+use config::memory_budget::MemoryReservation;
+use config::progress::{self, ProgressEvent};
 use config::{
     get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
     DEFAULT_PER_CPU_BUFFER_SIZE, MINIMIZER_BUCKETS_CHECKPOINT_SIZE, PACKETS_PRIORITY_DEFAULT,
@@ -17,6 +21,7 @@ use config::{
 };
 use config::{MAXIMUM_SECOND_BUCKETS_COUNT, USE_SECOND_BUCKET};
 use hashes::HashableSequence;
+use io::checksum::plain_sequence_checksum;
 use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::creads_utils::{
     CompressedReadsBucketData, CompressedReadsBucketDataSerializer,
@@ -26,6 +31,7 @@ use io::concurrent::temp_reads::extra_data::{
 };
 use io::sequences_reader::DnaSequence;
 use io::sequences_stream::{GenericSequencesStream, SequenceInfo};
+use log::info;
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
 use parallel_processor::buckets::MultiThreadBuckets;
@@ -37,7 +43,6 @@ use parallel_processor::execution_manager::executor_address::ExecutorAddress;
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::thread_pool::ExecThreadPool;
 use parallel_processor::execution_manager::units_io::{ExecutorInput, ExecutorInputAddressMode};
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parking_lot::RwLock;
 use std::cmp::max;
 use std::future::Future;
@@ -149,6 +154,8 @@ pub struct MinimizerBucketingCommonData<GlobalData> {
     pub max_second_buckets_count: usize,
     pub max_second_buckets_count_bits: usize,
     pub global_counters: Vec<Vec<AtomicU64>>,
+    /// Sum of the checksums of the sequences written to each bucket, checked when the buckets are read
+    pub buckets_checksums: Vec<AtomicU64>,
     pub global_data: GlobalData,
 }
 
@@ -178,6 +185,7 @@ impl<GlobalData> MinimizerBucketingCommonData<GlobalData> {
                         .collect()
                 })
                 .collect(),
+            buckets_checksums: (0..buckets_count).map(|_| AtomicU64::new(0)).collect(),
             global_data,
         }
     }
@@ -195,6 +203,9 @@ pub struct MinimizerBucketingExecutionContext<GlobalData> {
 
     pub partial_read_copyback: Option<usize>,
     pub copy_ident: bool,
+
+    /// Context of the instance running the bucketing, entered by the executors threads
+    pub instance: Arc<InstanceContext>,
 }
 
 pub struct GenericMinimizerBucketing;
@@ -217,6 +228,7 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
         let counters_log = context.common.max_second_buckets_count.ilog2();
         let mut counters: Vec<u8> =
             vec![0; context.common.max_second_buckets_count * context.common.buckets_count];
+        let mut checksums: Vec<u64> = vec![0; context.common.buckets_count];
 
         let mut tmp_reads_buffer = BucketsThreadDispatcher::<
             _,
@@ -230,9 +242,9 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
             BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, context.buckets.count()),
         );
 
-        // self.mem_tracker.update_memory_usage(&[
-        //     DEFAULT_PER_CPU_BUFFER_SIZE.octets as usize * context.buckets.count()
-        // ]);
+        let _buffers_reservation = MemoryReservation::with_size(
+            DEFAULT_PER_CPU_BUFFER_SIZE.as_bytes() * context.buckets.count(),
+        );
         let global_counters = &context.common.global_counters;
 
         while let Some(input_packet) = ops.receive_packet().await {
@@ -273,6 +285,9 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
                                     .fetch_add(256, Ordering::Relaxed);
                             }
 
+                            checksums[bucket as usize] = checksums[bucket as usize]
+                                .wrapping_add(plain_sequence_checksum(seq, flags));
+
                             tmp_reads_buffer.add_element_extended(
                                 bucket,
                                 &extra,
@@ -291,6 +306,12 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
                 TOT_BASES_COUNT.fetch_add(total_bases, Ordering::Relaxed) + total_bases;
             VALID_BASES_COUNT.fetch_add(sequences_splitter.valid_bases, Ordering::Relaxed);
 
+            progress::report_progress(|| ProgressEvent::BytesRead {
+                bytes: total_bases_count,
+                processed_files: context.processed_files.load(Ordering::Relaxed),
+                total_files: context.total_files,
+            });
+
             const TOTAL_BASES_DIFF_LOG: u64 = 10000000000;
 
             let do_print_log = LAST_TOTAL_COUNT
@@ -307,7 +328,7 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
                 let current_file = context.current_file.load(Ordering::Relaxed);
                 let processed_files = context.processed_files.load(Ordering::Relaxed);
 
-                println!(
+                info!(
                     "Elaborated {} sequences! [{} | {:.2}% qb] ({}[{}]/{} => {:.2}%) {}",
                     SEQ_COUNT.load(Ordering::Relaxed),
                     VALID_BASES_COUNT.load(Ordering::Relaxed),
@@ -318,9 +339,7 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
                     current_file,
                     context.total_files,
                     processed_files as f64 / max(1, context.total_files) as f64 * 100.0,
-                    PHASES_TIMES_MONITOR
-                        .read()
-                        .get_formatted_counter_without_memory()
+                    progress::get_times_desc()
                 );
             }
         }
@@ -334,6 +353,10 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
             }
         }
 
+        for (bucket, checksum) in checksums.into_iter().enumerate() {
+            context.common.buckets_checksums[bucket].fetch_add(checksum, Ordering::Relaxed);
+        }
+
         tmp_reads_buffer.finalize();
     }
 }
@@ -361,6 +384,7 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> AsyncExecutor
         _memory_tracker: MemoryTracker<Self>,
     ) -> Self::AsyncExecutorFuture<'a> {
         async move {
+            let _panic_guard = instance::enter_executor(&global_params.instance);
             while let Ok((address, _)) = receiver.obtain_address().await {
                 let max_concurrency = global_params.threads_count;
 
@@ -453,6 +477,7 @@ impl GenericMinimizerBucketing {
             partial_read_copyback,
             read_threads_count,
             copy_ident,
+            instance: instance::current(),
         });
 
         {
@@ -524,7 +549,10 @@ impl GenericMinimizerBucketing {
         let common_context = Arc::try_unwrap(global_context.common)
             .unwrap_or_else(|_| panic!("Cannot get common execution context!"));
 
-        let counters_analyzer = CountersAnalyzer::new(common_context.global_counters);
+        let counters_analyzer = CountersAnalyzer::new(
+            common_context.global_counters,
+            common_context.buckets_checksums,
+        );
         // counters_analyzer.print_debug();
 
         let counters_file = output_path.join("buckets-counters.dat");
@@ -1,5 +1,6 @@
 use crate::queue_data::MinimizerBucketingQueueData;
 use crate::MinimizerBucketingExecutionContext;
+use config::instance;
 use io::sequences_stream::GenericSequencesStream;
 use nightly_quirks::branch_pred::unlikely;
 use parallel_processor::execution_manager::executor::{
@@ -52,6 +53,11 @@ impl<
         let mut sequences_stream = SequencesStream::new();
 
         while let Some(mut input_packet) = ops.receive_packet().await {
+            // Skip the remaining files if the operation was cancelled
+            if context.instance.is_cancelled() {
+                continue;
+            }
+
             let mut data_packet = packets_pool.alloc_packet().await;
             let stream_info = input_packet.1.clone();
 
@@ -154,6 +160,7 @@ impl<
         _memory_tracker: MemoryTracker<Self>,
     ) -> Self::AsyncExecutorFuture<'a> {
         async move {
+            let _panic_guard = instance::enter_executor(&global_params.instance);
             while let Ok((address, _)) = receiver.obtain_address().await {
                 let read_threads_count = global_params.read_threads_count;
 
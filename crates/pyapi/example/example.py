@@ -0,0 +1,42 @@
+import ggcat
+
+instance = ggcat.GGCATInstance(temp_dir="/tmp", memory=2.0, threads_count=16)
+
+k = 31
+
+# Example building of a colored graph from three FASTA files
+# building also bcalm2-style links across maximal unitigs
+graph_file = instance.build(
+    ["../../../example-inputs/sal1.fa", "../../../example-inputs/sal2.fa", "../../../example-inputs/sal3.fa"],
+    "/tmp/sal-dbg.fa",
+    k,
+    colors=True,
+    color_names=["sal1", "sal2", "sal3"],
+    min_multiplicity=1,
+    extra_elaboration="unitig_links",
+)
+
+# Example building of a graph from in-memory sequences, with one color for each list
+instance.build_from_sequences(
+    [["ACGTACGTAGCTAGCTAGCTAGCTAGCATCGATCGACTAGCTA"], [("seq2", "TTAGCTAGCTAGCTAGCATCGATCGACTAGCTAGGCTAGCAT")]],
+    "/tmp/memory-dbg.fa",
+    k,
+    colors=True,
+    min_multiplicity=1,
+)
+
+color_names = ggcat.dump_colors(ggcat.get_colormap_file(graph_file))
+
+# The query results are returned as a list of dicts if no output file is given,
+# with the fraction of the query kmers found in each color index
+for result in instance.query(graph_file, "../../../example-inputs/query.fa", k, colors=True):
+    print("Query {}: {}".format(result["query_index"], {color_names[c]: f for c, f in result["matches"].items()}))
+
+# The queries can also be given as in-memory sequences
+for result in instance.query_from_sequences(graph_file, ["ACGTACGTAGCTAGCTAGCTAGCTAGCATCGATCGACTAGCTA"], k, colors=True):
+    print("Memory query {}: {}".format(result["query_index"], result["matches"]))
+
+for sequence, colors, same_colors in instance.dump_unitigs(graph_file, k, colors=True):
+    print("Dump unitig '{}'".format(sequence if len(sequence) < 100 else sequence[:100] + "..."))
+    if not same_colors:
+        print("\tColors: {}".format([color_names[c] for c in colors]))
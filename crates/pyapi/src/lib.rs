@@ -0,0 +1,862 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use ggcat_api::{
+    BuildOptions, CancellationToken, ColorIndexType, ColoredQueryOutputFormat, DnaSequence,
+    DnaSequencesFileType, DynamicSequencesStream, ExtraElaboration, GGCATConfig, GGCATInstance,
+    GeneralSequenceBlockData, MinimizerScheme, QueryOptions, SequenceInfo,
+};
+use numpy::PyArray1;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyFileNotFoundError, PyIOError, PyMemoryError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyString, PyTuple};
+
+create_exception!(ggcat, GGCATError, PyException);
+create_exception!(ggcat, CancelledError, GGCATError);
+
+/// Maximum number of unitigs buffered by the dump_unitigs iterator before blocking the dumping threads
+const DUMP_UNITIGS_QUEUE_SIZE: usize = 1024;
+
+fn to_py_error(error: ggcat_api::GGCATError) -> PyErr {
+    match error {
+        ggcat_api::GGCATError::InvalidOptions(reason) => PyValueError::new_err(reason),
+        ggcat_api::GGCATError::InputFileNotFound(path) => {
+            PyFileNotFoundError::new_err(path.display().to_string())
+        }
+        ggcat_api::GGCATError::Io(error) => PyIOError::new_err(error.to_string()),
+        ggcat_api::GGCATError::Cancelled => CancelledError::new_err("Operation cancelled"),
+        ggcat_api::GGCATError::OutOfMemory(message) => PyMemoryError::new_err(message),
+        error => GGCATError::new_err(error.to_string()),
+    }
+}
+
+fn parse_extra_elaboration(extra_elaboration: &str) -> PyResult<ExtraElaboration> {
+    Ok(match extra_elaboration {
+        "none" => ExtraElaboration::None,
+        "unitig_links" => ExtraElaboration::UnitigLinks,
+        "greedy_matchtigs" => ExtraElaboration::GreedyMatchtigs,
+        "eulertigs" => ExtraElaboration::Eulertigs,
+        "pathtigs" => ExtraElaboration::Pathtigs,
+        "color_compacted_unitigs" => ExtraElaboration::ColorCompactedUnitigs,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid extra elaboration: {}, expected one of none, unitig_links, greedy_matchtigs, eulertigs, pathtigs, color_compacted_unitigs",
+                extra_elaboration
+            )))
+        }
+    })
+}
+
+fn parse_color_output_format(color_output_format: &str) -> PyResult<ColoredQueryOutputFormat> {
+    Ok(match color_output_format {
+        "numbers" => ColoredQueryOutputFormat::JsonLinesWithNumbers,
+        "names" => ColoredQueryOutputFormat::JsonLinesWithNames,
+        "names_and_metadata" => ColoredQueryOutputFormat::JsonLinesWithNamesAndMetadata,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid color output format: {}, expected numbers, names or names_and_metadata",
+                color_output_format
+            )))
+        }
+    })
+}
+
+/// Reads a sequence or a sequence name given either as a str or as bytes
+fn extract_bytes(object: &PyAny) -> PyResult<Vec<u8>> {
+    if let Ok(string) = object.downcast::<PyString>() {
+        Ok(string.to_str()?.as_bytes().to_vec())
+    } else {
+        Ok(object.downcast::<PyBytes>()?.as_bytes().to_vec())
+    }
+}
+
+/// Sequences copied from the python iterables, each iterable is a block with the color of its index
+struct MemorySequencesStream {
+    blocks: Vec<Vec<(Vec<u8>, Vec<u8>)>>,
+}
+
+impl MemorySequencesStream {
+    /// Each item of the iterables can be a sequence or a (name, sequence) tuple
+    fn from_iterables(iterables: &PyAny) -> PyResult<Self> {
+        let mut blocks = vec![];
+        for iterable in iterables.iter()? {
+            let mut block = vec![];
+            for (index, item) in iterable?.iter()?.enumerate() {
+                let item = item?;
+                if let Ok(tuple) = item.downcast::<PyTuple>() {
+                    let (name, sequence): (&PyAny, &PyAny) = tuple.extract()?;
+                    block.push((extract_bytes(name)?, extract_bytes(sequence)?));
+                } else {
+                    block.push((index.to_string().into_bytes(), extract_bytes(item)?));
+                }
+            }
+            blocks.push(block);
+        }
+        Ok(Self { blocks })
+    }
+}
+
+impl DynamicSequencesStream for MemorySequencesStream {
+    fn read_block(
+        &self,
+        block: usize,
+        copy_ident_data: bool,
+        _partial_read_copyback: Option<usize>,
+        callback: &mut dyn FnMut(DnaSequence, SequenceInfo),
+    ) {
+        for (name, sequence) in &self.blocks[block] {
+            callback(
+                DnaSequence {
+                    ident_data: if copy_ident_data { name } else { &[] },
+                    seq: sequence,
+                    format: DnaSequencesFileType::FASTA,
+                },
+                SequenceInfo {
+                    color: Some(block as ColorIndexType),
+                },
+            );
+        }
+    }
+
+    fn estimated_base_count(&self, block: usize) -> u64 {
+        self.blocks[block]
+            .iter()
+            .map(|(_, sequence)| sequence.len() as u64)
+            .sum()
+    }
+}
+
+/// Query sequences given as a fasta file or as in-memory streams
+enum QueryInput {
+    File(PathBuf),
+    Streams(Vec<GeneralSequenceBlockData>),
+}
+
+/// Token that can be used to stop a running build or query from another thread,
+/// making it raise a CancelledError
+#[pyclass(name = "CancellationToken")]
+#[derive(Clone, Default)]
+struct PyCancellationToken(CancellationToken);
+
+#[pymethods]
+impl PyCancellationToken {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+/// Iterator over the (sequence, colors, same_colors) unitigs of a graph, with the colors as a numpy uint32 array.
+/// same_colors is true if the colors are the same of the previous unitig, so the colors array can be reused
+#[pyclass]
+struct UnitigsIterator {
+    receiver: Mutex<Receiver<(Vec<u8>, Vec<ColorIndexType>, bool)>>,
+    dump_thread: Option<JoinHandle<()>>,
+}
+
+#[pymethods]
+impl UnitigsIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(
+        mut slf: PyRefMut<'_, Self>,
+        py: Python<'_>,
+    ) -> PyResult<Option<(Py<PyString>, Py<PyArray1<ColorIndexType>>, bool)>> {
+        let receiver = &slf.receiver;
+        match py.allow_threads(|| receiver.lock().unwrap().recv()) {
+            Ok((sequence, colors, same_colors)) => Ok(Some((
+                PyString::new(py, &String::from_utf8_lossy(&sequence)).into(),
+                PyArray1::from_vec(py, colors).into(),
+                same_colors,
+            ))),
+            Err(_) => {
+                if let Some(dump_thread) = slf.dump_thread.take() {
+                    if py.allow_threads(|| dump_thread.join()).is_err() {
+                        return Err(GGCATError::new_err("Failed to dump the unitigs"));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// GGCAT instance, with its own threads pool and settings
+#[pyclass(name = "GGCATInstance")]
+struct PyGGCATInstance {
+    instance: Arc<GGCATInstance>,
+    threads_count: usize,
+}
+
+impl PyGGCATInstance {
+    fn build_options(
+        &self,
+        k: usize,
+        threads_count: Option<usize>,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        color_names: Option<Vec<String>>,
+        min_multiplicity: usize,
+        extra_elaboration: &str,
+        cancellation_token: Option<PyCancellationToken>,
+    ) -> PyResult<BuildOptions> {
+        let mut options = BuildOptions::builder(k)
+            .threads_count(threads_count.unwrap_or(self.threads_count))
+            .forward_only(forward_only)
+            .colors(colors)
+            .color_names(color_names.unwrap_or_default())
+            .min_multiplicity(min_multiplicity)
+            .extra_elaboration(parse_extra_elaboration(extra_elaboration)?);
+        if let Some(minimizer_length) = minimizer_length {
+            options = options.minimizer_length(minimizer_length);
+        }
+        if let Some(cancellation_token) = cancellation_token {
+            options = options.cancellation_token(cancellation_token.0);
+        }
+        options.build().map_err(to_py_error)
+    }
+
+    fn run_query(
+        &self,
+        py: Python<'_>,
+        input_graph: PathBuf,
+        input_query: QueryInput,
+        output_file_prefix: Option<PathBuf>,
+        k: usize,
+        threads_count: Option<usize>,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        color_output_format: &str,
+        cancellation_token: Option<PyCancellationToken>,
+    ) -> PyResult<PyObject> {
+        let mut options = QueryOptions::builder(k)
+            .threads_count(threads_count.unwrap_or(self.threads_count))
+            .forward_only(forward_only)
+            .colors(colors)
+            .color_output_format(parse_color_output_format(color_output_format)?);
+        if let Some(minimizer_length) = minimizer_length {
+            options = options.minimizer_length(minimizer_length);
+        }
+        if let Some(cancellation_token) = cancellation_token {
+            options = options.cancellation_token(cancellation_token.0);
+        }
+
+        // Without an output file the results are collected from the callback, and the file is only temporary
+        let results = Arc::new(Mutex::new(
+            BTreeMap::<usize, Vec<(ColorIndexType, f64)>>::new(),
+        ));
+        let return_results = output_file_prefix.is_none();
+        if return_results {
+            let results = results.clone();
+            options = options.results_callback(Arc::new(move |query_index, color, fraction| {
+                results
+                    .lock()
+                    .unwrap()
+                    .entry(query_index)
+                    .or_default()
+                    .push((color, fraction));
+            }));
+        }
+        let options = options.build().map_err(to_py_error)?;
+
+        let output_file_prefix = output_file_prefix.unwrap_or_else(|| {
+            static QUERY_INDEX: AtomicUsize = AtomicUsize::new(0);
+            std::env::temp_dir().join(format!(
+                "ggcat_query_{}_{}",
+                std::process::id(),
+                QUERY_INDEX.fetch_add(1, Ordering::Relaxed)
+            ))
+        });
+
+        let instance = self.instance.clone();
+        let output_file = py
+            .allow_threads(move || match input_query {
+                QueryInput::File(input_query) => {
+                    instance.query(input_graph, input_query, output_file_prefix, &options)
+                }
+                QueryInput::Streams(input_query) => instance.query_from_streams(
+                    input_graph,
+                    input_query,
+                    output_file_prefix,
+                    &options,
+                ),
+            })
+            .map_err(to_py_error)?;
+
+        if !return_results {
+            return Ok(output_file.to_string_lossy().to_string().into_py(py));
+        }
+        let _ = std::fs::remove_file(&output_file);
+
+        let results = std::mem::take(&mut *results.lock().unwrap());
+        let mut query_results = vec![];
+        for (query_index, matches) in results {
+            let result = PyDict::new(py);
+            result.set_item("query_index", query_index)?;
+            if colors {
+                let colors_matches = PyDict::new(py);
+                for (color, fraction) in matches {
+                    colors_matches.set_item(color, fraction)?;
+                }
+                result.set_item("matches", colors_matches)?;
+            } else {
+                result.set_item("match_fraction", matches[0].1)?;
+            }
+            query_results.push(result.to_object(py));
+        }
+
+        Ok(query_results.into_py(py))
+    }
+
+    fn run_build(
+        &self,
+        py: Python<'_>,
+        input_blocks: Vec<GeneralSequenceBlockData>,
+        output_file: PathBuf,
+        options: BuildOptions,
+    ) -> PyResult<String> {
+        let instance = self.instance.clone();
+        py.allow_threads(move || instance.build(input_blocks, output_file, &options))
+            .map(|path| path.to_string_lossy().to_string())
+            .map_err(to_py_error)
+    }
+}
+
+#[pymethods]
+impl PyGGCATInstance {
+    /// Creates a new instance, temp_dir defaults to the system temporary directory.
+    /// With memory_only all the temporary files are kept in memory, and the operations fail with a MemoryError
+    /// if they do not fit in the given memory.
+    /// memory and hard_memory_limit are in GB, threads_count defaults to all the available threads
+    #[new]
+    #[pyo3(signature = (
+        temp_dir = None,
+        memory_only = false,
+        memory = 2.0,
+        prefer_memory = true,
+        threads_count = None,
+        hard_memory_limit = None,
+        intermediate_compression_level = None,
+        stats_file = None
+    ))]
+    fn new(
+        temp_dir: Option<PathBuf>,
+        memory_only: bool,
+        memory: f64,
+        prefer_memory: bool,
+        threads_count: Option<usize>,
+        hard_memory_limit: Option<f64>,
+        intermediate_compression_level: Option<u32>,
+        stats_file: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let threads_count = threads_count.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|t| t.get())
+                .unwrap_or(1)
+        });
+
+        if memory_only && temp_dir.is_some() {
+            return Err(PyValueError::new_err(
+                "memory_only cannot be used together with temp_dir",
+            ));
+        }
+        let temp_dir = if memory_only {
+            None
+        } else {
+            Some(temp_dir.unwrap_or_else(std::env::temp_dir))
+        };
+
+        Ok(Self {
+            instance: GGCATInstance::create(GGCATConfig {
+                temp_dir,
+                memory,
+                prefer_memory,
+                hard_memory_limit,
+                total_threads_count: threads_count,
+                intermediate_compression_level,
+                stats_file,
+                minimizer_scheme: MinimizerScheme::Random,
+                progress_callback: None,
+            })
+            .map_err(to_py_error)?,
+            threads_count,
+        })
+    }
+
+    /// Builds a new graph from the given fasta/fastq files, returning the output file path.
+    /// extra_elaboration is one of none, unitig_links, greedy_matchtigs, eulertigs, pathtigs, color_compacted_unitigs
+    #[pyo3(signature = (
+        input_files,
+        output_file,
+        k,
+        threads_count = None,
+        forward_only = false,
+        minimizer_length = None,
+        colors = false,
+        color_names = None,
+        min_multiplicity = 2,
+        extra_elaboration = "none",
+        cancellation_token = None
+    ))]
+    fn build(
+        &self,
+        py: Python<'_>,
+        input_files: Vec<PathBuf>,
+        output_file: PathBuf,
+        k: usize,
+        threads_count: Option<usize>,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        color_names: Option<Vec<String>>,
+        min_multiplicity: usize,
+        extra_elaboration: &str,
+        cancellation_token: Option<PyCancellationToken>,
+    ) -> PyResult<String> {
+        let options = self.build_options(
+            k,
+            threads_count,
+            forward_only,
+            minimizer_length,
+            colors,
+            color_names,
+            min_multiplicity,
+            extra_elaboration,
+            cancellation_token,
+        )?;
+
+        self.run_build(
+            py,
+            input_files
+                .into_iter()
+                .map(GeneralSequenceBlockData::FASTA)
+                .collect(),
+            output_file,
+            options,
+        )
+    }
+
+    /// Builds a new graph from in-memory sequences, given as a list of iterables of sequences
+    /// or (name, sequence) tuples, one for each color. The sequences are copied before starting the build
+    #[pyo3(signature = (
+        sequences,
+        output_file,
+        k,
+        threads_count = None,
+        forward_only = false,
+        minimizer_length = None,
+        colors = false,
+        color_names = None,
+        min_multiplicity = 2,
+        extra_elaboration = "none",
+        cancellation_token = None
+    ))]
+    fn build_from_sequences(
+        &self,
+        py: Python<'_>,
+        sequences: &PyAny,
+        output_file: PathBuf,
+        k: usize,
+        threads_count: Option<usize>,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        color_names: Option<Vec<String>>,
+        min_multiplicity: usize,
+        extra_elaboration: &str,
+        cancellation_token: Option<PyCancellationToken>,
+    ) -> PyResult<String> {
+        let options = self.build_options(
+            k,
+            threads_count,
+            forward_only,
+            minimizer_length,
+            colors,
+            color_names,
+            min_multiplicity,
+            extra_elaboration,
+            cancellation_token,
+        )?;
+
+        let stream = Arc::new(MemorySequencesStream::from_iterables(sequences)?);
+        let input_blocks = (0..stream.blocks.len())
+            .map(|block| {
+                GeneralSequenceBlockData::Dynamic((
+                    stream.clone() as Arc<dyn DynamicSequencesStream>,
+                    block,
+                ))
+            })
+            .collect();
+
+        self.run_build(py, input_blocks, output_file, options)
+    }
+
+    /// Queries a (optionally) colored graph with the sequences of a fasta file.
+    /// If output_file_prefix is given the results are written to it and the output file path is returned,
+    /// otherwise they are returned as a list of dicts, one for each query sequence with matches.
+    /// color_output_format is numbers, names or names_and_metadata, used only for the output file
+    #[pyo3(signature = (
+        input_graph,
+        input_query,
+        k,
+        output_file_prefix = None,
+        threads_count = None,
+        forward_only = false,
+        minimizer_length = None,
+        colors = false,
+        color_output_format = "numbers",
+        cancellation_token = None
+    ))]
+    fn query(
+        &self,
+        py: Python<'_>,
+        input_graph: PathBuf,
+        input_query: PathBuf,
+        k: usize,
+        output_file_prefix: Option<PathBuf>,
+        threads_count: Option<usize>,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        color_output_format: &str,
+        cancellation_token: Option<PyCancellationToken>,
+    ) -> PyResult<PyObject> {
+        self.run_query(
+            py,
+            input_graph,
+            QueryInput::File(input_query),
+            output_file_prefix,
+            k,
+            threads_count,
+            forward_only,
+            minimizer_length,
+            colors,
+            color_output_format,
+            cancellation_token,
+        )
+    }
+
+    /// Queries a (optionally) colored graph with in-memory sequences, given as an iterable of sequences
+    /// or (name, sequence) tuples, indexed in order. The results are returned as in query
+    #[pyo3(signature = (
+        input_graph,
+        sequences,
+        k,
+        output_file_prefix = None,
+        threads_count = None,
+        forward_only = false,
+        minimizer_length = None,
+        colors = false,
+        color_output_format = "numbers",
+        cancellation_token = None
+    ))]
+    fn query_from_sequences(
+        &self,
+        py: Python<'_>,
+        input_graph: PathBuf,
+        sequences: &PyAny,
+        k: usize,
+        output_file_prefix: Option<PathBuf>,
+        threads_count: Option<usize>,
+        forward_only: bool,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        color_output_format: &str,
+        cancellation_token: Option<PyCancellationToken>,
+    ) -> PyResult<PyObject> {
+        let stream = Arc::new(MemorySequencesStream::from_iterables(PyTuple::new(
+            py,
+            [sequences],
+        ))?);
+
+        self.run_query(
+            py,
+            input_graph,
+            QueryInput::Streams(vec![GeneralSequenceBlockData::Dynamic((
+                stream as Arc<dyn DynamicSequencesStream>,
+                0,
+            ))]),
+            output_file_prefix,
+            k,
+            threads_count,
+            forward_only,
+            minimizer_length,
+            colors,
+            color_output_format,
+            cancellation_token,
+        )
+    }
+
+    /// Iterates the unitigs of the given graph, optionally with colors.
+    /// It's not guaranteed that maximal unitigs are returned, as only kmers with the same colors subset
+    /// are returned as whole unitigs to speedup colormap reading times
+    #[pyo3(signature = (
+        graph_input,
+        k,
+        minimizer_length = None,
+        colors = false,
+        threads_count = None
+    ))]
+    fn dump_unitigs(
+        &self,
+        graph_input: PathBuf,
+        k: usize,
+        minimizer_length: Option<usize>,
+        colors: bool,
+        threads_count: Option<usize>,
+    ) -> UnitigsIterator {
+        let (sender, receiver) = sync_channel(DUMP_UNITIGS_QUEUE_SIZE);
+        let instance = self.instance.clone();
+        let threads_count = threads_count.unwrap_or(self.threads_count);
+
+        let dump_thread = std::thread::Builder::new()
+            .name("ggcat_dump_unitigs".to_string())
+            .spawn(move || {
+                // The output function is called from one thread at a time, so that the same_colors flag
+                // refers to the previous unitig received by the iterator
+                instance.dump_unitigs(
+                    graph_input,
+                    k,
+                    minimizer_length,
+                    colors,
+                    threads_count,
+                    true,
+                    |sequence, colors, same_colors| {
+                        // The iterator could have been dropped before reaching the end
+                        let _ = sender.send((sequence.to_vec(), colors.to_vec(), same_colors));
+                    },
+                );
+            })
+            .unwrap();
+
+        UnitigsIterator {
+            receiver: Mutex::new(receiver),
+            dump_thread: Some(dump_thread),
+        }
+    }
+}
+
+/// Returns the color names of the given colormap, the color indexes returned
+/// by dump_unitigs and by the queries can be used to index this list
+#[pyfunction]
+fn dump_colors(input_colormap: PathBuf) -> Vec<String> {
+    GGCATInstance::dump_colors(input_colormap).collect()
+}
+
+/// Obtains the standard colormap file path from a graph file path
+#[pyfunction]
+fn get_colormap_file(graph_file: PathBuf) -> String {
+    GGCATInstance::get_colormap_file(graph_file)
+        .to_string_lossy()
+        .to_string()
+}
+
+#[pymodule]
+fn ggcat(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyGGCATInstance>()?;
+    m.add_class::<PyCancellationToken>()?;
+    m.add_class::<UnitigsIterator>()?;
+    m.add_function(wrap_pyfunction!(dump_colors, m)?)?;
+    m.add_function(wrap_pyfunction!(get_colormap_file, m)?)?;
+    m.add("GGCATError", py.get_type::<GGCATError>())?;
+    m.add("CancelledError", py.get_type::<CancelledError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyList;
+    use std::collections::HashMap;
+
+    const K: usize = 21;
+
+    /// Pseudo random sequences, long enough to make shared kmers between them unlikely
+    fn test_sequences(count: usize, length: usize) -> Vec<String> {
+        let mut state = 0x9E3779B97F4A7C15u64;
+        (0..count)
+            .map(|_| {
+                (0..length)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        b"ACGT"[(state & 3) as usize] as char
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn build_query_and_dump() {
+        pyo3::prepare_freethreaded_python();
+        let test_dir = std::env::temp_dir().join(format!("ggcat-py-test-{}", std::process::id()));
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let sequences = test_sequences(15, 300);
+
+        Python::with_gil(|py| {
+            let instance = PyGGCATInstance::new(
+                Some(test_dir.join("temp")),
+                false,
+                0.5,
+                true,
+                Some(2),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // The first 5 sequences are only in the first color, the last 5 only in the second one
+            let blocks = PyList::new(
+                py,
+                [
+                    PyList::new(py, &sequences[..10]),
+                    PyList::new(py, &sequences[5..]),
+                ],
+            );
+            let graph = instance
+                .build_from_sequences(
+                    py,
+                    blocks,
+                    test_dir.join("graph.fa"),
+                    K,
+                    None,
+                    false,
+                    None,
+                    true,
+                    Some(vec!["first".to_string(), "second".to_string()]),
+                    1,
+                    "none",
+                    None,
+                )
+                .unwrap();
+
+            assert_eq!(
+                dump_colors(PathBuf::from(get_colormap_file(PathBuf::from(&graph)))),
+                vec!["first".to_string(), "second".to_string()]
+            );
+
+            let unitigs = Py::new(
+                py,
+                instance.dump_unitigs(PathBuf::from(&graph), K, None, true, None),
+            )
+            .unwrap();
+            let mut colors_kmers = HashMap::<Vec<ColorIndexType>, usize>::new();
+            let mut last_colors = vec![];
+            for unitig in unitigs.as_ref(py).iter().unwrap() {
+                let (sequence, colors, same_colors): (String, &PyArray1<ColorIndexType>, bool) =
+                    unitig.unwrap().extract().unwrap();
+                if !same_colors {
+                    last_colors = colors.to_vec().unwrap();
+                }
+                *colors_kmers.entry(last_colors.clone()).or_default() += sequence.len() - K + 1;
+            }
+            let sequence_kmers = 5 * (300 - K + 1);
+            assert_eq!(colors_kmers[&vec![0]], sequence_kmers);
+            assert_eq!(colors_kmers[&vec![0, 1]], sequence_kmers);
+            assert_eq!(colors_kmers[&vec![1]], sequence_kmers);
+
+            let queries = PyList::new(py, [&sequences[0], &sequences[12]]);
+            let results = instance
+                .query_from_sequences(
+                    py,
+                    PathBuf::from(&graph),
+                    queries,
+                    K,
+                    None,
+                    None,
+                    false,
+                    None,
+                    true,
+                    "numbers",
+                    None,
+                )
+                .unwrap();
+            let results: Vec<&PyDict> = results.extract(py).unwrap();
+            let matches: Vec<(usize, HashMap<ColorIndexType, f64>)> = results
+                .iter()
+                .map(|result| {
+                    (
+                        result
+                            .get_item("query_index")
+                            .unwrap()
+                            .unwrap()
+                            .extract()
+                            .unwrap(),
+                        result
+                            .get_item("matches")
+                            .unwrap()
+                            .unwrap()
+                            .extract()
+                            .unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                matches,
+                vec![
+                    (0, HashMap::from([(0, 1.0)])),
+                    (1, HashMap::from([(1, 1.0)]))
+                ]
+            );
+
+            let query_file = test_dir.join("query.fa");
+            std::fs::write(&query_file, format!(">0\n{}\n", sequences[7])).unwrap();
+            let output_file: String = instance
+                .query(
+                    py,
+                    PathBuf::from(&graph),
+                    query_file,
+                    K,
+                    Some(test_dir.join("query-output")),
+                    None,
+                    false,
+                    None,
+                    false,
+                    "numbers",
+                    None,
+                )
+                .unwrap()
+                .extract(py)
+                .unwrap();
+            assert!(PathBuf::from(output_file).is_file());
+        });
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn memory_only_with_temp_dir() {
+        pyo3::prepare_freethreaded_python();
+        assert!(PyGGCATInstance::new(
+            Some(std::env::temp_dir()),
+            true,
+            0.5,
+            true,
+            Some(1),
+            None,
+            None,
+            None
+        )
+        .is_err());
+    }
+}
@@ -8,17 +8,15 @@ use crate::pipeline::parallel_kmers_query::parallel_kmers_counting;
 use crate::pipeline::querier_minimizer_bucketing::minimizer_bucketing;
 use ::dynamic_dispatch::dynamic_dispatch;
 use colors::colors_manager::{ColorMapReader, ColorsManager, ColorsMergeManager};
-use colors::DefaultColorsSerializer;
-use config::{INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW};
+use config::ColorIndexType;
+use config::{cancellation, instance, progress};
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::sequences_reader::SequencesReader;
 use io::sequences_stream::general::GeneralSequenceBlockData;
 use io::{compute_stats_from_input_blocks, generate_bucket_names};
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
-use std::sync::atomic::Ordering;
 
 mod pipeline;
 mod structs;
@@ -31,10 +29,16 @@ pub enum QuerierStartingStep {
     ColorMapReading = 3,
 }
 
+/// Function called for each (query index, color, fraction of the query kmers found in the color) result,
+/// the color is always 0 for non colored graphs. It can be called concurrently from multiple threads
+pub type QueryResultsVisitor<'a> = &'a (dyn Fn(usize, ColorIndexType, f64) + Send + Sync);
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ColoredQueryOutputFormat {
     JsonLinesWithNumbers,
     JsonLinesWithNames,
+    /// Color names, followed by the json metadata of the matched colors that have it
+    JsonLinesWithNamesAndMetadata,
 }
 
 #[dynamic_dispatch(BucketingHash = [
@@ -45,6 +49,8 @@ pub enum ColoredQueryOutputFormat {
     #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u32::ForwardSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u64::ForwardSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u128::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u256::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u512::ForwardSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u32::ForwardRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u64::ForwardRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u128::ForwardRabinKarpHashFactory,
@@ -52,6 +58,8 @@ pub enum ColoredQueryOutputFormat {
     #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u32::CanonicalSeqHashFactory,
     hashes::cn_seqhash::u64::CanonicalSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u256::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u512::CanonicalSeqHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u32::CanonicalRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u64::CanonicalRabinKarpHashFactory,
     #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u128::CanonicalRabinKarpHashFactory,
@@ -75,10 +83,11 @@ pub fn run_query<
     threads_count: usize,
     default_compression_level: Option<u32>,
     colored_query_output_format: ColoredQueryOutputFormat,
+    results_visitor: Option<QueryResultsVisitor>,
 ) -> PathBuf {
     let temp_dir = temp_dir.unwrap_or(PathBuf::new());
 
-    PHASES_TIMES_MONITOR.write().init();
+    progress::start_operation();
 
     BucketingHash::initialize(k);
     MergingHash::initialize(k);
@@ -96,8 +105,7 @@ pub fn run_query<
     let buckets_count_log = buckets_count_log.unwrap_or_else(|| file_stats.best_buckets_count_log);
 
     if let Some(default_compression_level) = default_compression_level {
-        INTERMEDIATE_COMPRESSION_LEVEL_SLOW.store(default_compression_level, Ordering::Relaxed);
-        INTERMEDIATE_COMPRESSION_LEVEL_FAST.store(default_compression_level, Ordering::Relaxed);
+        instance::with_current(|context| context.set_compression_level(default_compression_level));
     }
 
     let buckets_count = 1 << buckets_count_log;
@@ -128,6 +136,8 @@ pub fn run_query<
         )
     };
 
+    cancellation::check_cancelled();
+
     let counters_buckets = if step <= QuerierStartingStep::KmersCounting {
         parallel_kmers_counting::<BucketingHash, MergingHash, QuerierColorsManager, _>(
             buckets,
@@ -142,6 +152,8 @@ pub fn run_query<
         generate_bucket_names(temp_dir.join("counters"), buckets_count, None)
     };
 
+    cancellation::check_cancelled();
+
     let colored_buckets_prefix = temp_dir.join("color_counters");
 
     let query_kmers_count = {
@@ -166,20 +178,25 @@ pub fn run_query<
             color_map.colors_subsets_count(),
             output_file_prefix.clone(),
             &query_kmers_count,
+            results_visitor,
         )
     } else {
         generate_bucket_names(colored_buckets_prefix, buckets_count, None)
     };
 
+    cancellation::check_cancelled();
+
     if QuerierColorsManager::COLORS_ENABLED {
         let colormap_file = graph_input.with_extension("colors.dat");
-        let remapped_query_color_buckets = colormap_reading::<DefaultColorsSerializer>(
+        let remapped_query_color_buckets = colormap_reading(
             colormap_file,
             colored_buckets,
             temp_dir.clone(),
             queries_count,
         );
 
+        cancellation::check_cancelled();
+
         colored_query_output::<BucketingHash, MergingHash, QuerierColorsManager>(
             &color_map,
             remapped_query_color_buckets,
@@ -187,12 +204,11 @@ pub fn run_query<
             temp_dir,
             &query_kmers_count,
             colored_query_output_format,
+            results_visitor,
         );
     }
 
-    PHASES_TIMES_MONITOR
-        .write()
-        .print_stats("Query completed.".to_string());
+    progress::print_phases_stats("Query completed.".to_string());
 
     let output_file_name = if output_file_prefix.extension().is_none() {
         if QuerierColorsManager::COLORS_ENABLED {
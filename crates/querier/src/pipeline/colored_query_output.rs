@@ -1,20 +1,21 @@
 use crate::structs::query_colored_counters::{ColorsRange, QueryColoredCountersSerializer};
-use crate::ColoredQueryOutputFormat;
+use crate::{ColoredQueryOutputFormat, QueryResultsVisitor};
 use colors::colors_manager::ColorMapReader;
 use colors::colors_manager::{ColorsManager, ColorsMergeManager};
+use config::progress;
 use config::{
-    get_compression_level_info, get_memory_mode, ColorIndexType, SwapPriority,
-    DEFAULT_PREFETCH_AMOUNT, KEEP_FILES, QUERIES_COUNT_MIN_BATCH,
+    get_compression_level_info, get_memory_mode, remove_temp_files, ColorIndexType, SwapPriority,
+    DEFAULT_PREFETCH_AMOUNT, QUERIES_COUNT_MIN_BATCH,
 };
 use flate2::Compression;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::get_bucket_index;
+use log::debug;
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::buckets::readers::BucketReader;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
 use parallel_processor::buckets::LockFreeBucket;
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parking_lot::{Condvar, Mutex};
 use rayon::prelude::*;
 use std::fs::File;
@@ -59,10 +60,9 @@ pub fn colored_query_output<
     temp_dir: PathBuf,
     query_kmers_count: &[u64],
     colored_query_output_format: ColoredQueryOutputFormat,
+    results_visitor: Option<QueryResultsVisitor>,
 ) {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: colored query output".to_string());
+    progress::start_phase("phase: colored query output".to_string());
 
     let buckets_count = colored_query_buckets.len();
 
@@ -135,7 +135,7 @@ pub fn colored_query_output<
                 CompressedBinaryReader::new(
                     &input,
                     RemoveFileMode::Remove {
-                        remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                        remove_fs: remove_temp_files(),
                     },
                     DEFAULT_PREFETCH_AMOUNT,
                 )
@@ -213,6 +213,12 @@ pub fn colored_query_output<
                     for (i, qc) in temp_colors_list.group_by(|a, b| a.0 == b.0).enumerate() {
                         let color_index = qc[0].0;
                         let color_presence = qc.iter().map(|x| x.1).sum::<u64>();
+                        let color_fraction =
+                            (color_presence as f64) / (query_kmers_count[query as usize] as f64);
+
+                        if let Some(results_visitor) = results_visitor {
+                            results_visitor(query, color_index, color_fraction);
+                        }
 
                         if i != 0 {
                             write!(jsonline_buffer, ",").unwrap();
@@ -222,7 +228,8 @@ pub fn colored_query_output<
                             ColoredQueryOutputFormat::JsonLinesWithNumbers => {
                                 write!(jsonline_buffer, "\"{}\"", color_index)
                             }
-                            ColoredQueryOutputFormat::JsonLinesWithNames => {
+                            ColoredQueryOutputFormat::JsonLinesWithNames
+                            | ColoredQueryOutputFormat::JsonLinesWithNamesAndMetadata => {
                                 write!(
                                     jsonline_buffer,
                                     "\"{}\"",
@@ -232,14 +239,34 @@ pub fn colored_query_output<
                         }
                         .unwrap();
 
-                        write!(
-                            jsonline_buffer,
-                            ": {:.2}",
-                            (color_presence as f64) / (query_kmers_count[query as usize] as f64)
-                        )
-                        .unwrap();
+                        write!(jsonline_buffer, ": {:.2}", color_fraction).unwrap();
                     }
-                    writeln!(jsonline_buffer, "}}}}").unwrap();
+                    write!(jsonline_buffer, "}}").unwrap();
+
+                    if colored_query_output_format
+                        == ColoredQueryOutputFormat::JsonLinesWithNamesAndMetadata
+                    {
+                        write!(jsonline_buffer, ", \"metadata\":{{").unwrap();
+                        let mut first = true;
+                        for qc in temp_colors_list.group_by(|a, b| a.0 == b.0) {
+                            if let Some(metadata) = colormap.get_color_metadata(qc[0].0) {
+                                if !first {
+                                    write!(jsonline_buffer, ",").unwrap();
+                                }
+                                first = false;
+                                write!(
+                                    jsonline_buffer,
+                                    "\"{}\": {}",
+                                    colormap.get_color_name(qc[0].0, true),
+                                    metadata
+                                )
+                                .unwrap();
+                            }
+                        }
+                        write!(jsonline_buffer, "}}").unwrap();
+                    }
+
+                    writeln!(jsonline_buffer, "}}").unwrap();
                     compressed_stream.write_data(&jsonline_buffer);
                 }
 
@@ -268,7 +295,7 @@ pub fn colored_query_output<
             }
         });
 
-    println!(
+    debug!(
         "Operations count: {} vs real {}",
         OPS_COUNT.load(Ordering::Relaxed),
         COL_COUNT.load(Ordering::Relaxed)
@@ -3,10 +3,11 @@ use crate::structs::query_colored_counters::{
     ColorsRange, QueryColorDesc, QueryColoredCounters, QueryColoredCountersSerializer,
 };
 use colors::storage::deserializer::ColorsDeserializer;
-use colors::storage::ColorsSerializerTrait;
+use config::memory_budget::MemoryReservation;
+use config::progress;
 use config::{
-    get_compression_level_info, get_memory_mode, BucketIndexType, ColorIndexType, SwapPriority,
-    DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+    get_compression_level_info, get_memory_mode, remove_temp_files, BucketIndexType,
+    ColorIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT,
     MINIMIZER_BUCKETS_CHECKPOINT_SIZE, QUERIES_COUNT_MIN_BATCH,
 };
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
@@ -16,22 +17,18 @@ use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBi
 use parallel_processor::buckets::MultiThreadBuckets;
 use parallel_processor::fast_smart_bucket_sort::{fast_smart_radix_sort, SortKey};
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use rayon::prelude::*;
 use std::path::PathBuf;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-pub fn colormap_reading<CD: ColorsSerializerTrait>(
+pub fn colormap_reading(
     colormap_file: PathBuf,
     colored_query_buckets: Vec<PathBuf>,
     temp_dir: PathBuf,
     queries_count: u64,
 ) -> Vec<PathBuf> {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: colormap reading".to_string());
+    progress::start_phase("phase: colormap reading".to_string());
 
     let buckets_count = colored_query_buckets.len();
     let buckets_prefix_path = temp_dir.join("query_colors");
@@ -51,7 +48,7 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
     });
 
     let tlocal_colormap_decoder =
-        ScopedThreadLocal::new(move || ColorsDeserializer::<CD>::new(&colormap_file, false));
+        ScopedThreadLocal::new(move || ColorsDeserializer::new(&colormap_file, false));
 
     colored_query_buckets.par_iter().for_each(|input| {
         let mut colormap_decoder = tlocal_colormap_decoder.get();
@@ -70,7 +67,7 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
         CompressedBinaryReader::new(
             input,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         )
@@ -81,6 +78,10 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
                 counters_vec.push(h);
             },
         );
+        let _counters_reservation = MemoryReservation::with_size(
+            counters_vec.capacity()
+                * std::mem::size_of::<(CounterEntry<ColorIndexType>, ColorIndexType)>(),
+        );
 
         struct CountersCompare;
         impl SortKey<(CounterEntry<ColorIndexType>, ColorIndexType)> for CountersCompare {
@@ -1,9 +1,12 @@
+use crate::QueryResultsVisitor;
 use byteorder::ReadBytesExt;
 use colors::colors_manager::color_types::SingleKmerColorDataType;
 use colors::colors_manager::ColorsManager;
+use config::memory_budget::MemoryReservation;
+use config::progress;
 use config::{
-    get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
-    DEFAULT_PREFETCH_AMOUNT, KEEP_FILES, MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
+    get_compression_level_info, get_memory_mode, remove_temp_files, SwapPriority,
+    DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
 };
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraDataConsecutiveCompression, SequenceExtraDataOwned,
@@ -17,7 +20,6 @@ use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBi
 use parallel_processor::buckets::MultiThreadBuckets;
 use parallel_processor::fast_smart_bucket_sort::{fast_smart_radix_sort, SortKey};
 use parallel_processor::memory_fs::RemoveFileMode;
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parallel_processor::utils::scoped_thread_local::ScopedThreadLocal;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
@@ -104,10 +106,9 @@ pub fn counters_sorting<CX: ColorsManager>(
     colors_count: u64,
     output_file: PathBuf,
     query_kmers_count: &[u64],
+    results_visitor: Option<QueryResultsVisitor>,
 ) -> Vec<PathBuf> {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: counters sorting".to_string());
+    progress::start_phase("phase: counters sorting".to_string());
 
     let buckets_count = file_counters_inputs.len();
 
@@ -118,6 +119,8 @@ pub fn counters_sorting<CX: ColorsManager>(
         counters.extend((0..query_kmers_count.len()).map(|_| AtomicU64::new(0)));
         counters
     };
+    let _counters_reservation =
+        MemoryReservation::with_size(final_counters.capacity() * std::mem::size_of::<AtomicU64>());
 
     let color_buckets = if CX::COLORS_ENABLED {
         Arc::new(MultiThreadBuckets::<CompressedBinaryWriter>::new(
@@ -157,7 +160,7 @@ pub fn counters_sorting<CX: ColorsManager>(
         LockFreeBinaryReader::new(
             input,
             RemoveFileMode::Remove {
-                remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+                remove_fs: remove_temp_files(),
             },
             DEFAULT_PREFETCH_AMOUNT,
         )
@@ -168,6 +171,13 @@ pub fn counters_sorting<CX: ColorsManager>(
                 counters_vec.push(h);
             },
         );
+        let _counters_vec_reservation = MemoryReservation::with_size(
+            counters_vec.capacity()
+                * std::mem::size_of::<(
+                    CounterEntry<SingleKmerColorDataType<CX>>,
+                    SingleKmerColorDataType<CX>,
+                )>(),
+        );
 
         struct CountersCompare;
         impl<CX: SequenceExtraDataConsecutiveCompression<TempBuffer = ()>>
@@ -240,15 +250,17 @@ pub fn counters_sorting<CX: ColorsManager>(
             .zip(final_counters.iter())
             .enumerate()
         {
+            let matched_kmers = counter.load(Ordering::Relaxed);
+            if let Some(results_visitor) = results_visitor {
+                results_visitor(query_index, 0, matched_kmers as f64 / *info as f64);
+            }
+
             writer
                 .write_record(&[
                     query_index.to_string(),
-                    counter.load(Ordering::Relaxed).to_string(),
+                    matched_kmers.to_string(),
                     info.to_string(),
-                    format!(
-                        "{:.2}",
-                        (counter.load(Ordering::Relaxed) as f64 / *info as f64)
-                    ),
+                    format!("{:.2}", (matched_kmers as f64 / *info as f64)),
                 ])
                 .unwrap();
         }
@@ -7,11 +7,14 @@ use colors::colors_manager::color_types::{
     MinimizerBucketingSeqColorDataType, SingleKmerColorDataType,
 };
 use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
+use config::memory_budget::MemoryReservation;
+use config::progress;
 use config::{
     get_memory_mode, BucketIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
     MINIMUM_SUBBUCKET_KMERS_COUNT, RESPLITTING_MAX_K_M_DIFFERENCE,
 };
 use hashbrown::HashMap;
+use hashes::minimizer_scheme::MinimizerSelector;
 use hashes::HashFunction;
 use hashes::HashFunctionFactory;
 use hashes::{ExtendableHashTraitType, MinimizerHashFunctionFactory};
@@ -33,7 +36,6 @@ use parallel_processor::buckets::MultiThreadBuckets;
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::objects_pool::PoolObjectTrait;
 use parallel_processor::execution_manager::packet::{Packet, PacketTrait};
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::cmp::min;
 use std::io::{Read, Write};
 use std::marker::PhantomData;
@@ -173,8 +175,9 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         QuerierMinimizerBucketingExecutorFactory::new(&global_data.global_resplit_data)
     }
 
-    fn new_preprocessor(_global_data: &Arc<Self::GlobalExtraData>) -> Self::PreprocessorType {
+    fn new_preprocessor(global_data: &Arc<Self::GlobalExtraData>) -> Self::PreprocessorType {
         Self::PreprocessorType {
+            minimizer_selector: MinimizerSelector::new(global_data.k - global_data.m + 1),
             _phantom: PhantomData,
         }
     }
@@ -185,6 +188,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     ) -> Self::MapProcessorType {
         Self::MapProcessorType {
             map_packet: None,
+            memory_reservation: MemoryReservation::new(),
             _phantom: PhantomData,
         }
     }
@@ -207,11 +211,12 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 }
 
 struct ParallelKmersQueryPreprocessor<
-    H: HashFunctionFactory,
+    H: MinimizerHashFunctionFactory,
     MH: HashFunctionFactory,
     CX: ColorsManager,
 > {
-    _phantom: PhantomData<(H, MH, CX)>,
+    minimizer_selector: MinimizerSelector<H>,
+    _phantom: PhantomData<(MH, CX)>,
 }
 
 impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager>
@@ -219,7 +224,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     for ParallelKmersQueryPreprocessor<H, MH, CX>
 {
     fn get_sequence_bucket<C>(
-        &self,
+        &mut self,
         global_data: &<ParallelKmersQueryFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
         seq_data: &(u8, u8, C, CompressedRead),
         used_hash_bits: usize,
@@ -227,18 +232,11 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     ) -> BucketIndexType {
         let read = &seq_data.3;
 
-        let hashes = H::new(read.sub_slice(0..global_data.k), global_data.m);
-
-        let minimizer = hashes
-            .iter()
-            .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
-            .unwrap();
+        let minimizer = self
+            .minimizer_selector
+            .window_minimizer(read.sub_slice(0..global_data.k), global_data.m);
 
-        H::get_bucket(
-            used_hash_bits,
-            bucket_bits_count,
-            minimizer.to_unextendable(),
-        )
+        H::get_bucket(used_hash_bits, bucket_bits_count, minimizer)
     }
 }
 
@@ -279,6 +277,7 @@ struct ParallelKmersQueryMapProcessor<
     CX: ColorsManager,
 > {
     map_packet: Option<Packet<ParallelKmersQueryMapPacket<MH, SingleKmerColorDataType<CX>>>>,
+    memory_reservation: MemoryReservation,
     _phantom: PhantomData<(H, CX)>,
 }
 
@@ -337,6 +336,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             }
         }
 
+        self.memory_reservation.update(map_packet.get_size());
+
         GroupProcessStats {
             total_kmers: kmers_count,
             unique_kmers: kmers_count,
@@ -347,6 +348,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         &mut self,
         _global_data: &GlobalQueryMergeData,
     ) -> Packet<Self::MapStruct> {
+        self.memory_reservation.update(0);
         self.map_packet.take().unwrap()
     }
 }
@@ -420,9 +422,7 @@ pub fn parallel_kmers_counting<
     m: usize,
     threads_count: usize,
 ) -> Vec<PathBuf> {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: kmers counting".to_string());
+    progress::start_phase("phase: kmers counting".to_string());
 
     let counters_buckets = Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
         buckets_count,
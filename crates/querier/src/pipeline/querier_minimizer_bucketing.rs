@@ -3,10 +3,9 @@ use byteorder::ReadBytesExt;
 use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
 use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
 use colors::parsers::{SequenceIdent, SingleSequenceInfo};
+use config::progress;
 use config::BucketIndexType;
-use hashes::rolling::minqueue::RollingMinQueue;
-use hashes::ExtendableHashTraitType;
-use hashes::HashFunction;
+use hashes::minimizer_scheme::{get_minimizer_scheme, MinimizerScheme, MinimizerSelector};
 use hashes::MinimizerHashFunctionFactory;
 use io::concurrent::temp_reads::extra_data::{
     HasEmptyExtraBuffer, SequenceExtraData, SequenceExtraDataTempBufferManagement,
@@ -15,11 +14,12 @@ use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 use io::sequences_stream::fasta::FastaFileSequencesStream;
 use io::sequences_stream::SequenceInfo;
 use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use log::warn;
+use minimizer_bucketing::frequent_minimizers::sample_frequent_minimizers;
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
     MinimizerBucketingExecutorFactory, MinimizerInputSequence,
 };
-use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::num::NonZeroU64;
@@ -89,7 +89,7 @@ pub struct QuerierMinimizerBucketingGlobalData {
 }
 
 pub struct QuerierMinimizerBucketingExecutor<H: MinimizerHashFunctionFactory, CX: ColorsManager> {
-    minimizer_queue: RollingMinQueue<H>,
+    minimizer_selector: MinimizerSelector<H>,
     global_data: Arc<MinimizerBucketingCommonData<QuerierMinimizerBucketingGlobalData>>,
     _phantom: PhantomData<CX>,
 }
@@ -116,7 +116,7 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager> MinimizerBucketingExecu
         global_data: &Arc<MinimizerBucketingCommonData<Self::GlobalData>>,
     ) -> Self::ExecutorType {
         Self::ExecutorType {
-            minimizer_queue: RollingMinQueue::new(global_data.k - global_data.m + 1),
+            minimizer_selector: MinimizerSelector::new(global_data.k - global_data.m + 1),
             global_data: global_data.clone(),
             _phantom: PhantomData,
         }
@@ -165,8 +165,8 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
                 if CX::COLORS_ENABLED
                     && (color.debug_count() != sequence.seq.len() - self.global_data.k + 1)
                 {
-                    println!(
-                        "WARN: Sequence does not have enough colors, please check matching k size:\n{}\n{}",
+                    warn!(
+                        "Sequence does not have enough colors, please check matching k size:\n{}\n{}",
                         std::str::from_utf8(sequence.ident_data).unwrap(),
                         std::str::from_utf8(sequence.seq).unwrap()
                     );
@@ -218,11 +218,9 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
         second_bits: usize,
         mut push_sequence: F,
     ){
-        let hashes = H::new(sequence, self.global_data.m);
-
         let mut rolling_iter = self
-            .minimizer_queue
-            .make_iter(hashes.iter().map(|x| x.to_unextendable()));
+            .minimizer_selector
+            .rolling_minimizers(sequence, self.global_data.m);
 
         let mut last_index = 0;
         let mut last_hash = rolling_iter.next().unwrap();
@@ -275,9 +273,16 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
     k: usize,
     m: usize,
 ) -> ((Vec<PathBuf>, PathBuf), u64) {
-    PHASES_TIMES_MONITOR
-        .write()
-        .start_phase("phase: graph + query bucketing".to_string());
+    if get_minimizer_scheme() == MinimizerScheme::FrequencyAware {
+        sample_frequent_minimizers::<H, FastaFileSequencesStream>(
+            &[&graph_file],
+            threads_count,
+            k - m + 1,
+            m,
+        );
+    }
+
+    progress::start_phase("phase: graph + query bucketing".to_string());
 
     let input_files = vec![(graph_file, FileType::Graph), (query_file, FileType::Query)];
 
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use log::debug;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 pub static KCOUNTER: AtomicU64 = AtomicU64::new(0);
@@ -9,7 +10,7 @@ pub fn debug_increase() {
 }
 
 pub fn debug_print() {
-    println!("COUNTER: {:?}", KCOUNTER.load(Ordering::Relaxed));
+    debug!("COUNTER: {:?}", KCOUNTER.load(Ordering::Relaxed));
 }
 
 #[macro_export]
@@ -5,7 +5,7 @@ pub mod owned_drop;
 pub mod resource_counter;
 pub mod vec_slice;
 
-use std::cmp::max;
+use std::cmp::{max, min};
 
 pub struct Utils;
 
@@ -21,9 +21,12 @@ macro_rules! panic_debug {
     };
 }
 
+/// Maximum supported minimizer length
+pub const MAX_MINIMIZER_LENGTH: usize = 64;
+
 pub fn compute_best_m(k: usize) -> usize {
-    match k {
-        0..=13 => max(k / 2, k - 4),
+    let m = match k {
+        0..=13 => max(k / 2, k.saturating_sub(4)),
         14..=15 => 9,
         16..=21 => 10,
         22..=30 => 11,
@@ -31,7 +34,8 @@ pub fn compute_best_m(k: usize) -> usize {
         38..=42 => 13,
         43..=64 => 14,
         _ => ((k as f64) / 4.0).round() as usize,
-    }
+    };
+    min(m, MAX_MINIMIZER_LENGTH)
 }
 
 impl Utils {